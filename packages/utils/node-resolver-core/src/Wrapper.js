@@ -142,6 +142,12 @@ export default class NodeResolver {
       res.invalidateOnFileChange.push(pnp.resolveToUnqualified('pnpapi', null));
     }
 
+    for (let diagnostic of res.diagnostics) {
+      if (diagnostic.severity === 'warning') {
+        this.options.logger?.warn(await this.convertDiagnostic(diagnostic));
+      }
+    }
+
     if (res.error) {
       let diagnostic = await this.handleError(res.error, options);
       return {
@@ -393,6 +399,27 @@ export default class NodeResolver {
           }),
         };
       }
+      case 'DirectoryWithoutIndex': {
+        let relative = error.relative;
+        if (!relative.startsWith('.')) {
+          relative = './' + relative;
+        }
+
+        let expected = 'a file';
+        if (error.index_names.length > 0) {
+          let extensions =
+            error.extensions.length > 0
+              ? `.{${error.extensions.join(',')}}`
+              : '';
+          expected += ` or a directory containing ${error.index_names
+            .map(name => name + extensions)
+            .join(' or ')}`;
+        }
+
+        return {
+          message: md`'${relative}' is a directory; expected ${expected}.`,
+        };
+      }
       case 'ModuleNotFound': {
         let alternativeModules = await findAlternativeNodeModules(
           this.options.fs,
@@ -518,6 +545,18 @@ export default class NodeResolver {
             return {
               message: `Invalid URL: ${error.value}`,
             };
+          case 'InvalidPath':
+            return {
+              message: `Invalid character in specifier at offset ${error.value}`,
+            };
+          case 'UnknownBuiltin':
+            return {
+              message: md`Unknown builtin module '${error.value}'`,
+            };
+          case 'PackagesNotAllowed':
+            return {
+              message: md`Cannot import package '${error.value}', only builtin modules are allowed`,
+            };
           default:
             throw new Error('Unknown specifier error kind');
         }
@@ -527,8 +566,41 @@ export default class NodeResolver {
           message: md`Unknown url scheme or pipeline '${error.scheme}:'`,
         };
       }
+      case 'ElevatedDiagnostic':
+        return this.convertDiagnostic(error.diagnostic);
       case 'PackageJsonError': {
+        if (error.error.InvalidField) {
+          return this.handleError(
+            {type: 'JsonError', ...error.error.InvalidField},
+            options,
+          );
+        }
+
         let pkgContent = await this.options.fs.readFile(error.path, 'utf8');
+        if (error.error.UnmatchedConditions) {
+          let {conditions, active} = error.error.UnmatchedConditions;
+          return {
+            message: md`None of the conditions for '${options.filename}' in the '${error.module}' package matched`,
+            hints: [
+              `Conditions in package.json: ${conditions.join(', ')}`,
+              `Active conditions: ${active.join(', ') || '(none)'}`,
+            ],
+            codeFrames: [
+              {
+                filePath: error.path,
+                language: 'json',
+                code: pkgContent,
+                codeHighlights: generateJSONCodeHighlights(pkgContent, [
+                  {
+                    key: `/exports`,
+                    type: 'value',
+                  },
+                ]),
+              },
+            ],
+          };
+        }
+
         // TODO: find alternative exports?
         switch (error.error) {
           case 'PackagePathNotExported': {
@@ -633,9 +705,55 @@ export default class NodeResolver {
           ...(Array.isArray(nested) ? nested : nested ? [nested] : []),
         ];
       }
+      case 'SubpathNotExported': {
+        return {
+          message: md`Module '${options.filename}' is not exported from the '${error.package}' package`,
+          hints: [
+            md`The "exports" field of '${error.package}' maps '${error.subpath}' to null, which blocks importing it.`,
+          ],
+        };
+      }
+      case 'OwnSubpathNotExported': {
+        let suggestion =
+          error.suggestion == null
+            ? null
+            : error.package + error.suggestion.slice(1);
+        return {
+          message: md`Module '${options.filename}' is not exported from the '${error.package}' package`,
+          hints: [
+            md`Imports within '${error.package}' must go through its "exports" field, which doesn't define '${error.subpath}'.`,
+            ...(suggestion != null ? [`Did you mean '__${suggestion}__'?`] : []),
+          ],
+        };
+      }
       case 'IOError': {
         return {message: error.message};
       }
+      case 'AliasCycle': {
+        return {
+          message: md`Cannot resolve '${options.filename}' because its aliases form a cycle`,
+          hints: [error.chain.join(' -> ')],
+        };
+      }
+      case 'LimitExceeded': {
+        return {
+          message: md`Cannot resolve '${options.filename}': ${limitDescription(
+            error.limit,
+          )} exceeds the limit of ${error.max}.`,
+        };
+      }
+      case 'ResolutionLoop': {
+        return {
+          message: md`Cannot resolve '${options.filename}' because its mappings lead back to themselves`,
+          hints: error.steps.map(
+            step =>
+              `${step.key} -> ${step.target} (${path.relative(
+                this.options.projectRoot,
+                step.manifest,
+              )})`,
+          ),
+        };
+      }
     }
   }
 
@@ -730,6 +848,29 @@ export default class NodeResolver {
       }
     }
   }
+
+  async convertDiagnostic(diagnostic: any): Promise<Diagnostic> {
+    let codeFrames;
+    if (diagnostic.file != null && diagnostic.jsonPointer != null) {
+      let code = await this.options.fs.readFile(diagnostic.file, 'utf8');
+      codeFrames = [
+        {
+          filePath: diagnostic.file,
+          language: 'json',
+          code,
+          codeHighlights: generateJSONCodeHighlights(code, [
+            {key: diagnostic.jsonPointer, type: 'value'},
+          ]),
+        },
+      ];
+    }
+
+    return {
+      message: diagnostic.message,
+      hints: diagnostic.hints,
+      codeFrames,
+    };
+  }
 }
 
 function environmentToExportsConditions(
@@ -801,3 +942,22 @@ function mainFieldsToEntries(mainFields: Array<string>) {
 
   return entries;
 }
+
+function limitDescription(limit: string) {
+  switch (limit) {
+    case 'specifierLength':
+      return 'the specifier length';
+    case 'parentSegments':
+      return "the number of '..' segments";
+    case 'exportsKeys':
+      return 'the number of keys in the package exports or imports';
+    case 'ancestorDepth':
+      return 'the directory depth';
+    case 'aliasDepth':
+      return 'the number of chained aliases';
+    case 'redirectDepth':
+      return 'the number of nested aliases and imports';
+    default:
+      return limit;
+  }
+}
@@ -9,8 +9,8 @@ use std::{
 };
 
 use parcel_resolver::{
-  ExportsCondition, Extensions, Fields, FileCreateInvalidation, FileSystem, IncludeNodeModules,
-  Invalidations, OsFileSystem, Resolution, ResolverError, SpecifierType,
+  ExportsCondition, Fields, FileCreateInvalidation, FileMetadata, FileSystem, IncludeNodeModules,
+  Invalidations, OsFileSystem, Preset, Resolution, ResolverBuilder, ResolverError, SpecifierType,
 };
 
 #[napi(object)]
@@ -28,7 +28,7 @@ pub struct JsResolverOptions {
   pub fs: Option<JsFileSystemOptions>,
   pub include_node_modules:
     Option<napi::Either<bool, napi::Either<Vec<String>, HashMap<String, bool>>>>,
-  pub conditions: Option<u16>,
+  pub conditions: Option<u32>,
   pub module_dir_resolver: Option<JsFunction>,
   pub mode: u8,
   pub entries: Option<u8>,
@@ -152,6 +152,13 @@ impl<A: FileSystem, B: FileSystem> FileSystem for EitherFs<A, B> {
     }
   }
 
+  fn read<P: AsRef<Path>>(&self, path: P) -> std::io::Result<Vec<u8>> {
+    match self {
+      EitherFs::A(a) => a.read(path),
+      EitherFs::B(b) => b.read(path),
+    }
+  }
+
   fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
     match self {
       EitherFs::A(a) => a.is_file(path),
@@ -159,12 +166,26 @@ impl<A: FileSystem, B: FileSystem> FileSystem for EitherFs<A, B> {
     }
   }
 
+  fn file_metadata<P: AsRef<Path>>(&self, path: P) -> Option<Option<FileMetadata>> {
+    match self {
+      EitherFs::A(a) => a.file_metadata(path),
+      EitherFs::B(b) => b.file_metadata(path),
+    }
+  }
+
   fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
     match self {
       EitherFs::A(a) => a.is_dir(path),
       EitherFs::B(b) => b.is_dir(path),
     }
   }
+
+  fn read_dir<P: AsRef<Path>>(&self, path: P) -> std::io::Result<Vec<PathBuf>> {
+    match self {
+      EitherFs::A(a) => a.read_dir(path),
+      EitherFs::B(b) => b.read_dir(path),
+    }
+  }
 }
 
 #[napi(object)]
@@ -195,6 +216,7 @@ pub struct ResolveResult {
   pub query: Option<String>,
   pub side_effects: bool,
   pub error: JsUnknown,
+  pub diagnostics: JsUnknown,
 }
 
 #[napi]
@@ -217,41 +239,40 @@ impl Resolver {
       EitherFs::B(OsFileSystem)
     };
 
-    let mut resolver = match options.mode {
-      1 => parcel_resolver::Resolver::parcel(
-        Cow::Owned(project_root.into()),
-        parcel_resolver::CacheCow::Owned(parcel_resolver::Cache::new(fs)),
-      ),
-      2 => parcel_resolver::Resolver::node(
-        Cow::Owned(project_root.into()),
-        parcel_resolver::CacheCow::Owned(parcel_resolver::Cache::new(fs)),
-      ),
+    let preset = match options.mode {
+      1 => Preset::Parcel,
+      2 => Preset::Node,
       _ => return Err(napi::Error::new(napi::Status::InvalidArg, "Invalid mode")),
     };
+    let mut builder = ResolverBuilder::new(
+      Cow::Owned(project_root.into()),
+      parcel_resolver::CacheCow::Owned(parcel_resolver::Cache::new(fs)),
+    )
+    .preset(preset);
 
     if let Some(include_node_modules) = options.include_node_modules {
-      resolver.include_node_modules = Cow::Owned(match include_node_modules {
+      builder = builder.include_node_modules(Cow::Owned(match include_node_modules {
         napi::Either::A(b) => IncludeNodeModules::Bool(b),
         napi::Either::B(napi::Either::A(v)) => IncludeNodeModules::Array(v),
         napi::Either::B(napi::Either::B(v)) => IncludeNodeModules::Map(v),
-      });
+      }));
     }
 
     if let Some(conditions) = options.conditions {
-      resolver.conditions = ExportsCondition::from_bits_truncate(conditions);
+      builder = builder.conditions(ExportsCondition::from_bits_truncate(conditions));
     }
 
     if let Some(entries) = options.entries {
-      resolver.entries = Fields::from_bits_truncate(entries);
+      builder = builder.entry_fields(Fields::from_bits_truncate(entries));
     }
 
     if let Some(extensions) = options.extensions {
-      resolver.extensions = Extensions::Owned(extensions);
+      builder = builder.extensions(extensions);
     }
 
     if let Some(module_dir_resolver) = options.module_dir_resolver {
       let module_dir_resolver = FunctionRef::new(env, module_dir_resolver)?;
-      resolver.module_dir_resolver = Some(Arc::new(move |module: &str, from: &Path| {
+      builder = builder.module_dir_resolver(Arc::new(move |module: &str, from: &Path| {
         let call = |module: &str| -> napi::Result<PathBuf> {
           let env = module_dir_resolver.env;
           let s = env.create_string(module)?;
@@ -268,6 +289,9 @@ impl Resolver {
       }));
     }
 
+    let resolver = builder
+      .build()
+      .map_err(|err| napi::Error::new(napi::Status::InvalidArg, format!("{:?}", err)))?;
     Ok(Self { resolver })
   }
 
@@ -308,14 +332,16 @@ impl Resolver {
 
     let (invalidate_on_file_change, invalidate_on_file_create) =
       convert_invalidations(res.invalidations);
+    let diagnostics = env.to_js_value(&res.diagnostics)?;
     match res.result {
       Ok((res, query)) => Ok(ResolveResult {
         resolution: env.to_js_value(&res)?,
         invalidate_on_file_change,
         invalidate_on_file_create,
         side_effects,
-        query,
+        query: query.map(|q| q.raw().to_owned()),
         error: env.get_undefined()?.into_unknown(),
+        diagnostics,
       }),
       Err(err) => Ok(ResolveResult {
         resolution: env.get_undefined()?.into_unknown(),
@@ -324,6 +350,7 @@ impl Resolver {
         side_effects: true,
         query: None,
         error: env.to_js_value(&err)?,
+        diagnostics,
       }),
     }
   }
@@ -376,5 +403,6 @@ fn get_resolve_options(mut custom_conditions: Vec<String>) -> parcel_resolver::R
   parcel_resolver::ResolveOptions {
     conditions,
     custom_conditions,
+    ..Default::default()
   }
 }
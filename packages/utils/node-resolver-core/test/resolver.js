@@ -213,7 +213,7 @@ describe('resolver', function () {
         parent: path.join(rootDir, 'foo.js'),
       });
       assert.deepEqual(nullthrows(resolved).diagnostics, [
-        {message: "Cannot load file './nested' in './'.", hints: []},
+        {message: "'./nested' is a directory; expected a file."},
       ]);
     });
 
@@ -0,0 +1,148 @@
+//! Resolves a generated dependency graph, compares hashing interned paths against PathBufs, and
+//! measures the heap freed by `PathInterner::purge` once the results are dropped.
+//!
+//! Usage: cargo run --release --example intern_bench -- [modules]
+
+use std::{
+  alloc::{GlobalAlloc, Layout, System},
+  borrow::Cow,
+  collections::HashSet,
+  path::{Path, PathBuf},
+  sync::atomic::{AtomicUsize, Ordering},
+  time::Instant,
+};
+
+use parcel_resolver::{
+  Cache, CacheCow, InternedPath, OsFileSystem, Resolution, ResolverBuilder, SpecifierType,
+};
+
+const IMPORTS_PER_MODULE: usize = 8;
+
+/// Counts the bytes currently allocated on the heap.
+struct CountingAlloc;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    let ptr = System.alloc(layout);
+    if !ptr.is_null() {
+      LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+    }
+    ptr
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout);
+    LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+  }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+fn live_kib() -> f64 {
+  LIVE_BYTES.load(Ordering::Relaxed) as f64 / 1024.0
+}
+
+fn generate_graph(dir: &Path, modules: usize) -> std::io::Result<()> {
+  std::fs::create_dir_all(dir.join("src"))?;
+  for i in 0..modules {
+    std::fs::write(dir.join(format!("src/m{}.js", i)), "")?;
+  }
+  Ok(())
+}
+
+fn main() -> std::io::Result<()> {
+  let modules: usize = std::env::args()
+    .nth(1)
+    .and_then(|n| n.parse().ok())
+    .unwrap_or(10_000);
+
+  let dir = std::env::temp_dir().join(format!("parcel-resolver-intern-bench-{}", modules));
+  generate_graph(&dir, modules)?;
+  let root = dir.canonicalize()?;
+
+  // The cache outlives the resolver, so that its interner can be purged once the resolution
+  // cache and all results are dropped.
+  let cache = Cache::new(OsFileSystem::default());
+  let resolver = ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+    .resolution_cache(true)
+    .build()
+    .unwrap();
+
+  let importers: Vec<InternedPath> = (0..modules)
+    .map(|i| resolver.intern(&root.join(format!("src/m{}.js", i))))
+    .collect();
+
+  // Each module imports its next few neighbors, so every file is resolved many times.
+  let start = Instant::now();
+  let mut resolved = Vec::with_capacity(modules * IMPORTS_PER_MODULE);
+  for pass in 0..2 {
+    let pass_start = Instant::now();
+    for (i, from) in importers.iter().enumerate() {
+      for j in 1..=IMPORTS_PER_MODULE {
+        let specifier = format!("./m{}", (i + j) % modules);
+        let res =
+          resolver.resolve_interned(&specifier, from, SpecifierType::Esm, Default::default());
+        if let Ok((Resolution::Path(path), _)) = res.result {
+          if pass == 0 {
+            resolved.push(path);
+          }
+        }
+      }
+    }
+    println!(
+      "pass {}: resolved {} specifiers in {:.1} ms",
+      pass,
+      modules * IMPORTS_PER_MODULE,
+      pass_start.elapsed().as_secs_f64() * 1000.0
+    );
+  }
+  println!("total: {:.1} ms", start.elapsed().as_secs_f64() * 1000.0);
+
+  let unique: HashSet<&Path> = resolved.iter().map(|p| p.as_path()).collect();
+  let path_bytes: usize = resolved.iter().map(|p| p.as_os_str().len()).sum();
+  println!(
+    "{} resolutions share {} paths ({} bytes of path data if stored as separate PathBufs)",
+    resolved.len(),
+    unique.len(),
+    path_bytes
+  );
+
+  let owned: Vec<PathBuf> = resolved.iter().map(|p| p.to_path_buf()).collect();
+  let start = Instant::now();
+  let set: HashSet<&PathBuf> = owned.iter().collect();
+  println!(
+    "hash {} PathBufs: {:.1} ms ({} unique)",
+    owned.len(),
+    start.elapsed().as_secs_f64() * 1000.0,
+    set.len()
+  );
+
+  let start = Instant::now();
+  let set: HashSet<&InternedPath> = resolved.iter().collect();
+  println!(
+    "hash {} InternedPaths: {:.1} ms ({} unique)",
+    resolved.len(),
+    start.elapsed().as_secs_f64() * 1000.0,
+    set.len()
+  );
+
+  drop(set);
+  drop((owned, resolved, importers, resolver));
+  let interned = cache.interner().len();
+  let before = live_kib();
+  let start = Instant::now();
+  let removed = cache.interner().purge();
+  println!(
+    "purge: removed {} of {} interned paths in {:.1} ms, heap {:.0} KiB -> {:.0} KiB",
+    removed,
+    interned,
+    start.elapsed().as_secs_f64() * 1000.0,
+    before,
+    live_kib()
+  );
+
+  std::fs::remove_dir_all(&dir)
+}
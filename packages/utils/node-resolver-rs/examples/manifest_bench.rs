@@ -0,0 +1,112 @@
+//! Parses a large generated package.json and resolves a single "exports" subpath, to measure
+//! the cost of reading a manifest. Only the fields a resolution uses should be parsed, so the
+//! size of "keywords", "scripts", or the rest of "exports" should not dominate.
+//!
+//! Usage: cargo run --release --example manifest_bench -- [iterations]
+
+use std::{
+  alloc::{GlobalAlloc, Layout, System},
+  fmt::Write,
+  path::PathBuf,
+  sync::atomic::{AtomicUsize, Ordering},
+  time::{Duration, Instant},
+};
+
+use parcel_resolver::{ExportsCondition, PackageJson};
+
+/// Counts allocated bytes, so that the cost of parsing unused fields is visible.
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Generates a manifest of roughly 500KB, similar to large published packages.
+fn generate_manifest() -> String {
+  let mut json = String::from("{\n  \"name\": \"big\",\n  \"version\": \"1.0.0\",\n");
+
+  json.push_str("  \"keywords\": [");
+  for i in 0..4000 {
+    write!(json, "{}\"keyword-{}\"", if i == 0 { "" } else { ", " }, i).unwrap();
+  }
+  json.push_str("],\n  \"scripts\": {");
+  for i in 0..1000 {
+    let sep = if i == 0 { "" } else { "," };
+    write!(
+      json,
+      "{}\n    \"task-{}\": \"node scripts/task-{}.js --flag\"",
+      sep, i, i
+    )
+    .unwrap();
+  }
+  json.push_str("\n  },\n  \"dependencies\": {");
+  for i in 0..1000 {
+    let sep = if i == 0 { "" } else { "," };
+    write!(json, "{}\n    \"dep-{}\": \"^{}.0.0\"", sep, i, i % 20).unwrap();
+  }
+  json.push_str("\n  },\n  \"exports\": {\n    \".\": \"./dist/index.js\"");
+  for i in 0..3000 {
+    write!(
+      json,
+      ",\n    \"./feature-{i}\": {{\"types\": \"./dist/feature-{i}.d.ts\", \"import\": \"./dist/feature-{i}.mjs\", \"default\": \"./dist/feature-{i}.js\"}}",
+      i = i
+    )
+    .unwrap();
+  }
+  json.push_str("\n  }\n}\n");
+  json
+}
+
+fn main() {
+  let iterations: usize = std::env::args()
+    .nth(1)
+    .and_then(|n| n.parse().ok())
+    .unwrap_or(100);
+
+  let data = generate_manifest();
+  let path = PathBuf::from("/app/node_modules/big/package.json");
+  let mut parse_time = Duration::ZERO;
+  let mut resolve_time = Duration::ZERO;
+  let mut parse_bytes = 0;
+  let mut resolve_bytes = 0;
+  for _ in 0..iterations {
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let pkg = PackageJson::parse(path.clone(), &data).unwrap();
+    parse_time += start.elapsed();
+    parse_bytes += ALLOCATED.load(Ordering::Relaxed) - allocated;
+
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+    pkg
+      .resolve_package_exports("feature-1000", ExportsCondition::IMPORT, &[])
+      .unwrap();
+    resolve_time += start.elapsed();
+    resolve_bytes += ALLOCATED.load(Ordering::Relaxed) - allocated;
+  }
+
+  println!("manifest: {} KB", data.len() / 1024);
+  for (name, time, bytes) in [
+    ("parse", parse_time, parse_bytes),
+    ("first exports resolution", resolve_time, resolve_bytes),
+  ] {
+    println!(
+      "{}: {:.1} µs, {} KB allocated per manifest",
+      name,
+      time.as_secs_f64() * 1_000_000.0 / iterations as f64,
+      bytes / iterations / 1024
+    );
+  }
+}
@@ -0,0 +1,48 @@
+//! Resolves a list of specifiers and prints how long each resolution phase took.
+//!
+//! Usage: cargo run --example resolve_metrics -- <project_root> <from> <specifier>...
+
+use std::{borrow::Cow, path::PathBuf};
+
+use parcel_resolver::{Cache, CacheCow, OsFileSystem, ResolverBuilder, SpecifierType};
+
+fn main() {
+  let mut args = std::env::args().skip(1);
+  let (project_root, from) = match (args.next(), args.next()) {
+    (Some(project_root), Some(from)) => (PathBuf::from(project_root), PathBuf::from(from)),
+    _ => {
+      eprintln!("Usage: resolve_metrics <project_root> <from> <specifier>...");
+      std::process::exit(1);
+    }
+  };
+
+  let resolver = ResolverBuilder::new(
+    Cow::Owned(project_root),
+    CacheCow::Owned(Cache::new(OsFileSystem::default())),
+  )
+  .metrics(true)
+  .build()
+  .unwrap();
+
+  for specifier in args {
+    match resolver
+      .resolve(&specifier, &from, SpecifierType::Esm)
+      .result
+    {
+      Ok((resolution, _)) => println!("{} -> {:?}", specifier, resolution),
+      Err(err) => println!("{} -> error: {:?}", specifier, err),
+    }
+  }
+
+  let metrics = resolver.take_metrics();
+  println!();
+  println!("{:<14} {:>8} {:>14}", "phase", "count", "duration (us)");
+  for (phase, m) in metrics.iter() {
+    println!(
+      "{:<14} {:>8} {:>14.1}",
+      phase,
+      m.count,
+      m.duration_ns as f64 / 1000.0
+    );
+  }
+}
@@ -0,0 +1,46 @@
+//! Checks the "exports" field of a package for missing files and other mistakes.
+//!
+//! Usage: cargo run --example validate_exports -- <package_dir>
+
+use std::path::PathBuf;
+
+use parcel_resolver::{FileSystem, OsFileSystem, PackageJson};
+
+fn main() {
+  let dir = match std::env::args().nth(1) {
+    Some(dir) => PathBuf::from(dir),
+    None => {
+      eprintln!("Usage: validate_exports <package_dir>");
+      std::process::exit(1);
+    }
+  };
+
+  let fs = OsFileSystem::default();
+  let path = dir.join("package.json");
+  let data = match fs.read_to_string(&path) {
+    Ok(data) => data,
+    Err(err) => {
+      eprintln!("Could not read {}: {}", path.display(), err);
+      std::process::exit(1);
+    }
+  };
+
+  let pkg = match PackageJson::parse(path.clone(), &data) {
+    Ok(pkg) => pkg,
+    Err(err) => {
+      eprintln!("Could not parse {}: {}", path.display(), err);
+      std::process::exit(1);
+    }
+  };
+
+  let issues = pkg.validate_exports(&fs);
+  if issues.is_empty() {
+    println!("No issues found.");
+    return;
+  }
+
+  for issue in &issues {
+    println!("{:?}", issue);
+  }
+  std::process::exit(1);
+}
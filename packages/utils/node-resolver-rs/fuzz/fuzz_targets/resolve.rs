@@ -0,0 +1,37 @@
+//! Resolves arbitrary specifiers against the shared test fixture.
+//!
+//! Run with `cargo fuzz run resolve` from the crate directory. The seed inputs in
+//! `corpus/resolve` are also replayed by the `test_fuzz_corpus` unit test.
+
+#![no_main]
+
+use std::path::{Path, PathBuf};
+
+use libfuzzer_sys::fuzz_target;
+use parcel_resolver::{Cache, CacheCow, OsFileSystem, Resolver, SpecifierType};
+
+fn root() -> PathBuf {
+  Path::new(env!("CARGO_MANIFEST_DIR"))
+    .join("../..")
+    .join("node-resolver-core/test/fixture")
+}
+
+fuzz_target!(|data: &[u8]| {
+  let Ok(specifier) = std::str::from_utf8(data) else {
+    return;
+  };
+
+  let from = root().join("foo.js");
+  let parcel = Resolver::parcel(
+    root().into(),
+    CacheCow::Owned(Cache::new(OsFileSystem::default())),
+  );
+  let node = Resolver::node(
+    root().into(),
+    CacheCow::Owned(Cache::new(OsFileSystem::default())),
+  );
+  for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs, SpecifierType::Url] {
+    let _ = parcel.resolve(specifier, &from, specifier_type);
+    let _ = node.resolve(specifier, &from, specifier_type);
+  }
+});
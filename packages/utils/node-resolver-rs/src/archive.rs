@@ -0,0 +1,289 @@
+use std::{
+  collections::HashSet,
+  io::{Cursor, Error, ErrorKind, Read, Result},
+  path::{Component, Path, PathBuf},
+  sync::{Arc, Mutex},
+};
+
+use dashmap::DashMap;
+use zip::ZipArchive;
+
+use crate::{
+  fs::{FileMetadata, FileSystem, OsFileSystem},
+  path::normalize_path,
+};
+
+struct Archive {
+  zip: Mutex<ZipArchive<Cursor<Vec<u8>>>>,
+  files: HashSet<PathBuf>,
+  dirs: HashSet<PathBuf>,
+}
+
+impl Archive {
+  fn new(contents: Vec<u8>) -> Result<Archive> {
+    let zip = ZipArchive::new(Cursor::new(contents))?;
+
+    // Build the list of files and directories up front from the central directory.
+    // Archives don't necessarily contain entries for directories, so add all parents too.
+    let mut files = HashSet::new();
+    let mut dirs = HashSet::new();
+    dirs.insert(PathBuf::new());
+    for name in zip.file_names() {
+      let path = PathBuf::from(name);
+      for parent in path.ancestors().skip(1) {
+        dirs.insert(parent.to_path_buf());
+      }
+
+      if name.ends_with('/') {
+        dirs.insert(path);
+      } else {
+        files.insert(path);
+      }
+    }
+
+    Ok(Archive {
+      zip: Mutex::new(zip),
+      files,
+      dirs,
+    })
+  }
+
+  fn read_to_string(&self, path: &Path) -> Result<String> {
+    // Entry names always use forward slashes, regardless of platform.
+    let name = path
+      .components()
+      .map(|c| c.as_os_str().to_string_lossy())
+      .collect::<Vec<_>>()
+      .join("/");
+
+    let mut zip = self.zip.lock().unwrap();
+    let mut file = zip.by_name(&name)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(contents)
+  }
+}
+
+/// A file system that serves files inside `.zip` archives as if they were directories,
+/// e.g. `/cache/pkg.zip/node_modules/pkg/index.js`. All other paths are delegated to the
+/// base file system. Archives are read through the base file system when first used, and
+/// cached until `invalidate` is called. Failed reads are not cached, so an archive that is
+/// written later will be found.
+pub struct LayeredFileSystem<Fs = OsFileSystem> {
+  base: Fs,
+  archives: DashMap<PathBuf, Arc<Archive>>,
+}
+
+impl<Fs: FileSystem> LayeredFileSystem<Fs> {
+  pub fn new(base: Fs) -> Self {
+    Self {
+      base,
+      archives: DashMap::new(),
+    }
+  }
+
+  /// Splits a path into the archive containing it and the path within the archive.
+  fn split(&self, path: &Path) -> Option<(Arc<Archive>, PathBuf, PathBuf)> {
+    let mut archive_path = PathBuf::new();
+    let mut components = path.components();
+    while let Some(component) = components.next() {
+      archive_path.push(component);
+      if let Component::Normal(name) = component {
+        if name.to_str().is_some_and(|name| name.ends_with(".zip")) {
+          if let Some(archive) = self.archive(&archive_path) {
+            return Some((archive, archive_path, normalize_path(components.as_path())));
+          }
+        }
+      }
+    }
+
+    None
+  }
+
+  fn archive(&self, path: &Path) -> Option<Arc<Archive>> {
+    if let Some(archive) = self.archives.get(path) {
+      return Some(archive.clone());
+    }
+
+    if !self.base.is_file(path) {
+      return None;
+    }
+
+    let archive = Arc::new(Archive::new(self.base.read(path).ok()?).ok()?);
+    self.archives.insert(path.to_path_buf(), archive.clone());
+    Some(archive)
+  }
+
+  /// Forgets a cached archive, e.g. after it was replaced on disk. Note that the resolver's
+  /// `Cache` must also be invalidated for the paths inside it.
+  pub fn invalidate(&self, path: &Path) {
+    self.archives.remove(path);
+  }
+}
+
+impl<Fs: FileSystem> FileSystem for LayeredFileSystem<Fs> {
+  fn canonicalize<P: AsRef<Path>>(
+    &self,
+    path: P,
+    cache: &DashMap<PathBuf, Option<PathBuf>>,
+  ) -> Result<PathBuf> {
+    let path = path.as_ref();
+    match self.split(path) {
+      // Symlinks inside archives are not followed, so canonicalization stops at the archive boundary.
+      Some((archive, archive_path, inner)) => {
+        if !archive.files.contains(&inner) && !archive.dirs.contains(&inner) {
+          return Err(Error::new(ErrorKind::NotFound, "File not found in archive"));
+        }
+
+        Ok(self.base.canonicalize(&archive_path, cache)?.join(inner))
+      }
+      None => self.base.canonicalize(path, cache),
+    }
+  }
+
+  fn read_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+    let path = path.as_ref();
+    match self.split(path) {
+      Some((archive, _, inner)) => archive.read_to_string(&inner),
+      None => self.base.read_to_string(path),
+    }
+  }
+
+  fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+    let path = path.as_ref();
+    match self.split(path) {
+      Some((archive, _, inner)) => archive.files.contains(&inner),
+      None => self.base.is_file(path),
+    }
+  }
+
+  fn file_metadata<P: AsRef<Path>>(&self, path: P) -> Option<Option<FileMetadata>> {
+    let path = path.as_ref();
+    match self.split(path) {
+      Some((archive, _, inner)) => archive.files.contains(&inner).then_some(None),
+      None => self.base.file_metadata(path),
+    }
+  }
+
+  fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+    let path = path.as_ref();
+    match self.split(path) {
+      Some((archive, _, inner)) => archive.dirs.contains(&inner),
+      None => self.base.is_dir(path),
+    }
+  }
+
+  fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    match self.split(path) {
+      Some((archive, archive_path, inner)) => {
+        if !archive.dirs.contains(&inner) {
+          return Err(Error::new(
+            ErrorKind::NotFound,
+            "Directory not found in archive",
+          ));
+        }
+
+        Ok(
+          archive
+            .files
+            .iter()
+            .chain(archive.dirs.iter())
+            .filter(|entry| entry.parent() == Some(inner.as_path()))
+            .map(|entry| archive_path.join(entry))
+            .collect(),
+        )
+      }
+      None => self.base.read_dir(path),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Cache, CacheCow, MemoryFileSystem, Resolution, Resolver, SpecifierType};
+  use std::borrow::Cow;
+
+  #[test]
+  fn test_layered_fs() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    // pkg.zip contains node_modules/pkg/package.json and node_modules/pkg/lib/main.js.
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+      .parent()
+      .unwrap()
+      .join("node-resolver-core/test/fixture/archive");
+    let zip_path = root.join("pkg.zip");
+
+    let fs = LayeredFileSystem::new(OsFileSystem::default());
+    let pkg = zip_path.join("node_modules/pkg");
+    assert!(fs.is_file(root.join("index.js")));
+    assert!(fs.is_dir(&zip_path));
+    assert!(!fs.is_file(&zip_path));
+    assert!(fs.is_dir(&pkg));
+    assert!(fs.is_dir(pkg.join("lib")));
+    assert!(fs.is_file(pkg.join("lib/main.js")));
+    assert!(fs.is_file(pkg.join("lib/../lib/main.js")));
+    assert!(!fs.is_file(pkg.join("lib/missing.js")));
+    assert_eq!(
+      fs.read_to_string(pkg.join("lib/main.js"))?,
+      "export default 2;"
+    );
+    assert!(fs.read_to_string(pkg.join("lib/missing.js")).is_err());
+    assert_eq!(fs.read_dir(pkg.join("lib"))?, vec![pkg.join("lib/main.js")]);
+
+    let cache = DashMap::new();
+    assert_eq!(
+      fs.canonicalize(pkg.join("lib/./main.js"), &cache)?,
+      pkg.join("lib/main.js")
+    );
+    assert!(fs.canonicalize(pkg.join("missing.js"), &cache).is_err());
+
+    // Resolve a package inside the archive, as with Yarn PnP zip installs.
+    let mut resolver = Resolver::node(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let module_dir = zip_path.join("node_modules");
+    resolver.module_dir_resolver = Some(Arc::new(
+      move |module: &str, _: &Path| -> std::result::Result<PathBuf, crate::ResolverError> {
+        Ok(module_dir.join(module))
+      },
+    ));
+    assert_eq!(
+      resolver
+        .resolve("pkg", &root.join("index.js"), SpecifierType::Cjs)
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(pkg.join("lib/main.js").into())
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_layered_memory_fs() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let zip = std::fs::read(
+      Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .join("node-resolver-core/test/fixture/archive/pkg.zip"),
+    )?;
+
+    let fs = LayeredFileSystem::new(MemoryFileSystem::from_files([("/app/index.js", "")]));
+    let main = Path::new("/app/pkg.zip/node_modules/pkg/lib/main.js");
+    assert!(!fs.is_file(main));
+
+    // Missing archives are not cached, so they are found once written.
+    fs.base.write_file("/app/pkg.zip", zip);
+    assert!(fs.is_file(main));
+    assert_eq!(fs.read_to_string(main)?, "export default 2;");
+
+    fs.base.write_file("/app/pkg.zip", "not a zip");
+    assert!(fs.is_file(main));
+    fs.invalidate(Path::new("/app/pkg.zip"));
+    assert!(!fs.is_file(main));
+
+    Ok(())
+  }
+}
@@ -0,0 +1,417 @@
+//! Resolves a single specifier and prints the resolution, the steps taken, and the paths that
+//! would invalidate it. Intended for debugging, e.g. to paste the output into a bug report.
+//!
+//! Usage: cargo run --features cli --bin node-resolver -- --from <file> [options] <specifier>
+
+use std::{
+  borrow::Cow,
+  collections::BTreeSet,
+  path::{Path, PathBuf},
+  sync::mpsc,
+};
+
+use notify::{RecursiveMode, Watcher};
+use parcel_resolver::{
+  Cache, CacheCow, ExportsCondition, FileCreateInvalidation, OsFileSystem, Preset, Resolution,
+  ResolveOptions, ResolveResult, ResolverBuilder, SpecifierType,
+};
+
+const USAGE: &str = "\
+Usage: node-resolver --from <file> [options] <specifier>
+
+Options:
+  --from <file>              The file containing the import
+  --type <esm|cjs|url>       The specifier type (default: esm)
+  --root <dir>               The project root (default: the current directory)
+  --preset <name>            parcel, node, node-esm, or bundler (default: parcel)
+  --condition <name>         An additional \"exports\" condition. May be repeated.
+  --extension <ext>          An extension to try, replacing the preset's. May be repeated.
+  --alias <from>=<to>        An alias, like the \"alias\" field of the root package.json. May be repeated.
+  --json                     Print the result as JSON
+  --watch                    Resolve again whenever one of the invalidation paths changes";
+
+struct Args {
+  specifier: String,
+  from: PathBuf,
+  specifier_type: SpecifierType,
+  root: PathBuf,
+  preset: Preset,
+  conditions: Vec<String>,
+  extensions: Vec<String>,
+  aliases: Vec<(String, String)>,
+  json: bool,
+  watch: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+  let mut args = std::env::args().skip(1);
+  let mut specifier = None;
+  let mut from = None;
+  let mut specifier_type = SpecifierType::Esm;
+  let mut root = None;
+  let mut preset = Preset::Parcel;
+  let mut conditions = Vec::new();
+  let mut extensions = Vec::new();
+  let mut aliases = Vec::new();
+  let mut json = false;
+  let mut watch = false;
+
+  while let Some(arg) = args.next() {
+    let mut value = || {
+      args
+        .next()
+        .ok_or_else(|| format!("Missing value for {}", arg))
+    };
+    match arg.as_str() {
+      "--from" => from = Some(PathBuf::from(value()?)),
+      "--type" => {
+        specifier_type = match value()?.as_str() {
+          "esm" => SpecifierType::Esm,
+          "cjs" => SpecifierType::Cjs,
+          "url" => SpecifierType::Url,
+          other => return Err(format!("Unknown specifier type: {}", other)),
+        }
+      }
+      "--root" => root = Some(PathBuf::from(value()?)),
+      "--preset" => {
+        preset = match value()?.as_str() {
+          "parcel" => Preset::Parcel,
+          "node" => Preset::Node,
+          "node-esm" => Preset::NodeEsm,
+          "bundler" => Preset::Bundler,
+          other => return Err(format!("Unknown preset: {}", other)),
+        }
+      }
+      "--condition" => conditions.push(value()?),
+      "--extension" => extensions.push(value()?.trim_start_matches('.').to_owned()),
+      "--alias" => {
+        let alias = value()?;
+        match alias.split_once('=') {
+          Some((from, to)) if !from.is_empty() && !to.is_empty() => {
+            aliases.push((from.to_owned(), to.to_owned()))
+          }
+          _ => return Err(format!("Invalid alias, expected <from>=<to>: {}", alias)),
+        }
+      }
+      "--json" => json = true,
+      "--watch" => watch = true,
+      "-h" | "--help" => return Err(String::new()),
+      _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+      _ if specifier.is_none() => specifier = Some(arg.clone()),
+      _ => return Err(format!("Unexpected argument: {}", arg)),
+    }
+  }
+
+  let root = match root {
+    Some(root) => root,
+    None => std::env::current_dir().map_err(|e| e.to_string())?,
+  };
+
+  Ok(Args {
+    specifier: specifier.ok_or("Missing specifier")?,
+    from: from.ok_or("Missing --from")?,
+    specifier_type,
+    root,
+    preset,
+    conditions,
+    extensions,
+    aliases,
+    json,
+    watch,
+  })
+}
+
+struct Output {
+  specifier: String,
+  result: ResolveResult,
+  candidates: Vec<PathBuf>,
+}
+
+impl Output {
+  fn invalidation_paths(&self) -> (BTreeSet<PathBuf>, BTreeSet<String>) {
+    let mut changes = BTreeSet::new();
+    let mut creates = BTreeSet::new();
+    for path in self
+      .result
+      .invalidations
+      .invalidate_on_file_change
+      .read()
+      .unwrap()
+      .iter()
+    {
+      changes.insert(path.as_path().to_path_buf());
+    }
+    for invalidation in self
+      .result
+      .invalidations
+      .invalidate_on_file_create
+      .read()
+      .unwrap()
+      .iter()
+    {
+      creates.insert(match invalidation {
+        FileCreateInvalidation::Path(path) => path.as_path().display().to_string(),
+        FileCreateInvalidation::FileName { file_name, above } => {
+          format!("{} above {}", file_name, above.as_path().display())
+        }
+      });
+    }
+    (changes, creates)
+  }
+
+  fn to_json(&self) -> serde_json::Value {
+    let (changes, creates) = self.invalidation_paths();
+    let result = &self.result;
+    serde_json::json!({
+      "specifier": self.specifier,
+      "resolution": result.result.as_ref().ok().map(|(resolution, _)| resolution),
+      "query": result.result.as_ref().ok().and_then(|(_, query)| query.as_ref().map(|q| q.raw())),
+      "error": result.result.as_ref().err(),
+      "rewrites": result.rewrites.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+      "aliasTrace": result.alias_trace,
+      "consultedManifests": result.consulted_manifests,
+      "exportsTrace": result.exports_trace,
+      "candidates": self.candidates,
+      "diagnostics": result.diagnostics,
+      "symlink": result.symlink,
+      "origin": result.origin,
+      "invalidateOnFileChange": changes,
+      "invalidateOnFileCreate": creates,
+    })
+  }
+
+  fn print_pretty(&self) {
+    let result = &self.result;
+    println!("Specifier: {}", self.specifier);
+    match &result.result {
+      Ok((resolution, query)) => {
+        let resolution = match resolution {
+          Resolution::Path(path) => path.display().to_string(),
+          Resolution::Directory(path) => format!("{}/", path.display()),
+          other => format!("{:?}", other),
+        };
+        match query {
+          Some(query) => println!("Resolution: {}{}", resolution, query.raw()),
+          None => println!("Resolution: {}", resolution),
+        }
+      }
+      Err(err) => println!("Error [{}]: {}", err.code(), err),
+    }
+
+    let section = |title: &str, lines: Vec<String>| {
+      if !lines.is_empty() {
+        println!("\n{}:", title);
+        for line in lines {
+          println!("  {}", line);
+        }
+      }
+    };
+
+    section(
+      "Alias trace",
+      result
+        .alias_trace
+        .iter()
+        .map(|d| format!("{} via {:?}: {:?}", d.specifier, d.source, d.outcome))
+        .collect(),
+    );
+    section(
+      "Rewrites",
+      result.rewrites.iter().map(|r| r.to_string()).collect(),
+    );
+    section(
+      "Consulted package.json files",
+      result
+        .consulted_manifests
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect(),
+    );
+    section(
+      "Exports conditions",
+      result
+        .exports_trace
+        .iter()
+        .map(|step| {
+          let status = if step.matched { "matched" } else { "skipped" };
+          format!("{} -> {}: {}", step.key, step.condition, status)
+        })
+        .collect(),
+    );
+    section(
+      "Candidates",
+      self
+        .candidates
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect(),
+    );
+    section(
+      "Diagnostics",
+      result
+        .diagnostics
+        .iter()
+        .map(|d| format!("{:?} {}: {}", d.severity, d.code, d.message))
+        .collect(),
+    );
+
+    let (changes, creates) = self.invalidation_paths();
+    section(
+      "Invalidate on change",
+      changes.iter().map(|p| p.display().to_string()).collect(),
+    );
+    section("Invalidate on create", creates.into_iter().collect());
+  }
+}
+
+fn resolve(args: &Args) -> Result<Output, String> {
+  // A fresh cache is used each time, since parsed package.json files are never evicted.
+  let cache = Cache::new(OsFileSystem::default());
+  let mut builder =
+    ResolverBuilder::new(Cow::Borrowed(args.root.as_path()), CacheCow::Owned(cache))
+      .preset(args.preset)
+      .diagnostics(true);
+  if !args.extensions.is_empty() {
+    builder = builder.extensions(args.extensions.iter().cloned());
+  }
+  for (from, to) in &args.aliases {
+    builder = builder.alias(from.as_str(), to.as_str());
+  }
+  let resolver = builder.build().map_err(|e| format!("{:?}", e))?;
+
+  let mut options = ResolveOptions::default();
+  for condition in &args.conditions {
+    match ExportsCondition::try_from(condition.as_str()) {
+      Ok(condition) => options.conditions |= condition,
+      Err(()) => options.custom_conditions.push(condition.clone()),
+    }
+  }
+
+  Ok(Output {
+    result: resolver.resolve_with_options(
+      &args.specifier,
+      &args.from,
+      args.specifier_type,
+      options.clone(),
+    ),
+    candidates: resolver.resolve_candidates_with_options(
+      &args.specifier,
+      &args.from,
+      args.specifier_type,
+      options,
+    ),
+    specifier: args.specifier.clone(),
+  })
+}
+
+fn print(args: &Args, output: &Output) {
+  if args.json {
+    println!("{:#}", output.to_json());
+  } else {
+    output.print_pretty();
+  }
+}
+
+/// Watches the invalidation paths of the last resolution, and resolves again when they change.
+fn watch(args: &Args, mut output: Output) -> notify::Result<()> {
+  let (tx, rx) = mpsc::channel::<notify::Result<notify::Event>>();
+  let mut watcher = notify::recommended_watcher(tx)?;
+  loop {
+    let mut watched = Vec::new();
+    for path in watch_paths(&output) {
+      if watcher.watch(&path, RecursiveMode::NonRecursive).is_ok() {
+        watched.push(path);
+      }
+    }
+    eprintln!("\nWatching {} paths for changes...", watched.len());
+
+    // Wait for a change, then drain any events from the same burst of writes.
+    rx.recv()
+      .map_err(|e| notify::Error::generic(&e.to_string()))??;
+    while rx
+      .recv_timeout(std::time::Duration::from_millis(50))
+      .is_ok()
+    {}
+
+    for path in &watched {
+      let _ = watcher.unwatch(path);
+    }
+
+    match resolve(args) {
+      Ok(next) => {
+        println!();
+        print(args, &next);
+        output = next;
+      }
+      Err(err) => eprintln!("{}", err),
+    }
+  }
+}
+
+/// Returns the paths to watch: changed files are watched directly, and created files are
+/// detected by watching the closest existing directory they could be created in.
+fn watch_paths(output: &Output) -> BTreeSet<PathBuf> {
+  fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path
+      .ancestors()
+      .skip(1)
+      .find(|p| p.is_dir())
+      .map(Path::to_path_buf)
+  }
+
+  let invalidations = &output.result.invalidations;
+  let mut paths = BTreeSet::new();
+  for path in invalidations
+    .invalidate_on_file_change
+    .read()
+    .unwrap()
+    .iter()
+  {
+    paths.insert(path.as_path().to_path_buf());
+  }
+  for invalidation in invalidations
+    .invalidate_on_file_create
+    .read()
+    .unwrap()
+    .iter()
+  {
+    match invalidation {
+      FileCreateInvalidation::Path(path) => paths.extend(existing_ancestor(path.as_path())),
+      FileCreateInvalidation::FileName { above, .. } => {
+        paths.extend(above.as_path().ancestors().map(Path::to_path_buf))
+      }
+    }
+  }
+  paths
+}
+
+fn main() {
+  let args = match parse_args() {
+    Ok(args) => args,
+    Err(err) => {
+      if !err.is_empty() {
+        eprintln!("{}\n", err);
+      }
+      eprintln!("{}", USAGE);
+      std::process::exit(1);
+    }
+  };
+
+  let output = match resolve(&args) {
+    Ok(output) => output,
+    Err(err) => {
+      eprintln!("{}", err);
+      std::process::exit(1);
+    }
+  };
+  print(&args, &output);
+
+  if args.watch {
+    if let Err(err) = watch(&args, output) {
+      eprintln!("{}", err);
+      std::process::exit(1);
+    }
+  } else if output.result.result.is_err() {
+    std::process::exit(2);
+  }
+}
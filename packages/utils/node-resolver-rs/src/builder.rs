@@ -0,0 +1,561 @@
+use std::{
+  borrow::Cow,
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+use crate::{
+  incremental::ResolutionCache, metrics::MetricsCollector, AliasSource, AliasValue, CacheCow,
+  ExportsCondition, Extensions, ExternalPattern, Fallthrough, Fields, FileSystem, Flags,
+  IncludeNodeModules, IndexName, PackageJson, ParseOptions, ResolveModuleDir, Resolver,
+  ResolverBuilderError, ResolverLimits, Severity, Specifier, SpecifierType, SymlinkMode, TsVersion,
+};
+
+/// A set of default settings to start from when building a resolver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+  /// The Parcel resolver, with aliases, tsconfig, and Parcel-specific specifiers.
+  Parcel,
+  /// Node's CommonJS resolution algorithm.
+  Node,
+  /// Node's ESM resolution algorithm.
+  NodeEsm,
+  /// A generic bundler configuration, similar to webpack or esbuild.
+  Bundler,
+}
+
+impl Preset {
+  fn flags(&self) -> Flags {
+    match self {
+      Preset::Parcel => Flags::PARCEL,
+      Preset::Node => Flags::NODE_CJS,
+      Preset::NodeEsm => Flags::NODE_ESM,
+      Preset::Bundler => Flags::BUNDLER,
+    }
+  }
+
+  fn extensions(&self) -> Extensions<'static> {
+    match self {
+      Preset::Parcel => Extensions::Borrowed(&["ts", "tsx", "mjs", "js", "jsx", "cjs", "json"]),
+      Preset::Node => Extensions::Borrowed(&["js", "json", "node"]),
+      Preset::NodeEsm => Extensions::Borrowed(&[]),
+      Preset::Bundler => Extensions::Borrowed(&["tsx", "ts", "jsx", "mjs", "js", "cjs", "json"]),
+    }
+  }
+
+  fn entries(&self) -> Fields {
+    match self {
+      Preset::Parcel => Fields::MAIN | Fields::SOURCE | Fields::BROWSER | Fields::MODULE,
+      Preset::Node | Preset::NodeEsm => Fields::MAIN,
+      Preset::Bundler => Fields::MAIN | Fields::BROWSER | Fields::MODULE,
+    }
+  }
+
+  fn conditions(&self) -> ExportsCondition {
+    match self {
+      Preset::Parcel | Preset::Bundler => ExportsCondition::empty(),
+      Preset::Node | Preset::NodeEsm => ExportsCondition::NODE | ExportsCondition::NODE_ADDONS,
+    }
+  }
+}
+
+/// Builds a [Resolver], starting from a [Preset] and overriding individual settings.
+/// Settings are validated when the resolver is built, so incompatible combinations
+/// are reported as a [ResolverBuilderError] rather than silently ignored.
+pub struct ResolverBuilder<'a, Fs> {
+  project_root: Cow<'a, Path>,
+  cache: CacheCow<'a, Fs>,
+  preset: Preset,
+  flags: Option<Flags>,
+  tsconfig: Option<bool>,
+  extensions: Option<Extensions<'a>>,
+  index_names: Option<Vec<IndexName>>,
+  entries: Option<Fields>,
+  custom_entries: Vec<String>,
+  conditions: Option<ExportsCondition>,
+  condition_priority: Vec<String>,
+  module_dirs: Option<Vec<String>>,
+  include_node_modules: Option<Cow<'a, IncludeNodeModules>>,
+  module_dir_resolver: Option<Arc<ResolveModuleDir>>,
+  parse_options: ParseOptions,
+  unmatched_conditions: Fallthrough,
+  alias_precedence: Option<Vec<AliasSource>>,
+  aliases: Vec<(String, String)>,
+  limits: ResolverLimits,
+  externals: Vec<ExternalPattern>,
+  verify_externals: bool,
+  typescript_version: Option<TsVersion>,
+  prefer_source_in_workspace: bool,
+  dedupe_packages: bool,
+  dedupe_exclude: Vec<String>,
+  allow_directory_resolution: bool,
+  enforce_own_exports: bool,
+  symlink_mode: SymlinkMode,
+  diagnostics: bool,
+  diagnostic_levels: Vec<(String, Option<Severity>)>,
+  #[cfg(feature = "rayon")]
+  thread_pool: Option<Arc<rayon::ThreadPool>>,
+  metrics: bool,
+  resolution_cache: bool,
+}
+
+impl<'a, Fs: FileSystem> ResolverBuilder<'a, Fs> {
+  pub fn new(project_root: Cow<'a, Path>, cache: CacheCow<'a, Fs>) -> Self {
+    Self {
+      project_root,
+      cache,
+      preset: Preset::Parcel,
+      flags: None,
+      tsconfig: None,
+      extensions: None,
+      index_names: None,
+      entries: None,
+      custom_entries: Vec::new(),
+      conditions: None,
+      condition_priority: Vec::new(),
+      module_dirs: None,
+      include_node_modules: None,
+      module_dir_resolver: None,
+      parse_options: ParseOptions::default(),
+      unmatched_conditions: Fallthrough::default(),
+      alias_precedence: None,
+      aliases: Vec::new(),
+      limits: ResolverLimits::default(),
+      externals: Vec::new(),
+      verify_externals: false,
+      typescript_version: None,
+      prefer_source_in_workspace: false,
+      dedupe_packages: false,
+      dedupe_exclude: Vec::new(),
+      allow_directory_resolution: false,
+      enforce_own_exports: false,
+      symlink_mode: SymlinkMode::default(),
+      diagnostics: false,
+      diagnostic_levels: Vec::new(),
+      #[cfg(feature = "rayon")]
+      thread_pool: None,
+      metrics: false,
+      resolution_cache: false,
+    }
+  }
+
+  /// Sets the preset used for any settings that are not explicitly configured.
+  /// Defaults to [Preset::Parcel].
+  pub fn preset(mut self, preset: Preset) -> Self {
+    self.preset = preset;
+    self
+  }
+
+  /// Replaces the flags from the preset.
+  pub fn flags(mut self, flags: Flags) -> Self {
+    self.flags = Some(flags);
+    self
+  }
+
+  /// Enables or disables reading tsconfig.json files, regardless of the flags.
+  pub fn tsconfig(mut self, enabled: bool) -> Self {
+    self.tsconfig = Some(enabled);
+    self
+  }
+
+  /// Sets the extensions to try when a specifier omits one, without a leading ".".
+  pub fn extensions<I: IntoIterator<Item = S>, S: Into<String>>(mut self, extensions: I) -> Self {
+    self.extensions = Some(Extensions::Owned(
+      extensions.into_iter().map(|e| e.into()).collect(),
+    ));
+    self
+  }
+
+  /// Sets the file name (without extension) used when resolving a directory.
+  pub fn index_file(mut self, index_file: &'a str) -> Self {
+    self.index_names = Some(vec![index_file.into()]);
+    self
+  }
+
+  /// Sets the file names (without extensions) tried in order when resolving a directory,
+  /// e.g. `index`, `main`, then `IndexName::DirectoryName`. Defaults to `IndexName::DEFAULT`.
+  pub fn index_names<I: IntoIterator<Item = N>, N: Into<IndexName>>(mut self, names: I) -> Self {
+    self.index_names = Some(names.into_iter().map(|name| name.into()).collect());
+    self
+  }
+
+  /// Sets which package.json entry fields are used to resolve a package.
+  pub fn entry_fields(mut self, entries: Fields) -> Self {
+    self.entries = Some(entries);
+    self
+  }
+
+  /// Sets custom package.json entry fields, e.g. "svelte" or "sass", tried in priority order
+  /// before the fields set by `entry_fields`.
+  pub fn custom_entry_fields<I: IntoIterator<Item = S>, S: Into<String>>(
+    mut self,
+    fields: I,
+  ) -> Self {
+    self.custom_entries = fields.into_iter().map(|f| f.into()).collect();
+    self
+  }
+
+  /// Sets the "exports" conditions that are always enabled.
+  pub fn conditions(mut self, conditions: ExportsCondition) -> Self {
+    self.conditions = Some(conditions);
+    self
+  }
+
+  /// Sets condition names in priority order, e.g. `["worker", "browser"]`. When several are
+  /// active, only the first is used. See `Resolver::condition_priority`.
+  pub fn condition_priority<I: IntoIterator<Item = S>, S: Into<String>>(
+    mut self,
+    conditions: I,
+  ) -> Self {
+    self.condition_priority = conditions.into_iter().map(|c| c.into()).collect();
+    self
+  }
+
+  /// Sets the directory names searched for packages, in priority order. Defaults to `node_modules`.
+  pub fn module_dirs<I: IntoIterator<Item = S>, S: Into<String>>(mut self, module_dirs: I) -> Self {
+    self.module_dirs = Some(module_dirs.into_iter().map(|d| d.into()).collect());
+    self
+  }
+
+  pub fn include_node_modules(mut self, include_node_modules: Cow<'a, IncludeNodeModules>) -> Self {
+    self.include_node_modules = Some(include_node_modules);
+    self
+  }
+
+  /// Sets a custom function used to locate package directories (e.g. Yarn PnP).
+  pub fn module_dir_resolver(mut self, module_dir_resolver: Arc<ResolveModuleDir>) -> Self {
+    self.module_dir_resolver = Some(module_dir_resolver);
+    self
+  }
+
+  /// Sets prefixes of framework-provided virtual modules, e.g. `$app/` or `astro:`,
+  /// which resolve to `Resolution::Virtual` instead of being looked up on disk.
+  pub fn virtual_prefixes<I: IntoIterator<Item = S>, S: Into<String>>(
+    mut self,
+    prefixes: I,
+  ) -> Self {
+    self.parse_options.virtual_prefixes = prefixes.into_iter().map(|p| p.into()).collect();
+    self
+  }
+
+  /// Sets additional URL schemes that are allowed in specifiers and resolve to
+  /// `Resolution::External`, e.g. `https`.
+  pub fn external_schemes<I: IntoIterator<Item = S>, S: Into<String>>(
+    mut self,
+    schemes: I,
+  ) -> Self {
+    self.parse_options.external_schemes = schemes.into_iter().map(|s| s.into()).collect();
+    self
+  }
+
+  /// Sets query keys to drop from resolved queries and resolution cache keys, e.g.
+  /// cache-busting parameters like `v` or `t`.
+  pub fn ignored_query_keys<I: IntoIterator<Item = S>, S: Into<String>>(mut self, keys: I) -> Self {
+    self.parse_options.ignored_query_keys = keys.into_iter().map(|k| k.into()).collect();
+    self
+  }
+
+  /// Maps a custom scheme to a directory, so that e.g. `mylib:components/x` resolves
+  /// relative to that directory.
+  pub fn scheme_alias<S: Into<String>, P: Into<PathBuf>>(mut self, scheme: S, root: P) -> Self {
+    self
+      .parse_options
+      .scheme_aliases
+      .insert(scheme.into(), root.into());
+    self
+  }
+
+  /// Maps a typed import prefix to an import type, so that e.g. `text:./readme.md` resolves
+  /// `./readme.md` and reports the import type in `ResolveResult::import_type`.
+  pub fn typed_scheme<S: Into<String>, T: Into<String>>(
+    mut self,
+    scheme: S,
+    import_type: T,
+  ) -> Self {
+    self
+      .parse_options
+      .typed_schemes
+      .insert(scheme.into(), import_type.into());
+    self
+  }
+
+  /// Strips a leading byte order mark and zero-width characters from specifiers before
+  /// resolving them. Each strip is reported as a `ResolverWarning::InvisiblePrefixStripped`.
+  pub fn strip_invisible_prefix(mut self, strip: bool) -> Self {
+    self.parse_options.strip_invisible_prefix = strip;
+    self
+  }
+
+  /// Sets what happens when a package's "." conditions object matches no active conditions.
+  /// Defaults to [Fallthrough::Error].
+  pub fn unmatched_conditions(mut self, unmatched_conditions: Fallthrough) -> Self {
+    self.unmatched_conditions = unmatched_conditions;
+    self
+  }
+
+  /// Sets the order in which alias sources are consulted for bare and builtin specifiers.
+  /// Sources that are omitted are ignored. Defaults to [AliasSource::DEFAULT_PRECEDENCE].
+  pub fn alias_precedence<I: IntoIterator<Item = AliasSource>>(mut self, precedence: I) -> Self {
+    self.alias_precedence = Some(precedence.into_iter().collect());
+    self
+  }
+
+  /// Adds an alias, e.g. `react` to `preact/compat`, as if it were in the "alias" field of the
+  /// root package.json. Relative targets are resolved from the project root. Configured
+  /// aliases take precedence over the root package.json.
+  pub fn alias<F: Into<String>, T: Into<String>>(mut self, from: F, to: T) -> Self {
+    self.aliases.push((from.into(), to.into()));
+    self
+  }
+
+  /// Sets limits on the size of inputs. Defaults to [ResolverLimits::default].
+  pub fn limits(mut self, limits: ResolverLimits) -> Self {
+    self.limits = limits;
+    self
+  }
+
+  /// Adds packages that resolve to `Resolution::External` without reading the file system,
+  /// e.g. `react` or `@internal/*`. Subpaths of matching packages are external too.
+  pub fn externals<I: IntoIterator<Item = P>, P: Into<ExternalPattern>>(
+    mut self,
+    externals: I,
+  ) -> Self {
+    self
+      .externals
+      .extend(externals.into_iter().map(|p| p.into()));
+    self
+  }
+
+  /// Checks that packages matching the externals are installed, so that a missing package is
+  /// still reported as an error, while installed ones resolve to `Resolution::External`.
+  pub fn verify_externals(mut self, verify: bool) -> Self {
+    self.verify_externals = verify;
+    self
+  }
+
+  /// Selects mappings from each package's "typesVersions" field for the given TypeScript
+  /// version, when resolving the "types" entry.
+  pub fn typescript_version(mut self, version: TsVersion) -> Self {
+    self.typescript_version = Some(version);
+    self
+  }
+
+  /// Prefers source files over build output for packages outside node_modules (e.g. symlinked
+  /// monorepo members), using the "source" and "main:src" fields and the tsconfig `outDir`.
+  pub fn prefer_source_in_workspace(mut self, enabled: bool) -> Self {
+    self.prefer_source_in_workspace = enabled;
+    self
+  }
+
+  /// Replaces files in nested copies of a package with the same file in an ancestor
+  /// node_modules directory that contains the same version, so that it is bundled once.
+  pub fn dedupe_packages(mut self, enabled: bool) -> Self {
+    self.dedupe_packages = enabled;
+    self
+  }
+
+  /// Excludes packages from `dedupe_packages`, e.g. packages that locate files relative
+  /// to their install path.
+  pub fn dedupe_exclude<I: IntoIterator<Item = S>, S: Into<String>>(mut self, packages: I) -> Self {
+    self
+      .dedupe_exclude
+      .extend(packages.into_iter().map(|p| p.into()));
+    self
+  }
+
+  /// Resolves specifiers naming an existing directory to `Resolution::Directory`, rather than
+  /// loading an index file, e.g. for asset pipelines that copy a whole folder.
+  pub fn allow_directory_resolution(mut self, enabled: bool) -> Self {
+    self.allow_directory_resolution = enabled;
+    self
+  }
+
+  /// Requires tilde and absolute specifiers into the importer's own package to go through its
+  /// "exports" field, so that internal imports respect the package's public API. Relative
+  /// specifiers are unaffected.
+  pub fn enforce_own_exports(mut self, enabled: bool) -> Self {
+    self.enforce_own_exports = enabled;
+    self
+  }
+
+  /// Controls when resolved paths are replaced with their real path, e.g. to canonicalize
+  /// symlinked source directories while preserving pnpm's node_modules layout.
+  pub fn symlink_mode(mut self, mode: SymlinkMode) -> Self {
+    self.symlink_mode = mode;
+    self
+  }
+
+  /// Enables collection of additional diagnostic information in each `ResolveResult`,
+  /// such as the package.json files that were consulted.
+  pub fn diagnostics(mut self, enabled: bool) -> Self {
+    self.diagnostics = enabled;
+    self
+  }
+
+  /// Sets the severity of diagnostics with the given code, e.g. `PARCEL_PACKAGE_DEDUPLICATED`.
+  /// `Some(Severity::Error)` fails resolutions that would report it, and `None` silences it.
+  /// Setting the same code again replaces the previous level.
+  pub fn diagnostic_level(mut self, code: &str, level: Option<Severity>) -> Self {
+    self.diagnostic_levels.retain(|(c, _)| c != code);
+    self.diagnostic_levels.push((code.to_owned(), level));
+    self
+  }
+
+  /// Runs `Resolver::resolve_many` on the given thread pool instead of the global rayon pool,
+  /// e.g. to share a pool the embedder already manages.
+  #[cfg(feature = "rayon")]
+  pub fn with_thread_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+    self.thread_pool = Some(pool);
+    self
+  }
+
+  /// Enables collection of per-phase timing metrics.
+  pub fn metrics(mut self, enabled: bool) -> Self {
+    self.metrics = enabled;
+    self
+  }
+
+  /// Enables caching of resolution results, which is required for `Resolver::changed`.
+  pub fn resolution_cache(mut self, enabled: bool) -> Self {
+    self.resolution_cache = enabled;
+    self
+  }
+
+  fn resolved_flags(&self) -> Flags {
+    let mut flags = self.flags.unwrap_or_else(|| self.preset.flags());
+    if let Some(tsconfig) = self.tsconfig {
+      flags.set(Flags::TSCONFIG, tsconfig);
+    }
+    flags
+  }
+
+  /// Validates the configuration and builds the resolver.
+  pub fn build(self) -> Result<Resolver<'a, Fs>, ResolverBuilderError> {
+    let flags = self.resolved_flags();
+
+    if !flags.contains(Flags::EXPORTS) && matches!(self.conditions, Some(c) if !c.is_empty()) {
+      return Err(ResolverBuilderError::ConditionsWithoutExports);
+    }
+
+    if let Some(extensions) = &self.extensions {
+      if let Some(extension) = extensions
+        .iter()
+        .find(|e| e.is_empty() || e.starts_with('.'))
+      {
+        return Err(ResolverBuilderError::InvalidExtension {
+          extension: extension.to_owned(),
+        });
+      }
+
+      if !flags.contains(Flags::OPTIONAL_EXTENSIONS) && extensions.iter().next().is_some() {
+        return Err(ResolverBuilderError::ExtensionsWithoutOptionalExtensions);
+      }
+    }
+
+    if let Some(module_dirs) = &self.module_dirs {
+      if module_dirs.is_empty() {
+        return Err(ResolverBuilderError::EmptyModuleDirs);
+      }
+
+      if let Some(dir) = module_dirs
+        .iter()
+        .find(|d| d.is_empty() || d.contains(std::path::is_separator))
+      {
+        return Err(ResolverBuilderError::InvalidModuleDir { dir: dir.clone() });
+      }
+    }
+
+    if let Some((from, to)) = self
+      .aliases
+      .iter()
+      .find(|(from, to)| parse_alias(from, to).is_none())
+    {
+      return Err(ResolverBuilderError::InvalidAlias {
+        alias: format!("{}={}", from, to),
+      });
+    }
+
+    Ok(self.build_unchecked())
+  }
+
+  /// Builds the resolver without validation. Used for the preset constructors, which are always valid.
+  pub(crate) fn build_unchecked(self) -> Resolver<'a, Fs> {
+    let flags = self.resolved_flags();
+    let root_aliases = if self.aliases.is_empty() {
+      None
+    } else {
+      Some(PackageJson::from_aliases(
+        self.project_root.join("package.json"),
+        self
+          .aliases
+          .iter()
+          .filter_map(|(from, to)| parse_alias(from, to))
+          .collect(),
+      ))
+    };
+    Resolver {
+      project_root: self.project_root,
+      extensions: self.extensions.unwrap_or_else(|| self.preset.extensions()),
+      index_names: match self.index_names {
+        Some(names) => Cow::Owned(names),
+        None => Cow::Borrowed(IndexName::DEFAULT),
+      },
+      entries: self.entries.unwrap_or_else(|| self.preset.entries()),
+      custom_entries: Cow::Owned(self.custom_entries),
+      flags,
+      include_node_modules: self
+        .include_node_modules
+        .unwrap_or_else(|| Cow::Owned(IncludeNodeModules::default())),
+      conditions: self.conditions.unwrap_or_else(|| self.preset.conditions()),
+      condition_priority: Cow::Owned(self.condition_priority),
+      module_dirs: Cow::Owned(
+        self
+          .module_dirs
+          .unwrap_or_else(|| vec!["node_modules".into()]),
+      ),
+      module_dir_resolver: self.module_dir_resolver,
+      parse_options: self.parse_options,
+      unmatched_conditions: self.unmatched_conditions,
+      alias_precedence: match self.alias_precedence {
+        Some(precedence) => Cow::Owned(precedence),
+        None => Cow::Borrowed(AliasSource::DEFAULT_PRECEDENCE),
+      },
+      limits: self.limits,
+      root_aliases,
+      externals: Cow::Owned(self.externals),
+      verify_externals: self.verify_externals,
+      typescript_version: self.typescript_version,
+      prefer_source_in_workspace: self.prefer_source_in_workspace,
+      dedupe_packages: self.dedupe_packages,
+      dedupe_exclude: Cow::Owned(self.dedupe_exclude),
+      allow_directory_resolution: self.allow_directory_resolution,
+      enforce_own_exports: self.enforce_own_exports,
+      symlink_mode: self.symlink_mode,
+      diagnostics: self.diagnostics,
+      diagnostic_levels: Cow::Owned(self.diagnostic_levels),
+      #[cfg(feature = "rayon")]
+      thread_pool: self.thread_pool,
+      cache: self.cache,
+      metrics: if self.metrics {
+        Some(Arc::new(MetricsCollector::default()))
+      } else {
+        None
+      },
+      resolution_cache: if self.resolution_cache {
+        Some(ResolutionCache::default())
+      } else {
+        None
+      },
+    }
+  }
+}
+
+/// Parses a configured alias like an entry in the "alias" field of a package.json.
+fn parse_alias(from: &str, to: &str) -> Option<(Specifier<'static>, AliasValue<'static>)> {
+  let parse = |s: &str| {
+    Specifier::parse(s, SpecifierType::Cjs, Flags::empty())
+      .ok()
+      .map(|(specifier, _)| specifier.into_owned())
+  };
+  Some((parse(from)?, AliasValue::Specifier(parse(to)?)))
+}
@@ -1,7 +1,7 @@
 use std::{
   borrow::Cow,
   ops::Deref,
-  path::{Path, PathBuf},
+  path::{Component, Path, PathBuf},
   sync::Mutex,
 };
 
@@ -10,12 +10,18 @@ use elsa::sync::FrozenMap;
 use typed_arena::Arena;
 
 use crate::{
-  fs::{FileSystem, OsFileSystem},
+  fs::{FileMetadata, FileSystem, OsFileSystem},
+  intern::{InternedPath, PathInterner},
   package_json::{PackageJson, SourceField},
+  path::normalize_path,
   tsconfig::{TsConfig, TsConfigWrapper},
   ResolverError,
 };
 
+/// Caches file system metadata and parsed config files, shared between resolutions on any
+/// number of threads. Locks are only held while reading or writing an entry, never while
+/// resolving, so nested resolutions (e.g. tsconfig "extends") on a single thread can't deadlock.
+/// Two threads may occasionally read the same file, in which case the first result is kept.
 pub struct Cache<Fs = OsFileSystem> {
   pub fs: Fs,
   // This stores file content strings, which are borrowed when parsing package.json and tsconfig.json files.
@@ -24,13 +30,22 @@ pub struct Cache<Fs = OsFileSystem> {
   // way to associate a lifetime with owned data stored in the same struct. We only vend temporary references
   // from our public methods so this is ok for now. FrozenMap is an append only map, which doesn't require &mut
   // to insert into. Since each value is in a Box, it won't move and therefore references are stable.
-  packages: FrozenMap<PathBuf, Box<Result<PackageJson<'static>, ResolverError>>>,
-  tsconfigs: FrozenMap<PathBuf, Box<Result<TsConfigWrapper<'static>, ResolverError>>>,
-  is_file_cache: DashMap<PathBuf, bool>,
+  // Entries can't be removed while references to them may be alive, so each path keeps a version per
+  // generation instead, and `invalidate_path` bumps the generation. Stale versions are freed with the cache.
+  packages: FrozenMap<PathBuf, Box<Versions<PackageJson<'static>>>>,
+  tsconfigs: FrozenMap<PathBuf, Box<Versions<TsConfigWrapper<'static>>>>,
+  generations: DashMap<PathBuf, usize>,
+  // Maps tsconfig files to the tsconfigs that extend them, which must be re-read when they change.
+  extended_by: DashMap<PathBuf, Vec<PathBuf>>,
+  // None if the path isn't a file, otherwise the metadata returned by the file system, if any.
+  is_file_cache: DashMap<PathBuf, Option<Option<FileMetadata>>>,
   is_dir_cache: DashMap<PathBuf, bool>,
   realpath_cache: DashMap<PathBuf, Option<PathBuf>>,
+  interner: PathInterner,
 }
 
+type Versions<T> = FrozenMap<usize, Box<Result<T, ResolverError>>>;
+
 // Special Cow implementation for a Cache that doesn't require Clone.
 pub enum CacheCow<'a, Fs> {
   Borrowed(&'a Cache<Fs>),
@@ -74,20 +89,32 @@ impl<Fs: FileSystem> Cache<Fs> {
       arena: Mutex::new(Arena::new()),
       packages: FrozenMap::new(),
       tsconfigs: FrozenMap::new(),
+      generations: DashMap::new(),
+      extended_by: DashMap::new(),
       is_file_cache: DashMap::new(),
       is_dir_cache: DashMap::new(),
       realpath_cache: DashMap::new(),
+      interner: PathInterner::default(),
     }
   }
 
   pub fn is_file(&self, path: &Path) -> bool {
-    if let Some(is_file) = self.is_file_cache.get(path) {
-      return *is_file;
+    if let Some(metadata) = self.is_file_cache.get(path) {
+      return metadata.is_some();
     }
 
-    let is_file = self.fs.is_file(path);
-    self.is_file_cache.insert(path.to_path_buf(), is_file);
-    is_file
+    let metadata = self.fs.file_metadata(path);
+    self.is_file_cache.insert(path.to_path_buf(), metadata);
+    metadata.is_some()
+  }
+
+  /// Returns the metadata read when a path was checked by `is_file`, if any. This never reads
+  /// the file system, so it returns `None` for paths that haven't been checked.
+  pub(crate) fn cached_file_metadata(&self, path: &Path) -> Option<FileMetadata> {
+    self
+      .is_file_cache
+      .get(path)
+      .and_then(|metadata| (*metadata).flatten())
   }
 
   pub fn is_dir(&self, path: &Path) -> bool {
@@ -100,12 +127,87 @@ impl<Fs: FileSystem> Cache<Fs> {
     is_file
   }
 
+  /// Removes cached file system metadata for a changed path and its ancestors. If the path is a
+  /// package.json or tsconfig.json file, it is parsed again on the next read, along with any
+  /// tsconfig.json files that extend it.
+  ///
+  /// The previous version of a re-parsed file can't be freed while references to it may be
+  /// alive, so each change to a config file keeps one more version in memory. Long-running
+  /// watchers should periodically release them with `Cache::clear`.
+  pub fn invalidate_path(&self, path: &Path) {
+    for path in path.ancestors() {
+      self.is_file_cache.remove(path);
+      self.is_dir_cache.remove(path);
+      self.realpath_cache.remove(path);
+    }
+
+    if self.packages.get(path).is_some() || self.tsconfigs.get(path).is_some() {
+      *self.generations.entry(path.to_path_buf()).or_default() += 1;
+    }
+
+    if let Some((_, dependents)) = self.extended_by.remove(path) {
+      for dependent in dependents {
+        self.invalidate_path(&dependent);
+      }
+    }
+  }
+
+  /// Removes all cached metadata and config files, including the stale versions kept by
+  /// `invalidate_path`. Interned paths are kept, since callers may still hold them.
+  pub fn clear(&mut self) {
+    self.packages = FrozenMap::new();
+    self.tsconfigs = FrozenMap::new();
+    self.generations.clear();
+    self.extended_by.clear();
+    self.is_file_cache.clear();
+    self.is_dir_cache.clear();
+    self.realpath_cache.clear();
+    // The parsed files borrow from the arena, so it is replaced only after they are dropped.
+    self.arena = Mutex::new(Arena::new());
+  }
+
+  fn generation(&self, path: &Path) -> usize {
+    self
+      .generations
+      .get(path)
+      .map_or(0, |generation| *generation)
+  }
+
+  /// Returns a shared, pre-hashed copy of the given path. Interning importer paths
+  /// before resolving avoids re-hashing them for each resolution cache lookup.
+  pub fn intern(&self, path: &Path) -> InternedPath {
+    self.interner.intern(path)
+  }
+
+  pub fn interner(&self) -> &PathInterner {
+    &self.interner
+  }
+
   pub fn canonicalize(&self, path: &Path) -> Result<PathBuf, ResolverError> {
     Ok(self.fs.canonicalize(path, &self.realpath_cache)?)
   }
 
+  /// Returns the first ancestor of a previously canonicalized path (or the path itself) that
+  /// is a symlink. This only uses the results cached by `canonicalize`, so unlike comparing
+  /// against a fresh realpath, it never reads the file system.
+  pub(crate) fn cached_symlink(&self, path: &Path) -> Option<PathBuf> {
+    let mut prefix = PathBuf::new();
+    for component in normalize_path(path).components() {
+      prefix.push(component);
+      if matches!(component, Component::Normal(_))
+        && matches!(self.realpath_cache.get(&prefix).as_deref(), Some(Some(_)))
+      {
+        return Some(prefix);
+      }
+    }
+
+    None
+  }
+
   pub fn read_package<'a>(&'a self, path: Cow<Path>) -> Result<&'a PackageJson<'a>, ResolverError> {
-    if let Some(pkg) = self.packages.get(path.as_ref()) {
+    let generation = self.generation(&path);
+    let versions = versions(&self.packages, &path);
+    if let Some(pkg) = versions.get(&generation) {
       return clone_result(pkg);
     }
 
@@ -137,14 +239,13 @@ impl<Fs: FileSystem> Cache<Fs> {
       Ok(pkg)
     }
 
-    let path = path.into_owned();
-    let pkg = self.packages.insert(
-      path.clone(),
+    let pkg = versions.insert(
+      generation,
       Box::new(read_package(
         &self.fs,
         &self.realpath_cache,
         &self.arena,
-        path,
+        path.into_owned(),
       )),
     );
 
@@ -156,7 +257,9 @@ impl<Fs: FileSystem> Cache<Fs> {
     path: &Path,
     process: F,
   ) -> Result<&'a TsConfigWrapper<'a>, ResolverError> {
-    if let Some(tsconfig) = self.tsconfigs.get(path) {
+    let generation = self.generation(path);
+    let versions = versions(&self.tsconfigs, path);
+    if let Some(tsconfig) = versions.get(&generation) {
       return clone_result(tsconfig);
     }
 
@@ -179,11 +282,21 @@ impl<Fs: FileSystem> Cache<Fs> {
       Ok(tsconfig)
     }
 
-    let tsconfig = self.tsconfigs.insert(
-      path.to_owned(),
+    let tsconfig = versions.insert(
+      generation,
       Box::new(read_tsconfig(&self.fs, &self.arena, path, process)),
     );
 
+    if let Ok(tsconfig) = tsconfig {
+      for extended in tsconfig.compiler_options.chain().iter().skip(1) {
+        self
+          .extended_by
+          .entry(extended.clone())
+          .or_default()
+          .push(path.to_owned());
+      }
+    }
+
     clone_result(tsconfig)
   }
 }
@@ -193,12 +306,21 @@ fn read<F: FileSystem>(
   arena: &Mutex<Arena<Box<str>>>,
   path: &Path,
 ) -> std::io::Result<&'static mut str> {
+  // Read before locking, so that parallel resolutions don't wait on each other's file reads.
+  let data = fs.read_to_string(path)?.into_boxed_str();
   let arena = arena.lock().unwrap();
-  let data = arena.alloc(fs.read_to_string(path)?.into_boxed_str());
+  let data = arena.alloc(data);
   // The data lives as long as the arena. In public methods, we only vend temporary references.
   Ok(unsafe { &mut *(&mut **data as *mut str) })
 }
 
+fn versions<'a, T>(map: &'a FrozenMap<PathBuf, Box<Versions<T>>>, path: &Path) -> &'a Versions<T> {
+  match map.get(path) {
+    Some(versions) => versions,
+    None => map.insert(path.to_owned(), Box::new(FrozenMap::new())),
+  }
+}
+
 fn clone_result<T, E: Clone>(res: &Result<T, E>) -> Result<&T, E> {
   match res {
     Ok(v) => Ok(v),
@@ -0,0 +1,35 @@
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  Arc,
+};
+
+use crate::ResolverError;
+
+/// A flag shared between the caller and in-flight resolutions, e.g. to abandon a batch that
+/// was superseded by a rebuild. Resolutions check it before each file system read and before
+/// matching the "exports" field, and fail with `ResolverError::Cancelled` once it is set.
+/// Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+
+  pub(crate) fn check(&self) -> Result<(), ResolverError> {
+    if self.is_cancelled() {
+      return Err(ResolverError::Cancelled);
+    }
+
+    Ok(())
+  }
+}
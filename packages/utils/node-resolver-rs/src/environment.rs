@@ -0,0 +1,202 @@
+use crate::{ExportsCondition, ResolveOptions};
+
+/// The custom "exports" condition enabled when every target supports ES modules.
+pub const ESMODULES_CONDITION: &str = "esmodules";
+/// The custom "exports" condition enabled when every target also supports dynamic `import()`.
+pub const MODERN_CONDITION: &str = "modern";
+
+/// The minimum versions supporting `<script type="module">`, keyed by browserslist name.
+/// Matches the table in @parcel/core's Environment, plus Node's unflagged ES module support.
+const ESMODULES: &[(&str, &str)] = &[
+  ("edge", "16"),
+  ("firefox", "60"),
+  ("chrome", "61"),
+  ("safari", "10.1"),
+  ("opera", "48"),
+  ("ios_saf", "10.3"),
+  ("android", "76"),
+  ("and_chr", "76"),
+  ("and_ff", "68"),
+  ("samsung", "8.2"),
+  ("and_qq", "10.4"),
+  ("op_mob", "64"),
+  ("node", "12.17"),
+];
+
+/// The minimum versions supporting dynamic `import()`.
+const DYNAMIC_IMPORT: &[(&str, &str)] = &[
+  ("edge", "76"),
+  ("firefox", "67"),
+  ("chrome", "63"),
+  ("safari", "11.1"),
+  ("opera", "50"),
+  ("ios_saf", "11.3"),
+  ("android", "63"),
+  ("and_chr", "63"),
+  ("and_ff", "67"),
+  ("samsung", "8"),
+  ("and_qq", "10.4"),
+  ("op_mob", "64"),
+  ("node", "13.2"),
+];
+
+/// The targets of a build, used to select "exports" conditions, e.g. to pick between the
+/// modern and legacy entries of a package. Pass `resolve_options()` to
+/// `Resolver::resolve_with_options`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Environment {
+  /// Some target is a browser, enabling the "browser" condition.
+  pub browser: bool,
+  /// Every target supports ES modules, enabling the "esmodules" condition.
+  pub esmodules: bool,
+  /// Every target supports ES modules and dynamic `import()`, enabling the "modern" condition.
+  pub modern: bool,
+}
+
+impl Environment {
+  /// Derives the environment from browserslist queries, e.g. `["defaults"]` or
+  /// `["node 18"]`. An empty list uses browserslist's defaults.
+  #[cfg(feature = "browserslist")]
+  pub fn from_browserslist(queries: &[&str]) -> Result<Environment, browserslist::Error> {
+    let queries: &[&str] = if queries.is_empty() {
+      &["defaults"]
+    } else {
+      queries
+    };
+    let distribs = browserslist::resolve(queries, &browserslist::Opts::default())?;
+    Ok(Environment::from_targets(
+      distribs.iter().map(|d| (d.name(), d.version())),
+    ))
+  }
+
+  /// Derives the environment from resolved `(name, version)` pairs, using browserslist's
+  /// names, e.g. `("chrome", "61")` or `("ios_saf", "15.2-15.3")`.
+  pub fn from_targets<'a, I: IntoIterator<Item = (&'a str, &'a str)>>(targets: I) -> Environment {
+    let mut browser = false;
+    let mut esmodules = true;
+    let mut modern = true;
+    let mut empty = true;
+    for (name, version) in targets {
+      empty = false;
+      browser |= name != "node";
+      esmodules &= supports(ESMODULES, name, version);
+      modern &= supports(DYNAMIC_IMPORT, name, version);
+    }
+
+    if empty {
+      return Environment::default();
+    }
+
+    Environment {
+      browser,
+      esmodules,
+      modern: esmodules && modern,
+    }
+  }
+
+  pub fn conditions(&self) -> ExportsCondition {
+    if self.browser {
+      ExportsCondition::BROWSER
+    } else {
+      ExportsCondition::empty()
+    }
+  }
+
+  pub fn custom_conditions(&self) -> Vec<String> {
+    let mut conditions = Vec::new();
+    if self.esmodules {
+      conditions.push(ESMODULES_CONDITION.to_owned());
+    }
+    if self.modern {
+      conditions.push(MODERN_CONDITION.to_owned());
+    }
+    conditions
+  }
+
+  pub fn resolve_options(&self) -> ResolveOptions {
+    ResolveOptions {
+      conditions: self.conditions(),
+      custom_conditions: self.custom_conditions(),
+      ..Default::default()
+    }
+  }
+}
+
+fn supports(table: &[(&str, &str)], name: &str, version: &str) -> bool {
+  let min = match table.iter().find(|(n, _)| *n == name) {
+    Some((_, min)) => min,
+    None => return false,
+  };
+
+  // Ranges such as "15.2-15.3" are supported if their lowest version is.
+  let version = version.split('-').next().unwrap_or(version);
+  if version == "TP" {
+    return true;
+  }
+
+  match (parse_version(version), parse_version(min)) {
+    (Some(version), Some(min)) => version >= min,
+    _ => false,
+  }
+}
+
+fn parse_version(version: &str) -> Option<[u32; 3]> {
+  let mut components = [0; 3];
+  for (i, part) in version.split('.').enumerate() {
+    *components.get_mut(i)? = part.parse().ok()?;
+  }
+  Some(components)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_from_targets() {
+    let env = Environment::from_targets([("chrome", "61"), ("firefox", "60"), ("safari", "10.1")]);
+    assert_eq!(
+      env,
+      Environment {
+        browser: true,
+        esmodules: true,
+        modern: false,
+      }
+    );
+    assert_eq!(env.custom_conditions(), vec!["esmodules".to_owned()]);
+
+    let env = Environment::from_targets([("chrome", "120"), ("ios_saf", "16.6-16.7")]);
+    assert!(env.browser && env.esmodules && env.modern);
+    assert_eq!(env.conditions(), ExportsCondition::BROWSER);
+    assert_eq!(
+      env.resolve_options().custom_conditions,
+      vec!["esmodules".to_owned(), "modern".to_owned()]
+    );
+
+    // A single legacy target disables the modern conditions.
+    let env = Environment::from_targets([("chrome", "120"), ("ie", "11"), ("op_mini", "all")]);
+    assert!(env.browser && !env.esmodules && !env.modern);
+    assert!(env.custom_conditions().is_empty());
+
+    let env = Environment::from_targets([("node", "18.0.0")]);
+    assert!(!env.browser && env.esmodules && env.modern);
+    assert_eq!(env.conditions(), ExportsCondition::empty());
+
+    assert_eq!(Environment::from_targets([]), Environment::default());
+  }
+
+  #[cfg(feature = "browserslist")]
+  #[test]
+  fn test_from_browserslist() {
+    let env = Environment::from_browserslist(&["chrome >= 80", "firefox >= 78"]).unwrap();
+    assert!(env.browser && env.esmodules && env.modern);
+
+    let env = Environment::from_browserslist(&["ie 11", "chrome 90"]).unwrap();
+    assert!(env.browser && !env.esmodules && !env.modern);
+
+    let env = Environment::from_browserslist(&["node 16"]).unwrap();
+    assert!(!env.browser && env.esmodules && env.modern);
+
+    assert!(Environment::from_browserslist(&["not a query"]).is_err());
+  }
+}
@@ -1,10 +1,12 @@
-use crate::PackageJsonError;
-use crate::{cache::JsonError, specifier::SpecifierError};
+use crate::{cache::JsonError, specifier::SpecifierError, SpecifierType};
+use crate::{Limit, PackageJsonError};
+use std::fmt;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+/// Serialized with a `type` tag, plus the Node-compatible `code` from `ResolverError::code`.
 #[derive(Debug, Clone, PartialEq, serde::Serialize)]
-#[serde(tag = "type")]
+#[serde(tag = "type", remote = "Self")]
 pub enum ResolverError {
   UnknownScheme {
     scheme: String,
@@ -14,6 +16,18 @@ pub enum ResolverError {
     relative: PathBuf,
     from: PathBuf,
   },
+  /// The specifier named an existing directory, but it had no index file or package.json entry.
+  DirectoryWithoutIndex {
+    relative: PathBuf,
+    from: PathBuf,
+    /// The index file names that were tried, in order. Empty if directory indexes are not
+    /// supported for this specifier.
+    index_names: Vec<String>,
+    /// The extensions that were tried for the index file.
+    extensions: Vec<String>,
+    /// How the specifier was imported, which decides the Node error code and message.
+    specifier_type: SpecifierType,
+  },
   ModuleNotFound {
     module: String,
   },
@@ -43,6 +57,443 @@ pub enum ResolverError {
     tsconfig: PathBuf,
     error: Box<ResolverError>,
   },
+  /// The subpath matched a `null` target in the package's "exports" field, e.g.
+  /// `"./internal/*": null`, which explicitly prevents importing it.
+  SubpathNotExported {
+    package: String,
+    subpath: String,
+  },
+  /// With `Resolver::enforce_own_exports`, a tilde or absolute specifier named a subpath of the
+  /// importer's own package that is not defined by its "exports" field. `suggestion` is an
+  /// exported subpath that maps to the same file, if there is one.
+  OwnSubpathNotExported {
+    package: String,
+    subpath: String,
+    suggestion: Option<String>,
+  },
+  /// The resolution was cancelled via its `CancellationToken`.
+  Cancelled,
+  /// The package passed to `Resolver::resolve_bin` has no "bin" field.
+  PackageWithoutBin {
+    module: String,
+    package_path: PathBuf,
+  },
+  /// The package's "bin" field has no command with the requested name.
+  BinNotFound {
+    module: String,
+    bin: String,
+    package_path: PathBuf,
+    /// The commands that the "bin" field does provide.
+    available: Vec<String>,
+  },
+  /// A package.json alias led back to a specifier that was already aliased. `chain` lists
+  /// each specifier in order, ending with the repeated one.
+  AliasCycle {
+    chain: Vec<String>,
+  },
+  /// An input exceeded one of the configured `ResolverLimits`.
+  LimitExceeded {
+    limit: Limit,
+    max: usize,
+  },
+  /// Resolution redirected back to a step that was still in progress, e.g. an "imports" mapping
+  /// to a package whose aliases lead back to the importing file. `steps` lists the cycle,
+  /// ending with the repeated step.
+  ResolutionLoop {
+    steps: Vec<ResolutionStep>,
+  },
+  /// A diagnostic, e.g. a warning, was elevated to an error by `Resolver::diagnostic_levels`.
+  ElevatedDiagnostic {
+    diagnostic: Diagnostic,
+  },
+}
+
+/// A mapping applied while resolving, from `key` to `target`, declared in `manifest`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ResolutionStep {
+  pub manifest: PathBuf,
+  pub key: String,
+  pub target: String,
+}
+
+impl fmt::Display for ResolutionStep {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{} -> {} ({})",
+      self.key,
+      self.target,
+      self.manifest.display()
+    )
+  }
+}
+
+impl ResolverError {
+  /// The closest equivalent Node.js error code, e.g. `ERR_MODULE_NOT_FOUND`, for tools that
+  /// match on Node's codes. Errors without an equivalent use a code prefixed with `ERR_PARCEL_`.
+  pub fn code(&self) -> &'static str {
+    match self {
+      ResolverError::UnknownScheme { .. } => "ERR_UNSUPPORTED_ESM_URL_SCHEME",
+      ResolverError::UnknownError => "ERR_PARCEL_UNKNOWN",
+      ResolverError::FileNotFound { .. }
+      | ResolverError::ModuleNotFound { .. }
+      | ResolverError::ModuleEntryNotFound { .. }
+      | ResolverError::ModuleSubpathNotFound { .. } => "ERR_MODULE_NOT_FOUND",
+      ResolverError::DirectoryWithoutIndex {
+        specifier_type: SpecifierType::Cjs,
+        ..
+      } => "MODULE_NOT_FOUND",
+      ResolverError::DirectoryWithoutIndex { .. } => "ERR_UNSUPPORTED_DIR_IMPORT",
+      ResolverError::JsonError(_) => "ERR_INVALID_PACKAGE_CONFIG",
+      ResolverError::IOError(_) => "ERR_PARCEL_IO",
+      ResolverError::PackageJsonError { error, .. } => match error {
+        PackageJsonError::InvalidField(_) => "ERR_INVALID_PACKAGE_CONFIG",
+        PackageJsonError::InvalidPackageTarget => "ERR_INVALID_PACKAGE_TARGET",
+        PackageJsonError::PackagePathNotExported
+        | PackageJsonError::PackagePathBlocked
+        | PackageJsonError::UnmatchedConditions { .. } => "ERR_PACKAGE_PATH_NOT_EXPORTED",
+        PackageJsonError::InvalidSpecifier => "ERR_INVALID_MODULE_SPECIFIER",
+        PackageJsonError::ImportNotDefined => "ERR_PACKAGE_IMPORT_NOT_DEFINED",
+      },
+      ResolverError::PackageJsonNotFound { .. } => "ERR_PARCEL_PACKAGE_JSON_NOT_FOUND",
+      ResolverError::InvalidSpecifier(error) => match error {
+        SpecifierError::UrlError(_) => "ERR_INVALID_URL",
+        SpecifierError::InvalidFileUrl => "ERR_INVALID_FILE_URL_PATH",
+        _ => "ERR_INVALID_MODULE_SPECIFIER",
+      },
+      ResolverError::TsConfigExtendsNotFound { .. } => "ERR_PARCEL_TSCONFIG_EXTENDS_NOT_FOUND",
+      ResolverError::SubpathNotExported { .. } | ResolverError::OwnSubpathNotExported { .. } => {
+        "ERR_PACKAGE_PATH_NOT_EXPORTED"
+      }
+      ResolverError::Cancelled => "ABORT_ERR",
+      ResolverError::PackageWithoutBin { .. } | ResolverError::BinNotFound { .. } => {
+        "ERR_PARCEL_BIN_NOT_FOUND"
+      }
+      ResolverError::AliasCycle { .. } => "ERR_PARCEL_ALIAS_CYCLE",
+      ResolverError::LimitExceeded { .. } => "ERR_PARCEL_LIMIT_EXCEEDED",
+      ResolverError::ResolutionLoop { .. } => "ERR_PARCEL_RESOLUTION_LOOP",
+      ResolverError::ElevatedDiagnostic { diagnostic } => diagnostic.code,
+    }
+  }
+}
+
+impl serde::Serialize for ResolverError {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: serde::Serializer,
+  {
+    #[derive(serde::Serialize)]
+    struct WithCode<'a> {
+      #[serde(flatten, serialize_with = "serialize_tagged")]
+      error: &'a ResolverError,
+      code: &'static str,
+    }
+
+    // Calls the derived implementation, see `#[serde(remote = "Self")]` above.
+    fn serialize_tagged<S>(error: &&ResolverError, serializer: S) -> Result<S::Ok, S::Error>
+    where
+      S: serde::Serializer,
+    {
+      ResolverError::serialize(error, serializer)
+    }
+
+    WithCode {
+      error: self,
+      code: self.code(),
+    }
+    .serialize(serializer)
+  }
+}
+
+/// Messages for the common errors follow Node's phrasing, e.g. "Cannot find module", so that
+/// tools matching on Node's messages recognize them.
+impl fmt::Display for ResolverError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ResolverError::UnknownScheme { scheme } => write!(
+        f,
+        "Only URLs with a scheme in: file, data, and node are supported by the default ESM loader. Received protocol '{}:'",
+        scheme
+      ),
+      ResolverError::UnknownError => write!(f, "Unknown error"),
+      ResolverError::FileNotFound { relative, from } => write!(
+        f,
+        "Cannot find module '{}' imported from {}",
+        relative.display(),
+        from.display()
+      ),
+      ResolverError::DirectoryWithoutIndex {
+        relative,
+        from,
+        specifier_type: SpecifierType::Cjs,
+        ..
+      } => write!(
+        f,
+        "Cannot find module '{}' required from {}",
+        relative.display(),
+        from.display()
+      ),
+      ResolverError::DirectoryWithoutIndex { relative, from, .. } => write!(
+        f,
+        "Directory import '{}' is not supported resolving ES modules imported from {}",
+        relative.display(),
+        from.display()
+      ),
+      ResolverError::ModuleNotFound { module } => write!(f, "Cannot find package '{}'", module),
+      ResolverError::ModuleEntryNotFound {
+        entry_path, field, ..
+      } => write!(
+        f,
+        "Cannot find module '{}' from the \"{}\" field",
+        entry_path.display(),
+        field
+      ),
+      ResolverError::ModuleSubpathNotFound { path, .. } => {
+        write!(f, "Cannot find module '{}'", path.display())
+      }
+      ResolverError::JsonError(error) => write!(
+        f,
+        "Invalid package config {}: {}",
+        error.path.display(),
+        error.message
+      ),
+      ResolverError::IOError(error) => write!(f, "{}", error.0),
+      ResolverError::PackageJsonError { path, error, .. } => match error {
+        PackageJsonError::InvalidField(error) => write!(
+          f,
+          "Invalid package config {}: {}",
+          path.display(),
+          error.message
+        ),
+        PackageJsonError::InvalidPackageTarget => write!(
+          f,
+          "Invalid \"exports\" target defined in the package config {}",
+          path.display()
+        ),
+        PackageJsonError::PackagePathNotExported
+        | PackageJsonError::PackagePathBlocked
+        | PackageJsonError::UnmatchedConditions { .. } => write!(
+          f,
+          "Package subpath is not defined by \"exports\" in {}",
+          path.display()
+        ),
+        PackageJsonError::InvalidSpecifier => write!(
+          f,
+          "Invalid module specifier in the package config {}",
+          path.display()
+        ),
+        PackageJsonError::ImportNotDefined => write!(
+          f,
+          "Package import specifier is not defined in package {}",
+          path.display()
+        ),
+      },
+      ResolverError::PackageJsonNotFound { from } => {
+        write!(f, "Cannot find a package.json above {}", from.display())
+      }
+      ResolverError::InvalidSpecifier(error) => write!(f, "Invalid module specifier: {}", error),
+      ResolverError::TsConfigExtendsNotFound { tsconfig, error } => write!(
+        f,
+        "Cannot resolve \"extends\" in {}: {}",
+        tsconfig.display(),
+        error
+      ),
+      ResolverError::SubpathNotExported { package, subpath } => write!(
+        f,
+        "Package subpath '{}' is not defined by \"exports\" in {}",
+        subpath, package
+      ),
+      ResolverError::OwnSubpathNotExported {
+        package,
+        subpath,
+        suggestion,
+      } => {
+        write!(
+          f,
+          "Package subpath '{}' is not defined by \"exports\" in {}",
+          subpath, package
+        )?;
+        match suggestion.as_deref() {
+          Some(".") => write!(f, "; import '{}' instead", package),
+          Some(suggestion) => write!(f, "; import '{}{}' instead", package, &suggestion[1..]),
+          None => Ok(()),
+        }
+      }
+      ResolverError::Cancelled => write!(f, "The operation was aborted"),
+      ResolverError::PackageWithoutBin { module, .. } => {
+        write!(f, "Package '{}' has no \"bin\" field", module)
+      }
+      ResolverError::BinNotFound { module, bin, .. } => {
+        write!(f, "Package '{}' has no bin named '{}'", module, bin)
+      }
+      ResolverError::AliasCycle { chain } => write!(f, "Alias cycle: {}", chain.join(" -> ")),
+      ResolverError::LimitExceeded { limit, max } => {
+        write!(f, "Exceeded the limit of {} for {:?}", max, limit)
+      }
+      ResolverError::ResolutionLoop { steps } => {
+        write!(f, "Resolution loop:")?;
+        for step in steps {
+          write!(f, "\n  {}", step)?;
+        }
+        Ok(())
+      }
+      ResolverError::ElevatedDiagnostic { diagnostic } => write!(f, "{}", diagnostic.message),
+    }
+  }
+}
+
+impl std::error::Error for ResolverError {}
+
+/// An invalid combination of settings passed to a [ResolverBuilder](crate::ResolverBuilder).
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum ResolverBuilderError {
+  /// Exports conditions were configured, but the "exports" field is disabled.
+  ConditionsWithoutExports,
+  /// Extensions were configured, but optional extensions are disabled.
+  ExtensionsWithoutOptionalExtensions,
+  /// An extension was empty or included a leading ".".
+  InvalidExtension { extension: String },
+  /// The list of module directories was empty.
+  EmptyModuleDirs,
+  /// A module directory was empty or contained a path separator.
+  InvalidModuleDir { dir: String },
+  /// An alias key or value could not be parsed as a specifier.
+  InvalidAlias { alias: String },
+}
+
+/// A non-fatal problem encountered while resolving, e.g. a compatibility fallback that was applied.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum ResolverWarning {
+  /// A path in a package.json field used backslashes as separators, and was normalized.
+  ManifestBackslashes {
+    path: PathBuf,
+    field: &'static str,
+    value: String,
+  },
+  /// A workspace package's entry resolved to its build output, since no source file was found for it.
+  WorkspaceSourceNotFound { path: PathBuf, entry: PathBuf },
+  /// A byte order mark or zero-width characters were stripped from the start of a specifier.
+  /// `stripped` lists the removed code points, e.g. `U+FEFF`.
+  InvisiblePrefixStripped {
+    specifier: String,
+    stripped: Vec<String>,
+  },
+  /// A file in a nested copy of a package was replaced with the same file in an ancestor
+  /// node_modules directory, which contains the same version of the package.
+  PackageDeduplicated {
+    package: String,
+    version: String,
+    path: PathBuf,
+    deduped: PathBuf,
+  },
+}
+
+impl ResolverWarning {
+  /// A stable identifier for the kind of warning, used as the `Diagnostic::code`.
+  pub fn code(&self) -> &'static str {
+    match self {
+      ResolverWarning::ManifestBackslashes { .. } => "PARCEL_MANIFEST_BACKSLASHES",
+      ResolverWarning::WorkspaceSourceNotFound { .. } => "PARCEL_WORKSPACE_SOURCE_NOT_FOUND",
+      ResolverWarning::InvisiblePrefixStripped { .. } => "PARCEL_INVISIBLE_PREFIX_STRIPPED",
+      ResolverWarning::PackageDeduplicated { .. } => "PARCEL_PACKAGE_DEDUPLICATED",
+    }
+  }
+}
+
+/// How serious a `Diagnostic` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  Warning,
+  Error,
+}
+
+/// A problem reported alongside a resolution, in a form that can be rendered the same way
+/// regardless of where it came from. Each `ResolverWarning` becomes a diagnostic with
+/// `Severity::Warning`, unless `Resolver::diagnostic_levels` silences or elevates its code.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+  pub severity: Severity,
+  /// A stable identifier, e.g. `PARCEL_MANIFEST_BACKSLASHES`.
+  pub code: &'static str,
+  pub message: String,
+  /// The file the diagnostic refers to, e.g. a package.json.
+  pub file: Option<PathBuf>,
+  /// A JSON pointer to the value in `file` that caused the diagnostic, e.g. `/exports`.
+  pub json_pointer: Option<String>,
+  pub hints: Vec<String>,
+}
+
+impl From<ResolverWarning> for Diagnostic {
+  fn from(warning: ResolverWarning) -> Self {
+    let code = warning.code();
+    let (message, file, json_pointer, hints) = match warning {
+      ResolverWarning::ManifestBackslashes { path, field, value } => (
+        format!(
+          "Backslashes in the \"{}\" field of {} were treated as path separators",
+          field,
+          path.display()
+        ),
+        Some(path),
+        Some(format!("/{}", field)),
+        vec![format!("Use \"{}\" instead", value.replace('\\', "/"))],
+      ),
+      ResolverWarning::WorkspaceSourceNotFound { path, entry } => (
+        format!(
+          "No source file was found for {}, so the build output was used",
+          entry.display()
+        ),
+        Some(path.clone()),
+        None,
+        vec![format!(
+          "Add a \"source\" field to {}, or set \"rootDir\" and \"outDir\" in its tsconfig.json",
+          path.display()
+        )],
+      ),
+      ResolverWarning::InvisiblePrefixStripped {
+        specifier,
+        stripped,
+      } => (
+        format!(
+          "Invisible characters ({}) were removed from the start of \"{}\"",
+          stripped.join(", "),
+          specifier
+        ),
+        None,
+        None,
+        vec!["Remove these characters from the import specifier".to_owned()],
+      ),
+      ResolverWarning::PackageDeduplicated {
+        package,
+        version,
+        path,
+        deduped,
+      } => (
+        format!(
+          "{} was replaced with {}, since both are version {} of {}",
+          path.display(),
+          deduped.display(),
+          version,
+          package
+        ),
+        Some(path),
+        None,
+        Vec::new(),
+      ),
+    };
+
+    Diagnostic {
+      severity: Severity::Warning,
+      code,
+      message,
+      file,
+      json_pointer,
+      hints,
+    }
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -98,6 +549,154 @@ impl From<std::io::Error> for ResolverError {
 
 impl From<SpecifierError> for ResolverError {
   fn from(value: SpecifierError) -> Self {
-    ResolverError::InvalidSpecifier(value)
+    match value {
+      SpecifierError::UnknownScheme(scheme) => ResolverError::UnknownScheme { scheme },
+      value => ResolverError::InvalidSpecifier(value),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn node_messages() {
+    let errors = [
+      ResolverError::FileNotFound {
+        relative: "./foo.js".into(),
+        from: "/app/index.js".into(),
+      },
+      ResolverError::ModuleNotFound {
+        module: "pkg".into(),
+      },
+      ResolverError::DirectoryWithoutIndex {
+        relative: "./lib".into(),
+        from: "/app/index.js".into(),
+        index_names: vec![],
+        extensions: vec![],
+        specifier_type: SpecifierType::Esm,
+      },
+      ResolverError::DirectoryWithoutIndex {
+        relative: "./lib".into(),
+        from: "/app/index.js".into(),
+        index_names: vec![],
+        extensions: vec![],
+        specifier_type: SpecifierType::Cjs,
+      },
+      ResolverError::InvalidSpecifier(SpecifierError::UnknownBuiltin("node:foo".into())),
+      ResolverError::SubpathNotExported {
+        package: "pkg".into(),
+        subpath: "./internal".into(),
+      },
+      ResolverError::PackageJsonError {
+        module: "pkg".into(),
+        path: "/app/node_modules/pkg/package.json".into(),
+        error: PackageJsonError::PackagePathNotExported,
+      },
+      ResolverError::PackageJsonError {
+        module: "app".into(),
+        path: "/app/package.json".into(),
+        error: PackageJsonError::ImportNotDefined,
+      },
+      ResolverError::PackageJsonError {
+        module: "pkg".into(),
+        path: "/app/node_modules/pkg/package.json".into(),
+        error: PackageJsonError::InvalidPackageTarget,
+      },
+      ResolverError::UnknownScheme {
+        scheme: "foo".into(),
+      },
+      ResolverError::OwnSubpathNotExported {
+        package: "app".into(),
+        subpath: "./src/button.js".into(),
+        suggestion: Some("./button".into()),
+      },
+      ResolverError::Cancelled,
+    ];
+
+    let messages: Vec<_> = errors
+      .iter()
+      .map(|error| format!("{}: {}", error.code(), error))
+      .collect();
+    assert_eq!(
+      messages,
+      [
+        "ERR_MODULE_NOT_FOUND: Cannot find module './foo.js' imported from /app/index.js",
+        "ERR_MODULE_NOT_FOUND: Cannot find package 'pkg'",
+        "ERR_UNSUPPORTED_DIR_IMPORT: Directory import './lib' is not supported resolving ES modules imported from /app/index.js",
+        "MODULE_NOT_FOUND: Cannot find module './lib' required from /app/index.js",
+        "ERR_INVALID_MODULE_SPECIFIER: Invalid module specifier: 'node:foo' is not a builtin module",
+        "ERR_PACKAGE_PATH_NOT_EXPORTED: Package subpath './internal' is not defined by \"exports\" in pkg",
+        "ERR_PACKAGE_PATH_NOT_EXPORTED: Package subpath is not defined by \"exports\" in /app/node_modules/pkg/package.json",
+        "ERR_PACKAGE_IMPORT_NOT_DEFINED: Package import specifier is not defined in package /app/package.json",
+        "ERR_INVALID_PACKAGE_TARGET: Invalid \"exports\" target defined in the package config /app/node_modules/pkg/package.json",
+        "ERR_UNSUPPORTED_ESM_URL_SCHEME: Only URLs with a scheme in: file, data, and node are supported by the default ESM loader. Received protocol 'foo:'",
+        "ERR_PACKAGE_PATH_NOT_EXPORTED: Package subpath './src/button.js' is not defined by \"exports\" in app; import 'app/button' instead",
+        "ABORT_ERR: The operation was aborted",
+      ]
+    );
+  }
+
+  #[test]
+  fn serialize_code() {
+    assert_eq!(
+      serde_json::to_value(ResolverError::ModuleNotFound {
+        module: "pkg".into()
+      })
+      .unwrap(),
+      serde_json::json!({
+        "type": "ModuleNotFound",
+        "module": "pkg",
+        "code": "ERR_MODULE_NOT_FOUND"
+      })
+    );
+    assert_eq!(
+      serde_json::to_value(ResolverError::Cancelled).unwrap(),
+      serde_json::json!({ "type": "Cancelled", "code": "ABORT_ERR" })
+    );
+    assert_eq!(
+      serde_json::to_value(ResolverError::InvalidSpecifier(
+        SpecifierError::EmptySpecifier
+      ))
+      .unwrap(),
+      serde_json::json!({
+        "type": "InvalidSpecifier",
+        "kind": "EmptySpecifier",
+        "code": "ERR_INVALID_MODULE_SPECIFIER"
+      })
+    );
+  }
+
+  #[test]
+  fn serialize_diagnostic() {
+    let diagnostic = Diagnostic::from(ResolverWarning::ManifestBackslashes {
+      path: "/app/node_modules/pkg/package.json".into(),
+      field: "main",
+      value: ".\\lib\\main.js".into(),
+    });
+    assert_eq!(
+      serde_json::to_value(&diagnostic).unwrap(),
+      serde_json::json!({
+        "severity": "warning",
+        "code": "PARCEL_MANIFEST_BACKSLASHES",
+        "message": "Backslashes in the \"main\" field of /app/node_modules/pkg/package.json were treated as path separators",
+        "file": "/app/node_modules/pkg/package.json",
+        "jsonPointer": "/main",
+        "hints": ["Use \"./lib/main.js\" instead"]
+      })
+    );
+
+    let error = ResolverError::ElevatedDiagnostic {
+      diagnostic: Diagnostic {
+        severity: Severity::Error,
+        ..diagnostic
+      },
+    };
+    assert_eq!(error.code(), "PARCEL_MANIFEST_BACKSLASHES");
+    assert_eq!(
+      error.to_string(),
+      "Backslashes in the \"main\" field of /app/node_modules/pkg/package.json were treated as path separators"
+    );
   }
 }
@@ -0,0 +1,83 @@
+/// A package, or set of packages, that should not be resolved, e.g. peer dependencies
+/// of a library build. Matching bare specifiers resolve to `Resolution::External`
+/// without reading the file system.
+#[derive(Debug, Clone)]
+pub enum ExternalPattern {
+  /// A package name, e.g. `react`. Subpaths of the package (e.g. `react/jsx-runtime`) also match.
+  Exact(String),
+  /// All packages in a scope, e.g. `@internal/*`. Holds the scope, e.g. `@internal`.
+  Scope(String),
+  /// A regular expression matched against the whole specifier, e.g. `^lodash(\.|/)`.
+  #[cfg(feature = "regex")]
+  Regex(regex::Regex),
+}
+
+impl ExternalPattern {
+  /// Parses a pattern written as a package name, or as a scope wildcard such as `@internal/*`.
+  pub fn parse(pattern: &str) -> ExternalPattern {
+    match pattern.strip_suffix("/*") {
+      Some(scope) if scope.starts_with('@') && !scope.contains('/') => {
+        ExternalPattern::Scope(scope.to_owned())
+      }
+      _ => ExternalPattern::Exact(pattern.to_owned()),
+    }
+  }
+
+  /// Returns whether the package `module`, imported with the given subpath, matches the pattern.
+  #[cfg_attr(not(feature = "regex"), allow(unused_variables))]
+  pub fn matches(&self, module: &str, subpath: &str) -> bool {
+    match self {
+      ExternalPattern::Exact(name) => name == module,
+      ExternalPattern::Scope(scope) => module
+        .strip_prefix(scope.as_str())
+        .is_some_and(|rest| rest.starts_with('/')),
+      #[cfg(feature = "regex")]
+      ExternalPattern::Regex(regex) => {
+        if subpath.is_empty() {
+          regex.is_match(module)
+        } else {
+          regex.is_match(&format!("{}/{}", module, subpath))
+        }
+      }
+    }
+  }
+}
+
+impl From<&str> for ExternalPattern {
+  fn from(pattern: &str) -> Self {
+    ExternalPattern::parse(pattern)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_external_pattern() {
+    let react = ExternalPattern::parse("react");
+    assert!(react.matches("react", ""));
+    assert!(react.matches("react", "jsx-runtime"));
+    assert!(!react.matches("react-dom", ""));
+
+    let scope = ExternalPattern::parse("@internal/*");
+    assert!(matches!(&scope, ExternalPattern::Scope(s) if s == "@internal"));
+    assert!(scope.matches("@internal/utils", ""));
+    assert!(scope.matches("@internal/utils", "sub/path"));
+    assert!(!scope.matches("@internal-other/utils", ""));
+    assert!(!scope.matches("internal", ""));
+
+    let scoped = ExternalPattern::parse("@scope/pkg");
+    assert!(scoped.matches("@scope/pkg", "foo"));
+    assert!(!scoped.matches("@scope/other", ""));
+  }
+
+  #[cfg(feature = "regex")]
+  #[test]
+  fn test_external_regex() {
+    let pattern = ExternalPattern::Regex(regex::Regex::new("^lodash(/|$)").unwrap());
+    assert!(pattern.matches("lodash", ""));
+    assert!(pattern.matches("lodash", "get"));
+    assert!(!pattern.matches("lodash-es", ""));
+  }
+}
@@ -1,11 +1,20 @@
 use std::{
-  io::Result,
+  io::{Error, ErrorKind, Result},
   path::{Path, PathBuf},
+  time::SystemTime,
 };
 
 use crate::path::canonicalize;
 use dashmap::DashMap;
 
+/// The size and modification time of a resolved file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+  pub size: u64,
+  /// `None` if the platform or file system doesn't record modification times.
+  pub mtime: Option<SystemTime>,
+}
+
 pub trait FileSystem: Send + Sync {
   fn canonicalize<P: AsRef<Path>>(
     &self,
@@ -13,8 +22,27 @@ pub trait FileSystem: Send + Sync {
     cache: &DashMap<PathBuf, Option<PathBuf>>,
   ) -> Result<PathBuf>;
   fn read_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String>;
+  /// Reads a file as bytes, e.g. a `.zip` archive for `LayeredFileSystem`. Defaults to
+  /// `read_to_string`, so file systems that can contain binary files should override it.
+  fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+    self.read_to_string(path).map(String::into_bytes)
+  }
   fn is_file<P: AsRef<Path>>(&self, path: P) -> bool;
   fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool;
+  /// Checks whether a path is a file like `is_file`, also returning its metadata if it was read
+  /// by the same check (e.g. a single `stat`). Returns `None` if the path isn't a file, and
+  /// `Some(None)` if it is but no metadata is available, which is the default.
+  fn file_metadata<P: AsRef<Path>>(&self, path: P) -> Option<Option<FileMetadata>> {
+    self.is_file(path).then_some(None)
+  }
+  /// Lists the paths of the entries in a directory. Only needed for tooling such as
+  /// `PackageJson::validate_exports`, not for resolution, so this is optional.
+  fn read_dir<P: AsRef<Path>>(&self, _path: P) -> Result<Vec<PathBuf>> {
+    Err(Error::new(
+      ErrorKind::Unsupported,
+      "read_dir is not supported",
+    ))
+  }
 }
 
 #[derive(Default)]
@@ -33,6 +61,10 @@ impl FileSystem for OsFileSystem {
     std::fs::read_to_string(path)
   }
 
+  fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+    std::fs::read(path)
+  }
+
   fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
     let path: &Path = path.as_ref();
     path.is_file()
@@ -42,4 +74,18 @@ impl FileSystem for OsFileSystem {
     let path: &Path = path.as_ref();
     path.is_dir()
   }
+
+  fn file_metadata<P: AsRef<Path>>(&self, path: P) -> Option<Option<FileMetadata>> {
+    let metadata = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some(Some(FileMetadata {
+      size: metadata.len(),
+      mtime: metadata.modified().ok(),
+    }))
+  }
+
+  fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Vec<PathBuf>> {
+    std::fs::read_dir(path)?
+      .map(|entry| entry.map(|entry| entry.path()))
+      .collect()
+  }
 }
@@ -0,0 +1,296 @@
+use std::{
+  borrow::Cow,
+  collections::HashSet,
+  hash::Hash,
+  path::{Path, PathBuf},
+  sync::RwLock,
+};
+
+use dashmap::DashMap;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::{
+  intern::{InternedPath, PathInterner},
+  path::normalize_path,
+  AliasDecision, Diagnostic, ExportsTraceStep, FileCreateInvalidation, FileMetadata, Invalidations,
+  ModuleOrigin, Query, Resolution, ResolveOptions, ResolveResult, ResolverError, SpecifierRewrite,
+  SpecifierType, SymlinkInfo, TsConfigUsage,
+};
+
+/// Identifies a resolution request. Returned from `Resolver::changed` so that callers
+/// can correlate stale resolutions with their own graph. The resolution cache belongs to a
+/// single `Resolver`, whose settings are fixed when it is built, so keys only cover the request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolutionKey {
+  pub specifier: String,
+  pub from: InternedPath,
+  pub specifier_type: SpecifierType,
+  /// A hash of the `ResolveOptions` used for the request.
+  pub options_hash: u64,
+}
+
+impl ResolutionKey {
+  pub fn new(
+    specifier: &str,
+    from: InternedPath,
+    specifier_type: SpecifierType,
+    options: &ResolveOptions,
+  ) -> Self {
+    let mut data = Vec::new();
+    data.extend_from_slice(&options.conditions.bits().to_le_bytes());
+    data.push(options.context as u8);
+    data.push(options.ignore_query_in_cache as u8);
+    for condition in &options.custom_conditions {
+      data.extend_from_slice(condition.as_bytes());
+      data.push(0);
+    }
+
+    let specifier = if options.ignore_query_in_cache {
+      split_query(specifier, specifier_type).0
+    } else {
+      Cow::Borrowed(specifier)
+    };
+
+    ResolutionKey {
+      specifier: specifier.into_owned(),
+      from,
+      specifier_type,
+      options_hash: xxh3_64(&data),
+    }
+  }
+}
+
+/// Splits the query off a specifier, e.g. `./img.png?width=100#hash` into `./img.png#hash` and
+/// `?width=100`. CommonJS specifiers never have a query, so they are returned as is.
+pub(crate) fn split_query(
+  specifier: &str,
+  specifier_type: SpecifierType,
+) -> (Cow<'_, str>, Option<&str>) {
+  let start = match specifier.find('?') {
+    Some(start) if specifier_type != SpecifierType::Cjs => start,
+    _ => return (Cow::Borrowed(specifier), None),
+  };
+
+  let end = specifier[start..]
+    .find('#')
+    .map_or(specifier.len(), |i| start + i);
+  let query = Some(&specifier[start..end]);
+  if end == specifier.len() {
+    (Cow::Borrowed(&specifier[..start]), query)
+  } else {
+    (
+      Cow::Owned(format!("{}{}", &specifier[..start], &specifier[end..])),
+      query,
+    )
+  }
+}
+
+/// Removes the given query keys from a specifier, so that e.g. `./foo.js?v=1` and `./foo.js?v=2`
+/// share a cache entry when `v` is ignored.
+pub(crate) fn without_query_keys<'s>(
+  specifier: &'s str,
+  specifier_type: SpecifierType,
+  keys: &[String],
+) -> Cow<'s, str> {
+  let query = match split_query(specifier, specifier_type).1 {
+    Some(query) if !keys.is_empty() => query,
+    _ => return Cow::Borrowed(specifier),
+  };
+
+  match Query::new(query).without_keys(keys) {
+    Some(stripped) if stripped.raw() == query => Cow::Borrowed(specifier),
+    stripped => Cow::Owned(specifier.replacen(query, stripped.as_ref().map_or("", Query::raw), 1)),
+  }
+}
+
+struct CachedResolution {
+  result: Result<(Resolution, Option<Query<'static>>), ResolverError>,
+  invalidate_on_file_change: HashSet<InternedPath>,
+  invalidate_on_file_create: HashSet<FileCreateInvalidation>,
+  rewrites: Vec<SpecifierRewrite<'static>>,
+  diagnostics: Vec<Diagnostic>,
+  import_type: Option<String>,
+  alias_trace: Vec<AliasDecision>,
+  consulted_manifests: Vec<PathBuf>,
+  exports_trace: Vec<ExportsTraceStep>,
+  symlink: Option<SymlinkInfo>,
+  origin: Option<ModuleOrigin>,
+  extensionless: bool,
+  native_addon: bool,
+  metadata: Option<FileMetadata>,
+  tsconfigs_used: Vec<TsConfigUsage>,
+}
+
+/// Caches resolution results along with a reverse index from their invalidations,
+/// so that we can determine exactly which resolutions are affected by a file system change.
+#[derive(Default)]
+pub(crate) struct ResolutionCache {
+  entries: DashMap<ResolutionKey, CachedResolution>,
+  on_change: DashMap<InternedPath, HashSet<ResolutionKey>>,
+  on_create: DashMap<InternedPath, HashSet<ResolutionKey>>,
+  on_create_above: DashMap<String, HashSet<(InternedPath, ResolutionKey)>>,
+}
+
+impl ResolutionCache {
+  pub fn get(&self, key: &ResolutionKey, interner: &PathInterner) -> Option<ResolveResult> {
+    let entry = self.entries.get(key)?;
+    let mut invalidations = Invalidations::new(interner.clone());
+    invalidations.invalidate_on_file_create = RwLock::new(entry.invalidate_on_file_create.clone());
+    invalidations.invalidate_on_file_change = RwLock::new(entry.invalidate_on_file_change.clone());
+    Some(ResolveResult {
+      result: entry.result.clone(),
+      invalidations,
+      rewrites: entry.rewrites.clone(),
+      diagnostics: entry.diagnostics.clone(),
+      import_type: entry.import_type.clone(),
+      alias_trace: entry.alias_trace.clone(),
+      consulted_manifests: entry.consulted_manifests.clone(),
+      exports_trace: entry.exports_trace.clone(),
+      symlink: entry.symlink.clone(),
+      origin: entry.origin.clone(),
+      extensionless: entry.extensionless,
+      native_addon: entry.native_addon,
+      metadata: entry.metadata,
+      tsconfigs_used: entry.tsconfigs_used.clone(),
+    })
+  }
+
+  pub fn insert(&self, key: ResolutionKey, res: &ResolveResult) {
+    let invalidate_on_file_change = res
+      .invalidations
+      .invalidate_on_file_change
+      .read()
+      .unwrap()
+      .clone();
+    let invalidate_on_file_create = res
+      .invalidations
+      .invalidate_on_file_create
+      .read()
+      .unwrap()
+      .clone();
+
+    for path in &invalidate_on_file_change {
+      self
+        .on_change
+        .entry(path.clone())
+        .or_default()
+        .insert(key.clone());
+    }
+
+    for invalidation in &invalidate_on_file_create {
+      match invalidation {
+        FileCreateInvalidation::Path(path) => {
+          self
+            .on_create
+            .entry(path.clone())
+            .or_default()
+            .insert(key.clone());
+        }
+        FileCreateInvalidation::FileName { file_name, above } => {
+          self
+            .on_create_above
+            .entry(file_name.clone())
+            .or_default()
+            .insert((above.clone(), key.clone()));
+        }
+      }
+    }
+
+    self.entries.insert(
+      key,
+      CachedResolution {
+        result: res.result.clone(),
+        invalidate_on_file_change,
+        invalidate_on_file_create,
+        rewrites: res.rewrites.clone(),
+        diagnostics: res.diagnostics.clone(),
+        import_type: res.import_type.clone(),
+        alias_trace: res.alias_trace.clone(),
+        consulted_manifests: res.consulted_manifests.clone(),
+        exports_trace: res.exports_trace.clone(),
+        symlink: res.symlink.clone(),
+        origin: res.origin.clone(),
+        extensionless: res.extensionless,
+        native_addon: res.native_addon,
+        metadata: res.metadata,
+        tsconfigs_used: res.tsconfigs_used.clone(),
+      },
+    );
+  }
+
+  /// Evicts and returns the keys of all resolutions affected by changes to the given paths.
+  /// Each path may have been created, updated, or deleted.
+  pub fn changed(&self, paths: &[PathBuf]) -> Vec<ResolutionKey> {
+    let mut stale = HashSet::new();
+    for path in paths {
+      let path = InternedPath::new(normalize_path(path));
+      if let Some(keys) = self.on_change.get(&path) {
+        stale.extend(keys.iter().cloned());
+      }
+
+      // Creating a file also creates any missing parent directories, e.g. adding
+      // node_modules/foo/index.js may create node_modules/foo. Check each ancestor.
+      for dir in path.ancestors() {
+        if let Some(keys) = self.on_create.get(&InternedPath::from(dir)) {
+          stale.extend(keys.iter().cloned());
+        }
+
+        for entry in self.on_create_above.iter() {
+          let file_name = Path::new(entry.key());
+          if !dir.ends_with(file_name) {
+            continue;
+          }
+
+          // The file was created in this directory, which must be the "above" path or one of its ancestors.
+          if let Some(base) = dir.ancestors().nth(file_name.components().count()) {
+            for (above, key) in entry.value() {
+              if above.starts_with(base) {
+                stale.insert(key.clone());
+              }
+            }
+          }
+        }
+      }
+    }
+
+    for key in &stale {
+      self.evict(key);
+    }
+
+    stale.into_iter().collect()
+  }
+
+  fn evict(&self, key: &ResolutionKey) {
+    let entry = match self.entries.remove(key) {
+      Some((_, entry)) => entry,
+      None => return,
+    };
+
+    for path in &entry.invalidate_on_file_change {
+      remove_from_index(&self.on_change, path, key);
+    }
+
+    for invalidation in &entry.invalidate_on_file_create {
+      match invalidation {
+        FileCreateInvalidation::Path(path) => remove_from_index(&self.on_create, path, key),
+        FileCreateInvalidation::FileName { file_name, above } => remove_from_index(
+          &self.on_create_above,
+          file_name,
+          &(above.clone(), key.clone()),
+        ),
+      }
+    }
+  }
+}
+
+fn remove_from_index<K: Hash + Eq, V: Hash + Eq>(
+  index: &DashMap<K, HashSet<V>>,
+  key: &K,
+  value: &V,
+) {
+  if let Some(mut values) = index.get_mut(key) {
+    values.remove(value);
+  }
+  index.remove_if(key, |_, values| values.is_empty());
+}
@@ -0,0 +1,236 @@
+use std::{
+  fmt,
+  hash::{Hash, Hasher},
+  ops::Deref,
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+use dashmap::DashMap;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// A reference counted path with a precomputed hash. Cloning, hashing, and comparing
+/// paths from the same [PathInterner] are O(1), which makes them cheap to use as map keys.
+/// Paths constructed directly (e.g. via `From<PathBuf>`) are not shared, but compare
+/// equal to interned paths with the same contents.
+#[derive(Clone)]
+pub struct InternedPath(Arc<PathEntry>);
+
+struct PathEntry {
+  hash: u64,
+  path: PathBuf,
+}
+
+fn hash_path(path: &Path) -> u64 {
+  xxh3_64(path.as_os_str().to_string_lossy().as_bytes())
+}
+
+impl InternedPath {
+  pub fn new(path: PathBuf) -> Self {
+    InternedPath(Arc::new(PathEntry {
+      hash: hash_path(&path),
+      path,
+    }))
+  }
+
+  pub fn as_path(&self) -> &Path {
+    &self.0.path
+  }
+
+  /// Returns the precomputed hash of the path.
+  pub fn hash_value(&self) -> u64 {
+    self.0.hash
+  }
+
+  pub fn to_path_buf(&self) -> PathBuf {
+    self.0.path.clone()
+  }
+}
+
+impl PartialEq for InternedPath {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+      || (self.0.hash == other.0.hash && self.0.path.as_os_str() == other.0.path.as_os_str())
+  }
+}
+
+impl Eq for InternedPath {}
+
+impl Hash for InternedPath {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    state.write_u64(self.0.hash);
+  }
+}
+
+impl Deref for InternedPath {
+  type Target = Path;
+
+  fn deref(&self) -> &Path {
+    &self.0.path
+  }
+}
+
+impl AsRef<Path> for InternedPath {
+  fn as_ref(&self) -> &Path {
+    &self.0.path
+  }
+}
+
+impl From<PathBuf> for InternedPath {
+  fn from(path: PathBuf) -> Self {
+    InternedPath::new(path)
+  }
+}
+
+impl From<&Path> for InternedPath {
+  fn from(path: &Path) -> Self {
+    InternedPath::new(path.to_path_buf())
+  }
+}
+
+impl From<InternedPath> for PathBuf {
+  fn from(path: InternedPath) -> Self {
+    match Arc::try_unwrap(path.0) {
+      Ok(entry) => entry.path,
+      Err(entry) => entry.path.clone(),
+    }
+  }
+}
+
+impl PartialEq<Path> for InternedPath {
+  fn eq(&self, other: &Path) -> bool {
+    self.0.path.as_os_str() == other.as_os_str()
+  }
+}
+
+impl PartialEq<PathBuf> for InternedPath {
+  fn eq(&self, other: &PathBuf) -> bool {
+    self.0.path.as_os_str() == other.as_os_str()
+  }
+}
+
+impl fmt::Debug for InternedPath {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    self.0.path.fmt(f)
+  }
+}
+
+impl serde::Serialize for InternedPath {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    self.0.path.serialize(serializer)
+  }
+}
+
+impl<'de> serde::Deserialize<'de> for InternedPath {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    PathBuf::deserialize(deserializer).map(InternedPath::new)
+  }
+}
+
+/// Deduplicates paths so that equal paths share a single allocation.
+/// Entries are bucketed by hash in a sharded map, so interning from multiple
+/// threads does not contend on a global lock. Cloning the interner is cheap
+/// and shares the underlying storage.
+///
+/// Paths are kept until [PathInterner::purge] is called, so the interner grows with the
+/// number of distinct paths seen. Long-running processes (e.g. watchers) should purge it
+/// periodically.
+#[derive(Clone, Default)]
+pub struct PathInterner {
+  paths: Arc<DashMap<u64, Vec<InternedPath>>>,
+}
+
+impl PathInterner {
+  pub fn intern(&self, path: &Path) -> InternedPath {
+    let hash = hash_path(path);
+    if let Some(bucket) = self.paths.get(&hash) {
+      if let Some(interned) = bucket.iter().find(|p| p.as_os_str() == path.as_os_str()) {
+        return interned.clone();
+      }
+    }
+
+    let mut bucket = self.paths.entry(hash).or_default();
+    if let Some(interned) = bucket.iter().find(|p| p.as_os_str() == path.as_os_str()) {
+      return interned.clone();
+    }
+
+    let interned = InternedPath(Arc::new(PathEntry {
+      hash,
+      path: path.to_path_buf(),
+    }));
+    bucket.push(interned.clone());
+    interned
+  }
+
+  /// Returns the number of distinct paths that have been interned.
+  pub fn len(&self) -> usize {
+    self.paths.iter().map(|bucket| bucket.len()).sum()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.paths.is_empty()
+  }
+
+  /// Removes paths that are no longer referenced outside the interner, and returns how many
+  /// were removed. Paths that are still in use, e.g. in cached results, are kept.
+  pub fn purge(&self) -> usize {
+    let mut removed = 0;
+    self.paths.retain(|_, bucket| {
+      let len = bucket.len();
+      bucket.retain(|path| Arc::strong_count(&path.0) > 1);
+      removed += len - bucket.len();
+      !bucket.is_empty()
+    });
+    removed
+  }
+}
+
+impl fmt::Debug for PathInterner {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PathInterner")
+      .field("len", &self.len())
+      .finish()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::collections::HashSet;
+
+  #[test]
+  fn test_intern() {
+    let interner = PathInterner::default();
+    let a = interner.intern(Path::new("/foo/bar.js"));
+    let b = interner.intern(Path::new("/foo/bar.js"));
+    let c = interner.intern(Path::new("/foo/baz.js"));
+    assert!(Arc::ptr_eq(&a.0, &b.0));
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert_eq!(interner.len(), 2);
+
+    // Paths that weren't interned still compare and hash equal.
+    let d = InternedPath::from(PathBuf::from("/foo/bar.js"));
+    assert_eq!(a, d);
+    assert_eq!(HashSet::from([a.clone(), c]).get(&d), Some(&a));
+    assert_eq!(a, PathBuf::from("/foo/bar.js"));
+    assert_eq!(PathBuf::from(d), PathBuf::from("/foo/bar.js"));
+  }
+
+  #[test]
+  fn test_purge() {
+    let interner = PathInterner::default();
+    let a = interner.intern(Path::new("/foo/bar.js"));
+    interner.intern(Path::new("/foo/baz.js"));
+    assert_eq!(interner.purge(), 1);
+    assert_eq!(interner.len(), 1);
+    assert!(Arc::ptr_eq(
+      &interner.intern(Path::new("/foo/bar.js")).0,
+      &a.0
+    ));
+
+    drop(a);
+    assert_eq!(interner.purge(), 1);
+    assert!(interner.is_empty());
+  }
+}
@@ -1,30 +1,51 @@
-use std::{
-  collections::HashSet,
-  path::{Path, PathBuf},
-  sync::RwLock,
-};
+use std::{collections::HashSet, path::Path, sync::RwLock};
 
-use crate::{path::normalize_path, ResolverError};
+use crate::{
+  intern::{InternedPath, PathInterner},
+  path::normalize_path,
+  ResolverError,
+};
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub enum FileCreateInvalidation {
-  Path(PathBuf),
-  FileName { file_name: String, above: PathBuf },
+  Path(InternedPath),
+  FileName {
+    file_name: String,
+    above: InternedPath,
+  },
 }
 
 #[derive(Default, Debug)]
 pub struct Invalidations {
   pub invalidate_on_file_create: RwLock<HashSet<FileCreateInvalidation>>,
-  pub invalidate_on_file_change: RwLock<HashSet<PathBuf>>,
+  pub invalidate_on_file_change: RwLock<HashSet<InternedPath>>,
+  interner: PathInterner,
 }
 
 impl Invalidations {
+  /// Creates an empty set of invalidations whose paths are interned in the given interner.
+  pub fn new(interner: PathInterner) -> Self {
+    Invalidations {
+      interner,
+      ..Default::default()
+    }
+  }
+
+  /// The interner that paths in these invalidations are interned in.
+  pub fn interner(&self) -> &PathInterner {
+    &self.interner
+  }
+
+  fn intern(&self, path: &Path) -> InternedPath {
+    self.interner.intern(&normalize_path(path))
+  }
+
   pub fn invalidate_on_file_create(&self, path: &Path) {
     self
       .invalidate_on_file_create
       .write()
       .unwrap()
-      .insert(FileCreateInvalidation::Path(normalize_path(path)));
+      .insert(FileCreateInvalidation::Path(self.intern(path)));
   }
 
   pub fn invalidate_on_file_create_above<S: Into<String>>(&self, file_name: S, above: &Path) {
@@ -34,7 +55,7 @@ impl Invalidations {
       .unwrap()
       .insert(FileCreateInvalidation::FileName {
         file_name: file_name.into(),
-        above: normalize_path(above),
+        above: self.intern(above),
       });
   }
 
@@ -43,7 +64,7 @@ impl Invalidations {
       .invalidate_on_file_change
       .write()
       .unwrap()
-      .insert(normalize_path(invalidation));
+      .insert(self.intern(invalidation));
   }
 
   pub fn read<V, F: FnOnce() -> Result<V, ResolverError>>(
@@ -1,38 +1,77 @@
 use bitflags::bitflags;
 use once_cell::unsync::OnceCell;
-use specifier::{parse_package_specifier, parse_scheme};
+use specifier::{parse_package_specifier, parse_scheme, strip_invisible_prefix};
 use std::{
   borrow::Cow,
+  cell::RefCell,
   collections::HashMap,
-  path::{Path, PathBuf},
+  ffi::{OsStr, OsString},
+  path::{Component, Path, PathBuf},
   sync::Arc,
 };
 
-use package_json::{AliasValue, ExportsResolution, PackageJson};
-use specifier::Specifier;
+use incremental::{split_query, without_query_keys, ResolutionCache};
+use metrics::{MetricsCollector, Phase};
+use package_json::{AliasValue, ExportsResolution};
 use tsconfig::TsConfig;
 
+#[cfg(feature = "archive")]
+mod archive;
+mod builder;
 mod builtins;
 mod cache;
+mod cancellation;
+mod environment;
 mod error;
+mod externals;
 mod fs;
+mod incremental;
+mod intern;
 mod invalidations;
+mod limits;
+mod memory_fs;
+mod metrics;
 mod package_json;
 mod path;
+mod query;
 mod specifier;
+mod specifier_cache;
 mod tsconfig;
+mod types_versions;
 
+#[cfg(feature = "archive")]
+pub use archive::LayeredFileSystem;
+pub use builder::{Preset, ResolverBuilder};
 pub use cache::{Cache, CacheCow};
-pub use error::ResolverError;
-pub use fs::{FileSystem, OsFileSystem};
+pub use cancellation::CancellationToken;
+pub use environment::{Environment, ESMODULES_CONDITION, MODERN_CONDITION};
+pub use error::{
+  Diagnostic, ResolutionStep, ResolverBuilderError, ResolverError, ResolverWarning, Severity,
+};
+pub use externals::ExternalPattern;
+pub use fs::{FileMetadata, FileSystem, OsFileSystem};
+pub use incremental::ResolutionKey;
+pub use intern::{InternedPath, PathInterner};
 pub use invalidations::*;
-pub use package_json::{ExportsCondition, Fields, PackageJsonError};
-pub use specifier::SpecifierType;
+pub use limits::{Limit, ResolverLimits};
+pub use memory_fs::MemoryFileSystem;
+pub use metrics::{PhaseMetrics, ResolverMetrics};
+pub use package_json::{
+  ExportsCondition, ExportsLintIssue, ExportsOptions, ExportsTraceStep, Fields, ModuleType,
+  PackageJson, PackageJsonError,
+};
+pub use query::Query;
+pub use specifier::{
+  has_duplicate_slashes, split_specifier, NormalizedSpecifier, ParseContext, ParseOptions,
+  Specifier, SpecifierError, SpecifierMeta, SpecifierParts, SpecifierRewrite, SpecifierType,
+};
+pub use specifier_cache::SpecifierCache;
+pub use types_versions::TsVersion;
 
-use crate::path::resolve_path;
+use crate::path::{has_trailing_slash, is_directory_path, normalize_path, resolve_path};
 
 bitflags! {
-  pub struct Flags: u16 {
+  pub struct Flags: u32 {
     /// Parcel-style absolute paths resolved relative to project root.
     const ABSOLUTE_SPECIFIERS = 1 << 0;
     /// Parcel-style tilde specifiers resolved relative to nearest module root.
@@ -55,11 +94,33 @@ bitflags! {
     const PARENT_EXTENSION = 1 << 9;
     /// Whether to allow optional extensions in the "exports" field.
     const EXPORTS_OPTIONAL_EXTENSIONS = 1 << 10;
+    /// Whether to lexically normalize "." and ".." segments in package subpaths, e.g. `lodash/./fp/../merge`.
+    const NORMALIZE_PACKAGE_SUBPATH = 1 << 11;
+    /// Whether specifiers with a single leading "/" are parsed as relative to the project root, for all specifier types.
+    const ROOT_ABSOLUTE = 1 << 12;
+    /// Whether backslashes in package.json paths (e.g. `"main": ".\\lib\\index.js"`) are treated as separators.
+    const MANIFEST_BACKSLASHES = 1 << 13;
+    /// Whether backslashes in specifiers are treated as separators on every host, as on Windows.
+    /// Useful when parsing specifiers for a target platform other than the host.
+    const WINDOWS_SEPARATORS = 1 << 14;
+    /// Whether unscoped bare ESM specifiers (e.g. `sibling`) are first resolved relative to the
+    /// importing file, for transpiled output that dropped the leading "./". Builtins are unaffected.
+    const IMPLICIT_RELATIVE = 1 << 15;
+    /// Whether percent-encoded separators (`%2F` and `%5C`) in ESM and URL specifiers are an
+    /// error, as in Node ESM, rather than kept as part of a file name.
+    const REJECT_ENCODED_SEPARATORS = 1 << 16;
+    /// Whether bare specifiers must name a builtin, e.g. for a sandboxed runtime without npm
+    /// packages. Other package specifiers are rejected while parsing. Paths are unaffected.
+    const ONLY_BUILTINS = 1 << 17;
 
     /// Default Node settings for CommonJS.
     const NODE_CJS = Self::EXPORTS.bits | Self::DIR_INDEX.bits | Self::OPTIONAL_EXTENSIONS.bits;
     /// Default Node settings for ESM.
-    const NODE_ESM = Self::EXPORTS.bits;
+    const NODE_ESM = Self::EXPORTS.bits | Self::REJECT_ENCODED_SEPARATORS.bits;
+    /// Default Parcel settings.
+    const PARCEL = Self::ABSOLUTE_SPECIFIERS.bits | Self::TILDE_SPECIFIERS.bits | Self::NPM_SCHEME.bits | Self::ALIASES.bits | Self::TSCONFIG.bits | Self::EXPORTS.bits | Self::DIR_INDEX.bits | Self::OPTIONAL_EXTENSIONS.bits | Self::TYPESCRIPT_EXTENSIONS.bits | Self::PARENT_EXTENSION.bits | Self::EXPORTS_OPTIONAL_EXTENSIONS.bits | Self::MANIFEST_BACKSLASHES.bits;
+    /// Default settings for a generic bundler, similar to webpack or esbuild.
+    const BUNDLER = Self::ALIASES.bits | Self::TSCONFIG.bits | Self::EXPORTS.bits | Self::DIR_INDEX.bits | Self::OPTIONAL_EXTENSIONS.bits | Self::TYPESCRIPT_EXTENSIONS.bits | Self::EXPORTS_OPTIONAL_EXTENSIONS.bits;
     /// Default TypeScript settings.
     const TYPESCRIPT = Self::TSCONFIG.bits | Self::EXPORTS.bits | Self::DIR_INDEX.bits | Self::OPTIONAL_EXTENSIONS.bits | Self::TYPESCRIPT_EXTENSIONS.bits | Self::EXPORTS_OPTIONAL_EXTENSIONS.bits;
   }
@@ -78,18 +139,346 @@ impl Default for IncludeNodeModules {
   }
 }
 
+/// What to do when the conditions object for a package's "." export matches none of the
+/// active conditions and has no "default".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fallthrough {
+  /// Treat the package as not exported, as Node does.
+  #[default]
+  Error,
+  /// Fall back to the legacy entry fields, e.g. "main", so older dual-mode packages keep working.
+  LegacyMain,
+}
+
+/// A place where a bare or builtin specifier may be remapped before it is looked up in node_modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AliasSource {
+  /// The "alias" field of the package.json in the project root.
+  RootAlias,
+  /// The "alias" field of the package.json nearest to the importing file.
+  LocalAlias,
+  /// The "browser" field of the package.json nearest to the importing file.
+  /// Only consulted when the "browser" entry field is enabled. The object form of custom entry
+  /// fields, e.g. "react-native", is consulted next.
+  LocalBrowser,
+  /// The "paths" and "baseUrl" options of the tsconfig.json nearest to the importing file.
+  TsConfigPaths,
+}
+
+impl AliasSource {
+  /// The default precedence: the project root's aliases apply everywhere, then the importing
+  /// package's own aliases and browser field, then tsconfig paths. The first source that
+  /// remaps the specifier wins, and the rest are not consulted.
+  pub const DEFAULT_PRECEDENCE: &'static [AliasSource] = &[
+    AliasSource::RootAlias,
+    AliasSource::LocalAlias,
+    AliasSource::LocalBrowser,
+    AliasSource::TsConfigPaths,
+  ];
+}
+
+/// A file name, without an extension, tried when resolving a directory that has no
+/// package.json entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IndexName {
+  /// A literal file name, e.g. `index` or `main`.
+  Literal(Cow<'static, str>),
+  /// The name of the directory itself, e.g. `Button/Button.tsx`.
+  DirectoryName,
+}
+
+impl IndexName {
+  /// The default index names: only `index`.
+  pub const DEFAULT: &'static [IndexName] = &[IndexName::Literal(Cow::Borrowed("index"))];
+
+  /// The file name to try within the given directory, if any.
+  pub fn file_name<'s>(&'s self, dir: &'s Path) -> Option<&'s OsStr> {
+    match self {
+      IndexName::Literal(name) => Some(OsStr::new(name.as_ref())),
+      IndexName::DirectoryName => dir.file_name(),
+    }
+  }
+}
+
+impl From<&str> for IndexName {
+  fn from(name: &str) -> Self {
+    IndexName::Literal(Cow::Owned(name.to_owned()))
+  }
+}
+
+/// How an alias source was handled for a specifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AliasOutcome {
+  /// The source remapped the specifier (or failed while resolving its target).
+  Won,
+  /// The source was consulted but had no entry for the specifier.
+  NoMatch,
+  /// The source was not consulted because a source earlier in the precedence won.
+  Skipped,
+}
+
+/// Records which alias sources were consulted for a specifier, and in which order.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AliasDecision {
+  pub specifier: String,
+  pub source: AliasSource,
+  pub outcome: AliasOutcome,
+}
+
+/// The kind of path component that was a symlink, see [SymlinkInfo].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum LinkKind {
+  /// A package directory in node_modules, e.g. a linked workspace dependency.
+  Package,
+  /// Another directory along the path.
+  Directory,
+  /// The resolved file itself.
+  File,
+}
+
+/// Controls when resolved paths are replaced with their real path, see `Resolver::symlink_mode`.
+/// Only paths that pass through a symlink are affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SymlinkMode {
+  /// Always return the real path. This is the default.
+  #[default]
+  CanonicalizeAll,
+  /// Always return the path as reached, like Node's `--preserve-symlinks`.
+  PreserveAll,
+  /// Return the real path only for paths reached outside node_modules, e.g. through a symlinked
+  /// source directory. Anything reached through node_modules keeps its path, including linked
+  /// workspace packages.
+  CanonicalizeProjectOnly,
+  /// Return the real path unless it is inside node_modules, e.g. in pnpm's store, so that pnpm
+  /// packages keep the path their peer dependencies are resolved from. Unlike
+  /// `CanonicalizeProjectOnly`, linked workspace packages resolve to their real location.
+  PreserveNodeModulesOnly,
+}
+
+/// A tsconfig.json that applied to the importing file, see `ResolveResult::tsconfigs_used`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TsConfigUsage {
+  pub path: PathBuf,
+  /// Whether this tsconfig's `paths` or `baseUrl` produced the resolved path. Only one entry
+  /// can be set, since the first tsconfig in extends order that sets them wins.
+  pub affected_result: bool,
+}
+
+/// The package that contains a path, see `Resolver::find_package_root`.
+#[derive(Debug, Clone, Copy)]
+pub struct PackageRoot<'a> {
+  /// The path of the package.json.
+  pub manifest: &'a Path,
+  pub package: &'a PackageJson<'a>,
+  /// Whether the package is installed in node_modules, rather than part of the project.
+  pub in_node_modules: bool,
+}
+
+/// Files whose directory is the root of a project, see `Resolver::find_project_root`.
+const PROJECT_ROOT_MARKERS: &[&str] = &[
+  ".git",
+  "yarn.lock",
+  "package-lock.json",
+  "pnpm-lock.yaml",
+  "bun.lockb",
+  "bun.lock",
+];
+
+/// Describes how a resolved path was reached through a symlink. `link_path` is the path as
+/// reached through node_modules or the specifier, and `canonical_path` is the real path
+/// returned in `Resolution::Path`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymlinkInfo {
+  pub canonical_path: InternedPath,
+  pub link_path: PathBuf,
+  /// The first symlink within `link_path`.
+  pub link: PathBuf,
+  pub kind: LinkKind,
+}
+
+impl SymlinkInfo {
+  fn new(canonical_path: InternedPath, link_path: PathBuf, link: PathBuf) -> Self {
+    let kind = if link == link_path {
+      LinkKind::File
+    } else if is_package_dir(&link) {
+      LinkKind::Package
+    } else {
+      LinkKind::Directory
+    };
+
+    SymlinkInfo {
+      canonical_path,
+      link_path,
+      link,
+      kind,
+    }
+  }
+}
+
+/// Where a resolved module comes from, see `ResolveResult::origin`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum ModuleOrigin {
+  /// First-party code, including workspace packages linked into node_modules.
+  Project,
+  /// A dependency installed in node_modules, including pnpm's store.
+  #[serde(rename_all = "camelCase")]
+  NodeModules { package_name: String },
+  /// A module that is not bundled, i.e. `Resolution::External` or `Resolution::Global`.
+  External,
+  /// A runtime builtin module.
+  Builtin,
+}
+
+/// Returns the name of the package containing a path, from the segment after the last module
+/// directory, e.g. `foo` for `node_modules/.pnpm/foo@1.0.0/node_modules/foo/index.js`.
+fn package_name_in(path: &Path, module_dirs: &[String]) -> Option<String> {
+  let components: Vec<_> = path.components().map(|c| c.as_os_str()).collect();
+  let index = components
+    .iter()
+    .rposition(|c| module_dirs.iter().any(|dir| *c == dir.as_str()))?;
+  let name = components.get(index + 1)?.to_str()?;
+  if name.starts_with('@') {
+    let scoped = components.get(index + 2)?.to_str()?;
+    Some(format!("{}/{}", name, scoped))
+  } else {
+    Some(name.to_owned())
+  }
+}
+
+/// What an `AliasSource` is applied to.
+#[derive(Clone, Copy)]
+enum AliasInput<'s> {
+  /// A bare or builtin specifier, as written in the importing file.
+  Specifier(&'s Specifier<'s>),
+  /// A file that a path specifier resolved to, and the package containing it. Aliases match it
+  /// by its path relative to each package.json, e.g. `./lib/fs.js`.
+  File(&'s Path, Option<&'s PackageJson<'s>>),
+}
+
+/// Returns the specifier that aliases in `package` are matched against, or `None` for a file
+/// outside the package.
+fn alias_specifier<'s>(
+  package: &PackageJson,
+  input: AliasInput<'s>,
+) -> Option<Cow<'s, Specifier<'s>>> {
+  match input {
+    AliasInput::Specifier(specifier) => Some(Cow::Borrowed(specifier)),
+    AliasInput::File(path, _) => {
+      let path = path.strip_prefix(package.path.parent()?).ok()?;
+      Some(Cow::Owned(Specifier::Relative(Cow::Borrowed(path))))
+    }
+  }
+}
+
+/// Wraps an error from a package's package.json, e.g. an invalid "browser" field.
+fn package_json_error(package: &PackageJson, error: PackageJsonError) -> ResolverError {
+  ResolverError::PackageJsonError {
+    module: package.name.to_owned(),
+    path: package.path.clone(),
+    error,
+  }
+}
+
+/// Returns whether a path is a native addon, e.g. `build/Release/binding.node`.
+fn is_native_addon(path: &Path) -> bool {
+  path.extension().is_some_and(|ext| ext == "node")
+}
+
+/// Returns whether a directory is a package in node_modules, e.g. `node_modules/foo`
+/// or `node_modules/@scope/foo`.
+fn is_package_dir(dir: &Path) -> bool {
+  let parent = match dir.parent() {
+    Some(parent) => parent,
+    None => return false,
+  };
+
+  match parent.file_name().and_then(|name| name.to_str()) {
+    Some("node_modules") => true,
+    Some(scope) if scope.starts_with('@') => {
+      parent.parent().and_then(|p| p.file_name()) == Some("node_modules".as_ref())
+    }
+    _ => false,
+  }
+}
+
 type ResolveModuleDir = dyn Fn(&str, &Path) -> Result<PathBuf, ResolverError> + Send + Sync;
 
+/// Resolves specifiers using settings that are fixed when it is built, see [ResolverBuilder].
 pub struct Resolver<'a, Fs> {
-  pub project_root: Cow<'a, Path>,
-  pub extensions: Extensions<'a>,
-  pub index_file: &'a str,
-  pub entries: Fields,
-  pub flags: Flags,
-  pub include_node_modules: Cow<'a, IncludeNodeModules>,
-  pub conditions: ExportsCondition,
-  pub module_dir_resolver: Option<Arc<ResolveModuleDir>>,
+  project_root: Cow<'a, Path>,
+  extensions: Extensions<'a>,
+  /// The file names tried, in order and with each extension, when resolving a directory
+  /// without a package.json entry. Defaults to `IndexName::DEFAULT`.
+  index_names: Cow<'a, [IndexName]>,
+  entries: Fields,
+  /// Additional package.json entry fields, e.g. "svelte" or "sass", tried in order before
+  /// `entries`. See `PackageJson::custom_entry`.
+  custom_entries: Cow<'a, [String]>,
+  flags: Flags,
+  include_node_modules: Cow<'a, IncludeNodeModules>,
+  conditions: ExportsCondition,
+  /// Condition names in priority order, e.g. `["worker", "browser"]` for a worker build that
+  /// also enables "browser". Only the first of these that is active is kept, and the rest are
+  /// removed from the active conditions. Unlisted conditions are unaffected. This only decides
+  /// which conditions are active: between active conditions, key order in "exports" still
+  /// decides, as in Node.
+  condition_priority: Cow<'a, [String]>,
+  module_dirs: Cow<'a, [String]>,
+  module_dir_resolver: Option<Arc<ResolveModuleDir>>,
+  parse_options: ParseOptions,
+  unmatched_conditions: Fallthrough,
+  /// The order in which alias sources are consulted for bare and builtin specifiers.
+  /// Sources that are not listed are ignored. Defaults to `AliasSource::DEFAULT_PRECEDENCE`.
+  alias_precedence: Cow<'a, [AliasSource]>,
+  /// Limits on the size of inputs, which protect against pathological specifiers and packages.
+  limits: ResolverLimits,
+  /// Packages that resolve to `Resolution::External` without reading the file system.
+  externals: Cow<'a, [ExternalPattern]>,
+  /// Whether to check that packages matching `externals` are installed, so that a missing
+  /// package is still reported as an error. Matching packages resolve to `Resolution::External`.
+  verify_externals: bool,
+  /// The TypeScript version used to select a mapping from each package's "typesVersions"
+  /// field, when resolving the "types" entry. The field is ignored if this is `None`.
+  typescript_version: Option<TsVersion>,
+  /// Whether to prefer source files over build output for packages outside node_modules,
+  /// e.g. monorepo members symlinked from the workspace.
+  prefer_source_in_workspace: bool,
+  /// Whether to replace files in nested copies of a package (e.g. `node_modules/a/node_modules/b`)
+  /// with the same file in an ancestor node_modules directory that has the same version.
+  dedupe_packages: bool,
+  /// Packages that are never deduplicated, e.g. because they depend on their install location.
+  dedupe_exclude: Cow<'a, [String]>,
+  /// Whether a specifier naming an existing directory resolves to `Resolution::Directory`,
+  /// rather than to its package.json entries or index file, e.g. `url('./fonts/')` in an asset
+  /// pipeline. Files still take precedence unless the specifier ends with a slash.
+  allow_directory_resolution: bool,
+  /// Whether tilde and absolute specifiers that point into the importer's own package must go
+  /// through its "exports" field, like a self-reference by name, when the package has one.
+  /// Relative specifiers are unaffected. This enforces a package's public API within itself.
+  enforce_own_exports: bool,
+  /// When resolved paths are replaced with their real path. Defaults to
+  /// `SymlinkMode::CanonicalizeAll`.
+  symlink_mode: SymlinkMode,
+  /// Whether to collect additional diagnostic information in each `ResolveResult`,
+  /// such as `consulted_manifests`.
+  diagnostics: bool,
+  /// Overrides the severity of diagnostics by code, e.g. to fail resolutions that would warn
+  /// about `PARCEL_MANIFEST_BACKSLASHES`. `None` silences the code. Other codes are warnings.
+  diagnostic_levels: Cow<'a, [(String, Option<Severity>)]>,
+  /// The thread pool that `resolve_many` runs on. The global rayon pool is used if this is `None`.
+  #[cfg(feature = "rayon")]
+  thread_pool: Option<Arc<rayon::ThreadPool>>,
+  /// Aliases configured with `ResolverBuilder::alias`, as a package.json in the project root.
+  root_aliases: Option<PackageJson<'static>>,
   cache: CacheCow<'a, Fs>,
+  metrics: Option<Arc<MetricsCollector>>,
+  resolution_cache: Option<ResolutionCache>,
 }
 
 pub enum Extensions<'a> {
@@ -106,73 +495,159 @@ impl<'a> Extensions<'a> {
   }
 }
 
-#[derive(Default)]
+/// Selects the resolution rules for a kind of import, see `ResolveOptions::context`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ResolutionContext {
+  /// The rules configured on the resolver.
+  #[default]
+  Default,
+  /// The rules for Sass `@use` and `@import`. Bare specifiers are tried relative to the importer
+  /// first. Files are tried as written and with the `.scss`, `.sass`, and `.css` extensions, each
+  /// also as a partial with a leading `_` (e.g. `_functions.scss`). Non-partials take precedence.
+  /// Directories load an `index` or `_index` file, and packages use the "sass", "style", and
+  /// "main" entry fields in that order, along with the "sass" and "style" exports conditions.
+  Sass,
+  /// The rules for type information, e.g. when generating `.d.ts` files. The "types" exports
+  /// condition is added, and packages use the "types" (or "typings") entry field first.
+  /// Declaration files are preferred: `./foo.js` loads `./foo.d.ts` if it exists, likewise
+  /// `.d.mts` for `.mjs` and `.d.cts` for `.cjs`, and `./foo` tries each declaration extension
+  /// before the configured extensions.
+  Types,
+}
+
+const SASS_EXTENSIONS: &[&str] = &["scss", "sass", "css"];
+/// The extensions tried by Node's legacy main resolution, see `ResolveRequest::load_legacy_main`.
+const LEGACY_MAIN_EXTENSIONS: &[&str] = &["js", "json", "node"];
+const SASS_ENTRY_FIELDS: &[&str] = &["sass", "style"];
+const DECLARATION_EXTENSIONS: &[&str] = &["d.ts", "d.mts", "d.cts"];
+
+#[derive(Default, Clone)]
 pub struct ResolveOptions {
   pub conditions: ExportsCondition,
   pub custom_conditions: Vec<String>,
+  /// Cancels the resolution when set, see [CancellationToken].
+  pub cancellation: Option<CancellationToken>,
+  /// Shares a single resolution cache entry between specifiers that differ only in their query,
+  /// e.g. `./img.png?width=100` and `./img.png?width=200`. By default, each query is cached
+  /// separately. Either way, the query returned is the one from the request.
+  pub ignore_query_in_cache: bool,
+  /// The resolution rules to use for this request, e.g. `ResolutionContext::Sass` for
+  /// stylesheets. Defaults to the rules configured on the resolver.
+  pub context: ResolutionContext,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, serde::Serialize)]
 #[serde(tag = "type", content = "value")]
 pub enum Resolution {
   /// Resolved to a file path.
-  Path(PathBuf),
+  Path(InternedPath),
   /// Resolved to a runtime builtin module.
   Builtin(String),
   /// Resolved to an external module that should not be bundled.
-  External,
+  External { specifier: String },
   /// Resolved to an empty module (e.g. `false` in the package.json#browser field).
   Empty,
   /// Resolved to a global variable.
   Global(String),
+  /// Resolved to a virtual module provided by a framework, e.g. `$app/stores`.
+  Virtual(String),
+  /// Resolved to a directory, with `Resolver::allow_directory_resolution`.
+  Directory(InternedPath),
+}
+
+/// How far a failed resolution got, returned by `Resolver::resolve_lenient`. Editor tooling
+/// can use this to navigate into a package even if the requested subpath could not be resolved.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialResolution {
+  /// The root directory of the package that was located.
+  pub package_root: Option<PathBuf>,
+  /// The package.json of the package that was located.
+  pub manifest: Option<PathBuf>,
+  /// The "exports" key most similar to the subpath, if it was not exported.
+  pub closest_export: Option<String>,
+  /// The directory in which the file or index file was expected.
+  pub expected_dir: Option<PathBuf>,
+}
+
+impl PartialResolution {
+  fn expected_dir(error: &ResolverError) -> Option<PathBuf> {
+    match error {
+      ResolverError::FileNotFound { relative, from } => {
+        resolve_path(from, relative).parent().map(|p| p.to_owned())
+      }
+      ResolverError::DirectoryWithoutIndex { relative, from, .. } => {
+        Some(resolve_path(from, relative))
+      }
+      ResolverError::ModuleEntryNotFound { entry_path, .. } => {
+        entry_path.parent().map(|p| p.to_owned())
+      }
+      ResolverError::ModuleSubpathNotFound { path, .. } => path.parent().map(|p| p.to_owned()),
+      _ => None,
+    }
+  }
 }
 
 pub struct ResolveResult {
-  pub result: Result<(Resolution, Option<String>), ResolverError>,
+  pub result: Result<(Resolution, Option<Query<'static>>), ResolverError>,
   pub invalidations: Invalidations,
+  /// The specifiers that were remapped by aliases while resolving, in order.
+  pub rewrites: Vec<SpecifierRewrite<'static>>,
+  /// Non-fatal problems encountered while resolving, e.g. warnings. Diagnostics elevated to
+  /// errors are also returned as `ResolverError::ElevatedDiagnostic`. Empty in the common case,
+  /// which doesn't allocate.
+  pub diagnostics: Vec<Diagnostic>,
+  /// The import type indicated by a typed import prefix, e.g. `text` for `text:./readme.md`.
+  pub import_type: Option<String>,
+  /// The alias sources consulted for each bare or builtin specifier, including those reached
+  /// through other aliases. Only collected when `Resolver::diagnostics` is enabled.
+  pub alias_trace: Vec<AliasDecision>,
+  /// The package.json files that were read and used while resolving, in the order they were
+  /// first consulted. Unlike invalidations, this excludes paths that were probed but didn't exist.
+  /// Only collected when `Resolver::diagnostics` is enabled.
+  pub consulted_manifests: Vec<PathBuf>,
+  /// The "exports" conditions that were checked, in order, when resolution failed.
+  /// Only collected when `Resolver::diagnostics` is enabled.
+  pub exports_trace: Vec<ExportsTraceStep>,
+  /// Set when the resolved path was reached through a symlink, e.g. a linked package.
+  pub symlink: Option<SymlinkInfo>,
+  /// Whether the resolved module is first-party code or a dependency. This uses the real path,
+  /// so a package in pnpm's store is still a dependency, and a linked workspace package is part
+  /// of the project, regardless of `Resolver::symlink_mode`. `None` for empty and virtual
+  /// modules, and when resolution failed.
+  pub origin: Option<ModuleOrigin>,
+  /// Whether the resolved file has no extension, e.g. a `bin` script, so its type may need
+  /// to be detected from its contents (e.g. a shebang).
+  pub extensionless: bool,
+  /// Whether the resolved file is a native addon (a `.node` file), which bundlers usually
+  /// leave external rather than loading as JavaScript.
+  pub native_addon: bool,
+  /// The size and modification time of the resolved file, from the check made while resolving
+  /// it. `None` if the file system doesn't provide metadata or the file was reached without
+  /// being checked, e.g. through a symlink, so callers should fall back to their own `stat`.
+  pub metadata: Option<FileMetadata>,
+  /// The tsconfig.json nearest to the importing file followed by those it extends, in extends
+  /// order, if tsconfig files were read for this resolution. Each is also an invalidation.
+  pub tsconfigs_used: Vec<TsConfigUsage>,
 }
 
 impl<'a, Fs: FileSystem> Resolver<'a, Fs> {
   pub fn node(project_root: Cow<'a, Path>, cache: CacheCow<'a, Fs>) -> Self {
-    Self {
-      project_root,
-      extensions: Extensions::Borrowed(&["js", "json", "node"]),
-      index_file: "index",
-      entries: Fields::MAIN,
-      flags: Flags::NODE_CJS,
-      cache,
-      include_node_modules: Cow::Owned(IncludeNodeModules::default()),
-      conditions: ExportsCondition::NODE,
-      module_dir_resolver: None,
-    }
+    ResolverBuilder::new(project_root, cache)
+      .preset(Preset::Node)
+      .build_unchecked()
   }
 
   pub fn node_esm(project_root: Cow<'a, Path>, cache: CacheCow<'a, Fs>) -> Self {
-    Self {
-      project_root,
-      extensions: Extensions::Borrowed(&[]),
-      index_file: "index",
-      entries: Fields::MAIN,
-      flags: Flags::NODE_ESM,
-      cache,
-      include_node_modules: Cow::Owned(IncludeNodeModules::default()),
-      conditions: ExportsCondition::NODE,
-      module_dir_resolver: None,
-    }
+    ResolverBuilder::new(project_root, cache)
+      .preset(Preset::NodeEsm)
+      .build_unchecked()
   }
 
   pub fn parcel(project_root: Cow<'a, Path>, cache: CacheCow<'a, Fs>) -> Self {
-    Self {
-      project_root,
-      extensions: Extensions::Borrowed(&["ts", "tsx", "mjs", "js", "jsx", "cjs", "json"]),
-      index_file: "index",
-      entries: Fields::MAIN | Fields::SOURCE | Fields::BROWSER | Fields::MODULE,
-      flags: Flags::all(),
-      cache,
-      include_node_modules: Cow::Owned(IncludeNodeModules::default()),
-      conditions: ExportsCondition::empty(),
-      module_dir_resolver: None,
-    }
+    ResolverBuilder::new(project_root, cache)
+      .preset(Preset::Parcel)
+      .build_unchecked()
   }
 
   pub fn resolve<'s>(
@@ -191,31 +666,469 @@ impl<'a, Fs: FileSystem> Resolver<'a, Fs> {
     specifier_type: SpecifierType,
     options: ResolveOptions,
   ) -> ResolveResult {
-    let invalidations = Invalidations::default();
-    let (specifier, query) = match Specifier::parse(specifier, specifier_type, self.flags) {
+    let cache = match &self.resolution_cache {
+      Some(cache) => cache,
+      None => return self.resolve_uncached(specifier, from, specifier_type, options),
+    };
+
+    let key = self.resolution_key(specifier, self.cache.intern(from), specifier_type, &options);
+    self.resolve_cached(cache, specifier, key, options)
+  }
+
+  /// Resolves a batch of specifiers, returning the results in the same order. Once
+  /// `options.cancellation` is set, the remaining specifiers fail with `ResolverError::Cancelled`
+  /// without doing any work, so the results still include everything that completed before the
+  /// batch was cancelled.
+  ///
+  /// With the `rayon` feature, the requests are collected up front and resolved in parallel on
+  /// `thread_pool`, or on the global pool if none was provided.
+  pub fn resolve_many<'s, 'p, I>(&self, requests: I, options: &ResolveOptions) -> Vec<ResolveResult>
+  where
+    I: IntoIterator<Item = (&'s str, &'p Path, SpecifierType)>,
+  {
+    #[cfg(feature = "rayon")]
+    {
+      use rayon::prelude::*;
+
+      let requests: Vec<_> = requests.into_iter().collect();
+      let resolve = || {
+        requests
+          .into_par_iter()
+          .map(|(specifier, from, specifier_type)| {
+            self.resolve_with_options(specifier, from, specifier_type, options.clone())
+          })
+          .collect::<Vec<_>>()
+      };
+
+      // Installing from a thread that already belongs to the pool (e.g. a nested batch) runs
+      // inline rather than waiting for a free thread, so this can't deadlock a 1-thread pool.
+      match &self.thread_pool {
+        Some(pool) => pool.install(resolve),
+        None => resolve(),
+      }
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    requests
+      .into_iter()
+      .map(|(specifier, from, specifier_type)| {
+        self.resolve_with_options(specifier, from, specifier_type, options.clone())
+      })
+      .collect()
+  }
+
+  /// Resolves a specifier from an importer path that was already interned with `Resolver::intern`,
+  /// which avoids hashing the path again when looking up the resolution cache.
+  pub fn resolve_interned(
+    &self,
+    specifier: &str,
+    from: &InternedPath,
+    specifier_type: SpecifierType,
+    options: ResolveOptions,
+  ) -> ResolveResult {
+    let cache = match &self.resolution_cache {
+      Some(cache) => cache,
+      None => return self.resolve_uncached(specifier, from, specifier_type, options),
+    };
+
+    let key = self.resolution_key(specifier, from.clone(), specifier_type, &options);
+    self.resolve_cached(cache, specifier, key, options)
+  }
+
+  /// Returns the resolution cache key for a request, without the ignored query keys.
+  fn resolution_key(
+    &self,
+    specifier: &str,
+    from: InternedPath,
+    specifier_type: SpecifierType,
+    options: &ResolveOptions,
+  ) -> ResolutionKey {
+    ResolutionKey::new(
+      &without_query_keys(
+        specifier,
+        specifier_type,
+        &self.parse_options.ignored_query_keys,
+      ),
+      from,
+      specifier_type,
+      options,
+    )
+  }
+
+  fn resolve_cached(
+    &self,
+    cache: &ResolutionCache,
+    specifier: &str,
+    key: ResolutionKey,
+    options: ResolveOptions,
+  ) -> ResolveResult {
+    if let Some(mut res) = cache.get(&key, self.cache.interner()) {
+      // The entry may have been stored for a different query.
+      if options.ignore_query_in_cache {
+        if let Ok((_, query)) = &mut res.result {
+          *query = split_query(specifier, key.specifier_type)
+            .1
+            .and_then(|q| Query::new(q).without_keys(&self.parse_options.ignored_query_keys))
+            .map(Query::into_owned);
+        }
+      }
+      return res;
+    }
+
+    let res = self.resolve_uncached(specifier, &key.from, key.specifier_type, options);
+    // A cancelled resolution is incomplete, so it must be retried rather than cached.
+    if !matches!(res.result, Err(ResolverError::Cancelled)) {
+      cache.insert(key, &res);
+    }
+    res
+  }
+
+  /// Returns a shared, pre-hashed copy of the given path from the resolver's cache.
+  pub fn intern(&self, path: &Path) -> InternedPath {
+    self.cache.intern(path)
+  }
+
+  /// Like `resolve_with_options`, but when resolution fails, also returns how far it got, e.g.
+  /// the package that was located even though its subpath was not found. The partial result
+  /// is `None` if resolution succeeded or nothing was located. This never uses the resolution
+  /// cache, so a partial result is never stored or returned as a success.
+  pub fn resolve_lenient(
+    &self,
+    specifier: &str,
+    from: &Path,
+    specifier_type: SpecifierType,
+    options: ResolveOptions,
+  ) -> (ResolveResult, Option<PartialResolution>) {
+    let diagnostics = RequestDiagnostics {
+      partial: RefCell::new(Some(PartialResolution::default())),
+      ..Default::default()
+    };
+    let res = self.resolve_with_diagnostics(specifier, from, specifier_type, options, &diagnostics);
+    let partial = match (&res.result, diagnostics.partial.take()) {
+      (Err(error), Some(mut partial)) => {
+        partial.expected_dir = PartialResolution::expected_dir(error);
+        Some(partial).filter(|partial| *partial != PartialResolution::default())
+      }
+      _ => None,
+    };
+
+    (res, partial)
+  }
+
+  /// Resolves the executable script of a package from the "bin" field of its package.json,
+  /// e.g. for a task runner. The package is located like a bare specifier imported from `from`.
+  /// The command defaults to a subpath of `name` (e.g. `cli` in `@biomejs/biome/cli`), or else
+  /// the package name without its scope. Scripts without an extension (e.g. `bin/cli`) are tried
+  /// as is, then with each configured extension.
+  pub fn resolve_bin(
+    &self,
+    name: &str,
+    bin_name: Option<&str>,
+    from: &Path,
+  ) -> Result<PathBuf, ResolverError> {
+    let (module, subpath) = parse_package_specifier(name)?;
+    let bin_name = bin_name.or(Some(subpath).filter(|subpath| !matches!(*subpath, "" | ".")));
+    let specifier = Specifier::Package(Cow::Borrowed(module), Cow::Borrowed(subpath));
+    let invalidations = Invalidations::new(self.cache.interner().clone());
+    let diagnostics = RequestDiagnostics::default();
+    let request = ResolveRequest::new(
+      self,
+      &specifier,
+      SpecifierType::Cjs,
+      from,
+      &invalidations,
+      &diagnostics,
+    );
+    request.resolve_bin(module, bin_name)
+  }
+
+  /// Returns the paths that resolving a specifier checks, in order, e.g. each extension and
+  /// index file, and the package directory in each node_modules folder while ascending.
+  /// None of them need to exist: the list ends at the first file that does, or includes
+  /// every path that was tried if resolution fails. This is useful to explain a
+  /// `ModuleNotFound` or `FileNotFound` error.
+  pub fn resolve_candidates(
+    &self,
+    specifier: &str,
+    from: &Path,
+    specifier_type: SpecifierType,
+  ) -> Vec<PathBuf> {
+    self.resolve_candidates_with_options(specifier, from, specifier_type, Default::default())
+  }
+
+  /// Like `resolve_candidates`, with the same options as `resolve_with_options`, e.g. so that
+  /// the candidates match a resolution with additional conditions.
+  pub fn resolve_candidates_with_options(
+    &self,
+    specifier: &str,
+    from: &Path,
+    specifier_type: SpecifierType,
+    options: ResolveOptions,
+  ) -> Vec<PathBuf> {
+    let diagnostics = RequestDiagnostics {
+      candidates: RefCell::new(Some(Vec::new())),
+      ..Default::default()
+    };
+    self.resolve_with_diagnostics(specifier, from, specifier_type, options, &diagnostics);
+    diagnostics.candidates.take().unwrap_or_default()
+  }
+
+  fn resolve_uncached(
+    &self,
+    specifier: &str,
+    from: &Path,
+    specifier_type: SpecifierType,
+    options: ResolveOptions,
+  ) -> ResolveResult {
+    self.resolve_with_diagnostics(
+      specifier,
+      from,
+      specifier_type,
+      options,
+      &RequestDiagnostics::default(),
+    )
+  }
+
+  fn resolve_with_diagnostics(
+    &self,
+    specifier: &str,
+    from: &Path,
+    specifier_type: SpecifierType,
+    options: ResolveOptions,
+    diagnostics: &RequestDiagnostics,
+  ) -> ResolveResult {
+    let invalidations = Invalidations::new(self.cache.interner().clone());
+    self.warn_invisible_prefix(specifier, diagnostics);
+    let parsed = options
+      .cancellation
+      .as_ref()
+      .map_or(Ok(()), |token| token.check())
+      .and_then(|_| self.limits.check(Limit::SpecifierLength, specifier.len()))
+      .and_then(|_| {
+        self
+          .measure(Phase::Parse, || {
+            Specifier::parse_typed(specifier, specifier_type, self.flags, &self.parse_options)
+          })
+          .map_err(ResolverError::from)
+      });
+    let (specifier, query, import_type) = match parsed {
       Ok(s) => s,
       Err(e) => {
+        let mut result = Err(e);
+        let diagnostics = self.to_diagnostics(diagnostics.warnings.take(), &mut result);
         return ResolveResult {
-          result: Err(e.into()),
+          result,
           invalidations,
-        }
+          rewrites: Vec::new(),
+          diagnostics,
+          origin: None,
+          import_type: None,
+          alias_trace: Vec::new(),
+          consulted_manifests: Vec::new(),
+          exports_trace: Vec::new(),
+          symlink: None,
+          extensionless: false,
+          native_addon: false,
+          metadata: None,
+          tsconfigs_used: Vec::new(),
+        };
       }
     };
-    let mut request = ResolveRequest::new(self, &specifier, specifier_type, from, &invalidations);
+    let prioritized_conditions;
+    let mut request = ResolveRequest::new(
+      self,
+      &specifier,
+      specifier_type,
+      from,
+      &invalidations,
+      diagnostics,
+    );
     if !options.conditions.is_empty() || !options.custom_conditions.is_empty() {
       // If custom conditions are defined, these override the default conditions inferred from the specifier type.
       request.conditions = self.conditions | options.conditions;
       request.custom_conditions = options.custom_conditions.as_slice();
     }
+    request.cancellation = options.cancellation.as_ref();
+    request.context = options.context;
+    match options.context {
+      ResolutionContext::Sass => {
+        request.conditions |= ExportsCondition::SASS | ExportsCondition::STYLE
+      }
+      ResolutionContext::Types => request.conditions |= ExportsCondition::TYPES,
+      ResolutionContext::Default => {}
+    }
+    if let Some(custom_conditions) = prioritize_conditions(
+      &self.condition_priority,
+      &mut request.conditions,
+      request.custom_conditions,
+    ) {
+      prioritized_conditions = custom_conditions;
+      request.custom_conditions = &prioritized_conditions;
+    }
+
+    let external = match &specifier {
+      Specifier::Package(module, subpath)
+        if self.externals.iter().any(|p| p.matches(module, subpath)) =>
+      {
+        Some(Resolution::External {
+          specifier: specifier.to_string().into_owned(),
+        })
+      }
+      _ => None,
+    };
+
+    let result = match external {
+      Some(external) if !self.verify_externals => Ok(external),
+      // Resolve the package anyway, so that a missing package is reported as usual.
+      Some(external) => request.resolve().map(|_| external),
+      None => request.resolve(),
+    };
+    let result = match result {
+      Ok(Resolution::Path(path)) if self.dedupe_packages => request
+        .find_deduped_package(&path)
+        .map(|deduped| Resolution::Path(deduped.unwrap_or(path))),
+      result => result,
+    };
+    let query = query
+      .and_then(|q| Query::new(q).without_keys(&self.parse_options.ignored_query_keys))
+      .map(Query::into_owned);
+    let result = result.map(|r| (r, query));
+
+    // Several paths may have been canonicalized, e.g. while resolving tsconfig extends.
+    let symlink = match &result {
+      Ok((Resolution::Path(path), _)) => diagnostics
+        .symlinks
+        .take()
+        .into_iter()
+        .find(|s| s.canonical_path == *path),
+      _ => None,
+    };
 
-    let result = match request.resolve() {
-      Ok(r) => Ok((r, query.map(|q| q.to_owned()))),
-      Err(r) => Err(r),
+    let origin = match &result {
+      Ok((resolution, _)) => self.origin(resolution, symlink.as_ref()),
+      Err(_) => None,
+    };
+    let extensionless =
+      matches!(&result, Ok((Resolution::Path(path), _)) if path.extension().is_none());
+    let native_addon = matches!(&result, Ok((Resolution::Path(path), _)) if is_native_addon(path));
+    let metadata = match &result {
+      Ok((Resolution::Path(path), _)) => self.cache.cached_file_metadata(path),
+      _ => None,
+    };
+    let mut result = result;
+    let warnings = self.to_diagnostics(diagnostics.warnings.take(), &mut result);
+    let exports_trace = if result.is_err() {
+      diagnostics.exports_trace.take()
+    } else {
+      Vec::new()
     };
 
     ResolveResult {
       result,
       invalidations,
+      symlink,
+      origin,
+      extensionless,
+      native_addon,
+      metadata,
+      rewrites: diagnostics.rewrites.take(),
+      diagnostics: warnings,
+      import_type: import_type.map(|t| t.to_owned()),
+      alias_trace: diagnostics.alias_trace.take(),
+      consulted_manifests: diagnostics.manifests.take(),
+      exports_trace,
+      tsconfigs_used: diagnostics.tsconfigs.take(),
+    }
+  }
+
+  /// Determines where a resolved module comes from. Paths in a module directory are checked
+  /// against their real path, since a symlink in node_modules may point back into the project.
+  fn origin(&self, resolution: &Resolution, symlink: Option<&SymlinkInfo>) -> Option<ModuleOrigin> {
+    let path = match resolution {
+      Resolution::Path(path) | Resolution::Directory(path) => path,
+      Resolution::Builtin(_) => return Some(ModuleOrigin::Builtin),
+      Resolution::External { .. } | Resolution::Global(_) => return Some(ModuleOrigin::External),
+      Resolution::Empty | Resolution::Virtual(_) => return None,
+    };
+
+    // The path as reached, before canonicalizing, e.g. node_modules/foo for a pnpm package.
+    let reached = symlink.map_or(&**path, |s| s.link_path.as_path());
+    let reached_name = package_name_in(reached, &self.module_dirs);
+    if reached_name.is_none() && symlink.is_none() {
+      return Some(ModuleOrigin::Project);
+    }
+
+    // With `SymlinkMode::PreserveAll` and similar, the path may not be canonical yet.
+    let real = match symlink {
+      Some(_) => path.to_path_buf(),
+      None => self
+        .cache
+        .canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf()),
+    };
+    let package_name = match package_name_in(&real, &self.module_dirs) {
+      Some(name) => Some(name),
+      // A linked workspace package.
+      None if real.starts_with(&self.project_root) => None,
+      // Outside the project, e.g. a global store that node_modules links into.
+      None => reached_name,
+    };
+
+    Some(match package_name {
+      Some(package_name) => ModuleOrigin::NodeModules { package_name },
+      None => ModuleOrigin::Project,
+    })
+  }
+
+  /// Converts the warnings collected for a request to diagnostics, applying `diagnostic_levels`.
+  /// A successful result fails with the first diagnostic that was elevated to an error.
+  fn to_diagnostics<T>(
+    &self,
+    warnings: Vec<ResolverWarning>,
+    result: &mut Result<T, ResolverError>,
+  ) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for warning in warnings {
+      let mut diagnostic = Diagnostic::from(warning);
+      let level = self
+        .diagnostic_levels
+        .iter()
+        .find(|(code, _)| code == diagnostic.code)
+        .map_or(Some(Severity::Warning), |(_, level)| *level);
+      diagnostic.severity = match level {
+        Some(severity) => severity,
+        None => continue,
+      };
+
+      if diagnostic.severity == Severity::Error && result.is_ok() {
+        *result = Err(ResolverError::ElevatedDiagnostic {
+          diagnostic: diagnostic.clone(),
+        });
+      }
+      diagnostics.push(diagnostic);
+    }
+
+    diagnostics
+  }
+
+  /// Reports any invisible characters that parsing will strip from the start of a specifier.
+  fn warn_invisible_prefix(&self, specifier: &str, diagnostics: &RequestDiagnostics) {
+    if !self.parse_options.strip_invisible_prefix {
+      return;
+    }
+
+    let stripped = specifier.len() - strip_invisible_prefix(specifier).len();
+    if stripped > 0 {
+      diagnostics
+        .warnings
+        .borrow_mut()
+        .push(ResolverWarning::InvisiblePrefixStripped {
+          specifier: specifier.to_owned(),
+          stripped: specifier[..stripped]
+            .chars()
+            .map(|c| format!("U+{:04X}", c as u32))
+            .collect(),
+        });
     }
   }
 
@@ -231,53 +1144,194 @@ impl<'a, Fs: FileSystem> Resolver<'a, Fs> {
     }
   }
 
-  fn find_package(
+  /// Finds the nearest package.json with a name that contains the given file or directory,
+  /// skipping nameless ones such as `{"type": "module"}` in a build directory. Like resolution,
+  /// this does not look above node_modules or the project root. Invalidations are recorded so
+  /// that creating a package.json closer to `path` later invalidates the result.
+  pub fn find_package_root(
     &self,
-    from: &Path,
+    path: &Path,
     invalidations: &Invalidations,
-  ) -> Result<Option<&PackageJson>, ResolverError> {
-    if let Some(path) = self.find_ancestor_file(from, "package.json", invalidations) {
-      let package = self.cache.read_package(Cow::Owned(path))?;
-      return Ok(Some(package));
+  ) -> Result<Option<PackageRoot<'_>>, ResolverError> {
+    let mut dir = if self.is_dir(path) {
+      path
+    } else {
+      path.parent().unwrap_or(path)
+    };
+
+    while let Some(manifest) = self.find_ancestor_file(dir, "package.json", invalidations)? {
+      let package = self.read_package(Cow::Owned(manifest))?;
+      let package_dir = package.path.parent().unwrap();
+      if !package.name.is_empty() {
+        return Ok(Some(PackageRoot {
+          manifest: &package.path,
+          package,
+          in_node_modules: package
+            .path
+            .components()
+            .any(|c| c.as_os_str() == "node_modules"),
+        }));
+      }
+
+      match package_dir.parent() {
+        Some(parent) if package_dir != self.project_root => dir = parent,
+        _ => break,
+      }
     }
 
     Ok(None)
   }
 
-  fn find_ancestor_file(
+  /// Finds the root of the project containing the given file or directory: the nearest directory
+  /// with a lockfile or `.git`, or else the outermost directory with a package.json. Unlike
+  /// `find_package_root`, this ignores the configured project root, and walks out of node_modules.
+  pub fn find_project_root(
     &self,
-    from: &Path,
-    filename: &str,
+    path: &Path,
     invalidations: &Invalidations,
-  ) -> Option<PathBuf> {
-    let mut first = true;
-    for dir in from.ancestors() {
-      if let Some(filename) = dir.file_name() {
-        if filename == "node_modules" {
-          break;
-        }
-      }
+  ) -> Result<Option<PathBuf>, ResolverError> {
+    for marker in PROJECT_ROOT_MARKERS {
+      invalidations.invalidate_on_file_create_above(*marker, path);
+    }
 
-      let file = dir.join(filename);
-      if self.cache.is_file(&file) {
-        invalidations.invalidate_on_file_change(&file);
-        return Some(file);
+    let mut outermost_package = None;
+    for (depth, dir) in path.ancestors().enumerate() {
+      self.limits.check(Limit::AncestorDepth, depth + 1)?;
+      for marker in PROJECT_ROOT_MARKERS {
+        let file = dir.join(marker);
+        if self.is_file(&file) || self.is_dir(&file) {
+          invalidations.invalidate_on_file_change(&file);
+          return Ok(Some(dir.to_owned()));
+        }
       }
 
-      if dir == self.project_root {
-        break;
+      let package = dir.join("package.json");
+      if self.is_file(&package) {
+        outermost_package = Some(package);
       }
+    }
 
-      if first {
-        invalidations.invalidate_on_file_create_above(filename, from);
-      }
+    invalidations.invalidate_on_file_create_above("package.json", path);
+    Ok(outermost_package.map(|package| {
+      invalidations.invalidate_on_file_change(&package);
+      package.parent().unwrap().to_owned()
+    }))
+  }
 
-      first = false;
+  /// Notifies the resolver that the given paths were created, updated, or deleted.
+  /// Returns the keys of all cached resolutions that are now stale, and evicts them
+  /// so that the next request re-resolves. Returns an empty list if the resolution
+  /// cache is not enabled.
+  pub fn changed(&self, paths: &[PathBuf]) -> Vec<ResolutionKey> {
+    for path in paths {
+      self.cache.invalidate_path(path);
     }
 
-    None
+    match &self.resolution_cache {
+      Some(cache) => cache.changed(paths),
+      None => Vec::new(),
+    }
   }
-}
+
+  /// Clears the file system cache, see `Cache::clear`, which releases the config file versions
+  /// left behind by `changed`. Returns `false` if the cache is borrowed, in which case its owner
+  /// must clear it instead. Cached resolutions are kept, since they are invalidated separately.
+  pub fn clear_cache(&mut self) -> bool {
+    match &mut self.cache {
+      CacheCow::Owned(cache) => {
+        cache.clear();
+        true
+      }
+      CacheCow::Borrowed(_) => false,
+    }
+  }
+
+  /// Returns the metrics accumulated since the last call and resets them.
+  /// If metrics are not enabled, all values are zero.
+  pub fn take_metrics(&self) -> ResolverMetrics {
+    match &self.metrics {
+      Some(metrics) => metrics.take(),
+      None => ResolverMetrics::default(),
+    }
+  }
+
+  #[inline]
+  fn measure<T, F: FnOnce() -> T>(&self, phase: Phase, f: F) -> T {
+    match &self.metrics {
+      Some(metrics) => metrics.measure(phase, f),
+      None => f(),
+    }
+  }
+
+  fn is_file(&self, path: &Path) -> bool {
+    self.measure(Phase::Stat, || self.cache.is_file(path))
+  }
+
+  /// The index file names to try in a directory, without extensions.
+  fn index_file_names<'p>(&'p self, dir: &'p Path) -> Vec<&'p OsStr> {
+    self
+      .index_names
+      .iter()
+      .filter_map(|name| name.file_name(dir))
+      .collect()
+  }
+
+  fn is_dir(&self, path: &Path) -> bool {
+    self.measure(Phase::Stat, || self.cache.is_dir(path))
+  }
+
+  fn read_package(&self, path: Cow<Path>) -> Result<&PackageJson<'_>, ResolverError> {
+    self.measure(Phase::PackageJson, || self.cache.read_package(path))
+  }
+
+  fn find_package(
+    &self,
+    from: &Path,
+    invalidations: &Invalidations,
+  ) -> Result<Option<&PackageJson>, ResolverError> {
+    if let Some(path) = self.find_ancestor_file(from, "package.json", invalidations)? {
+      let package = self.read_package(Cow::Owned(path))?;
+      return Ok(Some(package));
+    }
+
+    Ok(None)
+  }
+
+  fn find_ancestor_file(
+    &self,
+    from: &Path,
+    filename: &str,
+    invalidations: &Invalidations,
+  ) -> Result<Option<PathBuf>, ResolverError> {
+    let mut first = true;
+    for (depth, dir) in from.ancestors().enumerate() {
+      self.limits.check(Limit::AncestorDepth, depth + 1)?;
+      if let Some(filename) = dir.file_name() {
+        if filename == "node_modules" {
+          break;
+        }
+      }
+
+      let file = dir.join(filename);
+      if self.is_file(&file) {
+        invalidations.invalidate_on_file_change(&file);
+        return Ok(Some(file));
+      }
+
+      if dir == self.project_root {
+        break;
+      }
+
+      if first {
+        invalidations.invalidate_on_file_create_above(filename, from);
+      }
+
+      first = false;
+    }
+
+    Ok(None)
+  }
+}
 
 struct ResolveRequest<'a, Fs> {
   resolver: &'a Resolver<'a, Fs>,
@@ -288,9 +1342,66 @@ struct ResolveRequest<'a, Fs> {
   tsconfig: OnceCell<Option<&'a TsConfig<'a>>>,
   root_package: OnceCell<Option<&'a PackageJson<'a>>>,
   invalidations: &'a Invalidations,
+  diagnostics: &'a RequestDiagnostics,
   conditions: ExportsCondition,
   custom_conditions: &'a [String],
   priority_extension: Option<&'a str>,
+  cancellation: Option<&'a CancellationToken>,
+  context: ResolutionContext,
+}
+
+/// Removes the conditions in `priority` that come after the first active one, e.g. "browser" when
+/// "worker" is preferred and both are active. Returns the remaining custom conditions if any
+/// were removed.
+fn prioritize_conditions(
+  priority: &[String],
+  conditions: &mut ExportsCondition,
+  custom_conditions: &[String],
+) -> Option<Vec<String>> {
+  let active = *conditions;
+  let first = priority
+    .iter()
+    .position(|name| match ExportsCondition::try_from(name.as_str()) {
+      Ok(condition) => active.contains(condition),
+      Err(()) => custom_conditions.contains(name),
+    })?;
+
+  let dropped = &priority[first + 1..];
+  for name in dropped {
+    if let Ok(condition) = ExportsCondition::try_from(name.as_str()) {
+      *conditions -= condition;
+    }
+  }
+
+  if custom_conditions.iter().any(|c| dropped.contains(c)) {
+    Some(
+      custom_conditions
+        .iter()
+        .filter(|c| !dropped.contains(c))
+        .cloned()
+        .collect(),
+    )
+  } else {
+    None
+  }
+}
+
+/// Rewrites, warnings, and consulted manifests collected across a request and its sub-requests.
+#[derive(Default)]
+struct RequestDiagnostics {
+  rewrites: RefCell<Vec<SpecifierRewrite<'static>>>,
+  warnings: RefCell<Vec<ResolverWarning>>,
+  manifests: RefCell<Vec<PathBuf>>,
+  alias_trace: RefCell<Vec<AliasDecision>>,
+  exports_trace: RefCell<Vec<ExportsTraceStep>>,
+  symlinks: RefCell<Vec<SymlinkInfo>>,
+  /// The paths checked while resolving, only collected for `Resolver::resolve_candidates`.
+  candidates: RefCell<Option<Vec<PathBuf>>>,
+  /// How far resolution got, only collected for `Resolver::resolve_lenient`.
+  partial: RefCell<Option<PartialResolution>>,
+  /// The mappings currently being followed, innermost last, see `ResolveRequest::redirect`.
+  steps: RefCell<Vec<ResolutionStep>>,
+  tsconfigs: RefCell<Vec<TsConfigUsage>>,
 }
 
 bitflags! {
@@ -308,6 +1419,7 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
     mut specifier_type: SpecifierType,
     from: &'a Path,
     invalidations: &'a Invalidations,
+    diagnostics: &'a RequestDiagnostics,
   ) -> Self {
     let mut flags = RequestFlags::empty();
     if let Some(ext) = from.extension() {
@@ -357,9 +1469,12 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
       tsconfig: OnceCell::new(),
       root_package: OnceCell::new(),
       invalidations,
+      diagnostics,
       conditions,
       custom_conditions: &[],
       priority_extension,
+      cancellation: None,
+      context: ResolutionContext::Default,
     }
   }
 
@@ -374,30 +1489,309 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
       return Ok(None);
     }
 
-    match package.resolve_aliases(&specifier, fields) {
-      Some(alias) => match alias.as_ref() {
-        AliasValue::Specifier(specifier) => {
-          let mut req = ResolveRequest::new(
-            &self.resolver,
-            specifier,
-            SpecifierType::Cjs,
-            &package.path,
-            self.invalidations,
-          );
-          req.priority_extension = self.priority_extension;
-          req.conditions = self.conditions;
-          req.custom_conditions = self.custom_conditions;
-          let resolved = req.resolve()?;
-          Ok(Some(resolved))
+    let alias = package
+      .resolve_aliases_with_field(specifier, fields)
+      .map_err(|err| package_json_error(package, err))?;
+    match alias {
+      Some((alias, field)) => match alias.as_ref() {
+        AliasValue::Specifier(aliased) => {
+          let (field, reason) = if field == Fields::SOURCE {
+            ("source", "source field")
+          } else if field == Fields::BROWSER {
+            ("browser", "browser field")
+          } else {
+            ("alias", "alias field")
+          };
+
+          let normalized;
+          let aliased = match aliased {
+            Specifier::Relative(path) if self.normalize_manifest_backslashes() => {
+              let value = path.to_string_lossy();
+              if value.contains('\\') {
+                self.warn_manifest_backslashes(package, field, &value);
+                normalized = Specifier::from(value.replace('\\', "/").as_str()).into_owned();
+                &normalized
+              } else {
+                aliased
+              }
+            }
+            _ => aliased,
+          };
+
+          // Values in the "browser" field that replace a file may also omit the leading "./",
+          // e.g. `{"./lib/fs.js": "lib/fs-browser.js"}`. Treat them as relative if the file
+          // exists within the package, rather than as a package named `lib`.
+          let relative;
+          let aliased = match aliased {
+            Specifier::Package(..)
+              if field == "browser"
+                && matches!(specifier, Specifier::Relative(_))
+                && self.package_file_exists(package, &aliased.to_string()) =>
+            {
+              relative =
+                Specifier::Relative(Cow::Owned(PathBuf::from(aliased.to_string().into_owned())));
+              &relative
+            }
+            _ => aliased,
+          };
+
+          self
+            .diagnostics
+            .rewrites
+            .borrow_mut()
+            .push(SpecifierRewrite {
+              before: specifier.clone().into_owned(),
+              after: aliased.clone().into_owned(),
+              reason,
+            });
+
+          // The aliased specifier may itself be aliased, e.g. a renamed package that is then
+          // patched locally. Follow the chain to a fixed point.
+          let mut chain = vec![
+            specifier.to_string().into_owned(),
+            aliased.to_string().into_owned(),
+          ];
+          let mut current = aliased.clone().into_owned();
+          loop {
+            let next = package
+              .resolve_aliases_with_field(&current, fields)
+              .map_err(|err| package_json_error(package, err))?;
+            let next = match next {
+              Some((alias, _)) => alias.into_owned(),
+              None => break,
+            };
+            let next = match next {
+              AliasValue::Specifier(next) => next,
+              AliasValue::Bool(false) => return Ok(Some(Resolution::Empty)),
+              AliasValue::Bool(true) => break,
+              AliasValue::Global { global } => {
+                return Ok(Some(Resolution::Global(global.into_owned())))
+              }
+            };
+
+            // An alias into the same package, e.g. `react` to `react/index.js`, is final.
+            match (&current, &next) {
+              (Specifier::Package(a, _), Specifier::Package(b, _)) if a == b => break,
+              _ => {}
+            }
+
+            let next_str = next.to_string().into_owned();
+            if chain.contains(&next_str) {
+              chain.push(next_str);
+              return Err(ResolverError::AliasCycle { chain });
+            }
+            chain.push(next_str);
+            self
+              .resolver
+              .limits
+              .check(Limit::AliasDepth, chain.len() - 1)?;
+
+            self
+              .diagnostics
+              .rewrites
+              .borrow_mut()
+              .push(SpecifierRewrite {
+                before: current.clone(),
+                after: next.clone().into_owned(),
+                reason,
+              });
+            current = next.into_owned();
+          }
+
+          self
+            .redirect(
+              &package.path,
+              &specifier.to_string(),
+              &current.to_string(),
+              || self.resolve_aliased(package, &current),
+            )
+            .map(Some)
         }
         AliasValue::Bool(false) => Ok(Some(Resolution::Empty)),
         AliasValue::Bool(true) => Ok(None),
-        AliasValue::Global { global } => Ok(Some(Resolution::Global((*global).to_owned()))),
+        AliasValue::Global { global } => Ok(Some(Resolution::Global(global.to_string()))),
       },
       None => Ok(None),
     }
   }
 
+  /// Applies the object form of the resolver's custom entry fields, e.g. `"react-native"`,
+  /// which remaps files and packages like the "browser" field.
+  fn resolve_custom_aliases(
+    &self,
+    package: &PackageJson,
+    specifier: &Specifier,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    if self.from == package.path {
+      return Ok(None);
+    }
+
+    for field in self.resolver.custom_entries.iter() {
+      let alias = match package.resolve_custom_aliases(specifier, field) {
+        Some(alias) => alias,
+        None => continue,
+      };
+
+      let aliased = match alias.as_ref() {
+        AliasValue::Specifier(aliased) => aliased,
+        AliasValue::Bool(false) => return Ok(Some(Resolution::Empty)),
+        _ => return Ok(None),
+      };
+
+      // Like the "browser" field, file values may omit the leading "./".
+      let relative;
+      let aliased = match aliased {
+        Specifier::Package(..)
+          if matches!(specifier, Specifier::Relative(_))
+            && self.package_file_exists(package, &aliased.to_string()) =>
+        {
+          relative =
+            Specifier::Relative(Cow::Owned(PathBuf::from(aliased.to_string().into_owned())));
+          &relative
+        }
+        _ => aliased,
+      };
+
+      self
+        .diagnostics
+        .rewrites
+        .borrow_mut()
+        .push(SpecifierRewrite {
+          before: specifier.clone().into_owned(),
+          after: aliased.clone().into_owned(),
+          reason: "custom entry field",
+        });
+      return self
+        .redirect(
+          &package.path,
+          &specifier.to_string(),
+          &aliased.to_string(),
+          || self.resolve_aliased(package, aliased),
+        )
+        .map(Some);
+    }
+
+    Ok(None)
+  }
+
+  /// Follows a mapping from `key` to `target` declared in `manifest`, e.g. an alias or an
+  /// "imports" entry, by running `resolve`. Mappings may lead to each other through
+  /// sub-requests, e.g. a root alias to a file whose package's "browser" field maps it back to
+  /// the aliased package. Each mapping is only valid once among those in progress, so a repeat
+  /// fails with `ResolutionLoop` instead of recursing forever. The total is bounded by
+  /// `Limit::RedirectDepth`.
+  fn redirect<T>(
+    &self,
+    manifest: &Path,
+    key: &str,
+    target: &str,
+    resolve: impl FnOnce() -> Result<T, ResolverError>,
+  ) -> Result<T, ResolverError> {
+    let step = ResolutionStep {
+      manifest: manifest.to_path_buf(),
+      key: key.to_owned(),
+      target: target.to_owned(),
+    };
+
+    {
+      let mut steps = self.diagnostics.steps.borrow_mut();
+      if let Some(i) = steps.iter().position(|s| *s == step) {
+        let mut cycle = steps[i..].to_vec();
+        cycle.push(step);
+        return Err(ResolverError::ResolutionLoop { steps: cycle });
+      }
+      self
+        .resolver
+        .limits
+        .check(Limit::RedirectDepth, steps.len() + 1)?;
+      steps.push(step);
+    }
+
+    let res = resolve();
+    self.diagnostics.steps.borrow_mut().pop();
+    res
+  }
+
+  /// Resolves the target of an alias, relative to the package.json that declared it.
+  fn resolve_aliased(
+    &self,
+    package: &PackageJson,
+    specifier: &Specifier,
+  ) -> Result<Resolution, ResolverError> {
+    let mut req = ResolveRequest::new(
+      self.resolver,
+      specifier,
+      SpecifierType::Cjs,
+      &package.path,
+      self.invalidations,
+      self.diagnostics,
+    );
+    req.priority_extension = self.priority_extension;
+    req.conditions = self.conditions;
+    req.custom_conditions = self.custom_conditions;
+    req.cancellation = self.cancellation;
+    req.context = self.context;
+    req.resolve()
+  }
+
+  /// Whether a path relative to a package exists, as is or with one of the configured extensions.
+  fn package_file_exists(&self, package: &PackageJson, path: &str) -> bool {
+    let path = package.path.parent().unwrap().join(path);
+    self.resolver.is_file(&path)
+      || self.resolver.extensions.iter().any(|ext| {
+        let mut s = path.clone().into_os_string();
+        s.push(".");
+        s.push(ext);
+        self.resolver.is_file(Path::new(&s))
+      })
+  }
+
+  /// Whether backslashes in package.json paths should be treated as separators. On Windows
+  /// they already are, so this only applies elsewhere. Specifiers from source code are never
+  /// normalized, since backslashes are valid in file names on other platforms.
+  fn normalize_manifest_backslashes(&self) -> bool {
+    !cfg!(windows) && self.resolver.flags.contains(Flags::MANIFEST_BACKSLASHES)
+  }
+
+  /// Normalizes backslashes in a path read from the given package.json field.
+  fn normalize_manifest_path(
+    &self,
+    package: &PackageJson,
+    path: PathBuf,
+    field: &'static str,
+  ) -> PathBuf {
+    if !self.normalize_manifest_backslashes() {
+      return path;
+    }
+
+    let relative = match path.strip_prefix(package.path.parent().unwrap()) {
+      Ok(relative) => relative.to_string_lossy().into_owned(),
+      Err(_) => return path,
+    };
+
+    if !relative.contains('\\') {
+      return path;
+    }
+
+    self.warn_manifest_backslashes(package, field, &relative);
+    resolve_path(&package.path, relative.replace('\\', "/"))
+  }
+
+  fn warn_manifest_backslashes(&self, package: &PackageJson, field: &'static str, value: &str) {
+    self.warn(ResolverWarning::ManifestBackslashes {
+      path: package.path.clone(),
+      field,
+      value: value.to_owned(),
+    });
+  }
+
+  fn warn(&self, warning: ResolverWarning) {
+    let mut warnings = self.diagnostics.warnings.borrow_mut();
+    if !warnings.contains(&warning) {
+      warnings.push(warning);
+    }
+  }
+
   fn root_package(&self) -> Result<&Option<&PackageJson>, ResolverError> {
     self
       .root_package
@@ -413,7 +1807,14 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
       Specifier::Tilde(specifier) if self.resolver.flags.contains(Flags::TILDE_SPECIFIERS) => {
         // Tilde path. Resolve relative to nearest node_modules directory,
         // the nearest directory with package.json or the project root - whichever comes first.
-        if let Some(p) = self.find_ancestor_file(&self.from, "package.json") {
+        if let Some(p) = self.find_ancestor_file(self.from, "package.json")? {
+          if let Some(res) = self
+            .resolve_own_export(&p.parent().unwrap().join(specifier), || {
+              self.resolve_relative(specifier, &p)
+            })?
+          {
+            return Ok(res);
+          }
           return self.resolve_relative(&specifier, &p);
         }
 
@@ -424,11 +1825,17 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
       Specifier::Absolute(specifier) => {
         // In Parcel mode, absolute paths are actually relative to the project root.
         if self.resolver.flags.contains(Flags::ABSOLUTE_SPECIFIERS) {
-          self.resolve_relative(
-            specifier.strip_prefix("/").unwrap(),
-            &self.resolver.project_root.join("index"),
-          )
-        } else if let Some(res) = self.load_path(&specifier, None)? {
+          let relative = specifier.strip_prefix("/").unwrap();
+          let index = self.resolver.project_root.join("index");
+          if let Some(res) = self
+            .resolve_own_export(&self.resolver.project_root.join(relative), || {
+              self.resolve_relative(relative, &index)
+            })?
+          {
+            return Ok(res);
+          }
+          self.resolve_relative(relative, &index)
+        } else if let Some(res) = self.load_specifier_path(specifier, None)? {
           Ok(res)
         } else {
           Err(ResolverError::FileNotFound {
@@ -437,18 +1844,32 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
           })
         }
       }
+      Specifier::RootRelative(specifier) => {
+        self.resolve_relative(specifier, &self.resolver.project_root.join("index"))
+      }
       Specifier::Hash(hash) => {
         if self.specifier_type == SpecifierType::Url {
           // An ID-only URL, e.g. `url(#clip-path)` for CSS rules. Ignore.
-          Ok(Resolution::External)
-        } else if self.specifier_type == SpecifierType::Esm
+          Ok(Resolution::External {
+            specifier: format!("#{}", hash),
+          })
+        } else if matches!(self.specifier_type, SpecifierType::Esm | SpecifierType::Cjs)
           && self.resolver.flags.contains(Flags::EXPORTS)
         {
-          // An internal package #import specifier.
+          // An internal package #import specifier. Like "exports", this matches the "import" or
+          // "require" condition depending on the specifier type, along with the global ones.
           let package = self.find_package(&self.from.parent().unwrap())?;
           if let Some(package) = package {
-            let res = package
-              .resolve_package_imports(&hash, self.conditions, self.custom_conditions)
+            self
+              .resolver
+              .limits
+              .check(Limit::ExportsKeys, package.imports_key_count())?;
+            self.check_cancelled()?;
+            let res = self
+              .resolver
+              .measure(Phase::Exports, || {
+                package.resolve_package_imports(hash, self.conditions, self.custom_conditions)
+              })
               .map_err(|e| ResolverError::PackageJsonError {
                 module: package.name.to_owned(),
                 path: package.path.clone(),
@@ -464,7 +1885,9 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
               ExportsResolution::Package(specifier) => {
                 let (module, subpath) = parse_package_specifier(&specifier)?;
                 // TODO: should this follow aliases??
-                return self.resolve_bare(module, subpath);
+                return self.redirect(&package.path, &format!("#{}", hash), &specifier, || {
+                  self.resolve_bare(module, subpath)
+                });
               }
               _ => {}
             }
@@ -478,7 +1901,21 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
         }
       }
       Specifier::Package(module, subpath) => {
-        // Bare specifier.
+        // Bare specifier. Ambiguous ones only resolve to a package if no local file exists.
+        // Sass always loads files relative to the importer first.
+        if self.context == ResolutionContext::Sass
+          || self
+            .specifier
+            .is_ambiguous(self.specifier_type, self.resolver.flags)
+        {
+          let relative = self.specifier.to_string();
+          match self.resolve_relative(Path::new(&*relative), self.from) {
+            Ok(res) => return Ok(res),
+            Err(ResolverError::Cancelled) => return Err(ResolverError::Cancelled),
+            Err(_) => {}
+          }
+        }
+
         self.resolve_bare(&module, &subpath)
       }
       Specifier::Builtin(builtin) => {
@@ -489,19 +1926,54 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
       }
       Specifier::Url(url) => {
         if self.specifier_type == SpecifierType::Url {
-          Ok(Resolution::External)
+          return Ok(Resolution::External {
+            specifier: url.as_ref().to_owned(),
+          });
+        }
+
+        // In ESM and CommonJS, data URLs and the schemes allowed by `ParseOptions::external_schemes`
+        // are left for the runtime to load.
+        let (scheme, _) = parse_scheme(url)?;
+        if scheme == "data"
+          || self
+            .resolver
+            .parse_options
+            .external_schemes
+            .iter()
+            .any(|s| *s == scheme)
+        {
+          Ok(Resolution::External {
+            specifier: url.as_ref().to_owned(),
+          })
         } else {
-          let (scheme, _) = parse_scheme(url)?;
           Err(ResolverError::UnknownScheme {
             scheme: scheme.into_owned(),
           })
         }
       }
+      Specifier::Virtual(specifier) => Ok(Resolution::Virtual(specifier.as_ref().to_owned())),
+      Specifier::SchemeAlias { scheme, rest } => {
+        match self
+          .resolver
+          .parse_options
+          .scheme_aliases
+          .get(scheme.as_ref())
+        {
+          Some(root) => self.resolve_relative(rest, &root.join("index")),
+          None => Err(ResolverError::UnknownScheme {
+            scheme: scheme.as_ref().to_owned(),
+          }),
+        }
+      }
       _ => Err(ResolverError::UnknownError),
     }
   }
 
-  fn find_ancestor_file(&self, from: &Path, filename: &str) -> Option<PathBuf> {
+  fn find_ancestor_file(
+    &self,
+    from: &Path,
+    filename: &str,
+  ) -> Result<Option<PathBuf>, ResolverError> {
     let from = from.parent().unwrap();
     self
       .resolver
@@ -509,22 +1981,112 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
   }
 
   fn find_package(&self, from: &Path) -> Result<Option<&'a PackageJson<'a>>, ResolverError> {
-    self.resolver.find_package(from, &self.invalidations)
+    self.check_cancelled()?;
+    let package = self.resolver.find_package(from, self.invalidations)?;
+    if let Some(package) = package {
+      self.consult_manifest(package);
+    }
+
+    Ok(package)
+  }
+
+  /// Fails with `ResolverError::Cancelled` if the request's cancellation token was set.
+  /// This is checked before reading the file system, so cancelled requests never populate
+  /// the shared caches with partial results.
+  fn check_cancelled(&self) -> Result<(), ResolverError> {
+    match self.cancellation {
+      Some(token) => token.check(),
+      None => Ok(()),
+    }
+  }
+
+  /// Updates the partial resolution, if one is being collected for `Resolver::resolve_lenient`.
+  fn record_partial<F: FnOnce(&mut PartialResolution)>(&self, f: F) {
+    if let Some(partial) = self.diagnostics.partial.borrow_mut().as_mut() {
+      f(partial);
+    }
+  }
+
+  /// Records a path that is about to be checked, for `Resolver::resolve_candidates`.
+  fn record_candidate(&self, path: &Path) {
+    if let Some(candidates) = self.diagnostics.candidates.borrow_mut().as_mut() {
+      candidates.push(path.to_owned());
+    }
+  }
+
+  /// Records that a package.json was used while resolving, if diagnostics are enabled.
+  fn consult_manifest(&self, package: &PackageJson) {
+    if !self.resolver.diagnostics {
+      return;
+    }
+
+    let mut manifests = self.diagnostics.manifests.borrow_mut();
+    if !manifests.contains(&package.path) {
+      manifests.push(package.path.clone());
+    }
   }
 
   fn resolve_relative(&self, specifier: &Path, from: &Path) -> Result<Resolution, ResolverError> {
+    self.resolver.limits.check(
+      Limit::ParentSegments,
+      specifier
+        .components()
+        .filter(|c| matches!(c, Component::ParentDir))
+        .count(),
+    )?;
+
     // Resolve aliases from the nearest package.json.
-    let path = resolve_path(from, specifier);
-    let package = if self.resolver.flags.contains(Flags::ALIASES) {
-      self.find_package(&path.parent().unwrap())?
-    } else {
-      None
+    let mut path = resolve_path(from, specifier);
+
+    // Preserve a trailing slash so that load_path only matches a directory, never e.g. `utils.js`.
+    // The same applies to `.` and `..`, which always refer to a directory.
+    if is_directory_path(specifier) && !has_trailing_slash(&path) {
+      let mut s = path.into_os_string();
+      s.push("/");
+      path = PathBuf::from(s);
+    }
+
+    let package = match path.parent() {
+      Some(dir) if self.resolver.flags.contains(Flags::ALIASES) => self.find_package(dir)?,
+      _ => None,
     };
 
-    if let Some(res) = self.load_path(&path, package)? {
+    if let Some(res) = self.load_specifier_path(&path, package)? {
       return Ok(res);
     }
 
+    // If the path exists as a directory, explain why it couldn't be loaded.
+    if self.resolver.is_dir(&path) {
+      let can_load_directory = self.can_load_directory();
+      return Err(ResolverError::DirectoryWithoutIndex {
+        relative: specifier.to_owned(),
+        from: from.to_owned(),
+        index_names: if can_load_directory {
+          self
+            .resolver
+            .index_file_names(&path)
+            .iter()
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect()
+        } else {
+          Vec::new()
+        },
+        extensions: if can_load_directory
+          && self.resolver.flags.contains(Flags::OPTIONAL_EXTENSIONS)
+        {
+          self
+            .resolver
+            .extensions
+            .iter()
+            .map(|e| e.to_owned())
+            .collect()
+        } else {
+          Vec::new()
+        },
+        specifier_type: self.specifier_type,
+      });
+    }
+
     Err(ResolverError::FileNotFound {
       relative: specifier.to_owned(),
       from: from.to_owned(),
@@ -538,12 +2100,14 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
       IncludeNodeModules::Map(m) => *m.get(module).unwrap_or(&true),
     };
 
+    let specifier = Specifier::Package(Cow::Borrowed(module), Cow::Borrowed(subpath));
     if !include {
-      return Ok(Resolution::External);
+      return Ok(Resolution::External {
+        specifier: specifier.into_owned_string(),
+      });
     }
 
     // Try aliases and tsconfig paths first.
-    let specifier = Specifier::Package(Cow::Borrowed(module), Cow::Borrowed(subpath));
     if let Some(res) = self.resolve_package_aliases_and_tsconfig_paths(&specifier)? {
       return Ok(res);
     }
@@ -555,83 +2119,318 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
     &self,
     specifier: &Specifier,
   ) -> Result<Option<Resolution>, ResolverError> {
-    if self.resolver.flags.contains(Flags::ALIASES) {
-      // First, check for an alias in the root package.json.
-      if let Some(package) = self.root_package()? {
-        if let Some(res) = self.resolve_aliases(package, &specifier, Fields::ALIAS)? {
-          return Ok(Some(res));
-        }
+    // Consult each source in order of precedence. The first one that remaps the specifier wins.
+    let precedence = &*self.resolver.alias_precedence;
+    for (i, source) in precedence.iter().enumerate() {
+      let res = self.resolve_alias_source(*source, AliasInput::Specifier(specifier));
+      if matches!(res, Ok(None)) {
+        self.trace_alias(specifier, *source, AliasOutcome::NoMatch);
+        continue;
       }
 
-      // Next, try the local package.json.
-      if let Some(package) = self.find_package(&self.from.parent().unwrap())? {
-        let mut fields = Fields::ALIAS;
-        if self.resolver.entries.contains(Fields::BROWSER) {
-          fields |= Fields::BROWSER;
-        }
-        if let Some(res) = self.resolve_aliases(package, &specifier, fields)? {
-          return Ok(Some(res));
-        }
+      self.trace_alias(specifier, *source, AliasOutcome::Won);
+      for skipped in &precedence[i + 1..] {
+        self.trace_alias(specifier, *skipped, AliasOutcome::Skipped);
       }
+      return res;
     }
 
-    // Next, check tsconfig.json for the paths and baseUrl options.
-    self.resolve_tsconfig_paths()
+    Ok(None)
   }
 
-  fn resolve_node_module(&self, module: &str, subpath: &str) -> Result<Resolution, ResolverError> {
-    // If there is a custom module directory resolver (e.g. Yarn PnP), use that.
-    if let Some(module_dir_resolver) = &self.resolver.module_dir_resolver {
-      let package_dir = module_dir_resolver(module, self.from)?;
-      return self.resolve_package(package_dir, module, subpath);
-    } else {
-      self.invalidations.invalidate_on_file_create_above(
-        format!("node_modules/{}", module),
-        self.from.parent().unwrap(),
-      );
-
-      for dir in self.from.ancestors() {
-        // Skip over node_modules directories
-        if let Some(filename) = dir.file_name() {
-          if filename == "node_modules" {
-            continue;
+  fn resolve_alias_source(
+    &self,
+    source: AliasSource,
+    input: AliasInput,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    let package = match source {
+      AliasSource::TsConfigPaths => {
+        return match input {
+          AliasInput::Specifier(_) => self.resolve_tsconfig_paths(),
+          AliasInput::File(..) => Ok(None),
+        }
+      }
+      _ if !self.resolver.flags.contains(Flags::ALIASES) => return Ok(None),
+      AliasSource::RootAlias => {
+        // Aliases configured on the resolver take precedence over the root package.json.
+        if let Some(aliases) = &self.resolver.root_aliases {
+          if let Some(specifier) = alias_specifier(aliases, input) {
+            if let Some(res) = self.resolve_aliases(aliases, &specifier, Fields::ALIAS)? {
+              return Ok(Some(res));
+            }
           }
         }
+        *self.root_package()?
+      }
+      AliasSource::LocalAlias | AliasSource::LocalBrowser => match input {
+        AliasInput::Specifier(_) => self.find_package(self.from.parent().unwrap())?,
+        AliasInput::File(_, package) => package,
+      },
+    };
+    let package = match package {
+      Some(package) => package,
+      None => return Ok(None),
+    };
+    let specifier = match alias_specifier(package, input) {
+      Some(specifier) => specifier,
+      None => return Ok(None),
+    };
 
-        let package_dir = dir.join("node_modules").join(module);
-        if self.resolver.cache.is_dir(&package_dir) {
-          return self.resolve_package(package_dir, module, subpath);
+    if source != AliasSource::LocalBrowser {
+      return self.resolve_aliases(package, &specifier, Fields::ALIAS);
+    }
+
+    if self.resolver.entries.contains(Fields::BROWSER) {
+      if let Some(res) = self.resolve_aliases(package, &specifier, Fields::BROWSER)? {
+        return Ok(Some(res));
+      }
+
+      // Keys in the "browser" field often omit the extension, e.g. `{"./lib/fs": ...}`,
+      // which should also match an import of `./lib/fs.js`. Packages with "exports" name their
+      // files exactly (and use the "browser" condition instead), so only exact keys apply there.
+      if let (AliasInput::File(..), Specifier::Relative(path)) = (input, specifier.as_ref()) {
+        let stem = if package.has_exports() {
+          None
+        } else {
+          self.strip_configured_extension(path)
+        };
+        if let Some(stem) = stem {
+          let specifier = Specifier::Relative(Cow::Owned(stem));
+          if let Some(res) = self.resolve_aliases(package, &specifier, Fields::BROWSER)? {
+            return Ok(Some(res));
+          }
         }
       }
     }
 
-    // NODE_PATH??
-
-    Err(ResolverError::ModuleNotFound {
-      module: module.to_owned(),
-    })
+    self.resolve_custom_aliases(package, &specifier)
   }
 
-  fn resolve_package(
+  fn trace_alias(&self, specifier: &Specifier, source: AliasSource, outcome: AliasOutcome) {
+    if self.resolver.diagnostics {
+      self
+        .diagnostics
+        .alias_trace
+        .borrow_mut()
+        .push(AliasDecision {
+          specifier: specifier.to_string().into_owned(),
+          source,
+          outcome,
+        });
+    }
+  }
+
+  fn resolve_node_module(&self, module: &str, subpath: &str) -> Result<Resolution, ResolverError> {
+    if let Some(package) = self.find_self_reference(module)? {
+      let package_dir = package.path.parent().unwrap().to_owned();
+      return self.resolve_package(package_dir, module, subpath);
+    }
+
+    let package_dir = self.find_node_module(module)?;
+    self.resolve_package(package_dir, module, subpath)
+  }
+
+  /// Finds the package.json that owns the importer, if the importer refers to its own package by
+  /// name. As in Node, this only applies to packages with an "exports" field, whose targets are
+  /// resolved against that package's directory. This allows packages nested in a monorepo to
+  /// import themselves without being linked into node_modules.
+  fn find_self_reference(
     &self,
-    mut package_dir: PathBuf,
+    module: &str,
+  ) -> Result<Option<&'a PackageJson<'a>>, ResolverError> {
+    if !self.resolver.flags.contains(Flags::EXPORTS) {
+      return Ok(None);
+    }
+
+    match self.find_package(self.from.parent().unwrap())? {
+      Some(package) if package.name == module && package.has_exports() => Ok(Some(package)),
+      _ => Ok(None),
+    }
+  }
+
+  /// Resolves a tilde or absolute specifier through the "exports" field of the importer's own
+  /// package, if `Resolver::enforce_own_exports` is enabled and `path` is inside that package.
+  /// If the subpath is not exported, the error suggests an exported subpath for the file that
+  /// `resolve_unrestricted` would have loaded. Returns `None` if exports don't apply.
+  fn resolve_own_export<F: FnOnce() -> Result<Resolution, ResolverError>>(
+    &self,
+    path: &Path,
+    resolve_unrestricted: F,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    if !self.resolver.enforce_own_exports || !self.resolver.flags.contains(Flags::EXPORTS) {
+      return Ok(None);
+    }
+
+    let package = match self.find_package(self.from.parent().unwrap())? {
+      Some(package) if package.has_exports() => package,
+      _ => return Ok(None),
+    };
+    let package_dir = package.path.parent().unwrap();
+    let subpath = match normalize_path(path).strip_prefix(package_dir) {
+      Ok(subpath) => subpath
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"),
+      Err(_) => return Ok(None),
+    };
+
+    match self.resolve_package(package_dir.to_owned(), package.name, &subpath) {
+      Err(ResolverError::PackageJsonError {
+        error: PackageJsonError::PackagePathNotExported,
+        ..
+      }) => {
+        let suggestion = match resolve_unrestricted() {
+          Ok(Resolution::Path(path)) => {
+            package.exported_subpath(&path, self.conditions, self.custom_conditions)
+          }
+          _ => None,
+        };
+        Err(ResolverError::OwnSubpathNotExported {
+          package: package.name.to_owned(),
+          subpath: format!("./{}", subpath),
+          suggestion,
+        })
+      }
+      res => res.map(Some),
+    }
+  }
+
+  /// Finds the directory of an installed package in the closest module directory
+  /// (e.g. node_modules) that contains it.
+  fn find_node_module(&self, module: &str) -> Result<PathBuf, ResolverError> {
+    // If there is a custom module directory resolver (e.g. Yarn PnP), use that.
+    if let Some(module_dir_resolver) = &self.resolver.module_dir_resolver {
+      return module_dir_resolver(module, self.from);
+    } else {
+      let module_dirs = &self.resolver.module_dirs;
+      for module_dir in module_dirs.iter() {
+        self.invalidations.invalidate_on_file_create_above(
+          format!("{}/{}", module_dir, module),
+          self.from.parent().unwrap(),
+        );
+      }
+
+      for (depth, dir) in self.from.ancestors().enumerate() {
+        self
+          .resolver
+          .limits
+          .check(Limit::AncestorDepth, depth + 1)?;
+
+        // Skip over node_modules directories
+        if let Some(filename) = dir.file_name() {
+          if module_dirs.iter().any(|d| filename == d.as_str()) {
+            continue;
+          }
+        }
+
+        self.check_cancelled()?;
+        for module_dir in module_dirs.iter() {
+          let package_dir = dir.join(module_dir).join(module);
+          self.record_candidate(&package_dir);
+          if self.resolver.is_dir(&package_dir) {
+            return Ok(package_dir);
+          }
+        }
+      }
+    }
+
+    // NODE_PATH??
+
+    Err(ResolverError::ModuleNotFound {
+      module: module.to_owned(),
+    })
+  }
+
+  fn resolve_bin(&self, module: &str, bin_name: Option<&str>) -> Result<PathBuf, ResolverError> {
+    let package_dir = self.find_node_module(module)?;
+    let package_path = package_dir.join("package.json");
+    let package = self
+      .invalidations
+      .read(&package_path, || {
+        self.resolver.read_package(Cow::Borrowed(&package_path))
+      })
+      .map_err(|err| match err {
+        ResolverError::IOError(_) => ResolverError::PackageJsonNotFound { from: package_dir },
+        err => err,
+      })?;
+    self.consult_manifest(package);
+
+    let names = package.bin_names();
+    if names.is_empty() {
+      return Err(ResolverError::PackageWithoutBin {
+        module: module.to_owned(),
+        package_path: package.path.clone(),
+      });
+    }
+
+    let bin_name = bin_name.unwrap_or_else(|| module.rsplit('/').next().unwrap_or(module));
+    let script = match package.bin(bin_name) {
+      Some(script) => self.normalize_manifest_path(package, script, "bin"),
+      None => {
+        return Err(ResolverError::BinNotFound {
+          module: module.to_owned(),
+          bin: bin_name.to_owned(),
+          package_path: package.path.clone(),
+          available: names.into_iter().map(|name| name.to_owned()).collect(),
+        })
+      }
+    };
+
+    if self.resolver.is_file(&script) {
+      return Ok(script);
+    }
+
+    // On Windows, a script may only exist as a `.cmd` shim.
+    let extensions = self
+      .resolver
+      .extensions
+      .iter()
+      .chain(cfg!(windows).then_some("cmd"));
+    for extension in extensions {
+      let mut path = script.clone().into_os_string();
+      path.push(".");
+      path.push(extension);
+      let path = PathBuf::from(path);
+      if self.resolver.is_file(&path) {
+        return Ok(path);
+      }
+    }
+
+    Err(ResolverError::ModuleEntryNotFound {
+      module: module.to_owned(),
+      entry_path: script,
+      package_path: package.path.clone(),
+      field: "bin",
+    })
+  }
+
+  fn resolve_package(
+    &self,
+    package_dir: PathBuf,
     module: &str,
     subpath: &str,
   ) -> Result<Resolution, ResolverError> {
+    self.check_cancelled()?;
     let package_path = package_dir.join("package.json");
     let package = self.invalidations.read(&package_path, || {
-      self
-        .resolver
-        .cache
-        .read_package(Cow::Borrowed(&package_path))
+      self.resolver.read_package(Cow::Borrowed(&package_path))
     });
 
     let package = match package {
-      Ok(package) => package,
+      Ok(package) => {
+        self.consult_manifest(package);
+        self.record_partial(|partial| {
+          partial.package_root = Some(package_dir.clone());
+          partial.manifest = Some(package_path.clone());
+          partial.closest_export = None;
+        });
+        package
+      }
       Err(ResolverError::IOError(_)) => {
         // No package.json in node_modules is probably invalid but we have tests for it...
         if self.resolver.flags.contains(Flags::DIR_INDEX) {
-          if let Some(res) = self.load_file(&package_dir.join(self.resolver.index_file), None)? {
+          if let Some(res) = self.load_index(&package_dir, None)? {
             return Ok(res);
           }
         }
@@ -643,9 +2442,37 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
       Err(err) => return Err(err),
     };
 
+    // Workspace packages may have stale or missing build output, so optionally prefer their sources.
+    let workspace_dir = if self.resolver.prefer_source_in_workspace {
+      self.workspace_package_dir(package)
+    } else {
+      None
+    };
+
+    if let Some(dir) = &workspace_dir {
+      if let Some(res) = self.load_workspace_source(package, dir, subpath)? {
+        return Ok(res);
+      }
+    }
+
+    let res = self.resolve_package_entry(package_dir, module, subpath, package)?;
+    match &workspace_dir {
+      Some(dir) => self.remap_workspace_output(package, dir, res, subpath.is_empty()),
+      None => Ok(res),
+    }
+  }
+
+  fn resolve_package_entry(
+    &self,
+    mut package_dir: PathBuf,
+    module: &str,
+    subpath: &str,
+    package: &PackageJson,
+  ) -> Result<Resolution, ResolverError> {
     // Try the "source" field first, if present.
     if self.resolver.entries.contains(Fields::SOURCE) && subpath.is_empty() {
       if let Some(source) = package.source() {
+        let source = self.normalize_manifest_path(package, source, "source");
         if let Some(res) = self.load_path(&source, Some(package))? {
           return Ok(res);
         }
@@ -655,41 +2482,117 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
     // If the exports field is present, use the Node ESM algorithm.
     // Otherwise, fall back to classic CJS resolution.
     if self.resolver.flags.contains(Flags::EXPORTS) && package.has_exports() {
-      let path = package
-        .resolve_package_exports(subpath, self.conditions, self.custom_conditions)
-        .map_err(|e| ResolverError::PackageJsonError {
+      self
+        .resolver
+        .limits
+        .check(Limit::ExportsKeys, package.exports_key_count())?;
+
+      // Array fallbacks are tried in order until one exists.
+      self.check_cancelled()?;
+      let exists = |path: &Path| self.export_exists(path, package);
+      let normalized = RefCell::new(Vec::new());
+      let res = self.resolver.measure(Phase::Exports, || {
+        package.resolve_package_exports_compat(
+          subpath,
+          self.conditions,
+          self.custom_conditions,
+          ExportsOptions {
+            normalize_backslashes: self.normalize_manifest_backslashes(),
+            exists: Some(&exists),
+            trace: self
+              .resolver
+              .diagnostics
+              .then_some(&self.diagnostics.exports_trace),
+            normalized: Some(&normalized),
+          },
+        )
+      });
+      if let Ok(path) = &res {
+        if let Some((_, target)) = normalized.borrow().iter().find(|(p, _)| p == path) {
+          self.warn_manifest_backslashes(package, "exports", target);
+        }
+      }
+      if matches!(res, Err(PackageJsonError::PackagePathNotExported)) {
+        self.record_partial(|partial| partial.closest_export = package.closest_export(subpath));
+      }
+      let path = match res {
+        // This only occurs for the "." subpath, so fall through to the entry fields below.
+        Err(PackageJsonError::UnmatchedConditions { .. })
+          if self.resolver.unmatched_conditions == Fallthrough::LegacyMain =>
+        {
+          None
+        }
+        Err(PackageJsonError::PackagePathBlocked) => {
+          return Err(ResolverError::SubpathNotExported {
+            package: package.name.to_owned(),
+            subpath: format!("./{}", subpath),
+          })
+        }
+        res => Some(res.map_err(|e| ResolverError::PackageJsonError {
           module: package.name.to_owned(),
           path: package.path.clone(),
           error: e,
-        })?;
+        })?),
+      };
 
-      // Extensionless specifiers are not supported in the exports field
-      // according to the Node spec (for both ESM and CJS). However, webpack
-      // didn't follow this, so there are many packages that rely on it (e.g. underscore).
-      if self
-        .resolver
-        .flags
-        .contains(Flags::EXPORTS_OPTIONAL_EXTENSIONS)
-      {
-        if let Some(res) = self.load_file(&path, Some(package))? {
+      if let Some(path) = path {
+        // Extensionless specifiers are not supported in the exports field
+        // according to the Node spec (for both ESM and CJS). However, webpack
+        // didn't follow this, so there are many packages that rely on it (e.g. underscore).
+        // Type lookups also try the declaration file next to a JavaScript target.
+        if self
+          .resolver
+          .flags
+          .contains(Flags::EXPORTS_OPTIONAL_EXTENSIONS)
+          || self.context == ResolutionContext::Types
+        {
+          if let Some(res) = self.load_file(&path, Some(package))? {
+            return Ok(res);
+          }
+        } else if let Some(res) = self.try_file_without_aliases(&path)? {
           return Ok(res);
         }
-      } else if let Some(res) = self.try_file_without_aliases(&path)? {
-        return Ok(res);
+
+        // TODO: track location of resolved field
+        return Err(ResolverError::ModuleSubpathNotFound {
+          module: module.to_owned(),
+          path,
+          package_path: package.path.clone(),
+        });
       }
+    }
 
-      // TODO: track location of resolved field
-      return Err(ResolverError::ModuleSubpathNotFound {
-        module: module.to_owned(),
-        path,
-        package_path: package.path.clone(),
-      });
-    } else if !subpath.is_empty() {
-      package_dir.push(subpath);
-      if let Some(res) = self.load_path(&package_dir, Some(&package))? {
+    // TypeScript redirects type lookups through "typesVersions" for matching compiler versions.
+    if self.resolver.entries.contains(Fields::TYPES) || self.context == ResolutionContext::Types {
+      if let Some(version) = self.resolver.typescript_version {
+        if let Some(paths) = package.types_versions(subpath, version) {
+          for path in paths {
+            if let Some(res) = self.load_path(&path, Some(package))? {
+              return Ok(res);
+            }
+          }
+        }
+      }
+    }
+
+    if !subpath.is_empty() {
+      // `pkg/` loads the package directory, like `require('./node_modules/pkg/')`.
+      package_dir.push(if subpath == "." { "" } else { subpath });
+      if let Some(res) = self.load_specifier_path(&package_dir, Some(package))? {
         return Ok(res);
       }
 
+      // Directory imports are unsupported in Node ESM, so explain why it couldn't be loaded.
+      if !self.can_load_directory() && self.resolver.is_dir(&package_dir) {
+        return Err(ResolverError::DirectoryWithoutIndex {
+          relative: PathBuf::from(format!("{}/{}", module, subpath)),
+          from: self.from.to_owned(),
+          index_names: Vec::new(),
+          extensions: Vec::new(),
+          specifier_type: self.specifier_type,
+        });
+      }
+
       return Err(ResolverError::ModuleSubpathNotFound {
         module: module.to_owned(),
         path: package_dir,
@@ -701,11 +2604,14 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
         return Ok(res);
       }
 
-      // Node ESM doesn't allow directory imports.
+      // Node ESM doesn't allow directory imports, but its legacy main resolution still loads
+      // the package's index file when there is no "main".
       if self.resolver.flags.contains(Flags::DIR_INDEX) {
-        if let Some(res) =
-          self.load_file(&package_dir.join(self.resolver.index_file), Some(&package))?
-        {
+        if let Some(res) = self.load_index(&package_dir, Some(package))? {
+          return Ok(res);
+        }
+      } else if matches!(res, Ok(None)) {
+        if let Some(res) = self.load_legacy_index(&package_dir, package)? {
           return Ok(res);
         }
       }
@@ -716,20 +2622,210 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
 
       return Err(ResolverError::ModuleSubpathNotFound {
         module: module.to_owned(),
-        path: package_dir.join(self.resolver.index_file),
+        path: match self.resolver.index_file_names(&package_dir).first() {
+          Some(name) => package_dir.join(name),
+          None => package_dir.clone(),
+        },
         package_path: package.path.clone(),
       });
     }
   }
 
+  /// Whether an "exports" target exists, following the same rules used to load it below.
+  fn export_exists(&self, path: &Path, package: &PackageJson) -> bool {
+    if self
+      .resolver
+      .flags
+      .contains(Flags::EXPORTS_OPTIONAL_EXTENSIONS)
+    {
+      matches!(self.load_file(path, Some(package)), Ok(Some(_)))
+    } else {
+      matches!(self.try_file_without_aliases(path), Ok(Some(_)))
+    }
+  }
+
+  /// Returns the real directory of a package that lives outside any node_modules directory,
+  /// e.g. a monorepo member symlinked from the workspace.
+  fn workspace_package_dir(&self, package: &PackageJson) -> Option<PathBuf> {
+    let realpath = self.resolver.cache.canonicalize(&package.path).ok()?;
+    if realpath
+      .components()
+      .any(|c| c.as_os_str() == "node_modules")
+    {
+      return None;
+    }
+
+    realpath.parent().map(|dir| dir.to_owned())
+  }
+
+  fn workspace_tsconfig(&self, dir: &Path) -> Result<Option<&'a TsConfig<'a>>, ResolverError> {
+    self.check_cancelled()?;
+    let path = dir.join("tsconfig.json");
+    if self.resolver.is_file(&path) {
+      Ok(Some(self.read_tsconfig(path)?))
+    } else {
+      self.invalidations.invalidate_on_file_create(&path);
+      Ok(None)
+    }
+  }
+
+  /// Tries a workspace package's sources before its build output: the "source" field,
+  /// then "main:src", then the entry or subpath mapped from `outDir` to `rootDir` via tsconfig.json.
+  /// If the package has an "exports" field, the subpath is mapped through it first.
+  fn load_workspace_source(
+    &self,
+    package: &PackageJson,
+    dir: &Path,
+    subpath: &str,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    let tsconfig = self.workspace_tsconfig(dir)?;
+    let has_exports = self.resolver.flags.contains(Flags::EXPORTS) && package.has_exports();
+    if !subpath.is_empty() && !has_exports {
+      return self.load_workspace_output_source(package, tsconfig, &dir.join(subpath));
+    }
+
+    if subpath.is_empty() {
+      for (source, field) in [
+        (package.source(), "source"),
+        (package.main_src(), "main:src"),
+      ] {
+        if let Some(source) = source {
+          let source = self.normalize_manifest_path(package, source, field);
+          if let Some(res) = self.load_path(&source, Some(package))? {
+            return Ok(Some(res));
+          }
+        }
+      }
+    }
+
+    if has_exports {
+      // Errors are reported when the exports are resolved again for the build output.
+      let target =
+        match package.resolve_package_exports(subpath, self.conditions, self.custom_conditions) {
+          Ok(target) => target,
+          Err(_) => return Ok(None),
+        };
+      return match target.strip_prefix(package.path.parent().unwrap()) {
+        Ok(target) => self.load_workspace_output_source(package, tsconfig, &dir.join(target)),
+        Err(_) => Ok(None),
+      };
+    }
+
+    let mut entries = package
+      .entries(self.resolver.entries)
+      .map_err(|err| package_json_error(package, err))?;
+    if let Some((entry, _)) = entries.next() {
+      if let Ok(entry) = entry.strip_prefix(package.path.parent().unwrap()) {
+        return self.load_workspace_output_source(package, tsconfig, &dir.join(entry));
+      }
+    }
+
+    Ok(None)
+  }
+
+  /// Loads the source file for a path inside a workspace package's tsconfig `outDir`, if it exists.
+  fn load_workspace_output_source(
+    &self,
+    package: &PackageJson,
+    tsconfig: Option<&TsConfig>,
+    path: &Path,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    let source = match tsconfig.and_then(|tsconfig| tsconfig.source_path(path)) {
+      Some(source) => source,
+      None => return Ok(None),
+    };
+
+    let extensions: &[&str] = match path.extension().and_then(|ext| ext.to_str()) {
+      None | Some("js") | Some("jsx") => &["ts", "tsx"],
+      Some("mjs") => &["mts"],
+      Some("cjs") => &["cts"],
+      _ => &[],
+    };
+
+    let without_extension = source.with_extension("");
+    for ext in extensions {
+      if let Some(res) = self.try_suffixes(&without_extension, ext, Some(package), false)? {
+        return Ok(Some(res));
+      }
+    }
+
+    self.try_file_without_aliases(&source)
+  }
+
+  /// Maps a file resolved inside a workspace package's build output back to its source, if a
+  /// reliable mapping exists. Otherwise the file is left alone, and a warning is attached if
+  /// the package entry fell back to the build output.
+  fn remap_workspace_output(
+    &self,
+    package: &PackageJson,
+    dir: &Path,
+    res: Resolution,
+    is_entry: bool,
+  ) -> Result<Resolution, ResolverError> {
+    let path = match &res {
+      Resolution::Path(path) => path,
+      _ => return Ok(res),
+    };
+
+    let tsconfig = self.workspace_tsconfig(dir)?;
+    if let Some(source) = self.load_workspace_output_source(package, tsconfig, path)? {
+      return Ok(source);
+    }
+
+    if is_entry {
+      let out_dir = tsconfig
+        .and_then(|tsconfig| tsconfig.out_dir())
+        .map_or_else(|| dir.join("dist"), |out_dir| out_dir.to_owned());
+      if path.starts_with(&out_dir) {
+        self.warn(ResolverWarning::WorkspaceSourceNotFound {
+          path: package.path.clone(),
+          entry: path.to_path_buf(),
+        });
+      }
+    }
+
+    Ok(res)
+  }
+
   fn try_package_entries(
     &self,
     package: &PackageJson,
   ) -> Result<Option<Resolution>, ResolverError> {
-    // Try all entry fields.
-    for (entry, field) in package.entries(self.resolver.entries) {
+    let (custom_entries, entries) = match self.context {
+      ResolutionContext::Sass => (
+        itertools::Either::Left(SASS_ENTRY_FIELDS.iter().copied()),
+        Fields::MAIN,
+      ),
+      ResolutionContext::Types => (
+        itertools::Either::Left((&[] as &[&str]).iter().copied()),
+        self.resolver.entries | Fields::TYPES,
+      ),
+      ResolutionContext::Default => (
+        itertools::Either::Right(self.resolver.custom_entries.iter().map(String::as_str)),
+        self.resolver.entries,
+      ),
+    };
+
+    // Custom fields come first. Unlike the built-in fields, a missing entry falls through to
+    // the next field, since e.g. a "svelte" field often points at sources that aren't published.
+    for field in custom_entries {
+      if let Some(entry) = package.custom_entry(field) {
+        if let Some(res) = self.load_path(&entry, Some(package))? {
+          return Ok(Some(res));
+        }
+      }
+    }
+
+    // Try the first entry field that is set.
+    let mut entries = package
+      .entries(entries)
+      .map_err(|err| package_json_error(package, err))?;
+    if let Some((entry, field)) = entries.next() {
+      let entry = self.normalize_manifest_path(package, entry, field);
       if let Some(res) = self.load_path(&entry, Some(package))? {
         return Ok(Some(res));
+      } else if let Some(res) = self.load_legacy_main(&entry, field, package)? {
+        return Ok(Some(res));
       } else {
         return Err(ResolverError::ModuleEntryNotFound {
           module: package.name.to_owned(),
@@ -743,48 +2839,157 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
     Ok(None)
   }
 
+  /// Node's legacy main resolution, which applies even in ESM where extensions and directory
+  /// imports are otherwise unsupported. "main" is tried with each of `LEGACY_MAIN_EXTENSIONS`,
+  /// then as a directory with an index file (e.g. `"main": "lib"`), and finally the package's own
+  /// index file is loaded. Unlike `load_directory`, a package.json inside the directory is not
+  /// consulted. When directories can be loaded, `load_path` has already handled this.
+  fn load_legacy_main(
+    &self,
+    entry: &Path,
+    field: &str,
+    package: &PackageJson,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    if field != "main" || self.can_load_directory() {
+      return Ok(None);
+    }
+
+    for ext in LEGACY_MAIN_EXTENSIONS {
+      let mut path = entry.as_os_str().to_owned();
+      path.push(".");
+      path.push(ext);
+      if let Some(res) = self.load_file(Path::new(&path), Some(package))? {
+        return Ok(Some(res));
+      }
+    }
+
+    if let Some(res) = self.load_legacy_index(entry, package)? {
+      return Ok(Some(res));
+    }
+
+    self.load_legacy_index(package.path.parent().unwrap(), package)
+  }
+
+  /// Loads `index.js`, `index.json`, or `index.node` from a directory, for legacy main resolution.
+  fn load_legacy_index(
+    &self,
+    dir: &Path,
+    package: &PackageJson,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    for ext in LEGACY_MAIN_EXTENSIONS {
+      if let Some(res) = self.load_file(&dir.join(format!("index.{}", ext)), Some(package))? {
+        return Ok(Some(res));
+      }
+    }
+
+    Ok(None)
+  }
+
   fn load_path(
     &self,
     path: &Path,
     package: Option<&PackageJson>,
   ) -> Result<Option<Resolution>, ResolverError> {
-    // Urls and Node ESM do not resolve directory index files.
-    let can_load_directory =
-      self.resolver.flags.contains(Flags::DIR_INDEX) && self.specifier_type != SpecifierType::Url;
-
     // If path ends with / only try loading as a directory.
-    let is_directory = can_load_directory
-      && path
-        .as_os_str()
-        .to_str()
-        .map(|s| s.ends_with('/'))
-        .unwrap_or(false);
-
-    if !is_directory {
+    if !has_trailing_slash(path) {
       if let Some(res) = self.load_file(path, package)? {
         return Ok(Some(res));
       }
     }
 
-    // Urls and Node ESM do not resolve directory index files.
-    if can_load_directory {
+    if self.can_load_directory() {
       return self.load_directory(path, package);
     }
 
     Ok(None)
   }
 
+  /// Like `load_path`, for a path named by the specifier itself rather than e.g. a package entry.
+  /// With `allow_directory_resolution`, an existing directory resolves to itself.
+  fn load_specifier_path(
+    &self,
+    path: &Path,
+    package: Option<&PackageJson>,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    if !self.resolver.allow_directory_resolution {
+      return self.load_path(path, package);
+    }
+
+    if !has_trailing_slash(path) {
+      if let Some(res) = self.load_file(path, package)? {
+        return Ok(Some(res));
+      }
+    }
+
+    self.try_directory(path)
+  }
+
+  /// Urls and Node ESM do not resolve directory index files.
+  /// Tries each index name in the directory, with each extension.
+  fn load_index(
+    &self,
+    dir: &Path,
+    package: Option<&PackageJson>,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    if self.context == ResolutionContext::Sass {
+      return self.load_sass_file(&dir.join("index"), package);
+    }
+
+    for name in self.resolver.index_file_names(dir) {
+      if let Some(res) = self.load_file(&dir.join(name), package)? {
+        return Ok(Some(res));
+      }
+    }
+
+    Ok(None)
+  }
+
+  /// Resolves an existing directory to itself. Only its existence is tracked, not its contents,
+  /// so adding or removing files inside it does not invalidate the resolution.
+  fn try_directory(&self, dir: &Path) -> Result<Option<Resolution>, ResolverError> {
+    self.check_cancelled()?;
+    if self.resolver.is_dir(dir) {
+      let canonical = self.real_path(dir)?;
+      self.invalidations.invalidate_on_file_change(dir);
+      Ok(Some(Resolution::Directory(
+        self.resolver.cache.intern(&canonical),
+      )))
+    } else {
+      self.invalidations.invalidate_on_file_create(dir);
+      Ok(None)
+    }
+  }
+
+  fn can_load_directory(&self) -> bool {
+    self.resolver.flags.contains(Flags::DIR_INDEX) && self.specifier_type != SpecifierType::Url
+  }
+
   fn load_file(
     &self,
     path: &Path,
     package: Option<&PackageJson>,
   ) -> Result<Option<Resolution>, ResolverError> {
+    if self.context == ResolutionContext::Sass {
+      return self.load_sass_file(path, package);
+    }
+
+    if self.context == ResolutionContext::Types {
+      if let Some(res) = self.load_declaration_file(path, package)? {
+        return Ok(Some(res));
+      }
+    }
+
+    // Native addons are binary, so never try JavaScript or TypeScript extensions for them.
+    if is_native_addon(path) {
+      return self.try_file(path, package, false);
+    }
+
     // First try the path as is.
     // TypeScript only supports resolving specifiers ending with `.ts` or `.tsx`
     // in a certain mode, but we always allow it.
-    // If there is no extension in the original specifier, only check aliases
-    // here and delay checking for an extensionless file until later (since this is unlikely).
-    if let Some(res) = self.try_suffixes(path, "", package, path.extension().is_none())? {
+    // This includes extensionless files (e.g. `bin/cli`), which take precedence over
+    // appending an extension, as in Node.
+    if let Some(res) = self.try_suffixes(path, "", package, false)? {
       return Ok(Some(res));
     }
 
@@ -842,17 +3047,63 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
     }
 
     // Try appending the configured extensions.
-    if let Some(res) = self.try_extensions(path, package, &self.resolver.extensions, true)? {
-      return Ok(Some(res));
+    self.try_extensions(path, package, &self.resolver.extensions, true)
+  }
+
+  /// Loads a file following Sass rules: the path as written, then with each style extension.
+  /// Each is tried before its partial, e.g. `functions.scss` before `_functions.scss`.
+  fn load_sass_file(
+    &self,
+    path: &Path,
+    package: Option<&PackageJson>,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    let partial = match path.file_name() {
+      Some(name) if !name.to_string_lossy().starts_with('_') => {
+        let mut partial = OsString::from("_");
+        partial.push(name);
+        Some(path.with_file_name(partial))
+      }
+      _ => None,
+    };
+
+    for ext in std::iter::once("").chain(SASS_EXTENSIONS.iter().copied()) {
+      for path in std::iter::once(path).chain(partial.as_deref()) {
+        if let Some(res) = self.try_suffixes(path, ext, package, false)? {
+          return Ok(Some(res));
+        }
+      }
     }
 
-    // If there is no extension in the specifier, try an extensionless file as a last resort.
-    if path.extension().is_none() {
-      if let Some(res) = self.try_suffixes(path, "", package, false)? {
+    Ok(None)
+  }
+
+  /// Loads the declaration file for a path, e.g. `foo.d.ts` for `foo.js` or `foo`.
+  /// Returns `None` to fall back to the usual rules, e.g. for TypeScript sources.
+  fn load_declaration_file(
+    &self,
+    path: &Path,
+    package: Option<&PackageJson>,
+  ) -> Result<Option<Resolution>, ResolverError> {
+    let declaration = match path.extension().and_then(|ext| ext.to_str()) {
+      Some("js" | "jsx") => Some("d.ts"),
+      Some("mjs") => Some("d.mts"),
+      Some("cjs") => Some("d.cts"),
+      _ => None,
+    };
+    if let Some(ext) = declaration {
+      if let Some(res) = self.try_suffixes(&path.with_extension(""), ext, package, false)? {
         return Ok(Some(res));
       }
     }
 
+    if self.specifier_type != SpecifierType::Url && !self.resolver.is_file(path) {
+      for ext in DECLARATION_EXTENSIONS {
+        if let Some(res) = self.try_suffixes(path, ext, package, false)? {
+          return Ok(Some(res));
+        }
+      }
+    }
+
     Ok(None)
   }
 
@@ -866,8 +3117,19 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
     if self.resolver.flags.contains(Flags::OPTIONAL_EXTENSIONS)
       && self.specifier_type != SpecifierType::Url
     {
+      // In a package with an explicit "type", prefer the extension matching its format.
+      let ordered = package.and_then(|p| p.module_type()).map(|module_type| {
+        let mut ordered: Vec<_> = extensions.iter().collect();
+        module_type.order_extensions(&mut ordered);
+        ordered
+      });
+      let extensions = match &ordered {
+        Some(ordered) => itertools::Either::Left(ordered.iter().copied()),
+        None => itertools::Either::Right(extensions.iter()),
+      };
+
       // Try appending each extension.
-      for ext in extensions.iter() {
+      for ext in extensions {
         // Skip parent extension if we already tried it.
         if skip_parent
           && self.resolver.flags.contains(Flags::PARENT_EXTENSION)
@@ -950,44 +3212,166 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
     package: Option<&PackageJson>,
     alias_only: bool,
   ) -> Result<Option<Resolution>, ResolverError> {
-    if self.resolver.flags.contains(Flags::ALIASES) {
-      // Check the project root package.json first.
-      if let Some(package) = self.root_package()? {
-        if let Ok(s) = path.strip_prefix(package.path.parent().unwrap()) {
-          let specifier = Specifier::Relative(Cow::Borrowed(s));
-          if let Some(res) = self.resolve_aliases(package, &specifier, Fields::ALIAS)? {
-            return Ok(Some(res));
-          }
-        }
+    // Files are matched against the same alias sources as bare specifiers, in the same order.
+    for source in &*self.resolver.alias_precedence {
+      let input = AliasInput::File(path, package);
+      if let Some(res) = self.resolve_alias_source(*source, input)? {
+        return Ok(Some(res));
       }
+    }
 
-      // Next try the local package.json.
-      if let Some(package) = package {
-        if let Ok(s) = path.strip_prefix(package.path.parent().unwrap()) {
-          let specifier = Specifier::Relative(Cow::Borrowed(s));
-          let mut fields = Fields::ALIAS;
-          if self.resolver.entries.contains(Fields::BROWSER) {
-            fields |= Fields::BROWSER;
-          }
-          if let Some(res) = self.resolve_aliases(package, &specifier, fields)? {
-            return Ok(Some(res));
+    if alias_only {
+      return Ok(None);
+    }
+
+    self.try_file_without_aliases(path)
+  }
+
+  /// Removes the extension from a path, if it is one of the configured extensions.
+  fn strip_configured_extension(&self, path: &Path) -> Option<PathBuf> {
+    let ext = path.extension()?;
+    if self
+      .resolver
+      .extensions
+      .iter()
+      .any(|e| ext == OsStr::new(e))
+    {
+      Some(path.with_extension(""))
+    } else {
+      None
+    }
+  }
+
+  /// Returns the same file in an ancestor node_modules directory, if the nested package that
+  /// `path` belongs to has an identical copy there, i.e. with the same name and version.
+  /// The outermost copy is preferred, so that all nested copies dedupe to the same file.
+  fn find_deduped_package(&self, path: &Path) -> Result<Option<InternedPath>, ResolverError> {
+    let is_node_modules = |dir: &Path| dir.file_name() == Some(OsStr::new("node_modules"));
+    let package_dir = match path.ancestors().find(|dir| is_package_dir(dir)) {
+      Some(dir) => dir,
+      None => return Ok(None),
+    };
+
+    // Only packages within another package's node_modules are nested, e.g. `node_modules/a`.
+    let owner = match package_dir
+      .ancestors()
+      .find(|dir| is_node_modules(dir))
+      .and_then(|dir| dir.parent())
+    {
+      Some(owner) if owner.ancestors().any(is_node_modules) => owner,
+      _ => return Ok(None),
+    };
+
+    let read = |dir: &Path| {
+      let package_path = dir.join("package.json");
+      self.invalidations.read(&package_path, || {
+        self.resolver.read_package(Cow::Borrowed(&package_path))
+      })
+    };
+
+    let package = match read(package_dir) {
+      Ok(package) => package,
+      Err(ResolverError::IOError(_)) => return Ok(None),
+      Err(err) => return Err(err),
+    };
+
+    if package.name.is_empty()
+      || package.version.is_empty()
+      || self
+        .resolver
+        .dedupe_exclude
+        .iter()
+        .any(|name| name == package.name)
+    {
+      return Ok(None);
+    }
+
+    let package_subdir = package_dir.strip_prefix(owner).unwrap();
+    let file = path.strip_prefix(package_dir).unwrap();
+    let mut deduped = None;
+    for (depth, dir) in owner.ancestors().enumerate().skip(1) {
+      // Copies outside the project aren't part of its install, so never dedupe to them.
+      if !dir.starts_with(&self.resolver.project_root) {
+        break;
+      }
+
+      self
+        .resolver
+        .limits
+        .check(Limit::AncestorDepth, depth + 1)?;
+      if is_node_modules(dir) {
+        continue;
+      }
+
+      let candidate_dir = dir.join(package_subdir);
+      match read(&candidate_dir) {
+        Ok(candidate) if candidate.name == package.name && candidate.version == package.version => {
+          if let Some(Resolution::Path(candidate)) =
+            self.try_file_without_aliases(&candidate_dir.join(file))?
+          {
+            deduped = Some(candidate);
           }
         }
+        _ => {}
       }
     }
 
-    if alias_only {
-      return Ok(None);
+    if let Some(deduped) = &deduped {
+      self.warn(ResolverWarning::PackageDeduplicated {
+        package: package.name.to_owned(),
+        version: package.version.to_owned(),
+        path: path.to_owned(),
+        deduped: deduped.to_path_buf(),
+      });
     }
 
-    self.try_file_without_aliases(path)
+    Ok(deduped)
+  }
+
+  /// Returns the path to return for an existing file or directory, according to the resolver's
+  /// `symlink_mode`.
+  fn real_path(&self, path: &Path) -> Result<PathBuf, ResolverError> {
+    let in_node_modules = |path: &Path| path.components().any(|c| c.as_os_str() == "node_modules");
+    match self.resolver.symlink_mode {
+      SymlinkMode::PreserveAll => return Ok(path.to_path_buf()),
+      SymlinkMode::CanonicalizeProjectOnly if in_node_modules(path) => {
+        return Ok(path.to_path_buf())
+      }
+      _ => {}
+    }
+
+    let canonical = self.resolver.measure(Phase::Canonicalize, || {
+      self.resolver.cache.canonicalize(path)
+    })?;
+    if self.resolver.symlink_mode == SymlinkMode::PreserveNodeModulesOnly
+      && in_node_modules(&canonical)
+    {
+      return Ok(path.to_path_buf());
+    }
+
+    Ok(canonical)
   }
 
   fn try_file_without_aliases(&self, path: &Path) -> Result<Option<Resolution>, ResolverError> {
-    if self.resolver.cache.is_file(path) {
-      Ok(Some(Resolution::Path(
-        self.resolver.cache.canonicalize(path)?,
-      )))
+    self.check_cancelled()?;
+    self.record_candidate(path);
+    if self.resolver.is_file(path) {
+      let canonical = self.real_path(path)?;
+      let canonical = self.resolver.cache.intern(&canonical);
+      if canonical != *path {
+        if let Some(link) = self.resolver.cache.cached_symlink(path) {
+          self
+            .diagnostics
+            .symlinks
+            .borrow_mut()
+            .push(SymlinkInfo::new(
+              canonical.clone(),
+              path.to_path_buf(),
+              link,
+            ));
+        }
+      }
+      Ok(Some(Resolution::Path(canonical)))
     } else {
       self.invalidations.invalidate_on_file_create(path);
       Ok(None)
@@ -1001,11 +3385,14 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
   ) -> Result<Option<Resolution>, ResolverError> {
     // Check if there is a package.json in this directory, and if so, use its entries.
     // Note that the "exports" field is NOT used here - only in resolve_node_module.
+    self.check_cancelled()?;
     let path = dir.join("package.json");
     let mut res = Ok(None);
-    let package = if let Ok(package) = self.invalidations.read(&path, || {
-      self.resolver.cache.read_package(Cow::Borrowed(&path))
-    }) {
+    let package = if let Ok(package) = self
+      .invalidations
+      .read(&path, || self.resolver.read_package(Cow::Borrowed(&path)))
+    {
+      self.consult_manifest(package);
       res = self.try_package_entries(&package);
       if matches!(res, Ok(Some(_))) {
         return res;
@@ -1016,11 +3403,8 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
     };
 
     // If no package.json, or no entries, try an index file with all possible extensions.
-    if self.resolver.flags.contains(Flags::DIR_INDEX) && self.resolver.cache.is_dir(dir) {
-      return self.load_file(
-        &dir.join(self.resolver.index_file),
-        package.or(parent_package),
-      );
+    if self.resolver.flags.contains(Flags::DIR_INDEX) && self.resolver.is_dir(dir) {
+      return self.load_index(dir, package.or(parent_package));
     }
 
     res
@@ -1028,9 +3412,12 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
 
   fn resolve_tsconfig_paths(&self) -> Result<Option<Resolution>, ResolverError> {
     if let Some(tsconfig) = self.tsconfig()? {
-      for path in tsconfig.paths(&self.specifier) {
+      for (path, origin) in tsconfig.paths_with_origin(self.specifier) {
         // TODO: should aliases apply to tsconfig paths??
         if let Some(res) = self.load_path(&path, None)? {
+          for usage in self.diagnostics.tsconfigs.borrow_mut().iter_mut() {
+            usage.affected_result = usage.path == origin;
+          }
           return Ok(Some(res));
         }
       }
@@ -1047,18 +3434,37 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
       && !self.flags.contains(RequestFlags::IN_NODE_MODULES)
     {
       self.tsconfig.get_or_try_init(|| {
-        if let Some(path) = self.find_ancestor_file(&self.from, "tsconfig.json") {
-          let tsconfig = self.read_tsconfig(path)?;
-          return Ok(Some(tsconfig));
-        }
+        self.resolver.measure(Phase::TsConfig, || {
+          if let Some(path) = self.find_ancestor_file(self.from, "tsconfig.json")? {
+            self.check_cancelled()?;
+            let tsconfig = self.read_tsconfig(path)?;
+            self.record_tsconfig(tsconfig);
+            return Ok(Some(tsconfig));
+          }
 
-        Ok(None)
+          Ok(None)
+        })
       })
     } else {
       Ok(&None)
     }
   }
 
+  /// Records a tsconfig and those it extends for `ResolveResult::tsconfigs_used`. The extended
+  /// files are only read once and then cached, so their invalidations are added here as well.
+  fn record_tsconfig(&self, tsconfig: &TsConfig) {
+    let mut used = self.diagnostics.tsconfigs.borrow_mut();
+    for path in tsconfig.chain() {
+      self.invalidations.invalidate_on_file_change(path);
+      if !used.iter().any(|usage| usage.path == *path) {
+        used.push(TsConfigUsage {
+          path: path.clone(),
+          affected_result: false,
+        });
+      }
+    }
+  }
+
   fn read_tsconfig(&self, path: PathBuf) -> Result<&'a TsConfig<'a>, ResolverError> {
     let tsconfig = self.invalidations.read(&path, || {
       self.resolver.cache.read_tsconfig(&path, |tsconfig| {
@@ -1103,24 +3509,52 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
               absolute_path
             }
             specifier @ Specifier::Package(..) => {
+              const TSCONFIG_INDEX: &[IndexName] =
+                &[IndexName::Literal(Cow::Borrowed("tsconfig.json"))];
               let resolver = Resolver {
                 project_root: Cow::Borrowed(&self.resolver.project_root),
                 extensions: Extensions::Borrowed(&["json"]),
-                index_file: "tsconfig.json",
+                index_names: Cow::Borrowed(TSCONFIG_INDEX),
                 entries: Fields::TSCONFIG,
+                custom_entries: Cow::Borrowed(&[]),
                 flags: Flags::NODE_CJS,
                 cache: CacheCow::Borrowed(&self.resolver.cache),
                 include_node_modules: Cow::Borrowed(self.resolver.include_node_modules.as_ref()),
                 conditions: ExportsCondition::TYPES,
+                module_dirs: Cow::Borrowed(&*self.resolver.module_dirs),
                 module_dir_resolver: self.resolver.module_dir_resolver.clone(),
+                parse_options: ParseOptions::default(),
+                unmatched_conditions: self.resolver.unmatched_conditions,
+                condition_priority: Cow::Borrowed(&[]),
+                alias_precedence: Cow::Borrowed(&*self.resolver.alias_precedence),
+                limits: self.resolver.limits,
+                root_aliases: None,
+                externals: Cow::Borrowed(&[]),
+                verify_externals: false,
+                typescript_version: None,
+                prefer_source_in_workspace: self.resolver.prefer_source_in_workspace,
+                dedupe_packages: false,
+                dedupe_exclude: Cow::Borrowed(&[]),
+                allow_directory_resolution: false,
+                enforce_own_exports: false,
+                symlink_mode: self.resolver.symlink_mode,
+                diagnostics: self.resolver.diagnostics,
+                diagnostic_levels: Cow::Borrowed(&[]),
+                #[cfg(feature = "rayon")]
+                thread_pool: None,
+                metrics: self.resolver.metrics.clone(),
+                resolution_cache: None,
               };
 
+              // This is not cancellable, since the parsed tsconfig is cached and shared with
+              // other requests, and must not record a cancelled resolution of its base config.
               let req = ResolveRequest::new(
                 &resolver,
                 specifier,
                 SpecifierType::Cjs,
                 &tsconfig.compiler_options.path,
                 self.invalidations,
+                self.diagnostics,
               );
 
               let res = req
@@ -1131,7 +3565,7 @@ impl<'a, Fs: FileSystem> ResolveRequest<'a, Fs> {
                 })?;
 
               if let Resolution::Path(res) = res {
-                res
+                PathBuf::from(res)
               } else {
                 return Err(ResolverError::TsConfigExtendsNotFound {
                   tsconfig: tsconfig.compiler_options.path.clone(),
@@ -1182,6 +3616,14 @@ mod tests {
     )
   }
 
+  /// A builder for the fixture root with a fresh cache, starting from the Parcel preset.
+  fn test_resolver_builder<'a>() -> ResolverBuilder<'a, OsFileSystem> {
+    ResolverBuilder::new(
+      root().into(),
+      CacheCow::Owned(Cache::new(OsFileSystem::default())),
+    )
+  }
+
   #[test]
   fn relative() {
     assert_eq!(
@@ -1190,7 +3632,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1198,7 +3640,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1206,7 +3648,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1214,7 +3656,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1226,7 +3668,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/bar.js"))
+      Resolution::Path(root().join("node_modules/foo/bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1234,7 +3676,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("nested/index.js"))
+      Resolution::Path(root().join("nested/index.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1242,7 +3684,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1264,19 +3706,23 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("priority/foo.js"))
+      Resolution::Path(root().join("priority/foo.js").into())
     );
 
     let invalidations = test_resolver()
       .resolve("./bar", &root().join("foo.js"), SpecifierType::Esm)
       .invalidations;
+    // An extensionless file would take precedence over bar.js.
     assert_eq!(
       *invalidations.invalidate_on_file_create.read().unwrap(),
-      HashSet::new()
+      HashSet::from([FileCreateInvalidation::Path(root().join("bar").into())])
     );
     assert_eq!(
       *invalidations.invalidate_on_file_change.read().unwrap(),
-      HashSet::from([root().join("package.json"), root().join("tsconfig.json")])
+      HashSet::from([
+        root().join("package.json").into(),
+        root().join("tsconfig.json").into()
+      ])
     );
   }
 
@@ -1288,7 +3734,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1300,7 +3746,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1312,7 +3758,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       node_resolver()
@@ -1324,7 +3770,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("foo.js"))
+      Resolution::Path(root().join("foo.js").into())
     );
     assert_eq!(
       node_resolver()
@@ -1336,7 +3782,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("foo.js"))
+      Resolution::Path(root().join("foo.js").into())
     );
   }
 
@@ -1348,7 +3794,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/index.js"))
+      Resolution::Path(root().join("node_modules/foo/index.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1356,7 +3802,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-main/main.js"))
+      Resolution::Path(root().join("node_modules/package-main/main.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1364,7 +3810,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-module/module.js"))
+      Resolution::Path(root().join("node_modules/package-module/module.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1376,7 +3822,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-browser/browser.js"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-browser/browser.js")
+          .into()
+      )
     );
     assert_eq!(
       test_resolver()
@@ -1388,7 +3838,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-fallback/index.js"))
+      Resolution::Path(root().join("node_modules/package-fallback/index.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1400,7 +3850,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-main-directory/nested/index.js"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-main-directory/nested/index.js")
+          .into()
+      )
     );
     assert_eq!(
       test_resolver()
@@ -1408,7 +3862,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/nested/baz.js"))
+      Resolution::Path(root().join("node_modules/foo/nested/baz.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1416,7 +3870,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/@scope/pkg/index.js"))
+      Resolution::Path(root().join("node_modules/@scope/pkg/index.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1428,7 +3882,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/@scope/pkg/foo/bar.js"))
+      Resolution::Path(root().join("node_modules/@scope/pkg/foo/bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1440,7 +3894,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/with space.mjs"))
+      Resolution::Path(root().join("node_modules/foo/with space.mjs").into())
     );
     assert_eq!(
       test_resolver()
@@ -1452,7 +3906,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/with space.mjs"))
+      Resolution::Path(root().join("node_modules/foo/with space.mjs").into())
     );
     assert_eq!(
       test_resolver()
@@ -1464,7 +3918,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/with space.mjs"))
+      Resolution::Path(root().join("node_modules/foo/with space.mjs").into())
     );
     assert_eq!(
       test_resolver()
@@ -1491,7 +3945,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/@scope/pkg/index.js"))
+      Resolution::Path(root().join("node_modules/@scope/pkg/index.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1512,17 +3966,20 @@ mod tests {
       .invalidations;
     assert_eq!(
       *invalidations.invalidate_on_file_create.read().unwrap(),
-      HashSet::from([FileCreateInvalidation::FileName {
-        file_name: "node_modules/foo".into(),
-        above: root()
-      },])
+      HashSet::from([
+        FileCreateInvalidation::FileName {
+          file_name: "node_modules/foo".into(),
+          above: root().into()
+        },
+        FileCreateInvalidation::Path(root().join("node_modules/foo/index").into()),
+      ])
     );
     assert_eq!(
       *invalidations.invalidate_on_file_change.read().unwrap(),
       HashSet::from([
-        root().join("node_modules/foo/package.json"),
-        root().join("package.json"),
-        root().join("tsconfig.json")
+        root().join("node_modules/foo/package.json").into(),
+        root().join("package.json").into(),
+        root().join("tsconfig.json").into()
       ])
     );
   }
@@ -1539,7 +3996,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-browser-alias/browser.js"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-browser-alias/browser.js")
+          .into()
+      )
     );
     assert_eq!(
       test_resolver()
@@ -1551,7 +4012,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-browser-alias/bar.js"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-browser-alias/bar.js")
+          .into()
+      )
     );
     assert_eq!(
       test_resolver()
@@ -1563,7 +4028,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-browser-alias/bar.js"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-browser-alias/bar.js")
+          .into()
+      )
     );
     assert_eq!(
       test_resolver()
@@ -1576,7 +4045,9 @@ mod tests {
         .unwrap()
         .0,
       Resolution::Path(
-        root().join("node_modules/package-browser-alias/subfolder1/subfolder2/subfile.js")
+        root()
+          .join("node_modules/package-browser-alias/subfolder1/subfolder2/subfile.js")
+          .into()
       )
     );
   }
@@ -1593,7 +4064,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-alias/bar.js"))
+      Resolution::Path(root().join("node_modules/package-alias/bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1605,7 +4076,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-alias/bar.js"))
+      Resolution::Path(root().join("node_modules/package-alias/bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1617,7 +4088,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-alias-glob/src/test.js"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-alias-glob/src/test.js")
+          .into()
+      )
     );
     assert_eq!(
       test_resolver()
@@ -1641,7 +4116,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-alias-glob/src/test.js"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-alias-glob/src/test.js")
+          .into()
+      )
     );
 
     let invalidations = test_resolver()
@@ -1653,17 +4132,22 @@ mod tests {
       .invalidations;
     assert_eq!(
       *invalidations.invalidate_on_file_create.read().unwrap(),
-      HashSet::from([FileCreateInvalidation::FileName {
-        file_name: "node_modules/package-alias".into(),
-        above: root()
-      },])
+      HashSet::from([
+        FileCreateInvalidation::FileName {
+          file_name: "node_modules/package-alias".into(),
+          above: root().into()
+        },
+        FileCreateInvalidation::Path(root().join("node_modules/package-alias/bar").into()),
+      ])
     );
     assert_eq!(
       *invalidations.invalidate_on_file_change.read().unwrap(),
       HashSet::from([
-        root().join("node_modules/package-alias/package.json"),
-        root().join("package.json"),
-        root().join("tsconfig.json")
+        root()
+          .join("node_modules/package-alias/package.json")
+          .into(),
+        root().join("package.json").into(),
+        root().join("tsconfig.json").into()
       ])
     );
   }
@@ -1676,7 +4160,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/index.js"))
+      Resolution::Path(root().join("node_modules/foo/index.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1688,8 +4172,9 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/index.js"))
+      Resolution::Path(root().join("node_modules/foo/index.js").into())
     );
+    // Aliases are chained: `aliased/bar` becomes `foo/bar`, which is aliased to `./bar.js`.
     assert_eq!(
       test_resolver()
         .resolve(
@@ -1700,7 +4185,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1708,7 +4193,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1720,7 +4205,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1732,7 +4217,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("nested/test.js"))
+      Resolution::Path(root().join("nested/test.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1740,7 +4225,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("nested/index.js"))
+      Resolution::Path(root().join("nested/index.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1752,7 +4237,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("nested/test.js"))
+      Resolution::Path(root().join("nested/test.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1764,7 +4249,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("nested/index.js"))
+      Resolution::Path(root().join("nested/index.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1772,7 +4257,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1780,7 +4265,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("nested/test.js"))
+      Resolution::Path(root().join("nested/test.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1788,7 +4273,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("nested/test.js"))
+      Resolution::Path(root().join("nested/test.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1800,7 +4285,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("nested/test.js"))
+      Resolution::Path(root().join("nested/test.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1820,7 +4305,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1828,7 +4313,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1836,7 +4321,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1848,7 +4333,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/baz.js"))
+      Resolution::Path(root().join("node_modules/foo/baz.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1860,7 +4345,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/baz.js"))
+      Resolution::Path(root().join("node_modules/foo/baz.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1872,7 +4357,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1884,6 +4369,147 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_browser_field_false() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/package.json",
+        r#"{
+          "name": "app",
+          "browser": {
+            "./server.js": false,
+            "crypto-lib": false,
+            "fs": false
+          }
+        }"#,
+      ),
+      ("/app/index.js", ""),
+      ("/app/server.js", ""),
+      ("/app/node_modules/crypto-lib/index.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let cache = Cache::new(fs);
+
+    let from = root.join("index.js");
+    let parcel = Resolver::parcel(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache));
+    let resolve = |resolver: &Resolver<MemoryFileSystem>, specifier| {
+      resolver
+        .resolve(specifier, &from, SpecifierType::Esm)
+        .result
+        .map(|res| res.0)
+    };
+
+    // A file mapped to false.
+    assert_eq!(resolve(&parcel, "./server.js"), Ok(Resolution::Empty));
+    // A package and a builtin mapped to false.
+    assert_eq!(resolve(&parcel, "crypto-lib"), Ok(Resolution::Empty));
+    assert_eq!(resolve(&parcel, "fs"), Ok(Resolution::Empty));
+
+    // The "browser" field is ignored when it isn't an entry field.
+    let node = Resolver::node(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache));
+    assert_eq!(
+      resolve(&node, "./server.js"),
+      Ok(Resolution::Path(root.join("server.js").into()))
+    );
+    assert_eq!(
+      resolve(&node, "crypto-lib"),
+      Ok(Resolution::Path(
+        root.join("node_modules/crypto-lib/index.js").into()
+      ))
+    );
+  }
+
+  #[test]
+  fn test_resolve_bin() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/package.json", r#"{"name": "app"}"#),
+      ("/app/src/index.js", ""),
+      (
+        "/app/node_modules/eslint/package.json",
+        r#"{"name": "eslint", "bin": {"eslint": "./bin/eslint.js"}}"#,
+      ),
+      ("/app/node_modules/eslint/bin/eslint.js", ""),
+      (
+        "/app/node_modules/@biomejs/biome/package.json",
+        r#"{"name": "@biomejs/biome", "bin": "bin/biome"}"#,
+      ),
+      ("/app/node_modules/@biomejs/biome/bin/biome", ""),
+      (
+        "/app/node_modules/tool/package.json",
+        r#"{"name": "tool", "bin": {"tool": "./cli", "tool-dev": "./dev.js"}}"#,
+      ),
+      ("/app/node_modules/tool/cli.js", ""),
+      ("/app/node_modules/tool/dev.js", ""),
+      (
+        "/app/node_modules/lib/package.json",
+        r#"{"name": "lib", "main": "index.js"}"#,
+      ),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let from = root.join("src/index.js");
+    let modules = root.join("node_modules");
+
+    // The map form.
+    assert_eq!(
+      resolver.resolve_bin("eslint", None, &from),
+      Ok(modules.join("eslint/bin/eslint.js"))
+    );
+    // The string form, named after the package without its scope. The script has no extension.
+    assert_eq!(
+      resolver.resolve_bin("@biomejs/biome", None, &from),
+      Ok(modules.join("@biomejs/biome/bin/biome"))
+    );
+    assert_eq!(
+      resolver.resolve_bin("@biomejs/biome", Some("biome"), &from),
+      Ok(modules.join("@biomejs/biome/bin/biome"))
+    );
+    assert_eq!(
+      resolver.resolve_bin("@biomejs/biome", Some("other"), &from),
+      Err(ResolverError::BinNotFound {
+        module: "@biomejs/biome".into(),
+        bin: "other".into(),
+        package_path: modules.join("@biomejs/biome/package.json"),
+        available: vec!["biome".into()],
+      })
+    );
+    // Configured extensions are tried, and the command may be given as a subpath.
+    assert_eq!(
+      resolver.resolve_bin("tool", None, &from),
+      Ok(modules.join("tool/cli.js"))
+    );
+    assert_eq!(
+      resolver.resolve_bin("tool/tool-dev", None, &from),
+      Ok(modules.join("tool/dev.js"))
+    );
+    assert_eq!(
+      resolver.resolve_bin("tool", Some("missing"), &from),
+      Err(ResolverError::BinNotFound {
+        module: "tool".into(),
+        bin: "missing".into(),
+        package_path: modules.join("tool/package.json"),
+        available: vec!["tool".into(), "tool-dev".into()],
+      })
+    );
+    assert_eq!(
+      resolver.resolve_bin("lib", None, &from),
+      Err(ResolverError::PackageWithoutBin {
+        module: "lib".into(),
+        package_path: modules.join("lib/package.json"),
+      })
+    );
+    assert_eq!(
+      resolver.resolve_bin("missing", None, &from),
+      Err(ResolverError::ModuleNotFound {
+        module: "missing".into()
+      })
+    );
+  }
+
   #[test]
   fn test_urls() {
     assert_eq!(
@@ -1896,7 +4522,9 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::External
+      Resolution::External {
+        specifier: "http://example.com/foo.png".into()
+      }
     );
     assert_eq!(
       test_resolver()
@@ -1908,7 +4536,9 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::External
+      Resolution::External {
+        specifier: "//example.com/foo.png".into()
+      }
     );
     assert_eq!(
       test_resolver()
@@ -1916,7 +4546,9 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::External
+      Resolution::External {
+        specifier: "#hash".into()
+      }
     );
     assert_eq!(
       test_resolver()
@@ -1931,13 +4563,48 @@ mod tests {
         scheme: "http".into()
       },
     );
+
+    let resolver = test_resolver_builder()
+      .external_schemes(["https"])
+      .build()
+      .unwrap();
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs] {
+      for specifier in [
+        "https://example.com/foo.js",
+        "data:text/javascript,export default 1",
+      ] {
+        assert_eq!(
+          resolver
+            .resolve(specifier, &root().join("foo.js"), specifier_type)
+            .result
+            .unwrap()
+            .0,
+          Resolution::External {
+            specifier: specifier.into()
+          }
+        );
+      }
+    }
+    assert_eq!(
+      resolver
+        .resolve(
+          "ftp://example.com/foo.js",
+          &root().join("foo.js"),
+          SpecifierType::Esm
+        )
+        .result
+        .unwrap_err(),
+      ResolverError::UnknownScheme {
+        scheme: "ftp".into()
+      },
+    );
     assert_eq!(
       test_resolver()
         .resolve("bar.js", &root().join("foo.js"), SpecifierType::Url)
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     // Reproduce bug for now
     // assert_eq!(
@@ -1956,7 +4623,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("bar.js"))
+      Resolution::Path(root().join("bar.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1964,7 +4631,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/index.js"))
+      Resolution::Path(root().join("node_modules/foo/index.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -1972,7 +4639,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/@scope/pkg/index.js"))
+      Resolution::Path(root().join("node_modules/@scope/pkg/index.js").into())
     );
   }
 
@@ -1988,7 +4655,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-exports/main.mjs"))
+      Resolution::Path(root().join("node_modules/package-exports/main.mjs").into())
     );
     assert_eq!(
       test_resolver()
@@ -2001,7 +4668,7 @@ mod tests {
         .unwrap()
         .0,
       // "browser" field is NOT used.
-      Resolution::Path(root().join("node_modules/package-exports/foo.mjs"))
+      Resolution::Path(root().join("node_modules/package-exports/foo.mjs").into())
     );
     assert_eq!(
       test_resolver()
@@ -2013,7 +4680,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-exports/features/test.mjs"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-exports/features/test.mjs")
+          .into()
+      )
     );
     assert_eq!(
       test_resolver()
@@ -2025,7 +4696,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-exports/features/test.mjs"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-exports/features/test.mjs")
+          .into()
+      )
     );
     assert_eq!(
       test_resolver()
@@ -2037,7 +4712,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-exports/features/test.mjs"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-exports/features/test.mjs")
+          .into()
+      )
     );
     assert_eq!(
       node_resolver()
@@ -2079,7 +4758,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-exports/features/test.mjs"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-exports/features/test.mjs")
+          .into()
+      )
     );
     assert_eq!(
       test_resolver()
@@ -2091,7 +4774,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-exports/with space.mjs"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-exports/with space.mjs")
+          .into()
+      )
     );
     // assert_eq!(
     //   test_resolver().resolve("package-exports/with%20space", &root().join("foo.js"), SpecifierType::Esm).unwrap().0,
@@ -2160,10 +4847,53 @@ mod tests {
   }
 
   #[test]
-  fn test_self_reference() {
-    assert_eq!(
-      test_resolver()
-        .resolve(
+  fn test_specifier_type_conditions() {
+    let resolve = |resolver: &Resolver<OsFileSystem>, specifier_type| {
+      resolver
+        .resolve("package-conditions", &root().join("foo.js"), specifier_type)
+        .result
+        .unwrap()
+        .0
+    };
+    let path = |file: &str| {
+      Resolution::Path(
+        root()
+          .join("node_modules/package-conditions")
+          .join(file)
+          .into(),
+      )
+    };
+
+    // The "import" or "require" condition is added based on the specifier type.
+    let resolver = node_resolver();
+    assert_eq!(
+      resolve(&resolver, SpecifierType::Esm),
+      path("node-import.mjs")
+    );
+    assert_eq!(
+      resolve(&resolver, SpecifierType::Cjs),
+      path("node-require.cjs")
+    );
+
+    let resolver = test_resolver_builder()
+      .conditions(ExportsCondition::BROWSER | ExportsCondition::PRODUCTION)
+      .build()
+      .unwrap();
+    assert_eq!(
+      resolve(&resolver, SpecifierType::Esm),
+      path("browser-import-prod.mjs")
+    );
+    assert_eq!(
+      resolve(&resolver, SpecifierType::Cjs),
+      path("browser-require-prod.cjs")
+    );
+  }
+
+  #[test]
+  fn test_self_reference() {
+    assert_eq!(
+      test_resolver()
+        .resolve(
           "package-exports",
           &root().join("node_modules/package-exports/foo.js"),
           SpecifierType::Esm
@@ -2171,7 +4901,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-exports/main.mjs"))
+      Resolution::Path(root().join("node_modules/package-exports/main.mjs").into())
     );
     assert_eq!(
       test_resolver()
@@ -2183,7 +4913,285 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-exports/foo.mjs"))
+      Resolution::Path(root().join("node_modules/package-exports/foo.mjs").into())
+    );
+  }
+
+  #[test]
+  fn test_self_reference_nested_package() -> Result<(), Box<dyn std::error::Error>> {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/package.json",
+        r#"{"name": "monorepo", "private": true}"#,
+      ),
+      ("/app/index.js", ""),
+      (
+        "/app/packages/nested/package.json",
+        r#"{"name": "@monorepo/nested", "exports": {".": "./src/index.js", "./utils": "./src/utils.js"}}"#,
+      ),
+      ("/app/packages/nested/src/index.js", ""),
+      ("/app/packages/nested/src/utils.js", ""),
+      ("/app/packages/nested/src/internal.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let nested = root.join("packages/nested");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier, from: &Path| {
+      resolver
+        .resolve(specifier, from, SpecifierType::Esm)
+        .result
+        .map(|(resolution, _)| resolution)
+    };
+
+    // Targets are resolved against the nested package, not the importer or the project root.
+    let from = nested.join("src/index.js");
+    assert_eq!(
+      resolve("@monorepo/nested/utils", &from)?,
+      Resolution::Path(nested.join("src/utils.js").into())
+    );
+    assert_eq!(
+      resolve("@monorepo/nested", &nested.join("src/utils.js"))?,
+      Resolution::Path(nested.join("src/index.js").into())
+    );
+    assert!(matches!(
+      resolve("@monorepo/nested/src/internal.js", &from),
+      Err(ResolverError::PackageJsonError {
+        error: PackageJsonError::PackagePathNotExported,
+        ..
+      })
+    ));
+
+    // Only the package that owns the importer can refer to itself.
+    assert_eq!(
+      resolve("@monorepo/nested", &root.join("index.js")),
+      Err(ResolverError::ModuleNotFound {
+        module: "@monorepo/nested".into()
+      })
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_package_subpath_parent_dir() {
+    let resolve = |specifier, specifier_type| {
+      test_resolver()
+        .resolve(specifier, &root().join("foo.js"), specifier_type)
+        .result
+        .map(|r| r.0)
+    };
+
+    // Never resolves into the sibling package.
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs] {
+      assert_eq!(
+        resolve("foo/../package-main/main.js", specifier_type),
+        Err(ResolverError::InvalidSpecifier(
+          SpecifierError::InvalidPackageSpecifier
+        ))
+      );
+    }
+    assert_eq!(
+      resolve("foo/%2e%2e/package-main/main.js", SpecifierType::Esm),
+      Err(ResolverError::InvalidSpecifier(
+        SpecifierError::InvalidPackageSpecifier
+      ))
+    );
+    assert_eq!(
+      resolve("foo/nested/../bar.js", SpecifierType::Cjs),
+      Ok(Resolution::Path(
+        root().join("node_modules/foo/bar.js").into()
+      ))
+    );
+    assert!(matches!(
+      resolve(
+        "package-exports/features/%252e%252e/foo",
+        SpecifierType::Esm
+      ),
+      Err(ResolverError::PackageJsonError {
+        error: PackageJsonError::InvalidSpecifier,
+        ..
+      })
+    ));
+  }
+
+  #[test]
+  fn test_imports_conditions() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/package.json",
+        r##"{
+          "name": "app",
+          "imports": {
+            "#internal/*": {
+              "node": {"import": "./src/internal/*.mjs", "require": "./src/internal/*.cjs"},
+              "default": "./src/internal/*.js"
+            },
+            "#addon": {"node-addons": "./build/addon.node", "default": "./src/addon.js"}
+          }
+        }"##,
+      ),
+      ("/app/src/index.js", ""),
+      ("/app/src/internal/foo.mjs", ""),
+      ("/app/src/internal/foo.cjs", ""),
+      ("/app/src/internal/foo.js", ""),
+      ("/app/src/addon.js", ""),
+      ("/app/build/addon.node", ""),
+    ]);
+    let cache = Cache::new(fs);
+    let from = Path::new("/app/src/index.js");
+    let resolve = |resolver: &Resolver<MemoryFileSystem>, specifier, specifier_type| {
+      resolver
+        .resolve(specifier, from, specifier_type)
+        .result
+        .unwrap()
+        .0
+    };
+    let path = |path: &str| Resolution::Path(PathBuf::from(path).into());
+
+    // Node matches "node", "node-addons", and "import" or "require" by specifier type.
+    let node = Resolver::node(Cow::Borrowed(Path::new("/app")), CacheCow::Borrowed(&cache));
+    assert_eq!(
+      resolve(&node, "#internal/foo", SpecifierType::Cjs),
+      path("/app/src/internal/foo.cjs")
+    );
+    assert_eq!(
+      resolve(&node, "#addon", SpecifierType::Cjs),
+      path("/app/build/addon.node")
+    );
+    let node_esm = Resolver::node_esm(Cow::Borrowed(Path::new("/app")), CacheCow::Borrowed(&cache));
+    assert_eq!(
+      resolve(&node_esm, "#internal/foo", SpecifierType::Esm),
+      path("/app/src/internal/foo.mjs")
+    );
+
+    let mut no_addons =
+      Resolver::node(Cow::Borrowed(Path::new("/app")), CacheCow::Borrowed(&cache));
+    no_addons.conditions = ExportsCondition::NODE;
+    assert_eq!(
+      resolve(&no_addons, "#addon", SpecifierType::Cjs),
+      path("/app/src/addon.js")
+    );
+
+    // Parcel has no global conditions by default.
+    let parcel = Resolver::parcel(Cow::Borrowed(Path::new("/app")), CacheCow::Borrowed(&cache));
+    assert_eq!(
+      resolve(&parcel, "#internal/foo", SpecifierType::Esm),
+      path("/app/src/internal/foo.js")
+    );
+    assert_eq!(
+      resolve(&parcel, "#addon", SpecifierType::Esm),
+      path("/app/src/addon.js")
+    );
+  }
+
+  #[test]
+  fn test_condition_priority() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/node_modules/browser-first/package.json",
+        r#"{
+          "name": "browser-first",
+          "exports": {
+            "./*": {
+              "browser": "./browser/*.js",
+              "worker": "./worker/*.js",
+              "react-native": "./native/*.js",
+              "default": "./default/*.js"
+            }
+          }
+        }"#,
+      ),
+      (
+        "/app/node_modules/worker-first/package.json",
+        r#"{
+          "name": "worker-first",
+          "exports": {
+            "./*": {
+              "worker": "./worker/*.js",
+              "browser": "./browser/*.js",
+              "default": "./default/*.js"
+            }
+          }
+        }"#,
+      ),
+      ("/app/src/index.js", ""),
+    ]);
+    for pkg in ["browser-first", "worker-first"] {
+      for dir in ["browser", "worker", "native", "default"] {
+        fs.write_file(format!("/app/node_modules/{}/{}/foo.js", pkg, dir), "");
+      }
+    }
+
+    let cache = Cache::new(fs);
+    let from = Path::new("/app/src/index.js");
+    let build = |conditions, priority: &[&str]| {
+      ResolverBuilder::new(Cow::Borrowed(Path::new("/app")), CacheCow::Borrowed(&cache))
+        .conditions(conditions)
+        .condition_priority(priority.iter().copied())
+        .build()
+        .unwrap()
+    };
+    let browser_worker = ExportsCondition::BROWSER | ExportsCondition::WORKER;
+    let resolver = build(browser_worker, &[]);
+    let resolve = |resolver: &Resolver<MemoryFileSystem>, specifier, custom_conditions: &[&str]| {
+      resolver
+        .resolve_with_options(
+          specifier,
+          from,
+          SpecifierType::Esm,
+          ResolveOptions {
+            custom_conditions: custom_conditions.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+          },
+        )
+        .result
+        .unwrap()
+        .0
+    };
+    let path = |path: &str| Resolution::Path(PathBuf::from(path).into());
+
+    // Without a priority, key order decides between active conditions.
+    assert_eq!(
+      resolve(&resolver, "browser-first/foo", &[]),
+      path("/app/node_modules/browser-first/browser/foo.js")
+    );
+    assert_eq!(
+      resolve(&resolver, "worker-first/foo", &[]),
+      path("/app/node_modules/worker-first/worker/foo.js")
+    );
+
+    // With a priority, lower priority conditions are dropped, so the key order doesn't matter.
+    let resolver = build(browser_worker, &["worker", "browser"]);
+    assert_eq!(
+      resolve(&resolver, "browser-first/foo", &[]),
+      path("/app/node_modules/browser-first/worker/foo.js")
+    );
+    assert_eq!(
+      resolve(&resolver, "worker-first/foo", &[]),
+      path("/app/node_modules/worker-first/worker/foo.js")
+    );
+
+    // Lower priority conditions are still used when no higher priority one is active.
+    let resolver = build(ExportsCondition::BROWSER, &["worker", "browser"]);
+    assert_eq!(
+      resolve(&resolver, "worker-first/foo", &[]),
+      path("/app/node_modules/worker-first/browser/foo.js")
+    );
+
+    // Custom conditions can be prioritized too.
+    let resolver = build(ExportsCondition::BROWSER, &["browser", "react-native"]);
+    assert_eq!(
+      resolve(&resolver, "browser-first/foo", &["react-native"]),
+      path("/app/node_modules/browser-first/browser/foo.js")
+    );
+    let resolver = build(ExportsCondition::BROWSER, &["react-native", "browser"]);
+    assert_eq!(
+      resolve(&resolver, "browser-first/foo", &["react-native"]),
+      path("/app/node_modules/browser-first/native/foo.js")
     );
   }
 
@@ -2199,7 +5207,11 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/package-exports/internal.mjs"))
+      Resolution::Path(
+        root()
+          .join("node_modules/package-exports/internal.mjs")
+          .into()
+      )
     );
     assert_eq!(
       test_resolver()
@@ -2211,7 +5223,25 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/index.js"))
+      Resolution::Path(root().join("node_modules/foo/index.js").into())
+    );
+    assert_eq!(
+      test_resolver()
+        .resolve(
+          "#internal?pool=primary",
+          &root().join("node_modules/package-exports/main.mjs"),
+          SpecifierType::Esm
+        )
+        .result
+        .unwrap(),
+      (
+        Resolution::Path(
+          root()
+            .join("node_modules/package-exports/internal.mjs")
+            .into()
+        ),
+        Some(Query::new("?pool=primary"))
+      )
     );
   }
 
@@ -2243,7 +5273,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("foo.js"))
+      Resolution::Path(root().join("foo.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -2255,7 +5285,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("nested/test.js"))
+      Resolution::Path(root().join("nested/test.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -2267,7 +5297,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/tsconfig-index/foo.js"))
+      Resolution::Path(root().join("node_modules/tsconfig-index/foo.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -2279,7 +5309,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/tsconfig-field/foo.js"))
+      Resolution::Path(root().join("node_modules/tsconfig-field/foo.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -2291,7 +5321,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/tsconfig-exports/foo.js"))
+      Resolution::Path(root().join("node_modules/tsconfig-exports/foo.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -2303,7 +5333,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("tsconfig/extends-extension/foo.js"))
+      Resolution::Path(root().join("tsconfig/extends-extension/foo.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -2349,7 +5379,10 @@ mod tests {
     );
     assert_eq!(
       *invalidations.invalidate_on_file_change.read().unwrap(),
-      HashSet::from([root().join("package.json"), root().join("tsconfig.json")])
+      HashSet::from([
+        root().join("package.json").into(),
+        root().join("tsconfig.json").into()
+      ])
     );
   }
 
@@ -2365,7 +5398,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("tsconfig/suffixes/a.ios.ts"))
+      Resolution::Path(root().join("tsconfig/suffixes/a.ios.ts").into())
     );
     assert_eq!(
       test_resolver()
@@ -2377,7 +5410,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("tsconfig/suffixes/a.ios.ts"))
+      Resolution::Path(root().join("tsconfig/suffixes/a.ios.ts").into())
     );
     assert_eq!(
       test_resolver()
@@ -2389,7 +5422,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("tsconfig/suffixes/b.ts"))
+      Resolution::Path(root().join("tsconfig/suffixes/b.ts").into())
     );
     assert_eq!(
       test_resolver()
@@ -2401,7 +5434,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("tsconfig/suffixes/b.ts"))
+      Resolution::Path(root().join("tsconfig/suffixes/b.ts").into())
     );
     assert_eq!(
       test_resolver()
@@ -2413,7 +5446,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("tsconfig/suffixes/c-test.ts"))
+      Resolution::Path(root().join("tsconfig/suffixes/c-test.ts").into())
     );
     assert_eq!(
       test_resolver()
@@ -2425,7 +5458,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("tsconfig/suffixes/c-test.ts"))
+      Resolution::Path(root().join("tsconfig/suffixes/c-test.ts").into())
     );
   }
 
@@ -2441,7 +5474,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("ts-extensions/a.ts"))
+      Resolution::Path(root().join("ts-extensions/a.ts").into())
     );
     assert_eq!(
       test_resolver()
@@ -2454,7 +5487,7 @@ mod tests {
         .unwrap()
         .0,
       // TSC always prioritizes .ts over .tsx
-      Resolution::Path(root().join("ts-extensions/a.ts"))
+      Resolution::Path(root().join("ts-extensions/a.ts").into())
     );
     assert_eq!(
       test_resolver()
@@ -2466,7 +5499,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("ts-extensions/a.mts"))
+      Resolution::Path(root().join("ts-extensions/a.mts").into())
     );
     assert_eq!(
       test_resolver()
@@ -2478,7 +5511,7 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("ts-extensions/a.cts"))
+      Resolution::Path(root().join("ts-extensions/a.cts").into())
     );
     assert_eq!(
       test_resolver()
@@ -2491,7 +5524,7 @@ mod tests {
         .unwrap()
         .0,
       // We deviate from TSC here to match Node/bundlers.
-      Resolution::Path(root().join("ts-extensions/b.js"))
+      Resolution::Path(root().join("ts-extensions/b.js").into())
     );
     assert_eq!(
       test_resolver()
@@ -2504,7 +5537,7 @@ mod tests {
         .unwrap()
         .0,
       // This matches TSC. c.js.ts seems kinda unlikely?
-      Resolution::Path(root().join("ts-extensions/c.ts"))
+      Resolution::Path(root().join("ts-extensions/c.ts").into())
     );
     assert_eq!(
       test_resolver()
@@ -2531,20 +5564,23 @@ mod tests {
     assert_eq!(
       *invalidations.invalidate_on_file_create.read().unwrap(),
       HashSet::from([
-        FileCreateInvalidation::Path(root().join("ts-extensions/a.js")),
+        FileCreateInvalidation::Path(root().join("ts-extensions/a.js").into()),
         FileCreateInvalidation::FileName {
           file_name: "package.json".into(),
-          above: root().join("ts-extensions")
+          above: root().join("ts-extensions").into()
         },
         FileCreateInvalidation::FileName {
           file_name: "tsconfig.json".into(),
-          above: root().join("ts-extensions")
+          above: root().join("ts-extensions").into()
         },
       ])
     );
     assert_eq!(
       *invalidations.invalidate_on_file_change.read().unwrap(),
-      HashSet::from([root().join("package.json"), root().join("tsconfig.json")])
+      HashSet::from([
+        root().join("package.json").into(),
+        root().join("tsconfig.json").into()
+      ])
     );
   }
 
@@ -2621,10 +5657,85 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_externals() {
+    let build = |verify| {
+      test_resolver_builder()
+        .externals(["react", "@scope/*", "foo"])
+        .verify_externals(verify)
+        .build()
+        .unwrap()
+    };
+    let resolver = build(false);
+    let resolve = |resolver: &Resolver<OsFileSystem>, specifier| {
+      resolver.resolve(specifier, &root().join("foo.js"), SpecifierType::Esm)
+    };
+
+    for specifier in ["react", "react/jsx-runtime", "@scope/pkg", "foo/nested/baz"] {
+      let res = resolve(&resolver, specifier);
+      assert_eq!(
+        res.result.unwrap().0,
+        Resolution::External {
+          specifier: specifier.into()
+        }
+      );
+      // Externals are resolved without touching the file system.
+      assert!(res
+        .invalidations
+        .invalidate_on_file_change
+        .read()
+        .unwrap()
+        .is_empty());
+      assert!(res
+        .invalidations
+        .invalidate_on_file_create
+        .read()
+        .unwrap()
+        .is_empty());
+    }
+
+    // Builtins and other packages are unaffected.
+    assert_eq!(
+      resolve(&resolver, "package-main").result.unwrap().0,
+      Resolution::Path(root().join("node_modules/package-main/main.js").into())
+    );
+    assert_eq!(
+      resolve(&resolver, "zlib").result.unwrap().0,
+      Resolution::Builtin("zlib".into())
+    );
+
+    // When verifying, missing packages are still errors.
+    let resolver = build(true);
+    assert_eq!(
+      resolve(&resolver, "react").result.unwrap_err(),
+      ResolverError::ModuleNotFound {
+        module: "react".into()
+      }
+    );
+    let res = resolve(&resolver, "foo/nested/baz");
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::External {
+        specifier: "foo/nested/baz".into()
+      }
+    );
+    assert!(!res
+      .invalidations
+      .invalidate_on_file_change
+      .read()
+      .unwrap()
+      .is_empty());
+  }
+
   #[test]
   fn test_include_node_modules() {
-    let mut resolver = test_resolver();
-    resolver.include_node_modules = Cow::Owned(IncludeNodeModules::Bool(false));
+    let build = |include_node_modules| {
+      test_resolver_builder()
+        .include_node_modules(Cow::Owned(include_node_modules))
+        .build()
+        .unwrap()
+    };
+    let resolver = build(IncludeNodeModules::Bool(false));
 
     assert_eq!(
       resolver
@@ -2632,7 +5743,9 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::External
+      Resolution::External {
+        specifier: "foo".into()
+      }
     );
     assert_eq!(
       resolver
@@ -2640,17 +5753,19 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::External
+      Resolution::External {
+        specifier: "@scope/pkg".into()
+      }
     );
 
-    resolver.include_node_modules = Cow::Owned(IncludeNodeModules::Array(vec!["foo".into()]));
+    let resolver = build(IncludeNodeModules::Array(vec!["foo".into()]));
     assert_eq!(
       resolver
         .resolve("foo", &root().join("foo.js"), SpecifierType::Esm)
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/foo/index.js"))
+      Resolution::Path(root().join("node_modules/foo/index.js").into())
     );
     assert_eq!(
       resolver
@@ -2658,10 +5773,12 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::External
+      Resolution::External {
+        specifier: "@scope/pkg".into()
+      }
     );
 
-    resolver.include_node_modules = Cow::Owned(IncludeNodeModules::Map(HashMap::from([
+    let resolver = build(IncludeNodeModules::Map(HashMap::from([
       ("foo".into(), false),
       ("@scope/pkg".into(), true),
     ])));
@@ -2671,7 +5788,9 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::External
+      Resolution::External {
+        specifier: "foo".into()
+      }
     );
     assert_eq!(
       resolver
@@ -2679,20 +5798,3660 @@ mod tests {
         .result
         .unwrap()
         .0,
-      Resolution::Path(root().join("node_modules/@scope/pkg/index.js"))
+      Resolution::Path(root().join("node_modules/@scope/pkg/index.js").into())
     );
   }
 
-  // #[test]
-  // fn test_visitor() {
-  //   let resolved = test_resolver().resolve("unified", &root(), SpecifierType::Esm).unwrap();
-  //   println!("{:?}", resolved);
-  //   if let Resolution::Path(p) = resolved {
-  //     let res = build_esm_graph(
-  //       &p,
-  //       root()
-  //     ).unwrap();
-  //     println!("{:?}", res);
-  //   }
-  // }
+  #[test]
+  fn test_metrics() {
+    let resolver = test_resolver();
+    resolver
+      .resolve("foo", &root().join("foo.js"), SpecifierType::Esm)
+      .result
+      .unwrap();
+    assert_eq!(resolver.take_metrics(), ResolverMetrics::default());
+
+    let resolver = test_resolver_builder().metrics(true).build().unwrap();
+    resolver
+      .resolve("foo", &root().join("foo.js"), SpecifierType::Esm)
+      .result
+      .unwrap();
+    resolver
+      .resolve("./bar", &root().join("foo.js"), SpecifierType::Esm)
+      .result
+      .unwrap();
+
+    let metrics = resolver.take_metrics();
+    assert_eq!(metrics.parse.count, 2);
+    assert!(metrics.package_json.count > 0);
+    assert!(metrics.stat.count > 0);
+    assert_eq!(metrics.canonicalize.count, 2);
+    assert_eq!(resolver.take_metrics(), ResolverMetrics::default());
+  }
+
+  #[test]
+  fn test_builder() {
+    let cache = Cache::new(OsFileSystem::default());
+    let builder = || ResolverBuilder::new(root().into(), CacheCow::Borrowed(&cache));
+
+    let resolver = builder().preset(Preset::Node).build().unwrap();
+    assert_eq!(resolver.flags, Flags::NODE_CJS);
+    assert_eq!(
+      resolver.conditions,
+      ExportsCondition::NODE | ExportsCondition::NODE_ADDONS
+    );
+    assert_eq!(
+      resolver
+        .resolve("foo", &root().join("foo.js"), SpecifierType::Cjs)
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(root().join("node_modules/foo/index.js").into())
+    );
+
+    // Explicit settings override the preset regardless of call order.
+    let resolver = builder()
+      .entry_fields(Fields::MAIN)
+      .tsconfig(false)
+      .preset(Preset::Parcel)
+      .build()
+      .unwrap();
+    assert_eq!(resolver.entries, Fields::MAIN);
+    assert_eq!(resolver.flags, Flags::PARCEL - Flags::TSCONFIG);
+
+    let resolver = builder()
+      .extensions(["js"])
+      .module_dirs(["web_modules", "node_modules"])
+      .build()
+      .unwrap();
+    assert_eq!(resolver.extensions.iter().collect::<Vec<_>>(), vec!["js"]);
+    assert_eq!(
+      resolver
+        .resolve("foo", &root().join("foo.js"), SpecifierType::Esm)
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(root().join("node_modules/foo/index.js").into())
+    );
+
+    assert_eq!(
+      builder()
+        .flags(Flags::NODE_CJS - Flags::EXPORTS)
+        .conditions(ExportsCondition::BROWSER)
+        .build()
+        .err(),
+      Some(ResolverBuilderError::ConditionsWithoutExports)
+    );
+    assert_eq!(
+      builder()
+        .preset(Preset::NodeEsm)
+        .extensions(["js"])
+        .build()
+        .err(),
+      Some(ResolverBuilderError::ExtensionsWithoutOptionalExtensions)
+    );
+    assert_eq!(
+      builder().extensions([".js"]).build().err(),
+      Some(ResolverBuilderError::InvalidExtension {
+        extension: ".js".into()
+      })
+    );
+    assert_eq!(
+      builder().module_dirs(Vec::<String>::new()).build().err(),
+      Some(ResolverBuilderError::EmptyModuleDirs)
+    );
+    assert_eq!(
+      builder().module_dirs(["a/b"]).build().err(),
+      Some(ResolverBuilderError::InvalidModuleDir { dir: "a/b".into() })
+    );
+    assert_eq!(
+      builder().alias("", "preact").build().err(),
+      Some(ResolverBuilderError::InvalidAlias {
+        alias: "=preact".into()
+      })
+    );
+  }
+
+  #[test]
+  fn test_builder_aliases() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/package.json",
+        r#"{"alias": {"react": "./react.js", "lodash": "./lodash.js"}}"#,
+      ),
+      ("/app/src/index.js", ""),
+      ("/app/react.js", ""),
+      ("/app/lodash.js", ""),
+      ("/app/shims/fs.js", ""),
+      ("/app/node_modules/preact/package.json", "{}"),
+      ("/app/node_modules/preact/compat/index.js", ""),
+      ("/app/node_modules/preact/compat/jsx-runtime.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let resolver = ResolverBuilder::new(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    )
+    .alias("react", "preact/compat")
+    .alias("fs", "./shims/fs.js")
+    .build()
+    .unwrap();
+    let resolve = |specifier| {
+      resolver
+        .resolve(specifier, &root.join("src/index.js"), SpecifierType::Esm)
+        .result
+        .map(|res| res.0)
+    };
+    let path = |path: &str| Ok(Resolution::Path(root.join(path).into()));
+
+    // Configured aliases take precedence over the root package.json, and apply to subpaths.
+    assert_eq!(
+      resolve("react"),
+      path("node_modules/preact/compat/index.js")
+    );
+    assert_eq!(
+      resolve("react/jsx-runtime"),
+      path("node_modules/preact/compat/jsx-runtime.js")
+    );
+    assert_eq!(resolve("lodash"), path("lodash.js"));
+    // Relative targets are resolved from the project root.
+    assert_eq!(resolve("fs"), path("shims/fs.js"));
+  }
+
+  #[test]
+  fn test_changed() {
+    let fs = MemoryFileSystem::from_files([("/app/foo.js", "")]);
+    let root = PathBuf::from("/app");
+    let from = root.join("foo.js");
+
+    let resolver = ResolverBuilder::new(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    )
+    .preset(Preset::Node)
+    .resolution_cache(true)
+    .build()
+    .unwrap();
+
+    let relative_key = ResolutionKey::new(
+      "./bar",
+      resolver.intern(&from),
+      SpecifierType::Cjs,
+      &Default::default(),
+    );
+    let package_key = ResolutionKey::new(
+      "pkg",
+      resolver.intern(&from),
+      SpecifierType::Cjs,
+      &Default::default(),
+    );
+    let resolve = |specifier| {
+      resolver
+        .resolve(specifier, &from, SpecifierType::Cjs)
+        .result
+        .map(|r| r.0)
+    };
+
+    assert!(resolve("./bar").is_err());
+    assert!(resolve("pkg").is_err());
+    assert_eq!(resolver.changed(&[root.join("unrelated.js")]), vec![]);
+
+    // Creating the file invalidates only the relative resolution.
+    resolver.cache.fs.write_file(root.join("bar.js"), "");
+    assert_eq!(resolver.changed(&[root.join("bar.js")]), vec![relative_key]);
+    assert_eq!(
+      resolve("./bar"),
+      Ok(Resolution::Path(root.join("bar.js").into()))
+    );
+
+    // Creating a file inside a new package directory invalidates the package resolution.
+    resolver
+      .cache
+      .fs
+      .write_file(root.join("node_modules/pkg/index.js"), "");
+    assert_eq!(
+      resolver.changed(&[root.join("node_modules/pkg/index.js")]),
+      vec![package_key]
+    );
+    assert_eq!(
+      resolve("pkg"),
+      Ok(Resolution::Path(
+        root.join("node_modules/pkg/index.js").into()
+      ))
+    );
+
+    // Stale keys are only reported once.
+    assert_eq!(resolver.changed(&[root.join("bar.js")]), vec![]);
+  }
+
+  #[test]
+  fn test_changed_config() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/node_modules/pkg/package.json",
+        r#"{"name": "pkg", "main": "a.js"}"#,
+      ),
+      ("/app/node_modules/pkg/a.js", ""),
+      ("/app/node_modules/pkg/b.js", ""),
+      (
+        "/app/tsconfig.base.json",
+        r#"{"compilerOptions": {"paths": {"@/*": ["./a/*"]}}}"#,
+      ),
+      (
+        "/app/tsconfig.json",
+        r#"{"extends": "./tsconfig.base.json"}"#,
+      ),
+      ("/app/a/foo.ts", ""),
+      ("/app/b/foo.ts", ""),
+      ("/app/index.ts", ""),
+    ]);
+
+    let mut resolver = ResolverBuilder::new(
+      Cow::Borrowed(Path::new("/app")),
+      CacheCow::Owned(Cache::new(fs)),
+    )
+    .resolution_cache(true)
+    .build()
+    .unwrap();
+    let resolve = |resolver: &Resolver<MemoryFileSystem>, specifier| {
+      resolver
+        .resolve(specifier, Path::new("/app/index.ts"), SpecifierType::Esm)
+        .result
+        .unwrap()
+        .0
+    };
+
+    assert_eq!(
+      resolve(&resolver, "pkg"),
+      Resolution::Path(Path::new("/app/node_modules/pkg/a.js").into())
+    );
+    assert_eq!(
+      resolve(&resolver, "@/foo"),
+      Resolution::Path(Path::new("/app/a/foo.ts").into())
+    );
+
+    // An edited package.json is parsed again.
+    let package_json = Path::new("/app/node_modules/pkg/package.json");
+    resolver
+      .cache
+      .fs
+      .write_file(package_json, r#"{"name": "pkg", "main": "b.js"}"#);
+    resolver.changed(&[package_json.into()]);
+    assert_eq!(
+      resolve(&resolver, "pkg"),
+      Resolution::Path(Path::new("/app/node_modules/pkg/b.js").into())
+    );
+
+    // So is a tsconfig.json that extends an edited file.
+    let base = Path::new("/app/tsconfig.base.json");
+    resolver.cache.fs.write_file(
+      base,
+      r#"{"compilerOptions": {"paths": {"@/*": ["./b/*"]}}}"#,
+    );
+    resolver.changed(&[base.into()]);
+    assert_eq!(
+      resolve(&resolver, "@/foo"),
+      Resolution::Path(Path::new("/app/b/foo.ts").into())
+    );
+
+    // Clearing the cache releases the stale versions, and the current files are read again.
+    assert!(resolver.clear_cache());
+    for (specifier, expected) in [("pkg", "node_modules/pkg/b.js"), ("@/foo", "b/foo.ts")] {
+      let res = resolver.resolve_uncached(
+        specifier,
+        Path::new("/app/index.ts"),
+        SpecifierType::Esm,
+        Default::default(),
+      );
+      assert_eq!(
+        res.result.unwrap().0,
+        Resolution::Path(Path::new("/app").join(expected).into())
+      );
+    }
+  }
+
+  #[test]
+  fn test_resolution_cache_query() {
+    let resolver = test_resolver_builder()
+      .resolution_cache(true)
+      .build()
+      .unwrap();
+    let from = root().join("foo.js");
+    let specifiers = ["./bar.js?width=100", "./bar.js?width=200", "./bar.js"];
+
+    // Entries shared between queries must not be returned to requests that cache each query
+    // separately, and vice versa.
+    for ignore_query_in_cache in [true, false, true] {
+      let options = ResolveOptions {
+        ignore_query_in_cache,
+        ..Default::default()
+      };
+
+      // Resolve each specifier twice, so that some of the results come from the cache.
+      let results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = specifiers
+          .iter()
+          .chain(specifiers.iter())
+          .map(|specifier| {
+            let (resolver, from, options) = (&resolver, &from, options.clone());
+            scope.spawn(move || {
+              let res = resolver.resolve_with_options(specifier, from, SpecifierType::Esm, options);
+              (*specifier, res.result.unwrap())
+            })
+          })
+          .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+      });
+
+      for (specifier, (resolution, query)) in results {
+        assert_eq!(resolution, Resolution::Path(root().join("bar.js").into()));
+        assert_eq!(
+          query.as_ref().map(|q| q.raw()),
+          specifier.find('?').map(|i| &specifier[i..])
+        );
+      }
+    }
+
+    assert_eq!(
+      split_query("./bar.js?a=1#hash", SpecifierType::Esm),
+      (Cow::Owned("./bar.js#hash".into()), Some("?a=1"))
+    );
+    assert_eq!(
+      split_query("./bar.js?a=1", SpecifierType::Cjs),
+      (Cow::Borrowed("./bar.js?a=1"), None)
+    );
+  }
+
+  #[test]
+  fn test_ignored_query_keys() {
+    let resolver = test_resolver_builder()
+      .ignored_query_keys(["v", "t"])
+      .resolution_cache(true)
+      .build()
+      .unwrap();
+    let from = root().join("foo.js");
+
+    let query = |specifier| {
+      let (resolution, query) = resolver
+        .resolve(specifier, &from, SpecifierType::Esm)
+        .result
+        .unwrap();
+      assert_eq!(resolution, Resolution::Path(root().join("bar.js").into()));
+      query.map(|q| q.raw().to_owned())
+    };
+    assert_eq!(query("./bar.js?v=abc&real=1"), Some("?real=1".into()));
+    // Cache-busting variants share a cache entry.
+    assert_eq!(query("./bar.js?real=1&t=123"), Some("?real=1".into()));
+    assert_eq!(query("./bar.js?v=abc"), None);
+    assert_eq!(query("./bar.js?v=def"), None);
+    let mut stale: Vec<_> = resolver
+      .changed(&[root().join("package.json")])
+      .into_iter()
+      .map(|key| key.specifier)
+      .collect();
+    stale.sort();
+    assert_eq!(stale, vec!["./bar.js", "./bar.js?real=1"]);
+
+    assert_eq!(
+      without_query_keys(
+        "./bar.js?v=abc&real=1#hash",
+        SpecifierType::Esm,
+        &["v".into()]
+      ),
+      Cow::<str>::Owned("./bar.js?real=1#hash".into())
+    );
+    assert_eq!(
+      without_query_keys("./bar.js?v=abc", SpecifierType::Cjs, &["v".into()]),
+      Cow::Borrowed("./bar.js?v=abc")
+    );
+  }
+
+  #[test]
+  fn test_virtual_prefixes() {
+    let resolver = test_resolver_builder()
+      .virtual_prefixes(["$app/", "$env/"])
+      .build()
+      .unwrap();
+    assert_eq!(
+      resolver
+        .resolve("$app/stores", &root().join("foo.js"), SpecifierType::Esm)
+        .result
+        .unwrap()
+        .0,
+      Resolution::Virtual("$app/stores".into())
+    );
+    assert_eq!(
+      resolver
+        .resolve(
+          "$env/static/public",
+          &root().join("foo.js"),
+          SpecifierType::Esm
+        )
+        .result
+        .unwrap()
+        .0,
+      Resolution::Virtual("$env/static/public".into())
+    );
+  }
+
+  #[test]
+  fn test_root_absolute() {
+    let resolver = test_resolver_builder()
+      .preset(Preset::Node)
+      .flags(Flags::NODE_CJS | Flags::ROOT_ABSOLUTE)
+      .build()
+      .unwrap();
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs, SpecifierType::Url] {
+      assert_eq!(
+        resolver
+          .resolve("/bar.js", &root().join("nested/test.js"), specifier_type)
+          .result
+          .unwrap()
+          .0,
+        Resolution::Path(root().join("bar.js").into())
+      );
+    }
+    assert_eq!(
+      resolver
+        .resolve(
+          "/nested/index.js",
+          &root().join("node_modules/foo/index.js"),
+          SpecifierType::Esm
+        )
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(root().join("nested/index.js").into())
+    );
+    assert_eq!(
+      resolver
+        .resolve(
+          "//example.com/foo.png",
+          &root().join("foo.css"),
+          SpecifierType::Url
+        )
+        .result
+        .unwrap()
+        .0,
+      Resolution::External {
+        specifier: "//example.com/foo.png".into()
+      }
+    );
+  }
+
+  #[test]
+  fn test_unmatched_conditions() {
+    assert_eq!(
+      test_resolver()
+        .resolve(
+          "package-conditions-main",
+          &root().join("foo.js"),
+          SpecifierType::Esm
+        )
+        .result
+        .unwrap_err(),
+      ResolverError::PackageJsonError {
+        module: "package-conditions-main".into(),
+        path: root().join("node_modules/package-conditions-main/package.json"),
+        error: PackageJsonError::UnmatchedConditions {
+          conditions: vec!["node".into()],
+          active: vec!["import".into(), "module".into()],
+        }
+      },
+    );
+
+    let builder = || test_resolver_builder().unmatched_conditions(Fallthrough::LegacyMain);
+    let resolver = builder().build().unwrap();
+    assert_eq!(
+      resolver
+        .resolve(
+          "package-conditions-main",
+          &root().join("foo.js"),
+          SpecifierType::Esm
+        )
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(
+        root()
+          .join("node_modules/package-conditions-main/main.js")
+          .into()
+      )
+    );
+    // Matching conditions are still used.
+    let resolver = builder()
+      .conditions(ExportsCondition::NODE)
+      .build()
+      .unwrap();
+    assert_eq!(
+      resolver
+        .resolve(
+          "package-conditions-main",
+          &root().join("foo.js"),
+          SpecifierType::Esm
+        )
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(
+        root()
+          .join("node_modules/package-conditions-main/node.js")
+          .into()
+      )
+    );
+  }
+
+  #[test]
+  fn test_rewrites() {
+    let res = test_resolver().resolve(
+      "./foo",
+      &root().join("node_modules/package-browser-alias/browser.js"),
+      SpecifierType::Esm,
+    );
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(
+        root()
+          .join("node_modules/package-browser-alias/bar.js")
+          .into()
+      )
+    );
+    assert_eq!(
+      res
+        .rewrites
+        .iter()
+        .map(|r| r.to_string())
+        .collect::<Vec<_>>(),
+      vec!["./foo → ./bar (browser field)"]
+    );
+
+    let res = test_resolver().resolve("./bar.js", &root().join("foo.js"), SpecifierType::Esm);
+    assert!(res.rewrites.is_empty());
+  }
+
+  #[test]
+  fn test_encoded_separators() {
+    assert_eq!(
+      test_resolver()
+        .resolve(
+          "./encoded/a%2Fb.js",
+          &root().join("foo.js"),
+          SpecifierType::Esm
+        )
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(root().join("encoded").join("a%2Fb.js").into())
+    );
+    assert_eq!(
+      test_resolver()
+        .resolve(
+          "./encoded/a/b.js",
+          &root().join("foo.js"),
+          SpecifierType::Esm
+        )
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(root().join("encoded/a/b.js").into())
+    );
+  }
+
+  #[test]
+  fn test_scheme_aliases() {
+    let resolver = test_resolver_builder()
+      .scheme_alias("mylib", root().join("nested"))
+      .build()
+      .unwrap();
+    assert_eq!(
+      resolver
+        .resolve("mylib:test", &root().join("foo.js"), SpecifierType::Esm)
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(root().join("nested/test.js").into())
+    );
+    assert_eq!(
+      resolver
+        .resolve("mylib:index.js", &root().join("foo.js"), SpecifierType::Url)
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(root().join("nested/index.js").into())
+    );
+    assert_eq!(
+      resolver
+        .resolve(
+          "otherlib:index.js",
+          &root().join("foo.js"),
+          SpecifierType::Url
+        )
+        .result
+        .unwrap()
+        .0,
+      Resolution::External {
+        specifier: "otherlib:index.js".into()
+      }
+    );
+  }
+
+  #[test]
+  #[cfg(not(windows))]
+  fn test_manifest_backslashes() {
+    let pkg = root().join("node_modules/package-backslash");
+    let res = test_resolver().resolve(
+      "package-backslash",
+      &root().join("foo.js"),
+      SpecifierType::Esm,
+    );
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(pkg.join("lib/main.js").into())
+    );
+    assert_eq!(
+      res.diagnostics,
+      vec![Diagnostic::from(ResolverWarning::ManifestBackslashes {
+        path: pkg.join("package.json"),
+        field: "main",
+        value: ".\\lib\\main.js".into()
+      })]
+    );
+
+    let res = test_resolver().resolve("./foo.js", &pkg.join("index.js"), SpecifierType::Esm);
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(pkg.join("lib/foo-browser.js").into())
+    );
+    assert_eq!(
+      res.diagnostics,
+      vec![Diagnostic::from(ResolverWarning::ManifestBackslashes {
+        path: pkg.join("package.json"),
+        field: "browser",
+        value: ".\\lib\\foo-browser.js".into()
+      })]
+    );
+
+    let pkg = root().join("node_modules/package-backslash-exports");
+    let res = test_resolver().resolve(
+      "package-backslash-exports/foo",
+      &root().join("foo.js"),
+      SpecifierType::Esm,
+    );
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(pkg.join("lib/foo.js").into())
+    );
+    assert_eq!(
+      res.diagnostics,
+      vec![Diagnostic::from(ResolverWarning::ManifestBackslashes {
+        path: pkg.join("package.json"),
+        field: "exports",
+        value: ".\\lib\\foo.js".into()
+      })]
+    );
+    assert_eq!(res.diagnostics[0].hints, ["Use \"./lib/foo.js\" instead"]);
+
+    // Without the flag, backslashes are part of the file name.
+    let resolver = test_resolver_builder()
+      .flags(Flags::PARCEL - Flags::MANIFEST_BACKSLASHES)
+      .build()
+      .unwrap();
+    let res = resolver.resolve(
+      "package-backslash",
+      &root().join("foo.js"),
+      SpecifierType::Esm,
+    );
+    assert!(matches!(
+      res.result,
+      Err(ResolverError::ModuleEntryNotFound { .. })
+    ));
+    assert!(res.diagnostics.is_empty());
+
+    // Backslashes in specifiers from source code are never normalized.
+    let res = test_resolver().resolve(
+      "package-backslash\\lib\\main.js",
+      &root().join("foo.js"),
+      SpecifierType::Esm,
+    );
+    assert!(res.result.is_err());
+    assert!(res.diagnostics.is_empty());
+  }
+
+  #[test]
+  #[cfg(not(windows))]
+  fn test_diagnostic_levels() {
+    let pkg = root().join("node_modules/package-backslash");
+    let warning = Diagnostic::from(ResolverWarning::ManifestBackslashes {
+      path: pkg.join("package.json"),
+      field: "main",
+      value: ".\\lib\\main.js".into(),
+    });
+
+    // Silenced codes are not reported.
+    let resolver = test_resolver_builder()
+      .diagnostic_level("PARCEL_MANIFEST_BACKSLASHES", None)
+      .build()
+      .unwrap();
+    let res = resolver.resolve(
+      "package-backslash",
+      &root().join("foo.js"),
+      SpecifierType::Esm,
+    );
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(pkg.join("lib/main.js").into())
+    );
+    assert!(res.diagnostics.is_empty());
+
+    // Elevated codes fail the resolution, and are still reported as diagnostics.
+    let resolver = test_resolver_builder()
+      .diagnostic_level("PARCEL_MANIFEST_BACKSLASHES", Some(Severity::Error))
+      .build()
+      .unwrap();
+    let res = resolver.resolve(
+      "package-backslash",
+      &root().join("foo.js"),
+      SpecifierType::Esm,
+    );
+    let error = Diagnostic {
+      severity: Severity::Error,
+      ..warning.clone()
+    };
+    assert_eq!(
+      res.result.unwrap_err(),
+      ResolverError::ElevatedDiagnostic {
+        diagnostic: error.clone()
+      }
+    );
+    assert_eq!(res.diagnostics, vec![error]);
+
+    // Other codes are unaffected.
+    let resolver = test_resolver_builder()
+      .diagnostic_level("PARCEL_PACKAGE_DEDUPLICATED", Some(Severity::Error))
+      .build()
+      .unwrap();
+    let res = resolver.resolve(
+      "package-backslash",
+      &root().join("foo.js"),
+      SpecifierType::Esm,
+    );
+    assert!(res.result.is_ok());
+    assert_eq!(res.diagnostics, vec![warning]);
+  }
+
+  #[test]
+  fn test_directory_without_index() {
+    let extensions = vec!["ts", "tsx", "mjs", "js", "jsx", "cjs", "json"];
+    for specifier_type in [SpecifierType::Cjs, SpecifierType::Esm] {
+      assert_eq!(
+        test_resolver()
+          .resolve("./dir-no-index", &root().join("foo.js"), specifier_type)
+          .result
+          .unwrap_err(),
+        ResolverError::DirectoryWithoutIndex {
+          relative: "dir-no-index".into(),
+          from: root().join("foo.js"),
+          index_names: vec!["index".into()],
+          extensions: extensions.iter().map(|e| e.to_string()).collect(),
+          specifier_type,
+        },
+      );
+    }
+
+    // Urls never resolve directory index files.
+    assert_eq!(
+      test_resolver()
+        .resolve("./dir-no-index", &root().join("foo.js"), SpecifierType::Url)
+        .result
+        .unwrap_err(),
+      ResolverError::DirectoryWithoutIndex {
+        relative: "dir-no-index".into(),
+        from: root().join("foo.js"),
+        index_names: Vec::new(),
+        extensions: Vec::new(),
+        specifier_type: SpecifierType::Url,
+      },
+    );
+
+    assert_eq!(
+      test_resolver()
+        .resolve("./missing", &root().join("foo.js"), SpecifierType::Esm)
+        .result
+        .unwrap_err(),
+      ResolverError::FileNotFound {
+        relative: "missing".into(),
+        from: root().join("foo.js"),
+      },
+    );
+  }
+
+  #[test]
+  fn test_trailing_slash() {
+    // A trailing slash only matches a directory, never a file with the same name.
+    for specifier_type in [SpecifierType::Cjs, SpecifierType::Esm] {
+      assert_eq!(
+        test_resolver()
+          .resolve("./dir-and-file/", &root().join("foo.js"), specifier_type)
+          .result
+          .unwrap()
+          .0,
+        Resolution::Path(root().join("dir-and-file/index.js").into())
+      );
+      assert_eq!(
+        test_resolver()
+          .resolve("./dir-and-file", &root().join("foo.js"), specifier_type)
+          .result
+          .unwrap()
+          .0,
+        Resolution::Path(root().join("dir-and-file.js").into())
+      );
+    }
+
+    assert_eq!(
+      test_resolver()
+        .resolve(
+          "./dir-and-file/",
+          &root().join("foo.js"),
+          SpecifierType::Url
+        )
+        .result
+        .unwrap_err(),
+      ResolverError::DirectoryWithoutIndex {
+        relative: "dir-and-file/".into(),
+        from: root().join("foo.js"),
+        index_names: Vec::new(),
+        extensions: Vec::new(),
+        specifier_type: SpecifierType::Url,
+      },
+    );
+    assert_eq!(
+      test_resolver()
+        .resolve("./bar/", &root().join("foo.js"), SpecifierType::Cjs)
+        .result
+        .unwrap_err(),
+      ResolverError::FileNotFound {
+        relative: "bar/".into(),
+        from: root().join("foo.js"),
+      },
+    );
+  }
+
+  #[test]
+  fn test_directory_imports() {
+    for specifier_type in [SpecifierType::Cjs, SpecifierType::Esm] {
+      let resolve = |specifier, from: &str| {
+        test_resolver()
+          .resolve(specifier, &root().join(from), specifier_type)
+          .result
+          .map(|r| r.0)
+      };
+
+      for specifier in [".", "./"] {
+        assert_eq!(
+          resolve(specifier, "nested/test.js").unwrap(),
+          Resolution::Path(root().join("nested/index.js").into())
+        );
+      }
+      for specifier in ["..", "../"] {
+        assert_eq!(
+          resolve(specifier, "nested/sub/test.js").unwrap(),
+          Resolution::Path(root().join("nested/index.js").into())
+        );
+      }
+
+      // The package.json entry is used if present.
+      assert_eq!(
+        resolve(".", "node_modules/package-main/foo.js").unwrap(),
+        Resolution::Path(root().join("node_modules/package-main/main.js").into())
+      );
+
+      assert!(matches!(
+        resolve("..", "dir-no-index/other.js"),
+        Err(ResolverError::DirectoryWithoutIndex { .. })
+      ));
+    }
+
+    // Urls never resolve directory index files.
+    assert!(matches!(
+      test_resolver()
+        .resolve(".", &root().join("nested/test.js"), SpecifierType::Url)
+        .result,
+      Err(ResolverError::DirectoryWithoutIndex { .. })
+    ));
+  }
+
+  #[test]
+  fn test_dot_specifiers() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/package.json",
+        r#"{"name": "pkg", "main": "lib/main.js"}"#,
+      ),
+      ("/app/index.js", ""),
+      ("/app/lib/main.js", ""),
+      ("/app/src/index.js", ""),
+      ("/app/src/file.js", ""),
+      ("/app/src/nested/index.js", ""),
+      ("/app/src/nested/file.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    for specifier_type in [SpecifierType::Cjs, SpecifierType::Esm] {
+      let resolve = |specifier, from: &str| {
+        resolver
+          .resolve(specifier, &root.join(from), specifier_type)
+          .result
+          .map(|res| res.0)
+      };
+      let path = |path: &str| Ok(Resolution::Path(root.join(path).into()));
+
+      // From the package root, the package's main entry is used, never the importing file.
+      for specifier in [".", "./"] {
+        assert_eq!(resolve(specifier, "index.js"), path("lib/main.js"));
+      }
+      for specifier in ["..", "../"] {
+        assert_eq!(resolve(specifier, "src/file.js"), path("lib/main.js"));
+      }
+
+      // From a nested file, the directory's index file is used.
+      for specifier in [".", "./"] {
+        assert_eq!(
+          resolve(specifier, "src/nested/file.js"),
+          path("src/nested/index.js")
+        );
+      }
+      for specifier in ["..", "../"] {
+        assert_eq!(
+          resolve(specifier, "src/nested/file.js"),
+          path("src/index.js")
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn test_typed_schemes() {
+    let resolver = test_resolver_builder()
+      .typed_scheme("text", "text")
+      .build()
+      .unwrap();
+
+    let res = resolver.resolve("text:./bar.js", &root().join("foo.js"), SpecifierType::Esm);
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root().join("bar.js").into())
+    );
+    assert_eq!(res.import_type.as_deref(), Some("text"));
+
+    let res = resolver.resolve("./bar.js", &root().join("foo.js"), SpecifierType::Esm);
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root().join("bar.js").into())
+    );
+    assert_eq!(res.import_type, None);
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn test_prefer_source_in_workspace() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/index.js", ""),
+      (
+        "/app/packages/ui/package.json",
+        r#"{"name": "@acme/ui", "main": "dist/index.js"}"#,
+      ),
+      (
+        "/app/packages/ui/tsconfig.json",
+        r#"{"compilerOptions": {"rootDir": "src", "outDir": "dist"}}"#,
+      ),
+      ("/app/packages/ui/src/index.ts", ""),
+      ("/app/packages/ui/src/button.tsx", ""),
+      ("/app/packages/ui/dist/button.js", ""),
+      ("/app/packages/ui/dist/extra.js", ""),
+      (
+        "/app/packages/utils/package.json",
+        r#"{"name": "@acme/utils", "main": "dist/index.js", "main:src": "src/index.ts"}"#,
+      ),
+      ("/app/packages/utils/src/index.ts", ""),
+      (
+        "/app/packages/legacy/package.json",
+        r#"{"name": "@acme/legacy", "main": "dist/index.js"}"#,
+      ),
+      ("/app/packages/legacy/dist/index.js", ""),
+      (
+        "/app/packages/icons/package.json",
+        r#"{"name": "@acme/icons", "exports": {".": "./dist/index.js", "./star": "./dist/icons/star.js"}}"#,
+      ),
+      (
+        "/app/packages/icons/tsconfig.json",
+        r#"{"compilerOptions": {"rootDir": "src", "outDir": "dist"}}"#,
+      ),
+      ("/app/packages/icons/src/index.ts", ""),
+      ("/app/packages/icons/src/icons/star.tsx", ""),
+      ("/app/packages/icons/src/internal.ts", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    for name in ["ui", "utils", "legacy", "icons"] {
+      fs.symlink(
+        root.join("packages").join(name),
+        root.join("node_modules/@acme").join(name),
+      );
+    }
+
+    let cache = Cache::new(fs);
+    let build = |prefer_source| {
+      ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+        .prefer_source_in_workspace(prefer_source)
+        .build()
+        .unwrap()
+    };
+    let resolver = build(true);
+    let resolve =
+      |specifier| resolver.resolve(specifier, &root.join("index.js"), SpecifierType::Esm);
+
+    // The entry is mapped from outDir to rootDir, even though the build output is missing.
+    let res = resolve("@acme/ui");
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root.join("packages/ui/src/index.ts").into())
+    );
+    assert!(res.diagnostics.is_empty());
+
+    // Deep imports into the build output are remapped when the source exists, and left alone otherwise.
+    assert_eq!(
+      resolve("@acme/ui/dist/button.js").result.unwrap().0,
+      Resolution::Path(root.join("packages/ui/src/button.tsx").into())
+    );
+    let res = resolve("@acme/ui/dist/extra.js");
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root.join("packages/ui/dist/extra.js").into())
+    );
+    assert!(res.diagnostics.is_empty());
+
+    assert_eq!(
+      resolve("@acme/utils").result.unwrap().0,
+      Resolution::Path(root.join("packages/utils/src/index.ts").into())
+    );
+
+    let res = resolve("@acme/legacy");
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root.join("packages/legacy/dist/index.js").into())
+    );
+    assert_eq!(
+      res.diagnostics,
+      vec![Diagnostic::from(ResolverWarning::WorkspaceSourceNotFound {
+        path: root.join("node_modules/@acme/legacy/package.json"),
+        entry: root.join("packages/legacy/dist/index.js"),
+      })]
+    );
+
+    // Subpaths are mapped through "exports" before the build output is mapped to source.
+    assert_eq!(
+      resolve("@acme/icons").result.unwrap().0,
+      Resolution::Path(root.join("packages/icons/src/index.ts").into())
+    );
+    assert_eq!(
+      resolve("@acme/icons/star").result.unwrap().0,
+      Resolution::Path(root.join("packages/icons/src/icons/star.tsx").into())
+    );
+    assert!(matches!(
+      resolve("@acme/icons/dist/internal.js").result,
+      Err(ResolverError::PackageJsonError {
+        error: PackageJsonError::PackagePathNotExported,
+        ..
+      })
+    ));
+
+    // Without the option, the build output is used.
+    let res = build(false).resolve(
+      "@acme/ui/dist/button.js",
+      &root.join("index.js"),
+      SpecifierType::Esm,
+    );
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root.join("packages/ui/dist/button.js").into())
+    );
+  }
+
+  #[test]
+  fn test_consulted_manifests() {
+    let resolver = test_resolver_builder().diagnostics(true).build().unwrap();
+
+    let res = resolver.resolve("package-main", &root().join("foo.js"), SpecifierType::Esm);
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root().join("node_modules/package-main/main.js").into())
+    );
+    assert_eq!(
+      res.consulted_manifests,
+      vec![
+        root().join("package.json"),
+        root().join("node_modules/package-main/package.json")
+      ]
+    );
+
+    let res = resolver.resolve(
+      "./foo",
+      &root().join("node_modules/package-browser-alias/browser.js"),
+      SpecifierType::Esm,
+    );
+    assert_eq!(
+      res.consulted_manifests,
+      vec![
+        root().join("node_modules/package-browser-alias/package.json"),
+        root().join("package.json")
+      ]
+    );
+
+    // Invalidations are unaffected, and manifests are only collected when enabled.
+    let without_diagnostics =
+      test_resolver().resolve("package-main", &root().join("foo.js"), SpecifierType::Esm);
+    assert!(without_diagnostics.consulted_manifests.is_empty());
+    let res = resolver.resolve("package-main", &root().join("foo.js"), SpecifierType::Esm);
+    assert_eq!(
+      *res.invalidations.invalidate_on_file_change.read().unwrap(),
+      *without_diagnostics
+        .invalidations
+        .invalidate_on_file_change
+        .read()
+        .unwrap()
+    );
+  }
+
+  #[test]
+  fn test_exports_shapes() {
+    let resolve = |specifier, specifier_type| {
+      test_resolver()
+        .resolve(specifier, &root().join("foo.js"), specifier_type)
+        .result
+        .unwrap()
+        .0
+    };
+
+    assert_eq!(
+      resolve("exports-string", SpecifierType::Esm),
+      Resolution::Path(root().join("node_modules/exports-string/main.js").into())
+    );
+
+    // Array fallbacks are tried in order until one exists.
+    assert_eq!(
+      resolve("exports-fallback", SpecifierType::Esm),
+      Resolution::Path(root().join("node_modules/exports-fallback/main.js").into())
+    );
+    assert_eq!(
+      resolve("exports-fallback/nested", SpecifierType::Esm),
+      Resolution::Path(
+        root()
+          .join("node_modules/exports-fallback/nested.mjs")
+          .into()
+      )
+    );
+    assert_eq!(
+      resolve("exports-fallback/nested", SpecifierType::Cjs),
+      Resolution::Path(
+        root()
+          .join("node_modules/exports-fallback/nested.cjs")
+          .into()
+      )
+    );
+  }
+
+  #[test]
+  fn test_alias_precedence() {
+    // Every source remaps the same specifier, and the same file, to a different file.
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/package.json",
+        r#"{"alias": {"dep": "./root-alias.js", "./pkg/file.js": "./root-alias.js"}}"#,
+      ),
+      ("/app/root-alias.js", ""),
+      (
+        "/app/pkg/package.json",
+        r#"{
+          "name": "pkg",
+          "alias": {"dep": "./local-alias.js", "./file.js": "./local-alias.js"},
+          "browser": {"dep": "./local-browser.js", "./file.js": "./local-browser.js"}
+        }"#,
+      ),
+      ("/app/pkg/file.js", ""),
+      ("/app/pkg/local-alias.js", ""),
+      ("/app/pkg/local-browser.js", ""),
+      (
+        "/app/pkg/tsconfig.json",
+        r#"{"compilerOptions": {"baseUrl": ".", "paths": {"dep": ["./tsconfig-paths.js"]}}}"#,
+      ),
+      ("/app/pkg/tsconfig-paths.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let from = root.join("pkg/index.js");
+
+    let cache = Cache::new(fs);
+    let resolve_specifier = |specifier: &str, precedence: &[AliasSource]| {
+      let resolver =
+        ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+          .alias_precedence(precedence.iter().copied())
+          .diagnostics(true)
+          .build()
+          .unwrap();
+      resolver.resolve(specifier, &from, SpecifierType::Esm)
+    };
+    let resolve = |precedence: &[AliasSource]| resolve_specifier("dep", precedence);
+    let target = |source| match source {
+      AliasSource::RootAlias => root.join("root-alias.js"),
+      AliasSource::LocalAlias => root.join("pkg/local-alias.js"),
+      AliasSource::LocalBrowser => root.join("pkg/local-browser.js"),
+      AliasSource::TsConfigPaths => root.join("pkg/tsconfig-paths.js"),
+    };
+
+    // For each pairwise conflict, whichever source comes first wins, in either order.
+    for (i, a) in AliasSource::DEFAULT_PRECEDENCE.iter().enumerate() {
+      for b in &AliasSource::DEFAULT_PRECEDENCE[i + 1..] {
+        for (first, second) in [(*a, *b), (*b, *a)] {
+          let res = resolve(&[first, second]);
+          assert_eq!(
+            res.result.unwrap().0,
+            Resolution::Path(target(first).into()),
+            "{:?} before {:?}",
+            first,
+            second
+          );
+          assert_eq!(
+            res.alias_trace,
+            vec![
+              AliasDecision {
+                specifier: "dep".into(),
+                source: first,
+                outcome: AliasOutcome::Won,
+              },
+              AliasDecision {
+                specifier: "dep".into(),
+                source: second,
+                outcome: AliasOutcome::Skipped,
+              },
+            ]
+          );
+        }
+      }
+    }
+
+    // By default, the root alias wins over the local package's browser field.
+    let res = resolve(AliasSource::DEFAULT_PRECEDENCE);
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root.join("root-alias.js").into())
+    );
+
+    // Sources that are omitted are never consulted.
+    let res = resolve(&[AliasSource::TsConfigPaths]);
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root.join("pkg/tsconfig-paths.js").into())
+    );
+    let res = resolve(&[]);
+    assert!(res.result.is_err());
+    assert!(res.alias_trace.is_empty());
+
+    // Sources without an entry for the specifier are consulted in order, and reported as such.
+    let res = resolve_specifier("other", AliasSource::DEFAULT_PRECEDENCE);
+    assert!(res.result.is_err());
+    assert_eq!(
+      res
+        .alias_trace
+        .iter()
+        .map(|d| (d.source, d.outcome))
+        .collect::<Vec<_>>(),
+      vec![
+        (AliasSource::RootAlias, AliasOutcome::NoMatch),
+        (AliasSource::LocalAlias, AliasOutcome::NoMatch),
+        (AliasSource::LocalBrowser, AliasOutcome::NoMatch),
+        (AliasSource::TsConfigPaths, AliasOutcome::NoMatch),
+      ]
+    );
+
+    // Relative specifiers are remapped by the same sources in the same order, except tsconfig
+    // paths, which only apply to bare specifiers.
+    for (i, a) in AliasSource::DEFAULT_PRECEDENCE.iter().enumerate() {
+      for b in &AliasSource::DEFAULT_PRECEDENCE[i + 1..] {
+        for (first, second) in [(*a, *b), (*b, *a)] {
+          let winner = match first {
+            AliasSource::TsConfigPaths => second,
+            _ => first,
+          };
+          let res = resolve_specifier("./file.js", &[first, second]);
+          assert_eq!(
+            res.result.unwrap().0,
+            Resolution::Path(target(winner).into()),
+            "{:?} before {:?}",
+            first,
+            second
+          );
+        }
+      }
+    }
+
+    for precedence in [&[AliasSource::TsConfigPaths][..], &[]] {
+      let res = resolve_specifier("./file.js", precedence);
+      assert_eq!(
+        res.result.unwrap().0,
+        Resolution::Path(root.join("pkg/file.js").into())
+      );
+    }
+  }
+
+  #[test]
+  fn test_limits() {
+    let limits = ResolverLimits::default();
+    let limit_exceeded = |limit| -> Result<Resolution, ResolverError> {
+      Err(ResolverError::LimitExceeded {
+        limit,
+        max: limits.max(limit),
+      })
+    };
+    let resolve = |specifier: &str, from: &Path| {
+      test_resolver()
+        .resolve(specifier, from, SpecifierType::Esm)
+        .result
+        .map(|r| r.0)
+    };
+
+    // A 10 MB specifier is rejected before it is parsed.
+    let specifier = "../".repeat(10 * 1024 * 1024 / 3);
+    assert_eq!(
+      resolve(&specifier, &root().join("foo.js")),
+      limit_exceeded(Limit::SpecifierLength)
+    );
+
+    let specifier = format!("./{}foo.js", "../".repeat(1000));
+    assert_eq!(
+      resolve(&specifier, &root().join("foo.js")),
+      limit_exceeded(Limit::ParentSegments)
+    );
+
+    // Walking up from a deeply nested directory stops at the limit.
+    let from = root().join("a/".repeat(2000)).join("foo.js");
+    assert_eq!(
+      resolve("deep-package", &from),
+      limit_exceeded(Limit::AncestorDepth)
+    );
+
+    // An exports map with 100k keys.
+    let exports = (0..100_000)
+      .map(|i| format!(r#""./{}": "./{}.js""#, i, i))
+      .collect::<Vec<_>>()
+      .join(",");
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/node_modules/big/package.json",
+        format!(r#"{{"name": "big", "exports": {{{}}}}}"#, exports),
+      ),
+      ("/app/node_modules/big/99999.js", String::new()),
+    ]);
+    let root = PathBuf::from("/app");
+    let cache = Cache::new(fs);
+    let resolver = Resolver::node(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache));
+    assert_eq!(
+      resolver
+        .resolve("big/99999", &root.join("index.js"), SpecifierType::Esm)
+        .result
+        .map(|r| r.0),
+      limit_exceeded(Limit::ExportsKeys)
+    );
+
+    // Limits can be raised for projects that need them.
+    let resolver = ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+      .preset(Preset::Node)
+      .limits(ResolverLimits {
+        max_exports_keys: 200_000,
+        ..Default::default()
+      })
+      .build()
+      .unwrap();
+    assert_eq!(
+      resolver
+        .resolve("big/99999", &root.join("index.js"), SpecifierType::Esm)
+        .result
+        .map(|r| r.0),
+      Ok(Resolution::Path(
+        root.join("node_modules/big/99999.js").into()
+      ))
+    );
+  }
+
+  #[test]
+  fn test_limits_symlink_depth() {
+    // A directory tree 10k levels deep via a symlink to itself.
+    let fs = MemoryFileSystem::from_files([("/app/foo.js", "")]);
+    let root = PathBuf::from("/app");
+    fs.symlink(".", root.join("loop"));
+    let from = root.join("loop/".repeat(10_000)).join("index.js");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    assert_eq!(
+      resolver
+        .resolve("./foo.js", &from, SpecifierType::Esm)
+        .result
+        .map(|r| r.0),
+      Err(ResolverError::LimitExceeded {
+        limit: Limit::AncestorDepth,
+        max: resolver.limits.max_ancestor_depth,
+      })
+    );
+  }
+
+  #[test]
+  fn test_symlink_info() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/packages/ui/package.json", "{}"),
+      ("/app/packages/ui/index.js", ""),
+      ("/app/packages/scoped/index.js", ""),
+      ("/app/src/real.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    fs.symlink("../packages/ui", root.join("node_modules/ui"));
+    fs.symlink(
+      "../../packages/scoped",
+      root.join("node_modules/@acme/scoped"),
+    );
+    fs.symlink("real.js", root.join("src/link.js"));
+    fs.symlink("src", root.join("lib"));
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let from = root.join("src/index.js");
+    let symlink = |specifier| {
+      let res = resolver.resolve(specifier, &from, SpecifierType::Esm);
+      assert!(res.result.is_ok(), "{}", specifier);
+      res.symlink
+    };
+
+    assert_eq!(
+      symlink("ui"),
+      Some(SymlinkInfo {
+        canonical_path: root.join("packages/ui/index.js").into(),
+        link_path: root.join("node_modules/ui/index.js"),
+        link: root.join("node_modules/ui"),
+        kind: LinkKind::Package,
+      })
+    );
+    assert_eq!(
+      symlink("@acme/scoped").map(|s| (s.link, s.kind)),
+      Some((root.join("node_modules/@acme/scoped"), LinkKind::Package))
+    );
+    assert_eq!(
+      symlink("./link.js"),
+      Some(SymlinkInfo {
+        canonical_path: root.join("src/real.js").into(),
+        link_path: root.join("src/link.js"),
+        link: root.join("src/link.js"),
+        kind: LinkKind::File,
+      })
+    );
+    assert_eq!(
+      symlink("../lib/real.js").map(|s| (s.link, s.kind)),
+      Some((root.join("lib"), LinkKind::Directory))
+    );
+    assert_eq!(symlink("./real.js"), None);
+  }
+
+  #[test]
+  fn test_fuzz_corpus() -> Result<(), Box<dyn std::error::Error>> {
+    // Seed inputs for the `resolve` cargo-fuzz target that previously caused hangs, deep
+    // recursion, or panics. Each must resolve (or fail) promptly.
+    let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/corpus/resolve");
+    let mut count = 0;
+    for entry in std::fs::read_dir(corpus)? {
+      let specifier = String::from_utf8_lossy(&std::fs::read(entry?.path())?).into_owned();
+      for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs, SpecifierType::Url] {
+        let _ = test_resolver().resolve(&specifier, &root().join("foo.js"), specifier_type);
+        let _ = node_resolver().resolve(&specifier, &root().join("foo.js"), specifier_type);
+      }
+      count += 1;
+    }
+
+    assert!(count > 0);
+    Ok(())
+  }
+
+  #[test]
+  fn test_strip_invisible_prefix() {
+    let resolver = test_resolver();
+
+    // By default, a BOM is part of the specifier, which is then a bare package name.
+    let res = resolver.resolve("\u{FEFF}./bar", &root().join("foo.js"), SpecifierType::Esm);
+    assert!(res.result.is_err());
+    assert!(res.diagnostics.is_empty());
+
+    let resolver = test_resolver_builder()
+      .strip_invisible_prefix(true)
+      .build()
+      .unwrap();
+    let res = resolver.resolve("\u{FEFF}./bar", &root().join("foo.js"), SpecifierType::Esm);
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root().join("bar.js").into())
+    );
+    assert_eq!(
+      res.diagnostics,
+      vec![Diagnostic::from(ResolverWarning::InvisiblePrefixStripped {
+        specifier: "\u{FEFF}./bar".into(),
+        stripped: vec!["U+FEFF".into()],
+      })]
+    );
+
+    let res = resolver.resolve(
+      "\u{200B}\u{FEFF}./bar",
+      &root().join("foo.js"),
+      SpecifierType::Cjs,
+    );
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root().join("bar.js").into())
+    );
+    assert_eq!(
+      res.diagnostics,
+      vec![Diagnostic::from(ResolverWarning::InvisiblePrefixStripped {
+        specifier: "\u{200B}\u{FEFF}./bar".into(),
+        stripped: vec!["U+200B".into(), "U+FEFF".into()],
+      })]
+    );
+
+    let res = resolver.resolve("./bar", &root().join("foo.js"), SpecifierType::Esm);
+    assert!(res.diagnostics.is_empty());
+  }
+
+  #[test]
+  fn test_exports_null_target() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/node_modules/blocked/package.json",
+        r#"{"name": "blocked", "exports": {".": "./index.js", "./*": "./*.js", "./internal/*": null}}"#,
+      ),
+      ("/app/node_modules/blocked/index.js", ""),
+      ("/app/node_modules/blocked/foo.js", ""),
+      ("/app/node_modules/blocked/internal/secret.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let resolver = Resolver::node(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier| {
+      resolver
+        .resolve(specifier, &root.join("index.js"), SpecifierType::Esm)
+        .result
+        .map(|r| r.0)
+    };
+
+    assert_eq!(
+      resolve("blocked/foo"),
+      Ok(Resolution::Path(
+        root.join("node_modules/blocked/foo.js").into()
+      ))
+    );
+    // The null target blocks the subpath, even though the file exists.
+    assert_eq!(
+      resolve("blocked/internal/secret"),
+      Err(ResolverError::SubpathNotExported {
+        package: "blocked".into(),
+        subpath: "./internal/secret".into(),
+      })
+    );
+  }
+
+  #[test]
+  fn test_cancellation() {
+    let resolver = test_resolver_builder()
+      .resolution_cache(true)
+      .build()
+      .unwrap();
+    let from = root().join("foo.js");
+    let specifiers = ["./bar", "foo", "package-main", "./nested", "@scope/pkg"];
+    let token = CancellationToken::new();
+    let options = ResolveOptions {
+      cancellation: Some(token.clone()),
+      ..Default::default()
+    };
+
+    // Cancel after the first two resolutions have completed.
+    let results = resolver.resolve_many(
+      specifiers.iter().enumerate().map(|(i, specifier)| {
+        if i == 2 {
+          token.cancel();
+        }
+        (*specifier, from.as_path(), SpecifierType::Esm)
+      }),
+      &options,
+    );
+    assert_eq!(results.len(), specifiers.len());
+    // With rayon, the requests are all collected (cancelling the token) before any are resolved.
+    let completed = if cfg!(feature = "rayon") { 0 } else { 2 };
+    if completed > 0 {
+      assert_eq!(
+        results[0].result.as_ref().unwrap().0,
+        Resolution::Path(root().join("bar.js").into())
+      );
+      assert_eq!(
+        results[1].result.as_ref().unwrap().0,
+        Resolution::Path(root().join("node_modules/foo/index.js").into())
+      );
+    }
+    for res in &results[completed..] {
+      assert_eq!(res.result.as_ref().unwrap_err(), &ResolverError::Cancelled);
+    }
+
+    // Cancelled resolutions are not cached, so resolving again without the token
+    // produces the same results as a fresh resolver.
+    let results = resolver.resolve_many(
+      specifiers.map(|s| (s, from.as_path(), SpecifierType::Esm)),
+      &Default::default(),
+    );
+    for (specifier, res) in specifiers.iter().zip(results) {
+      assert_eq!(
+        res.result.unwrap().0,
+        test_resolver()
+          .resolve(specifier, &from, SpecifierType::Esm)
+          .result
+          .unwrap()
+          .0,
+        "{}",
+        specifier
+      );
+    }
+  }
+
+  #[cfg(feature = "rayon")]
+  #[test]
+  fn test_resolve_many_single_thread_pool() -> Result<(), Box<dyn std::error::Error>> {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/package.json",
+        r#"{"name": "app", "alias": {"aliased": "pkg/feature"}}"#,
+      ),
+      (
+        "/app/node_modules/pkg/package.json",
+        r#"{
+          "name": "pkg",
+          "exports": {
+            ".": "./main.js",
+            "./feature": {"import": "./feature.mjs", "default": "./feature.js"}
+          }
+        }"#,
+      ),
+      ("/app/node_modules/pkg/main.js", ""),
+      ("/app/node_modules/pkg/feature.js", ""),
+      ("/app/node_modules/pkg/feature.mjs", ""),
+      ("/app/index.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let pool = Arc::new(rayon::ThreadPoolBuilder::new().num_threads(1).build()?);
+    let cache = Cache::new(fs);
+    let resolver = ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+      .with_thread_pool(pool.clone())
+      .resolution_cache(true)
+      .build()
+      .unwrap();
+
+    let from = root.join("index.js");
+    let requests = [
+      ("aliased", SpecifierType::Esm),
+      ("pkg", SpecifierType::Esm),
+      ("aliased", SpecifierType::Cjs),
+      ("aliased", SpecifierType::Esm),
+    ];
+    // Run the batch from within the pool's only thread, so that it is nested in a pool job.
+    let results = pool.install(|| {
+      resolver.resolve_many(
+        requests.map(|(specifier, specifier_type)| (specifier, from.as_path(), specifier_type)),
+        &Default::default(),
+      )
+    });
+
+    let expected = [
+      "node_modules/pkg/feature.mjs",
+      "node_modules/pkg/main.js",
+      "node_modules/pkg/feature.js",
+      "node_modules/pkg/feature.mjs",
+    ];
+    assert_eq!(results.len(), expected.len());
+    for (res, expected) in results.into_iter().zip(expected) {
+      assert_eq!(
+        res.result.unwrap().0,
+        Resolution::Path(root.join(expected).into())
+      );
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_extensionless_files() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/script", "#!/usr/bin/env node"),
+      ("/app/script.js", ""),
+      ("/app/other.js", ""),
+      (
+        "/app/node_modules/tool/package.json",
+        r#"{"name": "tool", "exports": {"./cli": "./bin/cli"}}"#,
+      ),
+      ("/app/node_modules/tool/bin/cli", ""),
+      ("/app/node_modules/tool/bin/cli.js", ""),
+      (
+        "/app/node_modules/legacy/package.json",
+        r#"{"name": "legacy", "main": "bin/main"}"#,
+      ),
+      ("/app/node_modules/legacy/bin/main", ""),
+      ("/app/node_modules/legacy/bin/main.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier| {
+      let res = resolver.resolve(specifier, &root.join("index.js"), SpecifierType::Esm);
+      (res.result.unwrap().0, res.extensionless)
+    };
+
+    // The exact path takes precedence over appending an extension, for each kind of specifier.
+    assert_eq!(
+      resolve("./script"),
+      (Resolution::Path(root.join("script").into()), true)
+    );
+    assert_eq!(
+      resolve("tool/cli"),
+      (
+        Resolution::Path(root.join("node_modules/tool/bin/cli").into()),
+        true
+      )
+    );
+    assert_eq!(
+      resolve("legacy"),
+      (
+        Resolution::Path(root.join("node_modules/legacy/bin/main").into()),
+        true
+      )
+    );
+    assert_eq!(
+      resolve("./other"),
+      (Resolution::Path(root.join("other.js").into()), false)
+    );
+    assert_eq!(
+      resolve("./script.js"),
+      (Resolution::Path(root.join("script.js").into()), false)
+    );
+  }
+
+  #[test]
+  fn test_types_versions() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/node_modules/lib/package.json",
+        r#"{
+          "name": "lib",
+          "types": "index.d.ts",
+          "typesVersions": {
+            ">=4.5": {"*": ["ts4.5/*"]},
+            ">=3.1 <4": {"*": ["ts3.1/*"]}
+          }
+        }"#,
+      ),
+      ("/app/node_modules/lib/index.d.ts", ""),
+      ("/app/node_modules/lib/sub.d.ts", ""),
+      ("/app/node_modules/lib/ts4.5/index.d.ts", ""),
+      ("/app/node_modules/lib/ts4.5/sub.d.ts", ""),
+      ("/app/node_modules/lib/ts3.1/index.d.ts", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let cache = Cache::new(fs);
+    let build = |version: Option<TsVersion>| {
+      let builder = ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+        .entry_fields(Fields::TYPES);
+      match version {
+        Some(version) => builder.typescript_version(version),
+        None => builder,
+      }
+      .build()
+      .unwrap()
+    };
+    let resolve = |resolver: &Resolver<MemoryFileSystem>, specifier| {
+      resolver
+        .resolve(specifier, &root.join("index.ts"), SpecifierType::Esm)
+        .result
+        .unwrap()
+        .0
+    };
+
+    let resolver = build(Some(TsVersion::new(4, 9, 0)));
+    assert_eq!(
+      resolve(&resolver, "lib"),
+      Resolution::Path(root.join("node_modules/lib/ts4.5/index.d.ts").into())
+    );
+    assert_eq!(
+      resolve(&resolver, "lib/sub.d.ts"),
+      Resolution::Path(root.join("node_modules/lib/ts4.5/sub.d.ts").into())
+    );
+
+    let resolver = build(Some(TsVersion::new(3, 8, 0)));
+    assert_eq!(
+      resolve(&resolver, "lib"),
+      Resolution::Path(root.join("node_modules/lib/ts3.1/index.d.ts").into())
+    );
+    // A mapped path that does not exist falls through to the package itself.
+    assert_eq!(
+      resolve(&resolver, "lib/sub.d.ts"),
+      Resolution::Path(root.join("node_modules/lib/sub.d.ts").into())
+    );
+
+    // No range matches, so the "types" field is used as is.
+    let resolver = build(Some(TsVersion::new(4, 2, 0)));
+    assert_eq!(
+      resolve(&resolver, "lib"),
+      Resolution::Path(root.join("node_modules/lib/index.d.ts").into())
+    );
+
+    let resolver = build(None);
+    assert_eq!(
+      resolve(&resolver, "lib"),
+      Resolution::Path(root.join("node_modules/lib/index.d.ts").into())
+    );
+  }
+
+  #[test]
+  fn test_index_names() -> Result<(), Box<dyn std::error::Error>> {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/foo.js", ""),
+      ("/app/Button/Button.tsx", ""),
+      ("/app/Button/index.js", ""),
+      ("/app/legacy/main.js", ""),
+      ("/app/empty/other.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let from = root.join("foo.js");
+
+    let cache = Cache::new(fs);
+    let builder = || {
+      ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+        .resolution_cache(true)
+    };
+    let resolve = |resolver: &Resolver<MemoryFileSystem>, specifier| {
+      resolver
+        .resolve(specifier, &from, SpecifierType::Esm)
+        .result
+        .map(|r| r.0)
+    };
+
+    // The default only tries "index".
+    let resolver = builder().build().unwrap();
+    assert_eq!(
+      resolve(&resolver, "./Button"),
+      Ok(Resolution::Path(root.join("Button/index.js").into()))
+    );
+    assert!(resolve(&resolver, "./legacy").is_err());
+
+    // Names are tried in order, each with every extension.
+    let resolver = builder()
+      .index_names([IndexName::DirectoryName, "index".into(), "main".into()])
+      .build()
+      .unwrap();
+    assert_eq!(
+      resolve(&resolver, "./Button"),
+      Ok(Resolution::Path(root.join("Button/Button.tsx").into()))
+    );
+    assert_eq!(
+      resolve(&resolver, "./legacy"),
+      Ok(Resolution::Path(root.join("legacy/main.js").into()))
+    );
+
+    match resolve(&resolver, "./empty") {
+      Err(ResolverError::DirectoryWithoutIndex { index_names, .. }) => {
+        assert_eq!(index_names, vec!["empty", "index", "main"])
+      }
+      res => panic!("unexpected result {:?}", res),
+    }
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_dedupe_packages() {
+    let fs = MemoryFileSystem::new();
+    let package = |path: &str, name: &str, version: &str| {
+      fs.write_file(
+        format!("/app/{}/package.json", path),
+        format!(r#"{{"name": "{}", "version": "{}"}}"#, name, version),
+      );
+      fs.write_file(format!("/app/{}/index.js", path), "");
+    };
+    package("node_modules/a", "a", "1.0.0");
+    package("node_modules/lodash", "lodash", "4.17.21");
+    package("node_modules/a/node_modules/lodash", "lodash", "4.17.21");
+    package("node_modules/react", "react", "18.2.0");
+    package("node_modules/a/node_modules/react", "react", "17.0.2");
+    package("node_modules/native", "native", "1.0.0");
+    package("node_modules/a/node_modules/native", "native", "1.0.0");
+    fs.write_file(
+      "/node_modules/lodash/package.json",
+      r#"{"name": "lodash", "version": "4.17.21"}"#,
+    );
+    fs.write_file("/node_modules/lodash/index.js", "");
+    let root = PathBuf::from("/app");
+    let from = root.join("node_modules/a/index.js");
+
+    let resolver = ResolverBuilder::new(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    )
+    .dedupe_packages(true)
+    .dedupe_exclude(["native"])
+    .build()
+    .unwrap();
+
+    // The copy above the project root is ignored.
+    let res = resolver.resolve("lodash", &from, SpecifierType::Esm);
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root.join("node_modules/lodash/index.js").into())
+    );
+    assert_eq!(
+      res.diagnostics,
+      vec![Diagnostic::from(ResolverWarning::PackageDeduplicated {
+        package: "lodash".into(),
+        version: "4.17.21".into(),
+        path: root.join("node_modules/a/node_modules/lodash/index.js"),
+        deduped: root.join("node_modules/lodash/index.js"),
+      })]
+    );
+
+    // Different versions and excluded packages keep the nested copy.
+    for specifier in ["react", "native"] {
+      let res = resolver.resolve(specifier, &from, SpecifierType::Esm);
+      assert_eq!(
+        res.result.unwrap().0,
+        Resolution::Path(
+          root
+            .join("node_modules/a/node_modules")
+            .join(specifier)
+            .join("index.js")
+            .into()
+        )
+      );
+      assert!(res.diagnostics.is_empty());
+    }
+
+    // Packages that are not nested are unaffected.
+    assert_eq!(
+      resolver
+        .resolve("lodash", &root.join("index.js"), SpecifierType::Esm)
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(root.join("node_modules/lodash/index.js").into())
+    );
+  }
+
+  #[test]
+  fn test_browser_field_lenient_keys() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/index.js", ""),
+      // Like readable-stream@3, whose keys mix extensionless and extensioned paths,
+      // e.g. `"./errors": "./errors-browser.js"`.
+      (
+        "/app/node_modules/readable-stream/package.json",
+        r#"{
+          "name": "readable-stream",
+          "main": "readable.js",
+          "browser": {
+            "util": false,
+            "./errors": "./errors-browser.js",
+            "./readable.js": "./readable-browser.js"
+          }
+        }"#,
+      ),
+      ("/app/node_modules/readable-stream/readable.js", ""),
+      ("/app/node_modules/readable-stream/readable-browser.js", ""),
+      ("/app/node_modules/readable-stream/errors.js", ""),
+      ("/app/node_modules/readable-stream/errors-browser.js", ""),
+      // Keys and values without the leading "./".
+      (
+        "/app/node_modules/sloppy/package.json",
+        r#"{
+          "name": "sloppy",
+          "main": "lib/index.js",
+          "browser": {
+            "lib/fs.js": "lib/fs-browser.js",
+            "lib/net": "lib/net-browser"
+          }
+        }"#,
+      ),
+      ("/app/node_modules/sloppy/lib/index.js", ""),
+      ("/app/node_modules/sloppy/lib/fs.js", ""),
+      ("/app/node_modules/sloppy/lib/fs-browser.js", ""),
+      ("/app/node_modules/sloppy/lib/net.js", ""),
+      ("/app/node_modules/sloppy/lib/net-browser.js", ""),
+      // Like node-fetch@2, with an extensionless "main" and a string "browser" field.
+      (
+        "/app/node_modules/node-fetch/package.json",
+        r#"{
+          "name": "node-fetch",
+          "main": "lib/index",
+          "module": "lib/index.mjs",
+          "browser": "./browser.js"
+        }"#,
+      ),
+      ("/app/node_modules/node-fetch/lib/index.js", ""),
+      ("/app/node_modules/node-fetch/lib/index.mjs", ""),
+      ("/app/node_modules/node-fetch/browser.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier, from: &str| {
+      resolver
+        .resolve(specifier, &root.join(from), SpecifierType::Cjs)
+        .result
+        .unwrap()
+        .0
+    };
+
+    assert_eq!(
+      resolve("readable-stream", "index.js"),
+      Resolution::Path(
+        root
+          .join("node_modules/readable-stream/readable-browser.js")
+          .into()
+      )
+    );
+    for specifier in ["./errors", "./errors.js"] {
+      assert_eq!(
+        resolve(specifier, "node_modules/readable-stream/readable.js"),
+        Resolution::Path(
+          root
+            .join("node_modules/readable-stream/errors-browser.js")
+            .into()
+        )
+      );
+    }
+    assert_eq!(
+      resolve("util", "node_modules/readable-stream/readable.js"),
+      Resolution::Empty
+    );
+
+    for specifier in ["./fs", "./fs.js"] {
+      assert_eq!(
+        resolve(specifier, "node_modules/sloppy/lib/index.js"),
+        Resolution::Path(root.join("node_modules/sloppy/lib/fs-browser.js").into())
+      );
+    }
+    for specifier in ["./net", "./net.js"] {
+      assert_eq!(
+        resolve(specifier, "node_modules/sloppy/lib/index.js"),
+        Resolution::Path(root.join("node_modules/sloppy/lib/net-browser.js").into())
+      );
+    }
+
+    assert_eq!(
+      resolve("node-fetch", "index.js"),
+      Resolution::Path(root.join("node_modules/node-fetch/browser.js").into())
+    );
+    // The main file itself is only remapped by an object "browser" field.
+    assert_eq!(
+      resolve("./node_modules/node-fetch/lib/index", "index.js"),
+      Resolution::Path(root.join("node_modules/node-fetch/lib/index.js").into())
+    );
+  }
+
+  #[test]
+  fn test_resolve_candidates() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/src/index.js", ""),
+      ("/app/node_modules/pkg/index.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let from = root.join("src/index.js");
+
+    let resolver = Resolver::node(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+
+    // Nothing exists, so every extension is tried.
+    assert_eq!(
+      resolver.resolve_candidates("./missing", &from, SpecifierType::Cjs),
+      vec![
+        root.join("src/missing"),
+        root.join("src/missing.js"),
+        root.join("src/missing.json"),
+        root.join("src/missing.node"),
+      ]
+    );
+
+    // The search ascends through node_modules directories, then stops at the index file.
+    assert_eq!(
+      resolver.resolve_candidates("pkg", &from, SpecifierType::Cjs),
+      vec![
+        root.join("src/index.js/node_modules/pkg"),
+        root.join("src/node_modules/pkg"),
+        root.join("node_modules/pkg"),
+        root.join("node_modules/pkg/index"),
+        root.join("node_modules/pkg/index.js"),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_resolve_lenient() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/index.js", ""),
+      (
+        "/app/node_modules/exported/package.json",
+        r#"{"name": "exported", "exports": {".": "./index.js", "./utils": "./utils.js"}}"#,
+      ),
+      ("/app/node_modules/exported/index.js", ""),
+      ("/app/node_modules/exported/utils.js", ""),
+      (
+        "/app/node_modules/legacy/package.json",
+        r#"{"name": "legacy"}"#,
+      ),
+      ("/app/node_modules/legacy/lib/index.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let from = root.join("index.js");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier| {
+      resolver.resolve_lenient(specifier, &from, SpecifierType::Esm, Default::default())
+    };
+
+    let (res, partial) = resolve("exported/util");
+    assert!(res.result.is_err());
+    assert_eq!(
+      partial,
+      Some(PartialResolution {
+        package_root: Some(root.join("node_modules/exported")),
+        manifest: Some(root.join("node_modules/exported/package.json")),
+        closest_export: Some("./utils".into()),
+        expected_dir: None,
+      })
+    );
+
+    let (res, partial) = resolve("legacy/lib/missing.js");
+    assert!(res.result.is_err());
+    assert_eq!(
+      partial,
+      Some(PartialResolution {
+        package_root: Some(root.join("node_modules/legacy")),
+        manifest: Some(root.join("node_modules/legacy/package.json")),
+        closest_export: None,
+        expected_dir: Some(root.join("node_modules/legacy/lib")),
+      })
+    );
+
+    // Nothing was located, or resolution succeeded.
+    assert_eq!(resolve("missing").1, None);
+    let (res, partial) = resolve("exported/utils");
+    assert!(res.result.is_ok());
+    assert_eq!(partial, None);
+  }
+
+  // #[test]
+  // fn test_visitor() {
+  //   let resolved = test_resolver().resolve("unified", &root(), SpecifierType::Esm).unwrap();
+  //   println!("{:?}", resolved);
+  //   if let Resolution::Path(p) = resolved {
+  //     let res = build_esm_graph(
+  //       &p,
+  //       root()
+  //     ).unwrap();
+  //     println!("{:?}", res);
+  //   }
+  // }
+
+  #[test]
+  fn test_package_trailing_slash() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/node_modules/plain/package.json",
+        r#"{"name": "plain", "main": "lib/main.js"}"#,
+      ),
+      ("/app/node_modules/plain/index.js", ""),
+      ("/app/node_modules/plain/lib/main.js", ""),
+      ("/app/node_modules/plain/sub/double.js", ""),
+      (
+        "/app/node_modules/@scope/plain/package.json",
+        r#"{"name": "@scope/plain", "main": "lib/main.js"}"#,
+      ),
+      ("/app/node_modules/@scope/plain/index.js", ""),
+      (
+        "/app/node_modules/exported/package.json",
+        r#"{"name": "exported", "exports": {".": "./index.js", "./sub/*": "./sub/*.js"}}"#,
+      ),
+      ("/app/node_modules/exported/index.js", ""),
+      ("/app/node_modules/exported/sub/double.js", ""),
+      (
+        "/app/node_modules/folder/package.json",
+        r#"{"name": "folder", "exports": {".": "./index.js", "./": "./"}}"#,
+      ),
+      ("/app/node_modules/folder/index.js", ""),
+      ("/app/index.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier, specifier_type| {
+      resolver
+        .resolve(specifier, &root.join("index.js"), specifier_type)
+        .result
+        .map(|res| res.0)
+    };
+    let path = |path: &str| Ok(Resolution::Path(root.join(path).into()));
+
+    // Without "exports", a trailing slash loads the package directory: its main entry, or the
+    // index file if the main entry doesn't exist.
+    assert_eq!(
+      resolve("plain", SpecifierType::Cjs),
+      path("node_modules/plain/lib/main.js")
+    );
+    assert_eq!(
+      resolve("plain/", SpecifierType::Cjs),
+      path("node_modules/plain/lib/main.js")
+    );
+    assert_eq!(
+      resolve("@scope/plain/", SpecifierType::Cjs),
+      path("node_modules/@scope/plain/index.js")
+    );
+    assert_eq!(
+      resolve("plain/sub//double", SpecifierType::Cjs),
+      resolve("plain/sub/double", SpecifierType::Cjs)
+    );
+
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs] {
+      // With "exports", the package directory must be exported explicitly.
+      assert!(matches!(
+        resolve("exported/", specifier_type),
+        Err(ResolverError::PackageJsonError {
+          error: PackageJsonError::PackagePathNotExported,
+          ..
+        })
+      ));
+      assert!(!matches!(
+        resolve("folder/", specifier_type),
+        Err(ResolverError::PackageJsonError {
+          error: PackageJsonError::PackagePathNotExported,
+          ..
+        })
+      ));
+
+      // Empty segments are collapsed, so they match the same "exports" key.
+      assert_eq!(
+        resolve("exported/sub//double", specifier_type),
+        path("node_modules/exported/sub/double.js")
+      );
+    }
+  }
+
+  #[test]
+  fn test_implicit_relative() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/src/index.js", ""),
+      ("/app/src/sibling.js", ""),
+      ("/app/src/fs.js", ""),
+      ("/app/src/lib/helper.js", ""),
+      (
+        "/app/node_modules/pkg/package.json",
+        r#"{"name": "pkg", "main": "main.js"}"#,
+      ),
+      ("/app/node_modules/pkg/main.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let cache = Cache::new(fs);
+    let build = |flags| {
+      ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+        .flags(flags)
+        .build()
+        .unwrap()
+    };
+    let resolver = build(Flags::PARCEL);
+    let from = root.join("src/index.js");
+    let resolve = |resolver: &Resolver<MemoryFileSystem>, specifier| {
+      resolver
+        .resolve(specifier, &from, SpecifierType::Esm)
+        .result
+        .map(|res| res.0)
+    };
+    let path = |path: &str| Ok(Resolution::Path(root.join(path).into()));
+
+    assert!(matches!(
+      resolve(&resolver, "sibling"),
+      Err(ResolverError::ModuleNotFound { .. })
+    ));
+
+    let resolver = build(Flags::PARCEL | Flags::IMPLICIT_RELATIVE);
+    assert_eq!(resolve(&resolver, "sibling"), path("src/sibling.js"));
+    assert_eq!(resolve(&resolver, "lib/helper"), path("src/lib/helper.js"));
+    // Without a local file, the package is used.
+    assert_eq!(resolve(&resolver, "pkg"), path("node_modules/pkg/main.js"));
+    // Builtins always win over local files.
+    assert_eq!(
+      resolve(&resolver, "fs"),
+      Ok(Resolution::Builtin("fs".into()))
+    );
+  }
+
+  #[test]
+  fn test_alias_chains() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/package.json",
+        r#"{
+          "name": "app",
+          "alias": {
+            "old-pkg": "new-pkg",
+            "new-pkg": "./patched/new-pkg.js",
+            "self": "self",
+            "a": "b",
+            "b": "c",
+            "c": "a"
+          }
+        }"#,
+      ),
+      ("/app/index.js", ""),
+      ("/app/patched/new-pkg.js", ""),
+      ("/app/node_modules/self/index.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let cache = Cache::new(fs);
+    let resolver = Resolver::parcel(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache));
+    let from = root.join("index.js");
+
+    // Two hops: old-pkg -> new-pkg -> ./patched/new-pkg.js.
+    let res = resolver.resolve("old-pkg", &from, SpecifierType::Esm);
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root.join("patched/new-pkg.js").into())
+    );
+    assert_eq!(res.rewrites.len(), 2);
+    assert!(res
+      .invalidations
+      .invalidate_on_file_change
+      .read()
+      .unwrap()
+      .iter()
+      .any(|p| p.as_path() == root.join("package.json")));
+
+    // An alias to itself is a fixed point.
+    assert_eq!(
+      resolver
+        .resolve("self", &from, SpecifierType::Esm)
+        .result
+        .unwrap()
+        .0,
+      Resolution::Path(root.join("node_modules/self/index.js").into())
+    );
+
+    assert_eq!(
+      resolver.resolve("a", &from, SpecifierType::Esm).result,
+      Err(ResolverError::AliasCycle {
+        chain: vec!["a".into(), "b".into(), "c".into(), "a".into()]
+      })
+    );
+
+    let resolver = ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+      .limits(ResolverLimits {
+        max_alias_depth: 1,
+        ..Default::default()
+      })
+      .build()
+      .unwrap();
+    assert_eq!(
+      resolver
+        .resolve("old-pkg", &from, SpecifierType::Esm)
+        .result,
+      Err(ResolverError::LimitExceeded {
+        limit: Limit::AliasDepth,
+        max: 1
+      })
+    );
+  }
+
+  #[test]
+  fn test_allow_directory_resolution() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/fonts/a.woff", ""),
+      ("/app/lib/index.js", ""),
+      (
+        "/app/node_modules/pkg/package.json",
+        r#"{"main": "lib/index.js"}"#,
+      ),
+      ("/app/node_modules/pkg/lib/index.js", ""),
+      ("/app/foo.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let from = root.join("foo.js");
+
+    let cache = Cache::new(fs);
+    let resolver = Resolver::parcel(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache));
+    assert_eq!(
+      resolver
+        .resolve("./lib", &from, SpecifierType::Esm)
+        .result
+        .map(|r| r.0),
+      Ok(Resolution::Path(root.join("lib/index.js").into()))
+    );
+
+    let resolver = ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+      .allow_directory_resolution(true)
+      .build()
+      .unwrap();
+    let resolve = |specifier, specifier_type| {
+      resolver
+        .resolve(specifier, &from, specifier_type)
+        .result
+        .map(|r| r.0)
+    };
+    assert_eq!(
+      resolve("./fonts/", SpecifierType::Url),
+      Ok(Resolution::Directory(root.join("fonts").into()))
+    );
+    assert_eq!(
+      resolve("./lib", SpecifierType::Esm),
+      Ok(Resolution::Directory(root.join("lib").into()))
+    );
+    assert_eq!(
+      resolve("pkg/lib/", SpecifierType::Esm),
+      Ok(Resolution::Directory(
+        root.join("node_modules/pkg/lib").into()
+      ))
+    );
+    // Files and package entries are unaffected.
+    assert_eq!(
+      resolve("./lib/index", SpecifierType::Esm),
+      Ok(Resolution::Path(root.join("lib/index.js").into()))
+    );
+    assert_eq!(
+      resolve("pkg", SpecifierType::Esm),
+      Ok(Resolution::Path(
+        root.join("node_modules/pkg/lib/index.js").into()
+      ))
+    );
+    assert!(resolve("./missing/", SpecifierType::Url).is_err());
+
+    // Only the directory itself is tracked, not its contents.
+    let res = resolver.resolve("./fonts/", &from, SpecifierType::Url);
+    let fonts = root.join("fonts");
+    let changed = res.invalidations.invalidate_on_file_change.read().unwrap();
+    assert!(changed.iter().any(|p| **p == *fonts));
+    assert!(!changed
+      .iter()
+      .any(|p| p.starts_with(&fonts) && **p != *fonts));
+  }
+
+  #[test]
+  fn test_main_directory() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/foo.js", ""),
+      (
+        "/app/node_modules/with-index/package.json",
+        r#"{"main": "lib"}"#,
+      ),
+      ("/app/node_modules/with-index/lib/index.js", ""),
+      (
+        "/app/node_modules/with-package/package.json",
+        r#"{"main": "lib"}"#,
+      ),
+      (
+        "/app/node_modules/with-package/lib/package.json",
+        r#"{"main": "entry.js"}"#,
+      ),
+      ("/app/node_modules/with-package/lib/entry.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let from = root.join("foo.js");
+
+    let cache = Cache::new(fs);
+    let resolve = |resolver: &Resolver<MemoryFileSystem>, specifier, specifier_type| {
+      resolver
+        .resolve(specifier, &from, specifier_type)
+        .result
+        .map(|r| r.0)
+    };
+    let parcel = Resolver::parcel(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache));
+    let node_esm = Resolver::node_esm(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache));
+    let with_index = Resolution::Path(root.join("node_modules/with-index/lib/index.js").into());
+
+    assert_eq!(
+      resolve(&parcel, "with-index", SpecifierType::Esm),
+      Ok(with_index.clone())
+    );
+    assert_eq!(
+      resolve(&node_esm, "with-index", SpecifierType::Esm),
+      Ok(with_index)
+    );
+
+    // Loading a directory consults its package.json, but Node's legacy main resolution doesn't.
+    assert_eq!(
+      resolve(&parcel, "with-package", SpecifierType::Esm),
+      Ok(Resolution::Path(
+        root.join("node_modules/with-package/lib/entry.js").into()
+      ))
+    );
+    assert!(matches!(
+      resolve(&node_esm, "with-package", SpecifierType::Esm),
+      Err(ResolverError::ModuleEntryNotFound { field: "main", .. })
+    ));
+
+    // Directory imports are still unsupported in Node ESM.
+    assert!(resolve(&node_esm, "with-index/lib", SpecifierType::Esm).is_err());
+  }
+
+  #[test]
+  fn test_module_type_extensions() {
+    let fs = MemoryFileSystem::from_files([("/app/foo.js", "")]);
+    let root = PathBuf::from("/app");
+    for (name, module_type) in [
+      ("esm", r#""type": "module","#),
+      ("cjs", r#""type": "commonjs","#),
+      ("untyped", ""),
+    ] {
+      let pkg = root.join("node_modules").join(name);
+      fs.write_file(
+        pkg.join("package.json"),
+        format!(
+          r#"{{"name": "{}", {} "exports": {{"./*": "./src/*"}}}}"#,
+          name, module_type
+        ),
+      );
+      fs.write_file(pkg.join("src/util.mjs"), "");
+      fs.write_file(pkg.join("src/util.cjs"), "");
+    }
+    let from = root.join("foo.js");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier| {
+      resolver
+        .resolve(specifier, &from, SpecifierType::Esm)
+        .result
+        .map(|r| r.0)
+    };
+    let path = |p: &str| Resolution::Path(root.join("node_modules").join(p).into());
+
+    // The same layout resolves to a different file depending on the package's "type".
+    assert_eq!(resolve("esm/util"), Ok(path("esm/src/util.mjs")));
+    assert_eq!(resolve("cjs/util"), Ok(path("cjs/src/util.cjs")));
+    // Without a "type", the configured order is used.
+    assert_eq!(resolve("untyped/util"), Ok(path("untyped/src/util.mjs")));
+
+    // The candidates show the order that was chosen.
+    let candidates = resolver.resolve_candidates("cjs/util", &from, SpecifierType::Esm);
+    let cjs = root.join("node_modules/cjs/src/util.cjs");
+    assert!(candidates.contains(&cjs));
+    assert!(!candidates.contains(&root.join("node_modules/cjs/src/util.mjs")));
+  }
+
+  #[test]
+  fn test_symlink_mode() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/index.js", ""),
+      ("/app/src/real/util.js", ""),
+      // A pnpm-style package, linked from the store.
+      (
+        "/app/node_modules/.pnpm/foo@1.0.0/node_modules/foo/package.json",
+        "{}",
+      ),
+      (
+        "/app/node_modules/.pnpm/foo@1.0.0/node_modules/foo/index.js",
+        "",
+      ),
+      // A linked workspace package.
+      ("/app/packages/lib/package.json", "{}"),
+      ("/app/packages/lib/index.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    fs.symlink("real", root.join("src/linked"));
+    fs.symlink(
+      ".pnpm/foo@1.0.0/node_modules/foo",
+      root.join("node_modules/foo"),
+    );
+    fs.symlink("../packages/lib", root.join("node_modules/lib"));
+    let from = root.join("index.js");
+
+    let cache = Cache::new(fs);
+    let build = |mode| {
+      ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+        .symlink_mode(mode)
+        .resolution_cache(true)
+        .build()
+        .unwrap()
+    };
+    let resolve = |resolver: &Resolver<MemoryFileSystem>, specifier| {
+      resolver
+        .resolve(specifier, &from, SpecifierType::Esm)
+        .result
+        .map(|r| r.0)
+    };
+    let path = |p: &str| Ok(Resolution::Path(root.join(p).into()));
+
+    let pnpm = "node_modules/.pnpm/foo@1.0.0/node_modules/foo/index.js";
+    let cases = [
+      (
+        SymlinkMode::CanonicalizeAll,
+        ["src/real/util.js", pnpm, "packages/lib/index.js"],
+      ),
+      (
+        SymlinkMode::PreserveAll,
+        [
+          "src/linked/util.js",
+          "node_modules/foo/index.js",
+          "node_modules/lib/index.js",
+        ],
+      ),
+      (
+        SymlinkMode::CanonicalizeProjectOnly,
+        [
+          "src/real/util.js",
+          "node_modules/foo/index.js",
+          "node_modules/lib/index.js",
+        ],
+      ),
+      (
+        SymlinkMode::PreserveNodeModulesOnly,
+        [
+          "src/real/util.js",
+          "node_modules/foo/index.js",
+          "packages/lib/index.js",
+        ],
+      ),
+    ];
+    for (mode, [util, foo, lib]) in cases {
+      let resolver = build(mode);
+      assert_eq!(
+        resolve(&resolver, "./src/linked/util.js"),
+        path(util),
+        "{:?}",
+        mode
+      );
+      assert_eq!(resolve(&resolver, "foo"), path(foo), "{:?}", mode);
+      assert_eq!(resolve(&resolver, "lib"), path(lib), "{:?}", mode);
+    }
+  }
+
+  #[test]
+  fn test_types_context() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/node_modules/typed/package.json",
+        r#"{
+          "name": "typed",
+          "exports": {
+            ".": {
+              "types": "./types/index.d.ts",
+              "import": "./esm/index.mjs",
+              "default": "./cjs/index.js"
+            },
+            "./utils": {"import": "./esm/utils.mjs", "default": "./cjs/utils.cjs"}
+          }
+        }"#,
+      ),
+      ("/app/node_modules/typed/types/index.d.ts", ""),
+      ("/app/node_modules/typed/esm/index.mjs", ""),
+      ("/app/node_modules/typed/esm/utils.mjs", ""),
+      ("/app/node_modules/typed/esm/utils.d.mts", ""),
+      ("/app/node_modules/typed/cjs/index.js", ""),
+      ("/app/node_modules/typed/cjs/utils.cjs", ""),
+      ("/app/node_modules/typed/cjs/utils.d.cts", ""),
+      (
+        "/app/node_modules/legacy/package.json",
+        r#"{"name": "legacy", "main": "./lib/main.js", "typings": "./lib/typings.d.ts"}"#,
+      ),
+      ("/app/node_modules/legacy/lib/main.js", ""),
+      ("/app/node_modules/legacy/lib/typings.d.ts", ""),
+      (
+        "/app/node_modules/untyped/package.json",
+        r#"{"name": "untyped", "main": "./index.js"}"#,
+      ),
+      ("/app/node_modules/untyped/index.js", ""),
+      ("/app/src/index.ts", ""),
+      ("/app/src/utils.js", ""),
+      ("/app/src/utils.d.ts", ""),
+      ("/app/src/source.ts", ""),
+    ]);
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(Path::new("/app")),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier, specifier_type, context| {
+      let options = ResolveOptions {
+        context,
+        ..Default::default()
+      };
+      resolver
+        .resolve_with_options(
+          specifier,
+          Path::new("/app/src/index.ts"),
+          specifier_type,
+          options,
+        )
+        .result
+        .unwrap()
+        .0
+    };
+    let types =
+      |specifier, specifier_type| resolve(specifier, specifier_type, ResolutionContext::Types);
+    let path = |path: &str| Resolution::Path(PathBuf::from(path).into());
+
+    // The "types" condition is only active for type lookups.
+    assert_eq!(
+      resolve("typed", SpecifierType::Esm, ResolutionContext::Default),
+      path("/app/node_modules/typed/esm/index.mjs")
+    );
+    assert_eq!(
+      types("typed", SpecifierType::Esm),
+      path("/app/node_modules/typed/types/index.d.ts")
+    );
+    assert_eq!(
+      types("typed", SpecifierType::Cjs),
+      path("/app/node_modules/typed/types/index.d.ts")
+    );
+
+    // Without a "types" condition, the declaration file next to the target is preferred.
+    assert_eq!(
+      resolve(
+        "typed/utils",
+        SpecifierType::Esm,
+        ResolutionContext::Default
+      ),
+      path("/app/node_modules/typed/esm/utils.mjs")
+    );
+    assert_eq!(
+      types("typed/utils", SpecifierType::Esm),
+      path("/app/node_modules/typed/esm/utils.d.mts")
+    );
+    assert_eq!(
+      types("typed/utils", SpecifierType::Cjs),
+      path("/app/node_modules/typed/cjs/utils.d.cts")
+    );
+
+    // Without "exports", the "types" or "typings" field comes first.
+    assert_eq!(
+      resolve("legacy", SpecifierType::Esm, ResolutionContext::Default),
+      path("/app/node_modules/legacy/lib/main.js")
+    );
+    assert_eq!(
+      types("legacy", SpecifierType::Esm),
+      path("/app/node_modules/legacy/lib/typings.d.ts")
+    );
+    // Packages without declarations fall back to the usual rules.
+    assert_eq!(
+      types("untyped", SpecifierType::Esm),
+      path("/app/node_modules/untyped/index.js")
+    );
+
+    // Relative specifiers prefer declaration files too, and TypeScript sources are used as is.
+    assert_eq!(
+      resolve("./utils.js", SpecifierType::Esm, ResolutionContext::Default),
+      path("/app/src/utils.js")
+    );
+    assert_eq!(
+      types("./utils.js", SpecifierType::Esm),
+      path("/app/src/utils.d.ts")
+    );
+    assert_eq!(
+      types("./utils", SpecifierType::Esm),
+      path("/app/src/utils.d.ts")
+    );
+    assert_eq!(
+      types("./source", SpecifierType::Esm),
+      path("/app/src/source.ts")
+    );
+  }
+
+  #[test]
+  fn test_module_origin() -> Result<(), Box<dyn std::error::Error>> {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/index.js", ""),
+      ("/app/src/real/util.js", ""),
+      // A pnpm-style package, linked from the store.
+      (
+        "/app/node_modules/.pnpm/foo@1.0.0/node_modules/foo/package.json",
+        "{}",
+      ),
+      (
+        "/app/node_modules/.pnpm/foo@1.0.0/node_modules/foo/index.js",
+        "",
+      ),
+      // A package linked from a global store outside the project.
+      ("/store/pkg@1.0.0/package.json", "{}"),
+      ("/store/pkg@1.0.0/index.js", ""),
+      // A linked workspace package, whose path in node_modules looks like a dependency.
+      ("/app/packages/lib/package.json", "{}"),
+      ("/app/packages/lib/index.js", ""),
+      ("/app/node_modules/@scope/bar/package.json", "{}"),
+      ("/app/node_modules/@scope/bar/index.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    fs.symlink("real", root.join("src/linked"));
+    fs.symlink(
+      ".pnpm/foo@1.0.0/node_modules/foo",
+      root.join("node_modules/foo"),
+    );
+    fs.symlink("../../store/pkg@1.0.0", root.join("node_modules/pkg"));
+    fs.symlink("../packages/lib", root.join("node_modules/lib"));
+    let from = root.join("index.js");
+
+    let cache = Cache::new(fs);
+    let build = |mode| {
+      ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+        .externals(["react"])
+        .symlink_mode(mode)
+        .build()
+        .unwrap()
+    };
+    let dependency = |name: &str| {
+      Some(ModuleOrigin::NodeModules {
+        package_name: name.into(),
+      })
+    };
+    let cases = [
+      ("./src/linked/util.js", Some(ModuleOrigin::Project)),
+      ("foo", dependency("foo")),
+      ("pkg", dependency("pkg")),
+      ("lib", Some(ModuleOrigin::Project)),
+      ("@scope/bar", dependency("@scope/bar")),
+      ("fs", Some(ModuleOrigin::Builtin)),
+      ("react", Some(ModuleOrigin::External)),
+      ("missing", None),
+    ];
+
+    // The origin doesn't depend on whether the returned path is canonical.
+    for mode in [
+      SymlinkMode::CanonicalizeAll,
+      SymlinkMode::PreserveAll,
+      SymlinkMode::CanonicalizeProjectOnly,
+      SymlinkMode::PreserveNodeModulesOnly,
+    ] {
+      let resolver = build(mode);
+      for (specifier, origin) in &cases {
+        let res = resolver.resolve(specifier, &from, SpecifierType::Esm);
+        assert_eq!(&res.origin, origin, "{} {:?}", specifier, mode);
+      }
+    }
+
+    assert_eq!(
+      serde_json::to_value(dependency("@scope/bar"))?,
+      serde_json::json!({ "type": "NodeModules", "packageName": "@scope/bar" })
+    );
+    assert_eq!(
+      serde_json::to_value(ModuleOrigin::Project)?,
+      serde_json::json!({ "type": "Project" })
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_custom_entry_fields() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/index.js", ""),
+      (
+        "/app/node_modules/ui/package.json",
+        r#"{"main": "dist/index.js", "svelte": "src/index.js"}"#,
+      ),
+      ("/app/node_modules/ui/dist/index.js", ""),
+      ("/app/node_modules/ui/src/index.js", ""),
+      ("/app/node_modules/ui/src/Button.svelte", ""),
+      (
+        "/app/node_modules/theme/package.json",
+        r#"{"main": "index.js", "sass": "scss/_index.scss", "svelte": "src/missing.js"}"#,
+      ),
+      ("/app/node_modules/theme/index.js", ""),
+      ("/app/node_modules/theme/scss/_index.scss", ""),
+      (
+        "/app/node_modules/native/package.json",
+        r#"{"main": "index.js", "react-native": {"./lib/fs.js": "./lib/fs-native.js", "jsdom": false}}"#,
+      ),
+      ("/app/node_modules/native/index.js", ""),
+      ("/app/node_modules/native/lib/fs.js", ""),
+      ("/app/node_modules/native/lib/fs-native.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let from = root.join("index.js");
+
+    let cache = Cache::new(fs);
+    let build = |fields: &[&str]| {
+      ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+        .custom_entry_fields(fields.iter().copied())
+        .build()
+        .unwrap()
+    };
+    let resolver = build(&[]);
+    let resolve = |resolver: &Resolver<_>, specifier, from: &Path| {
+      resolver
+        .resolve(specifier, from, SpecifierType::Esm)
+        .result
+        .map(|r| r.0)
+    };
+    let path = |p: &str| Ok(Resolution::Path(root.join(p).into()));
+
+    assert_eq!(
+      resolve(&resolver, "ui", &from),
+      path("node_modules/ui/dist/index.js")
+    );
+    assert_eq!(
+      resolve(&resolver, "theme", &from),
+      path("node_modules/theme/index.js")
+    );
+
+    let resolver = build(&["svelte", "sass"]);
+    assert_eq!(
+      resolve(&resolver, "ui", &from),
+      path("node_modules/ui/src/index.js")
+    );
+    // A missing custom entry falls through to the next field.
+    assert_eq!(
+      resolve(&resolver, "theme", &from),
+      path("node_modules/theme/scss/_index.scss")
+    );
+    // Subpaths are unaffected.
+    assert_eq!(
+      resolve(&resolver, "ui/src/Button.svelte", &from),
+      path("node_modules/ui/src/Button.svelte")
+    );
+
+    // The object form remaps files and packages, like the "browser" field.
+    let native_from = root.join("node_modules/native/index.js");
+    assert_eq!(
+      resolve(&resolver, "./lib/fs.js", &native_from),
+      path("node_modules/native/lib/fs.js")
+    );
+    let resolver = build(&["react-native"]);
+    assert_eq!(
+      resolve(&resolver, "./lib/fs.js", &native_from),
+      path("node_modules/native/lib/fs-native.js")
+    );
+    assert_eq!(
+      resolve(&resolver, "jsdom", &native_from),
+      Ok(Resolution::Empty)
+    );
+  }
+
+  #[test]
+  fn test_browser_field_mappings() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/package.json",
+        r#"{
+          "name": "app",
+          "browser": {
+            "./server.js": "./client.js",
+            "./lib/db.js": "./lib/db-browser.js",
+            "./lib/db-browser.js": "./lib/db-idb.js",
+            "./legacy.js": false,
+            "module-a": "module-b",
+            "module-c": false
+          }
+        }"#,
+      ),
+      (
+        "/app/node_modules/module-b/package.json",
+        r#"{"name": "module-b", "browser": {"./index.js": "./browser.js"}}"#,
+      ),
+    ]);
+    let root = PathBuf::from("/app");
+    for file in [
+      "index.js",
+      "server.js",
+      "client.js",
+      "legacy.js",
+      "lib/index.js",
+      "lib/db.js",
+      "lib/db-browser.js",
+      "lib/db-idb.js",
+      "node_modules/module-a/index.js",
+      "node_modules/module-b/index.js",
+      "node_modules/module-b/browser.js",
+      "node_modules/module-b/sub.js",
+      "node_modules/module-c/index.js",
+    ] {
+      fs.write_file(root.join(file), "");
+    }
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier, from: &str| {
+      resolver
+        .resolve(specifier, &root.join(from), SpecifierType::Esm)
+        .result
+        .map(|r| r.0)
+    };
+    let path = |p: &str| Ok(Resolution::Path(root.join(p).into()));
+
+    // Relative specifiers are mapped after resolving them against the importing file, so keys
+    // are matched relative to the package root wherever the import is.
+    assert_eq!(resolve("./server.js", "index.js"), path("client.js"));
+    assert_eq!(resolve("../server.js", "lib/index.js"), path("client.js"));
+    assert_eq!(resolve("./server", "index.js"), path("client.js"));
+    // A mapped target that is itself mapped is followed.
+    assert_eq!(resolve("./db.js", "lib/index.js"), path("lib/db-idb.js"));
+    assert_eq!(resolve("./legacy.js", "index.js"), Ok(Resolution::Empty));
+
+    // Package specifiers are mapped before node_modules is searched, and the target is resolved
+    // from scratch, including its own "browser" field.
+    assert_eq!(
+      resolve("module-a", "index.js"),
+      path("node_modules/module-b/browser.js")
+    );
+    assert_eq!(
+      resolve("module-a/sub.js", "index.js"),
+      path("node_modules/module-b/sub.js")
+    );
+    assert_eq!(resolve("module-c", "index.js"), Ok(Resolution::Empty));
+    // The mappings only apply within the package that declares them.
+    assert_eq!(
+      resolve("module-a", "node_modules/module-b/index.js"),
+      path("node_modules/module-a/index.js")
+    );
+    assert_eq!(
+      resolve("module-c", "node_modules/module-b/index.js"),
+      path("node_modules/module-c/index.js")
+    );
+  }
+
+  #[test]
+  fn test_resolution_loop() {
+    // The root aliases a package to one of its files, and the package's "browser" field maps
+    // that file back to the package. Each hop is a separate resolution, so this used to recurse
+    // until the stack overflowed.
+    let fs = MemoryFileSystem::from_files([
+      ("/app/index.js", ""),
+      (
+        "/app/package.json",
+        r#"{"name": "app", "alias": {"self-pkg": "./node_modules/self-pkg/lib.js"}}"#,
+      ),
+      (
+        "/app/node_modules/self-pkg/package.json",
+        r##"{
+          "name": "self-pkg",
+          "browser": {"./lib.js": "self-pkg"},
+          "imports": {"#internal": "self-pkg"}
+        }"##,
+      ),
+      ("/app/node_modules/self-pkg/index.js", ""),
+      ("/app/node_modules/self-pkg/lib.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let cache = Cache::new(fs);
+    let resolver = Resolver::parcel(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache));
+    let step = |manifest: &str, key: &str, target: &str| ResolutionStep {
+      manifest: root.join(manifest),
+      key: key.into(),
+      target: target.into(),
+    };
+    let alias = step("package.json", "self-pkg", "node_modules/self-pkg/lib.js");
+    let browser = step("node_modules/self-pkg/package.json", "lib.js", "self-pkg");
+
+    let res = resolver.resolve("self-pkg", &root.join("index.js"), SpecifierType::Esm);
+    assert_eq!(
+      res.result.map(|r| r.0),
+      Err(ResolverError::ResolutionLoop {
+        steps: vec![alias.clone(), browser.clone(), alias.clone()]
+      })
+    );
+
+    // The same loop, entered through the package's own "imports" field.
+    let res = resolver.resolve(
+      "#internal",
+      &root.join("node_modules/self-pkg/index.js"),
+      SpecifierType::Esm,
+    );
+    assert_eq!(
+      res.result.map(|r| r.0),
+      Err(ResolverError::ResolutionLoop {
+        steps: vec![alias.clone(), browser, alias]
+      })
+    );
+
+    // Nested mappings are bounded separately from alias chains.
+    let resolver = ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+      .limits(ResolverLimits {
+        max_redirect_depth: 1,
+        ..Default::default()
+      })
+      .build()
+      .unwrap();
+    assert_eq!(
+      resolver
+        .resolve("self-pkg", &root.join("index.js"), SpecifierType::Esm)
+        .result,
+      Err(ResolverError::LimitExceeded {
+        limit: Limit::RedirectDepth,
+        max: 1
+      })
+    );
+  }
+
+  #[test]
+  fn test_tsconfigs_used() {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/tsconfig.json",
+        r#"{"compilerOptions": {"baseUrl": ".", "paths": {"@lib/*": ["lib/*"]}}}"#,
+      ),
+      ("/app/lib/util.ts", ""),
+      // The inner tsconfig has no paths of its own.
+      (
+        "/app/packages/app/tsconfig.json",
+        r#"{"extends": "../../tsconfig.json", "compilerOptions": {"strict": true}}"#,
+      ),
+      ("/app/packages/app/src/index.ts", ""),
+      ("/app/packages/app/src/local.ts", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let from = root.join("packages/app/src/index.ts");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let inner = root.join("packages/app/tsconfig.json");
+    let outer = root.join("tsconfig.json");
+
+    // Resolve twice, since extended tsconfigs are cached after the first read.
+    for _ in 0..2 {
+      let res = resolver.resolve("@lib/util", &from, SpecifierType::Esm);
+      assert_eq!(
+        res.result.map(|r| r.0),
+        Ok(Resolution::Path(root.join("lib/util.ts").into()))
+      );
+      assert_eq!(
+        res.tsconfigs_used,
+        vec![
+          TsConfigUsage {
+            path: inner.clone(),
+            affected_result: false,
+          },
+          TsConfigUsage {
+            path: outer.clone(),
+            affected_result: true,
+          },
+        ]
+      );
+
+      let changed = res.invalidations.invalidate_on_file_change.read().unwrap();
+      assert!(changed.iter().any(|p| **p == *inner));
+      assert!(changed.iter().any(|p| **p == *outer));
+      // A tsconfig.json added next to the importer would take precedence.
+      let created = res.invalidations.invalidate_on_file_create.read().unwrap();
+      assert!(created.contains(&FileCreateInvalidation::FileName {
+        file_name: "tsconfig.json".into(),
+        above: root.join("packages/app/src").into(),
+      }));
+    }
+
+    // Used, but without paths affecting the result.
+    let res = resolver.resolve("lodash", &from, SpecifierType::Esm);
+    assert!(res.result.is_err());
+    assert!(res
+      .tsconfigs_used
+      .iter()
+      .all(|usage| !usage.affected_result));
+  }
+
+  #[test]
+  fn test_sass_context() -> Result<(), Box<dyn std::error::Error>> {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/styles/main.scss", ""),
+      ("/app/styles/_theme.scss", ""),
+      (
+        "/app/node_modules/bootstrap/package.json",
+        r#"{"main": "dist/js/bootstrap.js", "style": "dist/css/bootstrap.css", "sass": "scss/bootstrap.scss"}"#,
+      ),
+      ("/app/node_modules/bootstrap/dist/js/bootstrap.js", ""),
+      ("/app/node_modules/bootstrap/dist/css/bootstrap.css", ""),
+      ("/app/node_modules/bootstrap/scss/bootstrap.scss", ""),
+      ("/app/node_modules/bootstrap/scss/_functions.scss", ""),
+      ("/app/node_modules/bootstrap/scss/mixins.scss", ""),
+      ("/app/node_modules/bootstrap/scss/_mixins.scss", ""),
+      ("/app/node_modules/bootstrap/scss/forms/_index.scss", ""),
+      (
+        "/app/node_modules/plain-css/package.json",
+        r#"{"main": "index.js", "style": "index.css"}"#,
+      ),
+      ("/app/node_modules/plain-css/index.js", ""),
+      ("/app/node_modules/plain-css/index.css", ""),
+      (
+        "/app/node_modules/exported/package.json",
+        r#"{"exports": {".": {"sass": "./_index.scss", "default": "./index.js"}}}"#,
+      ),
+      ("/app/node_modules/exported/index.js", ""),
+      ("/app/node_modules/exported/_index.scss", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let bootstrap = root.join("node_modules/bootstrap");
+    let from = root.join("styles/main.scss");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier, context| {
+      let options = ResolveOptions {
+        context,
+        ..Default::default()
+      };
+      resolver
+        .resolve_with_options(specifier, &from, SpecifierType::Esm, options)
+        .result
+        .map(|(resolution, _)| resolution)
+    };
+    let sass = |specifier| resolve(specifier, ResolutionContext::Sass);
+
+    // Package entries come from "sass", then "style", then "main".
+    assert_eq!(
+      sass("bootstrap")?,
+      Resolution::Path(bootstrap.join("scss/bootstrap.scss").into())
+    );
+    assert_eq!(
+      resolve("bootstrap", ResolutionContext::Default)?,
+      Resolution::Path(bootstrap.join("dist/js/bootstrap.js").into())
+    );
+    assert_eq!(
+      sass("plain-css")?,
+      Resolution::Path(root.join("node_modules/plain-css/index.css").into())
+    );
+
+    // Partials are found with a leading underscore, but a non-partial takes precedence.
+    assert_eq!(
+      sass("bootstrap/scss/functions")?,
+      Resolution::Path(bootstrap.join("scss/_functions.scss").into())
+    );
+    assert_eq!(
+      sass("bootstrap/scss/mixins")?,
+      Resolution::Path(bootstrap.join("scss/mixins.scss").into())
+    );
+    assert_eq!(
+      sass("bootstrap/scss/_mixins")?,
+      Resolution::Path(bootstrap.join("scss/_mixins.scss").into())
+    );
+    assert_eq!(
+      sass("bootstrap/scss/forms")?,
+      Resolution::Path(bootstrap.join("scss/forms/_index.scss").into())
+    );
+
+    // The "sass" condition applies to exports.
+    assert_eq!(
+      sass("exported")?,
+      Resolution::Path(root.join("node_modules/exported/_index.scss").into())
+    );
+    assert_eq!(
+      resolve("exported", ResolutionContext::Default)?,
+      Resolution::Path(root.join("node_modules/exported/index.js").into())
+    );
+
+    // Bare specifiers load files relative to the importer first.
+    assert_eq!(
+      sass("theme")?,
+      Resolution::Path(root.join("styles/_theme.scss").into())
+    );
+    assert!(resolve("theme", ResolutionContext::Default).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_find_package_root() -> Result<(), Box<dyn std::error::Error>> {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/package.json", r#"{"name": "monorepo"}"#),
+      ("/app/yarn.lock", ""),
+      ("/app/packages/a/package.json", r#"{"name": "a"}"#),
+      ("/app/packages/a/.git/HEAD", ""),
+      ("/app/packages/a/dist/package.json", r#"{"type": "module"}"#),
+      ("/app/packages/a/dist/esm/index.js", ""),
+      ("/app/node_modules/dep/package.json", r#"{"name": "dep"}"#),
+      ("/app/node_modules/dep/lib/index.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+    let cache = Cache::new(fs);
+    let a = root.join("packages/a");
+    let dep = root.join("node_modules/dep");
+
+    let resolver = Resolver::parcel(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache));
+
+    // Nameless package.json files are skipped.
+    let invalidations = Invalidations::new(resolver.cache.interner().clone());
+    let package_root = resolver
+      .find_package_root(&a.join("dist/esm/index.js"), &invalidations)?
+      .unwrap();
+    assert_eq!(package_root.manifest, a.join("package.json"));
+    assert_eq!(package_root.package.name, "a");
+    assert!(!package_root.in_node_modules);
+    // Creating a package.json closer to the file, or naming the skipped one, changes the result.
+    assert!(invalidations
+      .invalidate_on_file_create
+      .read()
+      .unwrap()
+      .contains(&FileCreateInvalidation::FileName {
+        file_name: "package.json".into(),
+        above: resolver.intern(&a.join("dist/esm")),
+      }));
+    assert!(invalidations
+      .invalidate_on_file_change
+      .read()
+      .unwrap()
+      .contains(&resolver.intern(&a.join("dist/package.json"))));
+
+    let invalidations = Invalidations::new(resolver.cache.interner().clone());
+    let package_root = resolver
+      .find_package_root(&dep.join("lib/index.js"), &invalidations)?
+      .unwrap();
+    assert_eq!(package_root.manifest, dep.join("package.json"));
+    assert!(package_root.in_node_modules);
+
+    // Directories are searched themselves.
+    let package_root = resolver.find_package_root(&root, &invalidations)?.unwrap();
+    assert_eq!(package_root.package.name, "monorepo");
+
+    // The nearest lockfile or .git wins over package.json files.
+    assert_eq!(
+      resolver.find_project_root(&a.join("dist/esm/index.js"), &invalidations)?,
+      Some(a.clone())
+    );
+    assert_eq!(
+      resolver.find_project_root(&dep.join("lib/index.js"), &invalidations)?,
+      Some(root.clone())
+    );
+
+    // Without either, the outermost package.json is the root.
+    cache.fs.remove_file(root.join("yarn.lock"))?;
+    cache.invalidate_path(&root.join("yarn.lock"));
+    assert_eq!(
+      resolver.find_project_root(&dep.join("lib/index.js"), &invalidations)?,
+      Some(root.clone())
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_native_addons() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/addon.node", ""),
+      ("/app/other.node.js", ""),
+      ("/app/index.js", ""),
+      (
+        "/app/node_modules/native/package.json",
+        r#"{"name": "native", "main": "build/Release/binding.node"}"#,
+      ),
+      ("/app/node_modules/native/build/Release/binding.node", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve =
+      |specifier| resolver.resolve(specifier, &root.join("index.js"), SpecifierType::Cjs);
+
+    let res = resolve("./addon.node");
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root.join("addon.node").into())
+    );
+    assert!(res.native_addon);
+
+    let res = resolve("native");
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(
+        root
+          .join("node_modules/native/build/Release/binding.node")
+          .into()
+      )
+    );
+    assert!(res.native_addon);
+
+    // Extensions are not appended to `.node` specifiers.
+    assert!(resolve("./other.node").result.is_err());
+    assert!(!resolve("./index.js").native_addon);
+  }
+
+  /// Runs the cases in the `node-esm` fixture against `Resolver::node_esm`. The expected outcomes,
+  /// including error codes, were checked against Node 20 by importing each specifier from the
+  /// fixture root. They are not Node's own resolver tests: vendoring the fixtures from Node's
+  /// `test/es-module` and the WPT import-maps suite is still to do. Those cases can be added to
+  /// `cases.json` in the same format, with their files under the fixture root.
+  /// Each divergence is reported.
+  #[test]
+  fn test_node_esm_cases() -> Result<(), Box<dyn std::error::Error>> {
+    #[derive(serde::Deserialize)]
+    struct Case {
+      specifier: String,
+      resolved: Option<String>,
+      builtin: Option<String>,
+      code: Option<String>,
+    }
+
+    let root = root().join("node-esm");
+    let cases: Vec<Case> =
+      serde_json::from_str(&std::fs::read_to_string(root.join("cases.json"))?)?;
+    let resolver = Resolver::node_esm(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(OsFileSystem::default())),
+    );
+
+    let mut divergences = Vec::new();
+    for case in &cases {
+      let specifier = case.specifier.replace("{root}", &root.to_string_lossy());
+      let actual = match resolver
+        .resolve(&specifier, &root.join("main.js"), SpecifierType::Esm)
+        .result
+      {
+        Ok((Resolution::Path(path), _)) => format!("resolved {}", path.display()),
+        Ok((Resolution::Builtin(builtin), _)) => format!("builtin {}", builtin),
+        Ok((resolution, _)) => format!("{:?}", resolution),
+        Err(err) => format!("error {}", err.code()),
+      };
+      let expected = match (&case.resolved, &case.builtin, &case.code) {
+        (Some(path), _, _) => format!("resolved {}", root.join(path).display()),
+        (_, Some(builtin), _) => format!("builtin {}", builtin),
+        (_, _, Some(code)) => format!("error {}", code),
+        _ => panic!("case {} has no expected outcome", case.specifier),
+      };
+      if actual != expected {
+        divergences.push(format!(
+          "{}: expected {}, got {}",
+          case.specifier, expected, actual
+        ));
+      }
+    }
+
+    assert!(
+      divergences.is_empty(),
+      "diverged from Node:\n{}",
+      divergences.join("\n")
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn test_file_metadata() {
+    use std::sync::Mutex;
+
+    /// Records every file check, to make sure metadata doesn't cost an extra `stat`.
+    struct CountingFs {
+      fs: MemoryFileSystem,
+      file_checks: Mutex<Vec<PathBuf>>,
+      existence_only: bool,
+    }
+
+    impl CountingFs {
+      fn new(existence_only: bool) -> Self {
+        CountingFs {
+          fs: MemoryFileSystem::from_files([("/app/foo.js", "hello"), ("/app/index.js", "")]),
+          file_checks: Mutex::default(),
+          existence_only,
+        }
+      }
+    }
+
+    impl FileSystem for CountingFs {
+      fn canonicalize<P: AsRef<Path>>(
+        &self,
+        path: P,
+        cache: &dashmap::DashMap<PathBuf, Option<PathBuf>>,
+      ) -> std::io::Result<PathBuf> {
+        self.fs.canonicalize(path, cache)
+      }
+
+      fn read_to_string<P: AsRef<Path>>(&self, path: P) -> std::io::Result<String> {
+        self.fs.read_to_string(path)
+      }
+
+      fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.file_checks.lock().unwrap().push(path.as_ref().into());
+        self.fs.is_file(path)
+      }
+
+      fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.fs.is_dir(path)
+      }
+
+      fn file_metadata<P: AsRef<Path>>(&self, path: P) -> Option<Option<FileMetadata>> {
+        if self.existence_only {
+          return self.is_file(path).then_some(None);
+        }
+
+        self.file_checks.lock().unwrap().push(path.as_ref().into());
+        self.fs.file_metadata(path)
+      }
+    }
+
+    let root = PathBuf::from("/app");
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(CountingFs::new(false))),
+    );
+    let res = resolver.resolve("./foo", &root.join("index.js"), SpecifierType::Esm);
+    assert_eq!(
+      res.result.unwrap().0,
+      Resolution::Path(root.join("foo.js").into())
+    );
+    let metadata = res.metadata.unwrap();
+    assert_eq!(metadata.size, 5);
+    assert_eq!(metadata.mtime, None);
+
+    // Each path is checked once, and the resolved file's metadata comes from that check.
+    let checks = resolver.cache.fs.file_checks.lock().unwrap().clone();
+    let unique: HashSet<_> = checks.iter().collect();
+    assert_eq!(unique.len(), checks.len());
+    assert!(checks.contains(&root.join("foo.js")));
+
+    // A file system that only checks existence doesn't provide metadata.
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(root.as_path()),
+      CacheCow::Owned(Cache::new(CountingFs::new(true))),
+    );
+    let res = resolver.resolve("./foo", &root.join("index.js"), SpecifierType::Esm);
+    assert!(res.result.is_ok());
+    assert_eq!(res.metadata, None);
+  }
+
+  #[test]
+  fn test_enforce_own_exports() -> Result<(), Box<dyn std::error::Error>> {
+    let fs = MemoryFileSystem::from_files([
+      (
+        "/app/package.json",
+        r#"{"name": "app", "exports": {".": "./src/index.js", "./components/*": "./src/components/*.js"}}"#,
+      ),
+      ("/app/src/index.js", ""),
+      ("/app/src/internal.js", ""),
+      ("/app/src/components/Button.js", ""),
+    ]);
+    let root = PathBuf::from("/app");
+
+    let cache = Cache::new(fs);
+    let build = |enforce| {
+      ResolverBuilder::new(Cow::Borrowed(root.as_path()), CacheCow::Borrowed(&cache))
+        .enforce_own_exports(enforce)
+        .build()
+        .unwrap()
+    };
+    let resolver = build(true);
+    let from = root.join("src/index.js");
+    let resolve = |resolver: &Resolver<MemoryFileSystem>, specifier| {
+      resolver
+        .resolve(specifier, &from, SpecifierType::Esm)
+        .result
+        .map(|(resolution, _)| resolution)
+    };
+
+    // Tilde and absolute specifiers are resolved as package subpaths.
+    let button = Resolution::Path(root.join("src/components/Button.js").into());
+    assert_eq!(resolve(&resolver, "~/components/Button")?, button);
+    assert_eq!(resolve(&resolver, "/components/Button")?, button);
+    assert_eq!(
+      resolve(&resolver, "~")?,
+      Resolution::Path(from.clone().into())
+    );
+
+    // Files that are exported under another subpath suggest it.
+    assert_eq!(
+      resolve(&resolver, "~/src/components/Button.js"),
+      Err(ResolverError::OwnSubpathNotExported {
+        package: "app".into(),
+        subpath: "./src/components/Button.js".into(),
+        suggestion: Some("./components/Button".into()),
+      })
+    );
+    assert_eq!(
+      resolve(&resolver, "/src/internal"),
+      Err(ResolverError::OwnSubpathNotExported {
+        package: "app".into(),
+        subpath: "./src/internal".into(),
+        suggestion: None,
+      })
+    );
+
+    // Relative specifiers are unrestricted.
+    let internal = Resolution::Path(root.join("src/internal.js").into());
+    assert_eq!(resolve(&resolver, "./internal")?, internal);
+
+    let resolver = build(false);
+    assert_eq!(resolve(&resolver, "~/src/internal")?, internal);
+
+    Ok(())
+  }
 }
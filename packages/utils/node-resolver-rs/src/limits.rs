@@ -0,0 +1,70 @@
+use crate::ResolverError;
+
+/// A limit on the size of an input, enforced by [ResolverLimits].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Limit {
+  /// The length of a specifier, in bytes.
+  SpecifierLength,
+  /// The number of `..` segments in a relative specifier.
+  ParentSegments,
+  /// The number of keys in a package's "exports" or "imports" field, including nested
+  /// conditions and array fallbacks.
+  ExportsKeys,
+  /// The number of directories walked upward, e.g. when searching for a package.json
+  /// or node_modules directory.
+  AncestorDepth,
+  /// The number of aliases applied in a chain, e.g. `old-pkg` to `new-pkg` to a patched copy.
+  AliasDepth,
+  /// The number of aliases and "imports" mappings followed at once when they lead into each
+  /// other through nested resolutions.
+  RedirectDepth,
+}
+
+/// Limits that protect against pathological inputs (e.g. from fuzzing or malicious packages),
+/// which could otherwise make resolution very slow. Exceeding a limit fails the resolution
+/// with `ResolverError::LimitExceeded`. The defaults are far above what real projects need.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolverLimits {
+  pub max_specifier_length: usize,
+  pub max_parent_segments: usize,
+  pub max_exports_keys: usize,
+  pub max_ancestor_depth: usize,
+  pub max_alias_depth: usize,
+  pub max_redirect_depth: usize,
+}
+
+impl Default for ResolverLimits {
+  fn default() -> Self {
+    ResolverLimits {
+      max_specifier_length: 64 * 1024,
+      max_parent_segments: 256,
+      max_exports_keys: 50_000,
+      max_ancestor_depth: 1024,
+      max_alias_depth: 8,
+      max_redirect_depth: 32,
+    }
+  }
+}
+
+impl ResolverLimits {
+  pub fn max(&self, limit: Limit) -> usize {
+    match limit {
+      Limit::SpecifierLength => self.max_specifier_length,
+      Limit::ParentSegments => self.max_parent_segments,
+      Limit::ExportsKeys => self.max_exports_keys,
+      Limit::AncestorDepth => self.max_ancestor_depth,
+      Limit::AliasDepth => self.max_alias_depth,
+      Limit::RedirectDepth => self.max_redirect_depth,
+    }
+  }
+
+  pub(crate) fn check(&self, limit: Limit, value: usize) -> Result<(), ResolverError> {
+    let max = self.max(limit);
+    if value > max {
+      return Err(ResolverError::LimitExceeded { limit, max });
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,345 @@
+use std::{
+  collections::VecDeque,
+  io::{Error, ErrorKind, Result},
+  path::{Component, Path, PathBuf},
+};
+
+use dashmap::{DashMap, DashSet};
+
+use crate::{
+  fs::{FileMetadata, FileSystem},
+  path::normalize_path,
+};
+
+/// A file system that is entirely in memory, e.g. for deterministic tests or bundling sources
+/// that don't exist on disk. Directories are created implicitly for each file, and symlinks
+/// are followed like on a real file system. Files can be added while in use, but note that
+/// the resolver's `Cache` remembers which paths exist.
+#[derive(Default)]
+pub struct MemoryFileSystem {
+  files: DashMap<PathBuf, Vec<u8>>,
+  dirs: DashSet<PathBuf>,
+  symlinks: DashMap<PathBuf, PathBuf>,
+}
+
+impl MemoryFileSystem {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Creates a file system from a list of paths and their contents.
+  pub fn from_files<P: AsRef<Path>, S: Into<Vec<u8>>>(
+    files: impl IntoIterator<Item = (P, S)>,
+  ) -> Self {
+    let fs = Self::new();
+    for (path, contents) in files {
+      fs.write_file(path, contents);
+    }
+    fs
+  }
+
+  /// Creates or replaces a file, along with any missing parent directories.
+  pub fn write_file<P: AsRef<Path>, S: Into<Vec<u8>>>(&self, path: P, contents: S) {
+    let path = normalize_path(path.as_ref());
+    self.create_parent_dirs(&path);
+    self.files.insert(path, contents.into());
+  }
+
+  /// Creates a directory, along with any missing parent directories. Only needed for
+  /// directories without files, since writing a file creates its parents.
+  pub fn create_dir_all<P: AsRef<Path>>(&self, path: P) {
+    let path = normalize_path(path.as_ref());
+    self.create_parent_dirs(&path);
+    self.dirs.insert(path);
+  }
+
+  /// Creates a symlink at `link` pointing to `original`. Like `std::os::unix::fs::symlink`,
+  /// a relative `original` is resolved against the directory containing the link.
+  pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(&self, original: P, link: Q) {
+    let link = normalize_path(link.as_ref());
+    self.create_parent_dirs(&link);
+    self.symlinks.insert(link, original.as_ref().to_path_buf());
+  }
+
+  /// Removes a file. Its parent directories are kept.
+  pub fn remove_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    let path = self.resolve_links(path.as_ref(), None)?;
+    match self.files.remove(&path) {
+      Some(_) => Ok(()),
+      None => Err(not_found()),
+    }
+  }
+
+  fn create_parent_dirs(&self, path: &Path) {
+    for parent in path.ancestors().skip(1) {
+      if parent.as_os_str().is_empty() {
+        break;
+      }
+      self.dirs.insert(parent.to_path_buf());
+    }
+  }
+
+  /// Follows symlinks in each component of the path, like `path::canonicalize`.
+  /// The result is not checked for existence. Links that are followed are recorded in
+  /// `cache`, if given, so that the resolver can report them.
+  fn resolve_links(
+    &self,
+    path: &Path,
+    cache: Option<&DashMap<PathBuf, Option<PathBuf>>>,
+  ) -> Result<PathBuf> {
+    let mut ret = PathBuf::new();
+    let mut seen_links = 0;
+    let mut queue = VecDeque::new();
+
+    queue.push_back(path.to_path_buf());
+
+    while let Some(cur_path) = queue.pop_front() {
+      let mut components = cur_path.components();
+      while let Some(component) = components.next() {
+        match component {
+          Component::Prefix(_) | Component::RootDir => ret.push(component.as_os_str()),
+          Component::CurDir => {}
+          Component::ParentDir => {
+            ret.pop();
+          }
+          Component::Normal(c) => {
+            ret.push(c);
+
+            let link = match self.symlinks.get(&ret) {
+              Some(link) => link.clone(),
+              None => continue,
+            };
+            if let Some(cache) = cache {
+              cache.insert(ret.clone(), Some(link.clone()));
+            }
+
+            seen_links += 1;
+            if seen_links > 32 {
+              return Err(Error::new(ErrorKind::NotFound, "Too many symlinks"));
+            }
+
+            if link.is_absolute() {
+              ret = PathBuf::new();
+            } else {
+              ret.pop();
+            }
+
+            let remaining = components.as_path();
+            if !remaining.as_os_str().is_empty() {
+              queue.push_front(remaining.to_path_buf());
+            }
+            queue.push_front(link);
+            break;
+          }
+        }
+      }
+    }
+
+    Ok(ret)
+  }
+}
+
+fn not_found() -> Error {
+  Error::new(ErrorKind::NotFound, "File not found in memory")
+}
+
+impl FileSystem for MemoryFileSystem {
+  fn canonicalize<P: AsRef<Path>>(
+    &self,
+    path: P,
+    cache: &DashMap<PathBuf, Option<PathBuf>>,
+  ) -> Result<PathBuf> {
+    let path = self.resolve_links(path.as_ref(), Some(cache))?;
+    if self.files.contains_key(&path) || self.dirs.contains(&path) {
+      Ok(path)
+    } else {
+      Err(not_found())
+    }
+  }
+
+  fn read_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+    String::from_utf8(self.read(path)?).map_err(|err| Error::new(ErrorKind::InvalidData, err))
+  }
+
+  fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+    let path = self.resolve_links(path.as_ref(), None)?;
+    match self.files.get(&path) {
+      Some(contents) => Ok(contents.clone()),
+      None => Err(not_found()),
+    }
+  }
+
+  fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+    self
+      .resolve_links(path.as_ref(), None)
+      .is_ok_and(|path| self.files.contains_key(&path))
+  }
+
+  fn is_dir<P: AsRef<Path>>(&self, path: P) -> bool {
+    self
+      .resolve_links(path.as_ref(), None)
+      .is_ok_and(|path| self.dirs.contains(&path))
+  }
+
+  fn file_metadata<P: AsRef<Path>>(&self, path: P) -> Option<Option<FileMetadata>> {
+    let path = self.resolve_links(path.as_ref(), None).ok()?;
+    let contents = self.files.get(&path)?;
+    Some(Some(FileMetadata {
+      size: contents.len() as u64,
+      mtime: None,
+    }))
+  }
+
+  fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    let dir = self.resolve_links(path, None)?;
+    if !self.dirs.contains(&dir) {
+      return Err(not_found());
+    }
+
+    // Entries are sorted, since there is no natural order like on disk.
+    let mut entries: Vec<PathBuf> = self
+      .files
+      .iter()
+      .map(|entry| entry.key().clone())
+      .chain(self.dirs.iter().map(|entry| entry.key().clone()))
+      .chain(self.symlinks.iter().map(|entry| entry.key().clone()))
+      .filter(|entry| entry.parent() == Some(dir.as_path()))
+      .filter_map(|entry| entry.file_name().map(|name| path.join(name)))
+      .collect();
+    entries.sort();
+    entries.dedup();
+    Ok(entries)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{Cache, CacheCow, Resolution, Resolver, SpecifierType};
+  use std::borrow::Cow;
+
+  #[test]
+  fn test_memory_fs() -> Result<()> {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/src/index.js", "import 'pkg';"),
+      ("/store/pkg@1.0.0/package.json", r#"{"name": "pkg"}"#),
+      ("/store/pkg@1.0.0/index.js", "export default 1;"),
+    ]);
+    fs.symlink("../../store/pkg@1.0.0", "/app/node_modules/pkg");
+    fs.create_dir_all("/app/empty");
+
+    assert!(fs.is_file("/app/src/index.js"));
+    assert!(fs.is_file("/app/src/../src/index.js"));
+    assert!(!fs.is_file("/app/src"));
+    assert!(fs.is_dir("/app/src"));
+    assert!(fs.is_dir("/app/empty"));
+    assert!(fs.is_dir("/"));
+    assert!(!fs.is_file("/app/missing.js"));
+
+    // Symlinks are followed.
+    assert!(fs.is_dir("/app/node_modules/pkg"));
+    assert!(fs.is_file("/app/node_modules/pkg/index.js"));
+    assert_eq!(
+      fs.read_to_string("/app/node_modules/pkg/index.js")?,
+      "export default 1;"
+    );
+    let cache = DashMap::new();
+    assert_eq!(
+      fs.canonicalize("/app/node_modules/pkg/./index.js", &cache)?,
+      PathBuf::from("/store/pkg@1.0.0/index.js")
+    );
+    assert!(fs
+      .canonicalize("/app/node_modules/pkg/missing.js", &cache)
+      .is_err());
+    assert_eq!(
+      fs.file_metadata("/app/node_modules/pkg/index.js"),
+      Some(Some(FileMetadata {
+        size: 17,
+        mtime: None
+      }))
+    );
+
+    assert_eq!(
+      fs.read_dir("/app")?,
+      vec![
+        PathBuf::from("/app/empty"),
+        PathBuf::from("/app/node_modules"),
+        PathBuf::from("/app/src")
+      ]
+    );
+    assert_eq!(
+      fs.read_dir("/app/node_modules/pkg")?,
+      vec![
+        PathBuf::from("/app/node_modules/pkg/index.js"),
+        PathBuf::from("/app/node_modules/pkg/package.json")
+      ]
+    );
+
+    fs.remove_file("/app/node_modules/pkg/index.js")?;
+    assert!(!fs.is_file("/store/pkg@1.0.0/index.js"));
+    assert!(fs.remove_file("/store/pkg@1.0.0/index.js").is_err());
+
+    // Links that form a cycle are an error rather than looping forever.
+    fs.symlink("b", "/loop/a");
+    fs.symlink("a", "/loop/b");
+    assert!(!fs.is_file("/loop/a"));
+    assert!(fs.canonicalize("/loop/a", &cache).is_err());
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_resolve_in_memory() {
+    let fs = MemoryFileSystem::from_files([
+      ("/app/package.json", r#"{"name": "app"}"#),
+      ("/app/src/index.js", ""),
+      ("/app/src/utils.ts", ""),
+      (
+        "/app/node_modules/pkg/package.json",
+        r#"{"name": "pkg", "exports": {".": "./dist/index.js", "./feature": "./dist/feature.js"}}"#,
+      ),
+      ("/app/node_modules/pkg/dist/index.js", ""),
+      ("/app/node_modules/pkg/dist/feature.js", ""),
+      (
+        "/app/node_modules/legacy/package.json",
+        r#"{"name": "legacy", "main": "lib/main"}"#,
+      ),
+      ("/app/node_modules/legacy/lib/main.js", ""),
+    ]);
+
+    let resolver = Resolver::parcel(
+      Cow::Borrowed(Path::new("/app")),
+      CacheCow::Owned(Cache::new(fs)),
+    );
+    let resolve = |specifier| {
+      resolver
+        .resolve(
+          specifier,
+          Path::new("/app/src/index.js"),
+          SpecifierType::Esm,
+        )
+        .result
+        .map(|r| r.0)
+    };
+
+    assert_eq!(
+      resolve("./utils").unwrap(),
+      Resolution::Path(PathBuf::from("/app/src/utils.ts").into())
+    );
+    assert_eq!(
+      resolve("pkg").unwrap(),
+      Resolution::Path(PathBuf::from("/app/node_modules/pkg/dist/index.js").into())
+    );
+    assert_eq!(
+      resolve("pkg/feature").unwrap(),
+      Resolution::Path(PathBuf::from("/app/node_modules/pkg/dist/feature.js").into())
+    );
+    assert_eq!(
+      resolve("legacy").unwrap(),
+      Resolution::Path(PathBuf::from("/app/node_modules/legacy/lib/main.js").into())
+    );
+    assert!(resolve("pkg/missing").is_err());
+    assert!(resolve("missing").is_err());
+  }
+}
@@ -0,0 +1,99 @@
+use std::{
+  sync::atomic::{AtomicU64, Ordering},
+  time::Instant,
+};
+
+/// A phase of resolution that is tracked by the metrics collector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+  /// Parsing the specifier string.
+  Parse,
+  /// Finding and reading tsconfig.json files, including "extends".
+  TsConfig,
+  /// Reading and parsing package.json files.
+  PackageJson,
+  /// Matching the "exports" and "imports" fields.
+  Exports,
+  /// Checking whether files and directories exist.
+  Stat,
+  /// Resolving symlinks in the final path.
+  Canonicalize,
+}
+
+const PHASE_COUNT: usize = 6;
+
+/// Accumulated count and duration for a single phase.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseMetrics {
+  pub count: u64,
+  pub duration_ns: u64,
+}
+
+/// A snapshot of the metrics collected by a resolver.
+/// Phases may nest (e.g. a tsconfig "extends" lookup reads package.json files),
+/// so durations should not be summed to compute the total time.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolverMetrics {
+  pub parse: PhaseMetrics,
+  pub tsconfig: PhaseMetrics,
+  pub package_json: PhaseMetrics,
+  pub exports: PhaseMetrics,
+  pub stat: PhaseMetrics,
+  pub canonicalize: PhaseMetrics,
+}
+
+impl ResolverMetrics {
+  pub fn iter(&self) -> impl Iterator<Item = (&'static str, &PhaseMetrics)> {
+    [
+      ("parse", &self.parse),
+      ("tsconfig", &self.tsconfig),
+      ("package.json", &self.package_json),
+      ("exports", &self.exports),
+      ("stat", &self.stat),
+      ("canonicalize", &self.canonicalize),
+    ]
+    .into_iter()
+  }
+}
+
+/// Thread-safe accumulator for per-phase timings. Resolutions may run in parallel,
+/// so counters are updated atomically rather than behind a lock.
+#[derive(Default)]
+pub(crate) struct MetricsCollector {
+  counts: [AtomicU64; PHASE_COUNT],
+  durations: [AtomicU64; PHASE_COUNT],
+}
+
+impl MetricsCollector {
+  pub fn measure<T, F: FnOnce() -> T>(&self, phase: Phase, f: F) -> T {
+    let start = Instant::now();
+    let res = f();
+    let elapsed = start.elapsed().as_nanos() as u64;
+    let index = phase as usize;
+    self.counts[index].fetch_add(1, Ordering::Relaxed);
+    self.durations[index].fetch_add(elapsed, Ordering::Relaxed);
+    res
+  }
+
+  /// Returns the accumulated metrics and resets all counters.
+  pub fn take(&self) -> ResolverMetrics {
+    let take = |phase: Phase| {
+      let index = phase as usize;
+      PhaseMetrics {
+        count: self.counts[index].swap(0, Ordering::Relaxed),
+        duration_ns: self.durations[index].swap(0, Ordering::Relaxed),
+      }
+    };
+
+    ResolverMetrics {
+      parse: take(Phase::Parse),
+      tsconfig: take(Phase::TsConfig),
+      package_json: take(Phase::PackageJson),
+      exports: take(Phase::Exports),
+      stat: take(Phase::Stat),
+      canonicalize: take(Phase::Canonicalize),
+    }
+  }
+}
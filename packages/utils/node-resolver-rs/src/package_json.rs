@@ -1,18 +1,28 @@
 use bitflags::bitflags;
 use glob_match::{glob_match, glob_match_with_captures};
 use indexmap::IndexMap;
+use once_cell::sync::OnceCell;
+use percent_encoding::percent_decode_str;
 use serde::Deserialize;
+use serde_json::value::RawValue;
 use std::{
   borrow::Cow,
+  cell::RefCell,
   cmp::Ordering,
+  fmt,
+  marker::PhantomData,
   ops::Range,
   path::{Component, Path, PathBuf},
 };
 
 use crate::{
+  cache::JsonError,
+  fs::FileSystem,
   path::resolve_path,
   specifier::decode_path,
   specifier::{Specifier, SpecifierType},
+  types_versions::{self, TsVersion},
+  Flags,
 };
 
 bitflags! {
@@ -35,22 +45,46 @@ pub struct PackageJson<'a> {
   pub path: PathBuf,
   #[serde(default)]
   pub name: &'a str,
-  main: Option<&'a str>,
-  module: Option<&'a str>,
+  #[serde(default)]
+  pub version: &'a str,
+  #[serde(default, deserialize_with = "deserialize_optional_str")]
+  main: Option<Cow<'a, str>>,
+  #[serde(rename = "main:src")]
+  main_src: Option<&'a str>,
+  #[serde(default, deserialize_with = "deserialize_optional_str")]
+  module: Option<Cow<'a, str>>,
   tsconfig: Option<&'a str>,
   types: Option<&'a str>,
+  typings: Option<&'a str>,
+  #[serde(default)]
+  types_versions: IndexMap<&'a str, IndexMap<&'a str, Vec<&'a str>>>,
   #[serde(default)]
   pub source: SourceField<'a>,
   #[serde(default)]
-  browser: BrowserField<'a>,
+  browser: LazyField<'a, BrowserField<'static>>,
   #[serde(default)]
   alias: IndexMap<Specifier<'a>, AliasValue<'a>>,
   #[serde(default)]
-  exports: ExportsField<'a>,
+  exports: LazyField<'a, ExportsField<'static>>,
   #[serde(default)]
-  imports: IndexMap<ExportsKey<'a>, ExportsField<'a>>,
+  imports: LazyField<'a, ImportsField<'static>>,
   #[serde(default)]
   side_effects: SideEffects<'a>,
+  #[serde(default)]
+  bin: BinField<'a>,
+  #[serde(borrow)]
+  files: Option<Vec<&'a str>>,
+  #[serde(rename = "type")]
+  module_type: Option<&'a str>,
+  /// The original JSON, so that custom entry fields can be read on demand, and errors in
+  /// lazily parsed fields can be located.
+  #[serde(skip)]
+  raw: &'a str,
+  /// The number of keys in "exports" and "imports", counted once per manifest.
+  #[serde(skip)]
+  exports_keys: OnceCell<usize>,
+  #[serde(skip)]
+  imports_keys: OnceCell<usize>,
 }
 
 impl<'a> Default for PackageJson<'a> {
@@ -58,16 +92,26 @@ impl<'a> Default for PackageJson<'a> {
     PackageJson {
       path: Default::default(),
       name: "",
+      version: "",
       main: None,
+      main_src: None,
       module: None,
       tsconfig: None,
       types: None,
+      typings: None,
+      types_versions: Default::default(),
       source: Default::default(),
       browser: Default::default(),
       alias: Default::default(),
       exports: Default::default(),
       imports: Default::default(),
       side_effects: Default::default(),
+      bin: Default::default(),
+      files: None,
+      module_type: None,
+      raw: "",
+      exports_keys: OnceCell::new(),
+      imports_keys: OnceCell::new(),
     }
   }
 }
@@ -76,17 +120,42 @@ impl<'a> Default for PackageJson<'a> {
 #[serde(untagged)]
 pub enum BrowserField<'a> {
   None,
-  #[serde(borrow)]
-  String(&'a str),
+  #[serde(borrow, deserialize_with = "deserialize_str")]
+  String(Cow<'a, str>),
   Map(IndexMap<Specifier<'a>, AliasValue<'a>>),
 }
 
+impl<'a> BrowserField<'a> {
+  pub fn into_owned(self) -> BrowserField<'static> {
+    match self {
+      BrowserField::None => BrowserField::None,
+      BrowserField::String(browser) => BrowserField::String(Cow::Owned(browser.into_owned())),
+      BrowserField::Map(map) => BrowserField::Map(
+        map
+          .into_iter()
+          .map(|(key, value)| (key.into_owned(), value.into_owned()))
+          .collect(),
+      ),
+    }
+  }
+}
+
 impl<'a> Default for BrowserField<'a> {
   fn default() -> Self {
     BrowserField::None
   }
 }
 
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(untagged)]
+pub enum BinField<'a> {
+  #[default]
+  None,
+  #[serde(borrow)]
+  String(&'a str),
+  Map(IndexMap<&'a str, &'a str>),
+}
+
 #[derive(serde::Deserialize, Debug)]
 #[serde(untagged)]
 pub enum SourceField<'a> {
@@ -104,12 +173,10 @@ impl<'a> Default for SourceField<'a> {
   }
 }
 
-#[derive(serde::Deserialize, Debug, PartialEq)]
-#[serde(untagged)]
+#[derive(Debug, PartialEq)]
 pub enum ExportsField<'a> {
   None,
-  #[serde(borrow)]
-  String(&'a str),
+  String(Cow<'a, str>),
   Array(Vec<ExportsField<'a>>),
   Map(IndexMap<ExportsKey<'a>, ExportsField<'a>>),
 }
@@ -120,8 +187,54 @@ impl<'a> Default for ExportsField<'a> {
   }
 }
 
+/// Parsed "exports" and "imports" fields own their strings, see `LazyField`. Unlike an untagged
+/// enum, this doesn't buffer the value before trying each variant, which matters for large maps.
+impl<'de, 'a> Deserialize<'de> for ExportsField<'a> {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    deserializer.deserialize_any(ExportsFieldVisitor(PhantomData))
+  }
+}
+
+struct ExportsFieldVisitor<'a>(PhantomData<ExportsField<'a>>);
+
+impl<'de, 'a> serde::de::Visitor<'de> for ExportsFieldVisitor<'a> {
+  type Value = ExportsField<'a>;
+
+  fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str("a string, array, object, or null")
+  }
+
+  fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+    Ok(ExportsField::None)
+  }
+
+  fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+    Ok(ExportsField::String(Cow::Owned(v.to_owned())))
+  }
+
+  fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Self::Value, E> {
+    Ok(ExportsField::String(Cow::Owned(v)))
+  }
+
+  fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+    let mut targets = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+    while let Some(target) = seq.next_element()? {
+      targets.push(target);
+    }
+    Ok(ExportsField::Array(targets))
+  }
+
+  fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+    let mut targets = IndexMap::with_capacity(map.size_hint().unwrap_or(0));
+    while let Some(key) = map.next_key()? {
+      targets.insert(key, map.next_value()?);
+    }
+    Ok(ExportsField::Map(targets))
+  }
+}
+
 bitflags! {
-  pub struct ExportsCondition: u16 {
+  pub struct ExportsCondition: u32 {
     const IMPORT = 1 << 0;
     const REQUIRE = 1 << 1;
     const MODULE = 1 << 2;
@@ -138,6 +251,8 @@ bitflags! {
     const SASS = 1 << 13;
     const LESS = 1 << 14;
     const STYLUS = 1 << 15;
+    /// Active in Node unless native addons are disabled with `--no-addons`.
+    const NODE_ADDONS = 1 << 16;
   }
 }
 
@@ -167,6 +282,7 @@ impl TryFrom<&str> for ExportsCondition {
       "sass" => ExportsCondition::SASS,
       "less" => ExportsCondition::LESS,
       "stylus" => ExportsCondition::STYLUS,
+      "node-addons" => ExportsCondition::NODE_ADDONS,
       _ => return Err(()),
     })
   }
@@ -175,34 +291,48 @@ impl TryFrom<&str> for ExportsCondition {
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub enum ExportsKey<'a> {
   Main,
-  Pattern(&'a str),
+  Pattern(Cow<'a, str>),
   Condition(ExportsCondition),
-  CustomCondition(&'a str),
+  CustomCondition(Cow<'a, str>),
 }
 
 impl<'a> From<&'a str> for ExportsKey<'a> {
   fn from(key: &'a str) -> Self {
     if key == "." {
       ExportsKey::Main
-    } else if key.starts_with("./") {
-      ExportsKey::Pattern(&key[2..])
-    } else if key.starts_with('#') {
-      ExportsKey::Pattern(&key[1..])
+    } else if let Some(key) = key.strip_prefix("./") {
+      ExportsKey::Pattern(Cow::Borrowed(key))
+    } else if let Some(key) = key.strip_prefix('#') {
+      ExportsKey::Pattern(Cow::Borrowed(key))
     } else if let Ok(c) = ExportsCondition::try_from(key) {
       ExportsKey::Condition(c)
     } else {
-      ExportsKey::CustomCondition(key)
+      ExportsKey::CustomCondition(Cow::Borrowed(key))
     }
   }
 }
 
-impl<'a, 'de: 'a> Deserialize<'de> for ExportsKey<'a> {
+impl<'a> ExportsKey<'a> {
+  pub fn into_owned(self) -> ExportsKey<'static> {
+    match self {
+      ExportsKey::Main => ExportsKey::Main,
+      ExportsKey::Pattern(pattern) => ExportsKey::Pattern(Cow::Owned(pattern.into_owned())),
+      ExportsKey::Condition(condition) => ExportsKey::Condition(condition),
+      ExportsKey::CustomCondition(condition) => {
+        ExportsKey::CustomCondition(Cow::Owned(condition.into_owned()))
+      }
+    }
+  }
+}
+
+/// Like `ExportsField`, parsed keys own their strings.
+impl<'de, 'a> Deserialize<'de> for ExportsKey<'a> {
   fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
   where
     D: serde::Deserializer<'de>,
   {
-    let s: &'de str = Deserialize::deserialize(deserializer)?;
-    Ok(ExportsKey::from(s))
+    let key = deserialize_str(deserializer)?;
+    Ok(ExportsKey::from(&*key).into_owned())
   }
 }
 
@@ -213,10 +343,141 @@ pub enum AliasValue<'a> {
   Specifier(Specifier<'a>),
   Bool(bool),
   Global {
-    global: &'a str,
+    #[serde(deserialize_with = "deserialize_str")]
+    global: Cow<'a, str>,
   },
 }
 
+impl<'a> AliasValue<'a> {
+  pub fn into_owned(self) -> AliasValue<'static> {
+    match self {
+      AliasValue::Specifier(specifier) => AliasValue::Specifier(specifier.into_owned()),
+      AliasValue::Bool(value) => AliasValue::Bool(value),
+      AliasValue::Global { global } => AliasValue::Global {
+        global: Cow::Owned(global.into_owned()),
+      },
+    }
+  }
+}
+
+/// Custom entry fields that may be an object remapping files, like the "browser" field.
+const CUSTOM_MAP_FIELDS: &[&str] = &["react-native", "svelte"];
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(untagged)]
+enum CustomField<'a> {
+  #[serde(borrow)]
+  String(&'a str),
+  Map(IndexMap<Specifier<'a>, AliasValue<'a>>),
+}
+
+/// Deserializes a single top-level field of a package.json, skipping the others.
+struct CustomFieldSeed<'f>(&'f str);
+
+impl<'de, 'f> serde::de::DeserializeSeed<'de> for CustomFieldSeed<'f> {
+  type Value = Option<CustomField<'de>>;
+
+  fn deserialize<D: serde::Deserializer<'de>>(
+    self,
+    deserializer: D,
+  ) -> Result<Self::Value, D::Error> {
+    deserializer.deserialize_map(self)
+  }
+}
+
+impl<'de, 'f> serde::de::Visitor<'de> for CustomFieldSeed<'f> {
+  type Value = Option<CustomField<'de>>;
+
+  fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str("an object")
+  }
+
+  fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+    let mut value = None;
+    while let Some(key) = map.next_key::<Cow<'de, str>>()? {
+      if key == self.0 {
+        // Values of other types, e.g. `"svelte": true`, are ignored.
+        value = map.next_value::<Option<CustomField<'de>>>().ok().flatten();
+      } else {
+        map.next_value::<serde::de::IgnoredAny>()?;
+      }
+    }
+    Ok(value)
+  }
+}
+
+/// Deserializes a string, borrowing it from the JSON unless it contains escapes. Manifests
+/// authored on Windows may use paths like `".\\lib\\index.js"`, which can't be borrowed.
+pub(crate) fn deserialize_str<'de, D: serde::Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Cow<'de, str>, D::Error> {
+  deserializer.deserialize_str(StrVisitor)
+}
+
+fn deserialize_optional_str<'de, D: serde::Deserializer<'de>>(
+  deserializer: D,
+) -> Result<Option<Cow<'de, str>>, D::Error> {
+  struct Str<'a>(Cow<'a, str>);
+
+  impl<'de> Deserialize<'de> for Str<'de> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+      deserialize_str(deserializer).map(Str)
+    }
+  }
+
+  Ok(Option::<Str>::deserialize(deserializer)?.map(|s| s.0))
+}
+
+struct StrVisitor;
+
+impl<'de> serde::de::Visitor<'de> for StrVisitor {
+  type Value = Cow<'de, str>;
+
+  fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str("a string")
+  }
+
+  fn visit_borrowed_str<E: serde::de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+    Ok(Cow::Borrowed(v))
+  }
+
+  fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+    Ok(Cow::Owned(v.to_owned()))
+  }
+
+  fn visit_string<E: serde::de::Error>(self, v: String) -> Result<Self::Value, E> {
+    Ok(Cow::Owned(v))
+  }
+}
+
+/// The module format of `.js` files in a package, from its "type" field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleType {
+  CommonJs,
+  Module,
+}
+
+impl ModuleType {
+  /// Moves the extension that always has the other module format (e.g. `.cjs` in a
+  /// `"type": "module"` package) to just after its counterpart (`.mjs`), if it was tried first.
+  /// Other extensions keep their configured order.
+  pub fn order_extensions(&self, extensions: &mut Vec<&str>) {
+    let (preferred, other) = match self {
+      ModuleType::Module => ("mjs", "cjs"),
+      ModuleType::CommonJs => ("cjs", "mjs"),
+    };
+
+    let other_idx = extensions.iter().position(|ext| *ext == other);
+    let preferred_idx = extensions.iter().position(|ext| *ext == preferred);
+    if let (Some(other_idx), Some(preferred_idx)) = (other_idx, preferred_idx) {
+      if other_idx < preferred_idx {
+        let ext = extensions.remove(other_idx);
+        extensions.insert(preferred_idx, ext);
+      }
+    }
+  }
+}
+
 #[derive(serde::Deserialize, Clone, PartialEq, Debug)]
 #[serde(untagged)]
 pub enum SideEffects<'a> {
@@ -235,10 +496,73 @@ impl<'a> Default for SideEffects<'a> {
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub enum PackageJsonError {
+  /// A lazily parsed field, e.g. "exports", has an invalid value. Unlike other fields, these
+  /// are only parsed when first used. Holds the error's position in the file.
+  InvalidField(JsonError),
   InvalidPackageTarget,
   PackagePathNotExported,
+  /// The subpath matched an "exports" key whose target is `null`, which explicitly blocks it.
+  PackagePathBlocked,
   InvalidSpecifier,
   ImportNotDefined,
+  /// The conditions object for the "." subpath did not match any active conditions
+  /// and has no "default".
+  UnmatchedConditions {
+    conditions: Vec<String>,
+    active: Vec<String>,
+  },
+}
+
+/// A problem found in the "exports" field by `PackageJson::validate_exports`.
+/// Keys are subpaths as written in package.json, e.g. `./foo/*`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "type")]
+pub enum ExportsLintIssue {
+  /// The target file does not exist, or a wildcard target does not match any files.
+  MissingTarget { key: String, target: String },
+  /// The target is not a "./" relative path within the package.
+  InvalidTarget { key: String, target: String },
+  /// A subpath key appears inside a conditions object, or a condition key appears
+  /// alongside subpath keys. These are never matched.
+  InvalidKey { key: String },
+  /// A condition appears after "default", so it is never matched.
+  ShadowedKey { key: String, condition: String },
+  /// A conditions object has no "default" fallback.
+  MissingDefault { key: String },
+  /// A subpath key that can never be matched, because it contains more than one "*" or ends
+  /// with "/" (folder mappings are no longer supported). Patterns are matched by specificity
+  /// rather than order, so a valid pattern is never hidden by the keys before it.
+  UnreachableKey { key: String },
+  /// A published file, per the "files" field, that no export target reaches. Files that npm
+  /// always publishes, type declarations, and source maps are not reported.
+  UncoveredFile { path: String },
+}
+
+/// Extensions to the Node "exports" resolution algorithm.
+#[derive(Clone, Copy, Default)]
+pub struct ExportsOptions<'f> {
+  /// Whether to convert backslashes in targets to forward slashes, for packages authored
+  /// on Windows (e.g. `.\\lib\\index.js`).
+  pub normalize_backslashes: bool,
+  /// Checks whether a target exists. If set, array fallbacks are tried in order until one
+  /// exists, rather than only skipping invalid targets.
+  pub exists: Option<&'f dyn Fn(&Path) -> bool>,
+  /// Records each condition that was checked, e.g. to explain why a target didn't match.
+  pub trace: Option<&'f RefCell<Vec<ExportsTraceStep>>>,
+  /// Records each target whose backslashes were normalized, along with the path it resolved to,
+  /// so that only the target that was actually used is reported.
+  pub normalized: Option<&'f RefCell<Vec<(PathBuf, String)>>>,
+}
+
+/// A condition checked while matching an "exports" or "imports" target.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ExportsTraceStep {
+  /// The key whose conditions were checked, e.g. `./feature`, or `browser` for a
+  /// nested conditions object.
+  pub key: String,
+  pub condition: String,
+  /// Whether the condition was active, in which case its target was used.
+  pub matched: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -248,18 +572,278 @@ pub enum ExportsResolution<'a> {
   Package(Cow<'a, str>),
 }
 
+/// A conditions object or fallback array that is partway through being matched.
+enum TargetFrame<'a, 'r> {
+  Conditions {
+    key: &'a ExportsKey<'a>,
+    entries: indexmap::map::Iter<'a, ExportsKey<'a>, ExportsField<'a>>,
+    /// The "default" entry, which is deferred until no other condition matched.
+    default: Option<(&'a ExportsKey<'a>, &'a ExportsField<'a>)>,
+  },
+  Fallbacks {
+    key: &'a ExportsKey<'a>,
+    items: std::slice::Iter<'a, ExportsField<'a>>,
+    fallback: Option<ExportsResolution<'r>>,
+  },
+}
+
+/// Iterates over an "exports" or "imports" target and all of its nested targets in document
+/// order, using an explicit stack so that deeply nested fields can't overflow the call stack.
+struct ExportsFieldIter<'b, 'a> {
+  stack: Vec<&'b ExportsField<'a>>,
+}
+
+impl<'b, 'a> Iterator for ExportsFieldIter<'b, 'a> {
+  type Item = &'b ExportsField<'a>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let field = self.stack.pop()?;
+    match field {
+      ExportsField::Array(items) => self.stack.extend(items.iter().rev()),
+      ExportsField::Map(map) => self.stack.extend(map.values().rev()),
+      ExportsField::None | ExportsField::String(_) => {}
+    }
+    Some(field)
+  }
+}
+
+/// Counts the keys and array entries in the given targets.
+fn count_exports_keys<'b, 'a>(stack: Vec<&'b ExportsField<'a>>, count: usize) -> usize {
+  (ExportsFieldIter { stack }).fold(count, |count, field| {
+    count
+      + match field {
+        ExportsField::Array(items) => items.len(),
+        ExportsField::Map(map) => map.len(),
+        ExportsField::None | ExportsField::String(_) => 0,
+      }
+  })
+}
+
+type ImportsField<'a> = IndexMap<ExportsKey<'a>, ExportsField<'a>>;
+
+/// A package.json field that is only deserialized when first used. Published manifests often
+/// have large "exports", "imports", or "browser" objects, while most resolutions read one of
+/// them at most, so parsing them up front dominated the cost of reading a manifest. Until then,
+/// only the raw JSON is kept, borrowed from the file contents.
+///
+/// `T` is the field's type with a `'static` lifetime: the parsed value is converted to own its
+/// strings. Storing a `T<'a>` in a cell would make `PackageJson` invariant over `'a`, but the
+/// cache relies on shortening it, see `Cache::read_package`.
+pub(crate) struct LazyField<'a, T> {
+  raw: Option<&'a RawValue>,
+  value: OnceCell<Result<T, JsonError>>,
+}
+
+impl<'a, T: Default> LazyField<'a, T> {
+  /// Returns the value, deserializing it with `U` and converting it with `into_owned` on first
+  /// use. Errors are located in `package`.
+  fn get<U: Deserialize<'a>>(
+    &self,
+    package: &PackageJson<'a>,
+    into_owned: fn(U) -> T,
+  ) -> Result<&T, &JsonError> {
+    let value = self.value.get_or_init(|| match self.raw {
+      Some(raw) => match serde_json::from_str::<U>(raw.get()) {
+        Ok(value) => Ok(into_owned(value)),
+        Err(err) => Err(package.field_error(raw, err)),
+      },
+      None => Ok(T::default()),
+    });
+    value.as_ref()
+  }
+}
+
+impl<'a, T> Default for LazyField<'a, T> {
+  fn default() -> Self {
+    LazyField {
+      raw: None,
+      value: OnceCell::new(),
+    }
+  }
+}
+
+impl<'a, T> fmt::Debug for LazyField<'a, T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.raw {
+      Some(raw) => write!(f, "LazyField({})", raw.get()),
+      None => f.write_str("LazyField(None)"),
+    }
+  }
+}
+
+impl<'de: 'a, 'a, T> Deserialize<'de> for LazyField<'a, T> {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    Ok(LazyField {
+      raw: Some(<&'a RawValue>::deserialize(deserializer)?),
+      value: OnceCell::new(),
+    })
+  }
+}
+
+/// Creates a field from an already parsed value, e.g. in tests.
+impl<'a> From<ExportsField<'static>> for LazyField<'a, ExportsField<'static>> {
+  fn from(value: ExportsField<'static>) -> Self {
+    LazyField {
+      raw: None,
+      value: OnceCell::with_value(Ok(value)),
+    }
+  }
+}
+
+impl<'a> From<ImportsField<'static>> for LazyField<'a, ImportsField<'static>> {
+  fn from(value: ImportsField<'static>) -> Self {
+    LazyField {
+      raw: None,
+      value: OnceCell::with_value(Ok(value)),
+    }
+  }
+}
+
+impl<'a> From<BrowserField<'static>> for LazyField<'a, BrowserField<'static>> {
+  fn from(value: BrowserField<'static>) -> Self {
+    LazyField {
+      raw: None,
+      value: OnceCell::with_value(Ok(value)),
+    }
+  }
+}
+
 impl<'a> PackageJson<'a> {
+  /// Creates a package.json at `path` with only an "alias" field, for aliases configured on
+  /// the resolver rather than in a file.
+  pub(crate) fn from_aliases(
+    path: PathBuf,
+    alias: IndexMap<Specifier<'a>, AliasValue<'a>>,
+  ) -> Self {
+    PackageJson {
+      path,
+      alias,
+      ..PackageJson::default()
+    }
+  }
+
   pub fn parse(path: PathBuf, data: &'a str) -> serde_json::Result<PackageJson<'a>> {
     let mut parsed: PackageJson = serde_json::from_str(data)?;
     parsed.path = path;
+    parsed.raw = data;
     Ok(parsed)
   }
 
-  pub fn entries(&self, fields: Fields) -> EntryIter {
-    return EntryIter {
+  fn exports(&self) -> Result<&ExportsField<'a>, PackageJsonError> {
+    let exports = self
+      .exports
+      .get(self, |exports: ExportsField<'static>| exports);
+    exports.map_err(|err| PackageJsonError::InvalidField(err.clone()))
+  }
+
+  fn imports(&self) -> Result<&ImportsField<'a>, PackageJsonError> {
+    let imports = self
+      .imports
+      .get(self, |imports: ImportsField<'static>| imports);
+    imports.map_err(|err| PackageJsonError::InvalidField(err.clone()))
+  }
+
+  fn browser(&self) -> Result<&BrowserField<'a>, PackageJsonError> {
+    let browser = self.browser.get(self, BrowserField::into_owned);
+    browser.map_err(|err| PackageJsonError::InvalidField(err.clone()))
+  }
+
+  /// Converts an error in a lazily parsed field, which is relative to the field's value,
+  /// to one relative to the whole file.
+  fn field_error(&self, raw: &RawValue, err: serde_json::Error) -> JsonError {
+    // Errors from untagged enums, e.g. `BrowserField`, have no position, so they point at the
+    // start of the value instead.
+    let (err_line, err_column) = match err.line() {
+      0 => (1, 1),
+      line => (line, err.column()),
+    };
+    let offset = raw.get().as_ptr() as usize - self.raw.as_ptr() as usize;
+    let before = &self.raw[..offset];
+    let line = before.matches('\n').count() + err_line;
+    let column = if err_line == 1 {
+      before.len() - before.rfind('\n').map_or(0, |i| i + 1) + err_column
+    } else {
+      err_column
+    };
+
+    // The message may end with the relative position, so replace it.
+    let message = err.to_string();
+    let relative = format!(" at line {} column {}", err.line(), err.column());
+    JsonError {
+      path: self.path.clone(),
+      line,
+      column,
+      message: format!(
+        "{} at line {} column {}",
+        message.strip_suffix(&relative).unwrap_or(&message),
+        line,
+        column
+      ),
+    }
+  }
+
+  /// Returns the entry point from a custom field, e.g. "svelte" or "sass". Only "react-native"
+  /// and "svelte" may also be objects, which are read like the "browser" field.
+  pub fn custom_entry(&self, field: &str) -> Option<PathBuf> {
+    match self.custom_field(field)? {
+      CustomField::String(entry) => Some(resolve_path(&self.path, entry)),
+      CustomField::Map(map) => match map.get(&Specifier::Package(
+        Cow::Borrowed(self.name),
+        Cow::Borrowed(""),
+      )) {
+        Some(AliasValue::Specifier(Specifier::Relative(entry))) => {
+          Some(resolve_path(&self.path, entry))
+        }
+        _ => None,
+      },
+    }
+  }
+
+  /// Like `resolve_aliases`, but for the object form of a custom field.
+  pub fn resolve_custom_aliases(
+    &self,
+    specifier: &Specifier<'a>,
+    field: &str,
+  ) -> Option<Cow<'_, AliasValue<'_>>> {
+    let map = match self.custom_field(field)? {
+      CustomField::Map(map) => map,
+      CustomField::String(_) => return None,
+    };
+
+    let alias = self
+      .resolve_alias(&map, specifier)
+      .or_else(|| self.lookup_unprefixed_file(&map, specifier))?;
+    // The map only lives for this call, so the result must be owned. Like the "browser" field,
+    // these maps only contain specifiers and `false`.
+    let alias = match alias.as_ref() {
+      AliasValue::Specifier(s) => AliasValue::Specifier(s.clone().into_owned()),
+      AliasValue::Bool(b) => AliasValue::Bool(*b),
+      AliasValue::Global { .. } => return None,
+    };
+    Some(Cow::Owned(alias))
+  }
+
+  /// Reads a field that isn't otherwise deserialized. Custom fields are rarely configured, so
+  /// rather than keeping every unknown field of every package.json, the JSON is scanned again.
+  fn custom_field(&self, field: &str) -> Option<CustomField<'a>> {
+    let mut deserializer = serde_json::Deserializer::from_str(self.raw);
+    match serde::de::DeserializeSeed::deserialize(CustomFieldSeed(field), &mut deserializer) {
+      Ok(Some(CustomField::Map(_))) if !CUSTOM_MAP_FIELDS.contains(&field) => None,
+      Ok(value) => value,
+      Err(_) => None,
+    }
+  }
+
+  /// Returns the entries in `fields`. Fails if the "browser" field is requested but invalid.
+  pub fn entries(&self, fields: Fields) -> Result<EntryIter<'_>, PackageJsonError> {
+    if fields.contains(Fields::BROWSER) {
+      self.browser()?;
+    }
+
+    Ok(EntryIter {
       package: self,
       fields,
-    };
+    })
   }
 
   pub fn source(&self) -> Option<PathBuf> {
@@ -279,8 +863,164 @@ impl<'a> PackageJson<'a> {
     }
   }
 
+  /// The "main:src" field, which some monorepos use to point at a package's source entry.
+  pub fn main_src(&self) -> Option<PathBuf> {
+    self
+      .main_src
+      .map(|main_src| resolve_path(&self.path, main_src))
+  }
+
+  /// The command names provided by the "bin" field. A string "bin" field provides a single
+  /// command named after the package, without its scope (e.g. `biome` for `@biomejs/biome`).
+  pub fn bin_names(&self) -> Vec<&str> {
+    match &self.bin {
+      BinField::None => Vec::new(),
+      BinField::String(_) => vec![self.name.rsplit('/').next().unwrap_or(self.name)],
+      BinField::Map(map) => map.keys().copied().collect(),
+    }
+  }
+
+  /// The script for a command in the "bin" field, see `bin_names`.
+  pub fn bin(&self, name: &str) -> Option<PathBuf> {
+    let target = match &self.bin {
+      BinField::None => None,
+      BinField::String(target) => Some(*target).filter(|_| self.bin_names() == [name]),
+      BinField::Map(map) => map.get(name).copied(),
+    };
+
+    target.map(|target| resolve_path(&self.path, target))
+  }
+
+  /// The paths to try for a subpath according to the "typesVersions" field, when compiling with
+  /// the given TypeScript version. The package root maps the "types" field, or `index.d.ts`.
+  /// Returns `None` if no version range or path pattern matches.
+  pub fn types_versions(&self, subpath: &str, version: TsVersion) -> Option<Vec<PathBuf>> {
+    let mapping = types_versions::select(&self.types_versions, version)?;
+    let path = if subpath.is_empty() {
+      self.types.or(self.typings).unwrap_or("index.d.ts")
+    } else {
+      subpath
+    };
+
+    let targets = types_versions::map_path(mapping, path)?;
+    Some(
+      targets
+        .iter()
+        .map(|target| resolve_path(&self.path, target))
+        .collect(),
+    )
+  }
+
+  /// The "exports" subpath key most similar to a subpath that was not exported, e.g. `./utils`
+  /// for `util`. Returns `None` if no key is reasonably close.
+  pub fn closest_export(&self, subpath: &str) -> Option<String> {
+    let map = match self.exports().ok()? {
+      ExportsField::Map(map) => map,
+      _ => return None,
+    };
+
+    let subpath = format!("./{}", subpath);
+    map
+      .keys()
+      .filter(|key| matches!(key, ExportsKey::Pattern(_)))
+      .map(|key| {
+        let key = exports_key_name(key);
+        (edit_distance(&key, &subpath), key)
+      })
+      .filter(|(distance, key)| *distance <= key.len().max(subpath.len()) / 2)
+      .min_by_key(|(distance, _)| *distance)
+      .map(|(_, key)| key)
+  }
+
+  /// The "exports" subpath that maps to a file, the reverse of `resolve_package_exports`, e.g.
+  /// `./button` for `src/components/button.js`. Exact subpaths are preferred over patterns, then
+  /// shorter subpaths. Returns `None` if the file isn't exported under the given conditions.
+  pub fn exported_subpath(
+    &self,
+    path: &Path,
+    conditions: ExportsCondition,
+    custom_conditions: &[String],
+  ) -> Option<String> {
+    // Pattern targets are resolved with a placeholder match. This is a Unicode noncharacter,
+    // which won't occur in a real path, unlike NUL, which targets must not contain.
+    const PLACEHOLDER: &str = "\u{FFFF}";
+
+    let resolves_to_path = |subpath: &str| {
+      matches!(
+        self.resolve_package_exports(subpath, conditions, custom_conditions),
+        Ok(resolved) if resolved == path
+      )
+    };
+
+    let map = match self.exports().ok()? {
+      ExportsField::None => return None,
+      ExportsField::Map(map) if map.keys().any(|key| matches!(key, ExportsKey::Pattern(_))) => map,
+      // Otherwise, only the package entry point is exported.
+      _ => return resolves_to_path("").then(|| ".".to_owned()),
+    };
+
+    let pattern_match = |key, target, pattern: &str| {
+      let template = match self.resolve_package_target(
+        key,
+        target,
+        PLACEHOLDER,
+        false,
+        conditions,
+        custom_conditions,
+        ExportsOptions::default(),
+      ) {
+        Ok(ExportsResolution::Path(template)) => template,
+        _ => return None,
+      };
+      let (prefix, suffix) = template.to_str()?.split_once(PLACEHOLDER)?;
+      let matched = path.to_str()?.strip_prefix(prefix)?.strip_suffix(suffix)?;
+      (!matched.is_empty()).then(|| pattern.replacen('*', matched, 1))
+    };
+
+    let mut best: Option<(bool, String)> = None;
+    for (key, target) in map {
+      let candidate = match key {
+        ExportsKey::Main => Some((false, String::new())),
+        ExportsKey::Pattern(pattern) if !pattern.contains('*') => {
+          Some((false, pattern.to_string()))
+        }
+        ExportsKey::Pattern(pattern) => pattern_match(key, target, pattern).map(|s| (true, s)),
+        _ => None,
+      };
+
+      // Check the subpath as a whole, since a more specific key may map it elsewhere or to null.
+      if let Some((is_pattern, subpath)) = candidate {
+        let is_better = best.as_ref().is_none_or(|(best_is_pattern, best)| {
+          (is_pattern, subpath.len()) < (*best_is_pattern, best.len())
+        });
+        if is_better && resolves_to_path(&subpath) {
+          best = Some((is_pattern, subpath));
+        }
+      }
+    }
+
+    best.map(|(_, subpath)| {
+      if subpath.is_empty() {
+        ".".to_owned()
+      } else {
+        format!("./{}", subpath)
+      }
+    })
+  }
+
+  /// The module type from the "type" field, or `None` if it is missing or unrecognized.
+  /// Node treats a missing "type" as CommonJS, but this distinguishes an explicit one.
+  pub fn module_type(&self) -> Option<ModuleType> {
+    match self.module_type {
+      Some("module") => Some(ModuleType::Module),
+      Some("commonjs") => Some(ModuleType::CommonJs),
+      _ => None,
+    }
+  }
+
   pub fn has_exports(&self) -> bool {
-    self.exports != ExportsField::None
+    // An invalid field counts, so that resolving through it reports the error.
+    !matches!(self.exports(), Ok(ExportsField::None))
   }
 
   pub fn resolve_package_exports(
@@ -289,8 +1029,26 @@ impl<'a> PackageJson<'a> {
     conditions: ExportsCondition,
     custom_conditions: &[String],
   ) -> Result<PathBuf, PackageJsonError> {
+    self.resolve_package_exports_compat(
+      subpath,
+      conditions,
+      custom_conditions,
+      ExportsOptions::default(),
+    )
+  }
+
+  /// Like `resolve_package_exports`, with extensions to the Node algorithm from [ExportsOptions].
+  pub fn resolve_package_exports_compat(
+    &self,
+    subpath: &'a str,
+    conditions: ExportsCondition,
+    custom_conditions: &[String],
+    options: ExportsOptions<'_>,
+  ) -> Result<PathBuf, PackageJsonError> {
+    let exports = self.exports()?;
+
     // If exports is an Object with both a key starting with "." and a key not starting with ".", throw an Invalid Package Configuration error.
-    if let ExportsField::Map(map) = &self.exports {
+    if let ExportsField::Map(map) = exports {
       let mut has_conditions = false;
       let mut has_patterns = false;
       for key in map.keys() {
@@ -308,26 +1066,66 @@ impl<'a> PackageJson<'a> {
 
     if subpath.is_empty() {
       let mut main_export = &ExportsField::None;
-      match &self.exports {
+      match exports {
         ExportsField::None | ExportsField::String(_) | ExportsField::Array(_) => {
-          main_export = &self.exports;
+          main_export = exports;
         }
         ExportsField::Map(map) => {
           if let Some(v) = map.get(&ExportsKey::Main) {
             main_export = v;
           } else if !map.keys().any(|k| matches!(k, ExportsKey::Pattern(_))) {
-            main_export = &self.exports;
+            main_export = exports;
+          }
+        }
+      }
+
+      if main_export != &ExportsField::None {
+        match self.resolve_package_target(
+          &ExportsKey::Main,
+          main_export,
+          "",
+          false,
+          conditions,
+          custom_conditions,
+          options,
+        )? {
+          ExportsResolution::Path(path) => return Ok(path),
+          ExportsResolution::None => {
+            // Report which conditions were available, since this is a common source of confusion.
+            if let ExportsField::Map(map) = main_export {
+              return Err(PackageJsonError::UnmatchedConditions {
+                conditions: map.keys().map(exports_key_name).collect(),
+                active: CONDITION_NAMES
+                  .into_iter()
+                  .filter(|name| matches!(ExportsCondition::try_from(*name), Ok(c) if conditions.contains(c)))
+                  .map(|name| name.to_owned())
+                  .chain(custom_conditions.iter().cloned())
+                  .collect(),
+              });
+            }
+          }
+          ExportsResolution::Package(..) => {}
+        }
+      }
+    } else if subpath == "." {
+      // The package directory itself (`pkg/`) is only exported by an exact "./" mapping.
+      if let ExportsField::Map(exports) = exports {
+        if let Some((key, target)) = exports.get_key_value(&ExportsKey::Pattern(Cow::Borrowed("")))
+        {
+          if let ExportsResolution::Path(path) = self.resolve_package_target(
+            key,
+            target,
+            "",
+            false,
+            conditions,
+            custom_conditions,
+            options,
+          )? {
+            return Ok(path);
           }
         }
       }
-
-      if main_export != &ExportsField::None {
-        match self.resolve_package_target(main_export, "", false, conditions, custom_conditions)? {
-          ExportsResolution::Path(path) => return Ok(path),
-          ExportsResolution::None | ExportsResolution::Package(..) => {}
-        }
-      }
-    } else if let ExportsField::Map(exports) = &self.exports {
+    } else if let ExportsField::Map(exports) = exports {
       // All exports must start with "." at this point.
       match self.resolve_package_imports_exports(
         subpath,
@@ -335,6 +1133,7 @@ impl<'a> PackageJson<'a> {
         false,
         conditions,
         custom_conditions,
+        options,
       )? {
         ExportsResolution::Path(path) => return Ok(path),
         ExportsResolution::None | ExportsResolution::Package(..) => {}
@@ -356,13 +1155,14 @@ impl<'a> PackageJson<'a> {
 
     match self.resolve_package_imports_exports(
       specifier,
-      &self.imports,
+      self.imports()?,
       true,
       conditions,
       custom_conditions,
-    )? {
-      ExportsResolution::None => {}
-      res => return Ok(res),
+      ExportsOptions::default(),
+    ) {
+      Ok(ExportsResolution::None) | Err(PackageJsonError::PackagePathBlocked) => {}
+      res => return res,
     }
 
     Err(PackageJsonError::ImportNotDefined)
@@ -370,99 +1170,191 @@ impl<'a> PackageJson<'a> {
 
   fn resolve_package_target(
     &self,
+    key: &'a ExportsKey<'a>,
     target: &'a ExportsField,
     pattern_match: &str,
     is_imports: bool,
     conditions: ExportsCondition,
     custom_conditions: &[String],
+    options: ExportsOptions<'_>,
   ) -> Result<ExportsResolution<'_>, PackageJsonError> {
-    match target {
-      ExportsField::String(target) => {
-        if !target.starts_with("./") {
-          if !is_imports || target.starts_with("../") || target.starts_with('/') {
-            return Err(PackageJsonError::InvalidPackageTarget);
-          }
-
-          if pattern_match != "" {
-            let target = target.replace('*', pattern_match);
-            return Ok(ExportsResolution::Package(Cow::Owned(target)));
-          }
-
-          return Ok(ExportsResolution::Package(Cow::Borrowed(target)));
+    // Conditions and fallbacks may be nested arbitrarily deep, so we use an explicit stack
+    // rather than recursion. Each frame is resumed with the result of its current child.
+    // Each target is paired with the key it was reached through, for tracing.
+    let mut stack: Vec<TargetFrame<'a, '_>> = Vec::new();
+    let mut next = Some((key, target));
+    loop {
+      let mut result = match next.take() {
+        Some((_, ExportsField::String(target))) => {
+          self.resolve_target_string(target, pattern_match, is_imports, options)
+        }
+        Some((key, ExportsField::Map(target))) => {
+          stack.push(TargetFrame::Conditions {
+            key,
+            entries: target.iter(),
+            default: None,
+          });
+          Ok(ExportsResolution::None)
+        }
+        Some((_, ExportsField::Array(target))) if target.is_empty() => {
+          Err(PackageJsonError::PackagePathNotExported)
+        }
+        Some((key, ExportsField::Array(target))) => {
+          stack.push(TargetFrame::Fallbacks {
+            key,
+            items: target.iter(),
+            fallback: None,
+          });
+          Ok(ExportsResolution::None)
         }
+        Some((_, ExportsField::None)) | None => Ok(ExportsResolution::None),
+      };
 
-        let target = if pattern_match == "" {
-          Cow::Borrowed(*target)
-        } else {
-          Cow::Owned(target.replace('*', pattern_match))
+      // Pass the result up the stack until a frame has another target to try.
+      while next.is_none() {
+        let frame = match stack.last_mut() {
+          Some(frame) => frame,
+          None => return result,
         };
 
-        // If target split on "/" or "\" contains any "", ".", "..", or "node_modules" segments after
-        // the first "." segment, case insensitive and including percent encoded variants,
-        // throw an Invalid Package Target error.
-        let target_path = decode_path(target.as_ref(), SpecifierType::Esm).0;
-        if target_path
-          .components()
-          .enumerate()
-          .any(|(index, c)| match c {
-            Component::ParentDir => true,
-            Component::CurDir => index > 0,
-            Component::Normal(c) => c.eq_ignore_ascii_case("node_modules"),
-            _ => false,
-          })
-        {
-          return Err(PackageJsonError::InvalidPackageTarget);
-        }
+        match frame {
+          TargetFrame::Conditions {
+            key,
+            entries,
+            default,
+          } => {
+            // We must iterate in object insertion order, except that "default" is only tried
+            // once no other condition matched, even if it comes first. Errors and matches are
+            // returned as is.
+            if matches!(result, Ok(ExportsResolution::None)) {
+              let record = |condition: &ExportsKey, matched: bool| {
+                if let Some(trace) = options.trace {
+                  trace.borrow_mut().push(ExportsTraceStep {
+                    key: trace_key_name(key, is_imports),
+                    condition: exports_key_name(condition),
+                    matched,
+                  });
+                }
+              };
 
-        let resolved_target = resolve_path(&self.path, &target_path);
-        return Ok(ExportsResolution::Path(resolved_target));
-      }
-      ExportsField::Map(target) => {
-        // We must iterate in object insertion order.
-        for (key, value) in target {
-          let matches = match key {
-            ExportsKey::Condition(key) => {
-              *key == ExportsCondition::DEFAULT || conditions.contains(*key)
+              next = entries.find_map(|(condition, value)| {
+                let matched = match condition {
+                  ExportsKey::Condition(c) if *c == ExportsCondition::DEFAULT => {
+                    default.get_or_insert((condition, value));
+                    return None;
+                  }
+                  ExportsKey::Condition(c) => conditions.contains(*c),
+                  ExportsKey::CustomCondition(c) => custom_conditions.iter().any(|k| k == c),
+                  _ => return None,
+                };
+                record(condition, matched);
+                matched.then_some((condition, value))
+              });
+
+              if next.is_none() {
+                if let Some((condition, value)) = default.take() {
+                  record(condition, true);
+                  next = Some((condition, value));
+                }
+              }
             }
-            ExportsKey::CustomCondition(key) => custom_conditions.iter().any(|k| k == key),
-            _ => false,
-          };
-          if matches {
-            match self.resolve_package_target(
-              value,
-              pattern_match,
-              is_imports,
-              conditions,
-              custom_conditions,
-            )? {
-              ExportsResolution::None => continue,
-              res => return Ok(res),
+          }
+          TargetFrame::Fallbacks {
+            key,
+            items,
+            fallback,
+          } => {
+            match result {
+              Err(_) | Ok(ExportsResolution::None) => {}
+              // Try the remaining entries, but keep the first valid target in case none exist.
+              Ok(ExportsResolution::Path(path)) if matches!(options.exists, Some(exists) if !exists(&path)) =>
+              {
+                fallback.get_or_insert(ExportsResolution::Path(path));
+              }
+              Ok(res) => {
+                stack.pop();
+                result = Ok(res);
+                continue;
+              }
+            }
+
+            next = items.next().map(|item| (*key, item));
+            if next.is_none() {
+              result = Ok(fallback.take().unwrap_or(ExportsResolution::None));
+            } else {
+              result = Ok(ExportsResolution::None);
             }
           }
         }
-      }
-      ExportsField::Array(target) => {
-        if target.is_empty() {
-          return Err(PackageJsonError::PackagePathNotExported);
-        }
 
-        for item in target {
-          match self.resolve_package_target(
-            item,
-            pattern_match,
-            is_imports,
-            conditions,
-            custom_conditions,
-          ) {
-            Err(_) | Ok(ExportsResolution::None) => continue,
-            Ok(res) => return Ok(res),
-          }
+        if next.is_none() {
+          stack.pop();
         }
       }
-      ExportsField::None => return Ok(ExportsResolution::None),
+    }
+  }
+
+  fn resolve_target_string(
+    &self,
+    target: &'a str,
+    pattern_match: &str,
+    is_imports: bool,
+    options: ExportsOptions<'_>,
+  ) -> Result<ExportsResolution<'_>, PackageJsonError> {
+    // If patternMatch split on "/" or "\" contains any ".", "..", or "node_modules" segments,
+    // case insensitive and including percent encoded variants, throw an Invalid Module
+    // Specifier error. Otherwise, a wildcard could capture e.g. `../other-pkg/file.js`.
+    if is_invalid_pattern_match(pattern_match) {
+      return Err(PackageJsonError::InvalidSpecifier);
     }
 
-    Ok(ExportsResolution::None)
+    let is_relative =
+      target.starts_with("./") || (options.normalize_backslashes && target.starts_with(".\\"));
+    if !is_relative {
+      if !is_imports || target.starts_with("../") || target.starts_with('/') {
+        return Err(PackageJsonError::InvalidPackageTarget);
+      }
+
+      if !pattern_match.is_empty() {
+        let target = target.replace('*', pattern_match);
+        return Ok(ExportsResolution::Package(Cow::Owned(target)));
+      }
+
+      return Ok(ExportsResolution::Package(Cow::Borrowed(target)));
+    }
+
+    let raw_target = target;
+    let target = if options.normalize_backslashes && target.contains('\\') {
+      Cow::Owned(target.replace('\\', "/"))
+    } else {
+      Cow::Borrowed(target)
+    };
+
+    let target = if pattern_match.is_empty() {
+      target
+    } else {
+      Cow::Owned(target.replace('*', pattern_match))
+    };
+
+    // If target split on "/" or "\" contains any "", ".", "..", or "node_modules" segments after
+    // the first "." segment, case insensitive and including percent encoded variants,
+    // throw an Invalid Package Target error.
+    let target_path = decode_path(target.as_ref(), SpecifierType::Esm)
+      .map_err(|_| PackageJsonError::InvalidPackageTarget)?
+      .0;
+    if is_invalid_target_path(&target_path) {
+      return Err(PackageJsonError::InvalidPackageTarget);
+    }
+
+    let resolved_target = resolve_path(&self.path, &target_path);
+    if let Some(normalized) = options.normalized {
+      if options.normalize_backslashes && raw_target.contains('\\') {
+        normalized
+          .borrow_mut()
+          .push((resolved_target.clone(), raw_target.to_owned()));
+      }
+    }
+    Ok(ExportsResolution::Path(resolved_target))
   }
 
   fn resolve_package_imports_exports(
@@ -472,85 +1364,324 @@ impl<'a> PackageJson<'a> {
     is_imports: bool,
     conditions: ExportsCondition,
     custom_conditions: &[String],
+    options: ExportsOptions<'_>,
   ) -> Result<ExportsResolution<'_>, PackageJsonError> {
-    let pattern = ExportsKey::Pattern(match_key);
-    if let Some(target) = match_obj.get(&pattern) {
+    let pattern = ExportsKey::Pattern(Cow::Borrowed(match_key));
+    if let Some((key, target)) = match_obj.get_key_value(&pattern) {
       if !match_key.contains('*') {
-        return self.resolve_package_target(target, "", is_imports, conditions, custom_conditions);
+        if *target == ExportsField::None {
+          return Err(PackageJsonError::PackagePathBlocked);
+        }
+
+        return self.resolve_package_target(
+          key,
+          target,
+          "",
+          is_imports,
+          conditions,
+          custom_conditions,
+          options,
+        );
       }
     }
 
-    let mut best_key = "";
-    let mut best_match = "";
-    for key in match_obj.keys() {
-      if let ExportsKey::Pattern(key) = key {
-        if let Some((pattern_base, pattern_trailer)) = key.split_once('*') {
-          if match_key.starts_with(pattern_base)
-            && !pattern_trailer.contains('*')
-            && (pattern_trailer.is_empty()
-              || (match_key.len() >= key.len() && match_key.ends_with(pattern_trailer)))
-            && pattern_key_compare(best_key, key) == Ordering::Greater
-          {
-            best_key = key;
-            best_match = &match_key[pattern_base.len()..match_key.len() - pattern_trailer.len()];
-          }
-        }
+    let patterns = match_obj.iter().filter_map(|entry| match entry.0 {
+      ExportsKey::Pattern(key) => Some((entry, key.as_ref())),
+      _ => None,
+    });
+    if let Some(((key, target), best_match)) = best_pattern_match(match_key, patterns) {
+      if *target == ExportsField::None {
+        return Err(PackageJsonError::PackagePathBlocked);
       }
-    }
 
-    if !best_key.is_empty() {
       return self.resolve_package_target(
-        &match_obj[&ExportsKey::Pattern(best_key)],
+        key,
+        target,
         best_match,
         is_imports,
         conditions,
         custom_conditions,
+        options,
       );
     }
 
     Ok(ExportsResolution::None)
   }
 
+  /// Checks the "exports" field against the file system, e.g. for library authors to catch
+  /// mistakes before publishing. Wildcard targets must match at least one file.
+  pub fn validate_exports<Fs: FileSystem>(&self, fs: &Fs) -> Vec<ExportsLintIssue> {
+    let mut issues = Vec::new();
+    let exports = match self.exports() {
+      Ok(exports) => exports,
+      Err(_) => return issues,
+    };
+    match exports {
+      ExportsField::None => {}
+      ExportsField::Map(map)
+        if map
+          .keys()
+          .any(|k| matches!(k, ExportsKey::Main | ExportsKey::Pattern(..))) =>
+      {
+        for (key, target) in map {
+          match key {
+            ExportsKey::Pattern(pattern)
+              if pattern.matches('*').count() > 1 || pattern.ends_with('/') =>
+            {
+              issues.push(ExportsLintIssue::UnreachableKey {
+                key: exports_key_name(key),
+              })
+            }
+            ExportsKey::Main | ExportsKey::Pattern(..) => {
+              self.validate_exports_target(fs, &exports_key_name(key), target, &mut issues)
+            }
+            _ => issues.push(ExportsLintIssue::InvalidKey {
+              key: exports_key_name(key),
+            }),
+          }
+        }
+      }
+      exports => self.validate_exports_target(fs, ".", exports, &mut issues),
+    }
+
+    self.validate_exports_coverage(fs, exports, &mut issues);
+    issues
+  }
+
+  /// Reports files published via the "files" field that aren't reachable through any target.
+  fn validate_exports_coverage<Fs: FileSystem>(
+    &self,
+    fs: &Fs,
+    exports: &ExportsField,
+    issues: &mut Vec<ExportsLintIssue>,
+  ) {
+    let files = match &self.files {
+      Some(files) if *exports != ExportsField::None => files,
+      _ => return,
+    };
+
+    let targets: Vec<&str> = ExportsFieldIter {
+      stack: vec![exports],
+    }
+    .filter_map(|field| match field {
+      ExportsField::String(target) => target.strip_prefix("./"),
+      _ => None,
+    })
+    .collect();
+
+    self.walk_package_files(fs, "", |relative| {
+      if is_published(files, relative)
+        && !is_always_published(relative)
+        && !targets
+          .iter()
+          .any(|target| target_matches_file(target, relative))
+      {
+        issues.push(ExportsLintIssue::UncoveredFile {
+          path: format!("./{}", relative),
+        });
+      }
+      false
+    });
+  }
+
+  fn validate_exports_target<Fs: FileSystem>(
+    &self,
+    fs: &Fs,
+    key: &str,
+    target: &ExportsField,
+    issues: &mut Vec<ExportsLintIssue>,
+  ) {
+    match target {
+      ExportsField::None => {}
+      ExportsField::String(target) => {
+        let target_path = match decode_path(target, SpecifierType::Esm) {
+          Ok((path, _)) if target.starts_with("./") && !is_invalid_target_path(&path) => path,
+          _ => {
+            issues.push(ExportsLintIssue::InvalidTarget {
+              key: key.to_owned(),
+              target: target.to_string(),
+            });
+            return;
+          }
+        };
+
+        let exists = match target[2..].split_once('*') {
+          Some((prefix, suffix)) if key.contains('*') => {
+            self.expand_exports_wildcard(fs, prefix, suffix)
+          }
+          _ if target.ends_with('/') => fs.is_dir(resolve_path(&self.path, &target_path)),
+          _ => fs.is_file(resolve_path(&self.path, &target_path)),
+        };
+
+        if !exists {
+          issues.push(ExportsLintIssue::MissingTarget {
+            key: key.to_owned(),
+            target: target.to_string(),
+          });
+        }
+      }
+      ExportsField::Array(targets) => {
+        for target in targets {
+          self.validate_exports_target(fs, key, target, issues);
+        }
+      }
+      ExportsField::Map(conditions) => {
+        let mut has_default = false;
+        for (condition, value) in conditions {
+          if matches!(condition, ExportsKey::Main | ExportsKey::Pattern(..)) {
+            issues.push(ExportsLintIssue::InvalidKey {
+              key: exports_key_name(condition),
+            });
+            continue;
+          }
+
+          if has_default {
+            issues.push(ExportsLintIssue::ShadowedKey {
+              key: key.to_owned(),
+              condition: exports_key_name(condition),
+            });
+            continue;
+          }
+
+          has_default = *condition == ExportsKey::Condition(ExportsCondition::DEFAULT);
+          self.validate_exports_target(fs, key, value, issues);
+        }
+
+        if !has_default {
+          issues.push(ExportsLintIssue::MissingDefault {
+            key: key.to_owned(),
+          });
+        }
+      }
+    }
+  }
+
+  /// Returns whether any file in the package matches a wildcard target, e.g. `dist/*.js`.
+  fn expand_exports_wildcard<Fs: FileSystem>(&self, fs: &Fs, prefix: &str, suffix: &str) -> bool {
+    // Only the directory containing the fixed part of the prefix needs to be searched.
+    // The wildcard may match across "/", so search recursively from there.
+    let dir = prefix.rsplit_once('/').map_or("", |(dir, _)| dir);
+    self.walk_package_files(fs, dir, |relative| {
+      // The wildcard must match at least one character.
+      relative.len() > prefix.len() + suffix.len()
+        && relative.starts_with(prefix)
+        && relative.ends_with(suffix)
+    })
+  }
+
+  /// Calls `f` with the "/" separated path of each file in a directory of the package, relative
+  /// to the package root, until it returns true. Nested node_modules directories are skipped.
+  fn walk_package_files<Fs: FileSystem, F: FnMut(&str) -> bool>(
+    &self,
+    fs: &Fs,
+    dir: &str,
+    mut f: F,
+  ) -> bool {
+    let root = match self.path.parent() {
+      Some(root) => root,
+      None => return false,
+    };
+
+    let mut stack = vec![root.join(dir)];
+    while let Some(dir) = stack.pop() {
+      let entries = match fs.read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_) => continue,
+      };
+
+      for path in entries {
+        if fs.is_dir(&path) {
+          if path.file_name().is_some_and(|name| name != "node_modules") {
+            stack.push(path);
+          }
+          continue;
+        }
+
+        if let Ok(relative) = path.strip_prefix(root) {
+          let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+          if f(&relative) {
+            return true;
+          }
+        }
+      }
+    }
+
+    false
+  }
+
+  /// Returns the number of keys in the "exports" field, including nested conditions and
+  /// array fallbacks. The count is cached, so the field is only walked once per manifest.
+  pub(crate) fn exports_key_count(&self) -> usize {
+    *self.exports_keys.get_or_init(|| match self.exports() {
+      Ok(exports) => count_exports_keys(vec![exports], 0),
+      Err(_) => 0,
+    })
+  }
+
+  /// Like `exports_key_count`, but for the "imports" field.
+  pub(crate) fn imports_key_count(&self) -> usize {
+    *self.imports_keys.get_or_init(|| match self.imports() {
+      Ok(imports) => count_exports_keys(imports.values().collect(), imports.len()),
+      Err(_) => 0,
+    })
+  }
+
   pub fn resolve_aliases(
     &self,
     specifier: &Specifier<'a>,
     fields: Fields,
-  ) -> Option<Cow<'_, AliasValue>> {
+  ) -> Result<Option<Cow<'_, AliasValue>>, PackageJsonError> {
+    Ok(
+      self
+        .resolve_aliases_with_field(specifier, fields)?
+        .map(|(alias, _)| alias),
+    )
+  }
+
+  /// Like `resolve_aliases`, but also returns which field the alias came from.
+  pub fn resolve_aliases_with_field(
+    &self,
+    specifier: &Specifier<'a>,
+    fields: Fields,
+  ) -> Result<Option<(Cow<'_, AliasValue<'_>>, Fields)>, PackageJsonError> {
     if fields.contains(Fields::SOURCE) {
-      match &self.source {
-        SourceField::Map(source) => match self.resolve_alias(source, specifier) {
-          None => {}
-          res => return res,
-        },
-        _ => {}
+      if let SourceField::Map(source) = &self.source {
+        if let Some(res) = self.resolve_alias(source, specifier) {
+          return Ok(Some((res, Fields::SOURCE)));
+        }
       }
     }
 
     if fields.contains(Fields::ALIAS) {
-      match self.resolve_alias(&self.alias, specifier) {
-        None => {}
-        res => return res,
+      if let Some(res) = self.resolve_alias(&self.alias, specifier) {
+        return Ok(Some((res, Fields::ALIAS)));
       }
     }
 
     if fields.contains(Fields::BROWSER) {
-      match &self.browser {
-        BrowserField::Map(browser) => match self.resolve_alias(browser, specifier) {
-          None => {}
-          res => return res,
-        },
-        _ => {}
+      if let BrowserField::Map(browser) = self.browser()? {
+        if let Some(res) = self.resolve_alias(browser, specifier) {
+          return Ok(Some((res, Fields::BROWSER)));
+        }
+
+        if let Some(res) = self.lookup_unprefixed_file(browser, specifier) {
+          return Ok(Some((res, Fields::BROWSER)));
+        }
       }
     }
 
-    None
+    Ok(None)
   }
 
-  fn resolve_alias(
+  fn resolve_alias<'m>(
     &self,
-    map: &'a IndexMap<Specifier<'a>, AliasValue<'a>>,
+    map: &'m IndexMap<Specifier<'a>, AliasValue<'a>>,
     specifier: &Specifier<'a>,
-  ) -> Option<Cow<'_, AliasValue>> {
+  ) -> Option<Cow<'m, AliasValue<'m>>> {
     if let Some(alias) = self.lookup_alias(map, specifier) {
       return Some(alias);
     }
@@ -617,11 +1748,34 @@ impl<'a> PackageJson<'a> {
     None
   }
 
-  fn lookup_alias(
+  /// Many packages omit the leading "./" from file keys in the "browser" field,
+  /// e.g. `{"lib/fs.js": "lib/fs-browser.js"}`. These keys are parsed as package specifiers,
+  /// so look up a relative file path the same way. Keys that are only a package name
+  /// (e.g. `{"fs": false}`) never match a file.
+  fn lookup_unprefixed_file<'m>(
+    &self,
+    map: &'m IndexMap<Specifier<'a>, AliasValue<'a>>,
+    specifier: &Specifier<'a>,
+  ) -> Option<Cow<'m, AliasValue<'m>>> {
+    let path = match specifier {
+      Specifier::Relative(path) => path.to_str()?,
+      _ => return None,
+    };
+
+    match Specifier::parse(path, SpecifierType::Cjs, Flags::empty()) {
+      Ok((key @ Specifier::Package(..), _)) => match &key {
+        Specifier::Package(module, subpath) if subpath.is_empty() && !module.contains('.') => None,
+        _ => map.get(&key.into_owned()).map(Cow::Borrowed),
+      },
+      _ => None,
+    }
+  }
+
+  fn lookup_alias<'m>(
     &self,
-    map: &'a IndexMap<Specifier<'a>, AliasValue<'a>>,
+    map: &'m IndexMap<Specifier<'a>, AliasValue<'a>>,
     specifier: &Specifier<'a>,
-  ) -> Option<Cow<'_, AliasValue>> {
+  ) -> Option<Cow<'m, AliasValue<'m>>> {
     if let Some(value) = map.get(specifier) {
       return Some(Cow::Borrowed(value));
     }
@@ -750,7 +1904,169 @@ fn replace_captures<'a>(s: &'a str, path: &str, captures: &Vec<Range<usize>>) ->
   res
 }
 
-fn pattern_key_compare(a: &str, b: &str) -> Ordering {
+fn is_invalid_target_path(target_path: &Path) -> bool {
+  target_path
+    .components()
+    .enumerate()
+    .any(|(index, c)| match c {
+      Component::ParentDir => true,
+      Component::CurDir => index > 0,
+      Component::Normal(c) => c.eq_ignore_ascii_case("node_modules"),
+      _ => false,
+    })
+}
+
+fn is_invalid_pattern_match(pattern_match: &str) -> bool {
+  pattern_match.split(['/', '\\']).any(|segment| {
+    let segment = percent_decode_str(segment).decode_utf8_lossy();
+    segment == "." || segment == ".." || segment.eq_ignore_ascii_case("node_modules")
+  })
+}
+
+const CONDITION_NAMES: [&str; 17] = [
+  "import",
+  "require",
+  "module",
+  "node",
+  "browser",
+  "worker",
+  "worklet",
+  "electron",
+  "development",
+  "production",
+  "types",
+  "default",
+  "style",
+  "sass",
+  "less",
+  "stylus",
+  "node-addons",
+];
+
+/// The number of single character insertions, deletions, or substitutions between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b: Vec<char> = b.chars().collect();
+  let mut prev: Vec<usize> = (0..=b.len()).collect();
+  for (i, ca) in a.chars().enumerate() {
+    let mut cur = vec![i + 1; b.len() + 1];
+    for (j, cb) in b.iter().enumerate() {
+      cur[j + 1] = (prev[j] + (ca != *cb) as usize)
+        .min(prev[j + 1] + 1)
+        .min(cur[j] + 1);
+    }
+    prev = cur;
+  }
+
+  prev[b.len()]
+}
+
+/// The name of a key in a traced "exports" or "imports" field, e.g. `./feature` or `#dep`.
+fn trace_key_name(key: &ExportsKey, is_imports: bool) -> String {
+  match key {
+    ExportsKey::Pattern(pattern) if is_imports => format!("#{}", pattern),
+    key => exports_key_name(key),
+  }
+}
+
+/// Returns whether a file is included by the "files" field of package.json. Entries are globs
+/// or paths of files or directories, and entries starting with "!" exclude files.
+fn is_published(files: &[&str], relative: &str) -> bool {
+  let matches = |entry: &str| {
+    let entry = entry.trim_start_matches("./").trim_end_matches('/');
+    glob_match(entry, relative)
+      || relative
+        .strip_prefix(entry)
+        .is_some_and(|rest| rest.starts_with('/'))
+      || glob_match(&format!("{}/**", entry), relative)
+  };
+
+  let mut published = false;
+  for entry in files {
+    match entry.strip_prefix('!') {
+      Some(entry) => published &= !matches(entry),
+      None => published |= matches(entry),
+    }
+  }
+  published
+}
+
+/// Files that npm publishes regardless of the "files" field, and files that are found next to
+/// an exported module rather than imported directly.
+fn is_always_published(relative: &str) -> bool {
+  const DECLARATION_SUFFIXES: [&str; 4] = [".d.ts", ".d.mts", ".d.cts", ".map"];
+  if DECLARATION_SUFFIXES
+    .iter()
+    .any(|suffix| relative.ends_with(suffix))
+  {
+    return true;
+  }
+
+  if relative.contains('/') {
+    return false;
+  }
+
+  let name = relative.to_ascii_lowercase();
+  name == "package.json"
+    || ["readme", "license", "licence", "changelog"]
+      .iter()
+      .any(|prefix| name.starts_with(prefix))
+}
+
+/// Returns whether an "exports" target (without the leading "./") reaches a file, either
+/// directly or through a wildcard.
+fn target_matches_file(target: &str, relative: &str) -> bool {
+  match target.split_once('*') {
+    Some((prefix, suffix)) => {
+      relative.len() > prefix.len() + suffix.len()
+        && relative.starts_with(prefix)
+        && relative.ends_with(suffix)
+    }
+    None => target == relative,
+  }
+}
+
+fn exports_key_name(key: &ExportsKey) -> String {
+  match key {
+    ExportsKey::Main => ".".to_owned(),
+    ExportsKey::Pattern(pattern) => format!("./{}", pattern),
+    ExportsKey::Condition(condition) => CONDITION_NAMES
+      .into_iter()
+      .find(|name| ExportsCondition::try_from(*name) == Ok(*condition))
+      .unwrap_or_default()
+      .to_owned(),
+    ExportsKey::CustomCondition(condition) => condition.to_string(),
+  }
+}
+
+/// Finds the pattern that best matches `match_key`, following Node's PATTERN_KEY_COMPARE order.
+/// Each pattern is given without its "./" or "#" prefix, along with the value to return for it.
+/// Returns that value and the non-empty substring captured by the "*".
+pub(crate) fn best_pattern_match<'k, 'm, T: Copy>(
+  match_key: &'m str,
+  patterns: impl Iterator<Item = (T, &'k str)>,
+) -> Option<(T, &'m str)> {
+  let mut best: Option<(T, &str, &'m str)> = None;
+  for (value, pattern) in patterns {
+    if let Some((pattern_base, pattern_trailer)) = pattern.split_once('*') {
+      if match_key.starts_with(pattern_base)
+        && match_key != pattern_base
+        && !pattern_trailer.contains('*')
+        && (pattern_trailer.is_empty()
+          || (match_key.len() >= pattern.len() && match_key.ends_with(pattern_trailer)))
+        && best.is_none_or(|(_, best_pattern, _)| {
+          pattern_key_compare(best_pattern, pattern) == Ordering::Greater
+        })
+      {
+        let capture = &match_key[pattern_base.len()..match_key.len() - pattern_trailer.len()];
+        best = Some((value, pattern, capture));
+      }
+    }
+  }
+
+  best.map(|(value, _, capture)| (value, capture))
+}
+
+pub(crate) fn pattern_key_compare(a: &str, b: &str) -> Ordering {
   let a_pos = a.chars().position(|c| c == '*');
   let b_pos = b.chars().position(|c| c == '*');
   let base_length_a = a_pos.map_or(a.len(), |p| p + 1);
@@ -789,44 +2105,40 @@ impl<'a> Iterator for EntryIter<'a> {
 
     if self.fields.contains(Fields::TYPES) {
       self.fields.remove(Fields::TYPES);
-      if let Some(types) = self.package.types {
+      if let Some(types) = self.package.types.or(self.package.typings) {
         return Some((resolve_path(&self.package.path, types), "types"));
       }
     }
 
     if self.fields.contains(Fields::BROWSER) {
       self.fields.remove(Fields::BROWSER);
-      match &self.package.browser {
-        BrowserField::None => {}
-        BrowserField::String(browser) => {
-          return Some((resolve_path(&self.package.path, browser), "browser"))
+      match self.package.browser() {
+        Ok(BrowserField::None) | Err(_) => {}
+        Ok(BrowserField::String(browser)) => {
+          return Some((resolve_path(&self.package.path, &**browser), "browser"))
+        }
+        Ok(BrowserField::Map(map)) => {
+          if let Some(AliasValue::Specifier(Specifier::Relative(s))) = map.get(&Specifier::Package(
+            Cow::Borrowed(self.package.name),
+            Cow::Borrowed(""),
+          )) {
+            return Some((resolve_path(&self.package.path, s), "browser"));
+          }
         }
-        BrowserField::Map(map) => match map.get(&Specifier::Package(
-          Cow::Borrowed(self.package.name),
-          Cow::Borrowed(""),
-        )) {
-          Some(AliasValue::Specifier(s)) => match s {
-            Specifier::Relative(s) => {
-              return Some((resolve_path(&self.package.path, s), "browser"))
-            }
-            _ => {}
-          },
-          _ => {}
-        },
       }
     }
 
     if self.fields.contains(Fields::MODULE) {
       self.fields.remove(Fields::MODULE);
-      if let Some(module) = self.package.module {
-        return Some((resolve_path(&self.package.path, module), "module"));
+      if let Some(module) = &self.package.module {
+        return Some((resolve_path(&self.package.path, &**module), "module"));
       }
     }
 
     if self.fields.contains(Fields::MAIN) {
       self.fields.remove(Fields::MAIN);
-      if let Some(main) = self.package.main {
-        return Some((resolve_path(&self.package.path, main), "main"));
+      if let Some(main) = &self.package.main {
+        return Some((resolve_path(&self.package.path, &**main), "main"));
       }
     }
 
@@ -855,7 +2167,7 @@ mod tests {
     let pkg = PackageJson {
       path: "/foo/package.json".into(),
       name: "foobar",
-      exports: ExportsField::String("./exports.js"),
+      exports: ExportsField::String("./exports.js".into()).into(),
       ..PackageJson::default()
     };
 
@@ -875,8 +2187,9 @@ mod tests {
       path: "/foo/package.json".into(),
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
-        ".".into() => ExportsField::String("./exports.js")
-      }),
+        ".".into() => ExportsField::String("./exports.js".into())
+      })
+      .into(),
       ..PackageJson::default()
     };
 
@@ -900,10 +2213,11 @@ mod tests {
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
         ".".into() => ExportsField::Map(indexmap! {
-          "import".into() => ExportsField::String("./import.js"),
-          "require".into() => ExportsField::String("./require.js")
+          "import".into() => ExportsField::String("./import.js".into()),
+          "require".into() => ExportsField::String("./require.js".into())
         })
-      }),
+      })
+      .into(),
       ..PackageJson::default()
     };
 
@@ -923,14 +2237,20 @@ mod tests {
         .unwrap(),
       PathBuf::from("/foo/require.js")
     );
-    assert!(matches!(
+    assert_eq!(
       pkg.resolve_package_exports("", ExportsCondition::empty(), &[]),
-      Err(PackageJsonError::PackagePathNotExported)
-    ));
-    assert!(matches!(
-      pkg.resolve_package_exports("", ExportsCondition::NODE, &[]),
-      Err(PackageJsonError::PackagePathNotExported)
-    ));
+      Err(PackageJsonError::UnmatchedConditions {
+        conditions: vec!["import".into(), "require".into()],
+        active: vec![]
+      })
+    );
+    assert_eq!(
+      pkg.resolve_package_exports("", ExportsCondition::NODE, &["custom".into()]),
+      Err(PackageJsonError::UnmatchedConditions {
+        conditions: vec!["import".into(), "require".into()],
+        active: vec!["node".into(), "custom".into()]
+      })
+    );
   }
 
   #[test]
@@ -939,11 +2259,12 @@ mod tests {
       path: "/foo/package.json".into(),
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
-        "./foo".into() => ExportsField::String("./exports.js"),
-        "./.invisible".into() => ExportsField::String("./.invisible.js"),
-        "./".into() => ExportsField::String("./"),
-        "./*".into() => ExportsField::String("./*.js")
-      }),
+        "./foo".into() => ExportsField::String("./exports.js".into()),
+        "./.invisible".into() => ExportsField::String("./.invisible.js".into()),
+        "./".into() => ExportsField::String("./".into()),
+        "./*".into() => ExportsField::String("./*.js".into())
+      })
+      .into(),
       ..PackageJson::default()
     };
 
@@ -974,10 +2295,11 @@ mod tests {
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
         "./foo".into() => ExportsField::Map(indexmap! {
-          "import".into() => ExportsField::String("./import.js"),
-          "require".into() => ExportsField::String("./require.js")
+          "import".into() => ExportsField::String("./import.js".into()),
+          "require".into() => ExportsField::String("./require.js".into())
         })
-      }),
+      })
+      .into(),
       ..PackageJson::default()
     };
 
@@ -1014,11 +2336,12 @@ mod tests {
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
         "node".into() => ExportsField::Map(indexmap! {
-          "import".into() => ExportsField::String("./import.js"),
-          "require".into() => ExportsField::String("./require.js")
+          "import".into() => ExportsField::String("./import.js".into()),
+          "require".into() => ExportsField::String("./require.js".into())
         }),
-        "default".into() => ExportsField::String("./default.js")
-      }),
+        "default".into() => ExportsField::String("./default.js".into())
+      })
+      .into(),
       ..PackageJson::default()
     };
 
@@ -1060,23 +2383,139 @@ mod tests {
       path: "/foo/package.json".into(),
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
-        "custom".into() => ExportsField::String("./custom.js"),
-        "default".into() => ExportsField::String("./default.js")
-      }),
+        "custom".into() => ExportsField::String("./custom.js".into()),
+        "default".into() => ExportsField::String("./default.js".into())
+      })
+      .into(),
+      ..PackageJson::default()
+    };
+    assert_eq!(
+      pkg
+        .resolve_package_exports("", ExportsCondition::NODE, &["custom".into()])
+        .unwrap(),
+      PathBuf::from("/foo/custom.js")
+    );
+    assert_eq!(
+      pkg
+        .resolve_package_exports("", ExportsCondition::NODE, &[])
+        .unwrap(),
+      PathBuf::from("/foo/default.js")
+    );
+  }
+
+  #[test]
+  fn exports_trace() {
+    let pkg = PackageJson {
+      path: "/foo/package.json".into(),
+      name: "foobar",
+      exports: ExportsField::Map(indexmap! {
+        "./feature".into() => ExportsField::Map(indexmap! {
+          "browser".into() => ExportsField::Map(indexmap! {
+            "import".into() => ExportsField::String("./browser.mjs".into())
+          }),
+          "node".into() => ExportsField::String("./node.js".into()),
+          "custom".into() => ExportsField::String("./custom.js".into())
+        })
+      })
+      .into(),
+      ..PackageJson::default()
+    };
+
+    let trace = RefCell::new(Vec::new());
+    assert_eq!(
+      pkg.resolve_package_exports_compat(
+        "feature",
+        ExportsCondition::BROWSER | ExportsCondition::REQUIRE,
+        &[],
+        ExportsOptions {
+          trace: Some(&trace),
+          ..Default::default()
+        },
+      ),
+      Err(PackageJsonError::PackagePathNotExported)
+    );
+
+    let step = |key: &str, condition: &str, matched| ExportsTraceStep {
+      key: key.into(),
+      condition: condition.into(),
+      matched,
+    };
+    assert_eq!(
+      trace.into_inner(),
+      vec![
+        step("./feature", "browser", true),
+        step("browser", "import", false),
+        step("./feature", "node", false),
+        step("./feature", "custom", false),
+      ]
+    );
+  }
+
+  #[test]
+  fn default_condition_last() {
+    let pkg = PackageJson {
+      path: "/foo/package.json".into(),
+      name: "foobar",
+      exports: ExportsField::Map(indexmap! {
+        ".".into() => ExportsField::Map(indexmap! {
+          "default".into() => ExportsField::String("./default.js".into()),
+          "import".into() => ExportsField::String("./import.mjs".into()),
+          "node".into() => ExportsField::Map(indexmap! {
+            "require".into() => ExportsField::String("./node.cjs".into())
+          })
+        })
+      })
+      .into(),
       ..PackageJson::default()
     };
+
+    // "default" loses to a later condition that matches, even though it comes first.
     assert_eq!(
       pkg
-        .resolve_package_exports("", ExportsCondition::NODE, &["custom".into()])
+        .resolve_package_exports("", ExportsCondition::IMPORT, &[])
         .unwrap(),
-      PathBuf::from("/foo/custom.js")
+      PathBuf::from("/foo/import.mjs")
     );
+    // It wins once no other condition leads to a target, including nested ones.
     assert_eq!(
       pkg
         .resolve_package_exports("", ExportsCondition::NODE, &[])
         .unwrap(),
       PathBuf::from("/foo/default.js")
     );
+    assert_eq!(
+      pkg
+        .resolve_package_exports("", ExportsCondition::NODE | ExportsCondition::REQUIRE, &[])
+        .unwrap(),
+      PathBuf::from("/foo/node.cjs")
+    );
+
+    let trace = RefCell::new(Vec::new());
+    pkg
+      .resolve_package_exports_compat(
+        "",
+        ExportsCondition::NODE,
+        &[],
+        ExportsOptions {
+          trace: Some(&trace),
+          ..Default::default()
+        },
+      )
+      .unwrap();
+    let conditions: Vec<_> = trace
+      .into_inner()
+      .into_iter()
+      .map(|step| (step.condition, step.matched))
+      .collect();
+    assert_eq!(
+      conditions,
+      vec![
+        ("import".into(), false),
+        ("node".into(), true),
+        ("require".into(), false),
+        ("default".into(), true),
+      ]
+    );
   }
 
   #[test]
@@ -1087,15 +2526,16 @@ mod tests {
       exports: ExportsField::Map(indexmap! {
         "./lite".into() => ExportsField::Map(indexmap! {
           "node".into() => ExportsField::Map(indexmap! {
-            "import".into() => ExportsField::String("./node_import.js"),
-            "require".into() => ExportsField::String("./node_require.js")
+            "import".into() => ExportsField::String("./node_import.js".into()),
+            "require".into() => ExportsField::String("./node_require.js".into())
           }),
           "browser".into() => ExportsField::Map(indexmap! {
-            "import".into() => ExportsField::String("./browser_import.js"),
-            "require".into() => ExportsField::String("./browser_require.js")
+            "import".into() => ExportsField::String("./browser_import.js".into()),
+            "require".into() => ExportsField::String("./browser_require.js".into())
           }),
         })
-      }),
+      })
+      .into(),
       ..PackageJson::default()
     };
 
@@ -1151,11 +2591,12 @@ mod tests {
       path: "/foo/package.json".into(),
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
-        "./*".into() => ExportsField::String("./cheese/*.mjs"),
-        "./pizza/*".into() => ExportsField::String("./pizza/*.mjs"),
-        "./burritos/*".into() => ExportsField::String("./burritos/*/*.mjs"),
-        "./literal".into() => ExportsField::String("./literal/*.js"),
-      }),
+        "./*".into() => ExportsField::String("./cheese/*.mjs".into()),
+        "./pizza/*".into() => ExportsField::String("./pizza/*.mjs".into()),
+        "./burritos/*".into() => ExportsField::String("./burritos/*/*.mjs".into()),
+        "./literal".into() => ExportsField::String("./literal/*.js".into()),
+      })
+      .into(),
       ..PackageJson::default()
     };
 
@@ -1200,10 +2641,11 @@ mod tests {
       path: "/foo/package.json".into(),
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
-        "./*".into() => ExportsField::String("./*.js"),
+        "./*".into() => ExportsField::String("./*.js".into()),
         "./*.js".into() => ExportsField::None,
         "./internal/*".into() => ExportsField::None,
-      }),
+      })
+      .into(),
       ..PackageJson::default()
     };
     assert_eq!(
@@ -1214,10 +2656,25 @@ mod tests {
     );
     assert!(matches!(
       pkg.resolve_package_exports("file.js", ExportsCondition::empty(), &[]),
-      Err(PackageJsonError::PackagePathNotExported)
+      Err(PackageJsonError::PackagePathBlocked)
     ));
     assert!(matches!(
       pkg.resolve_package_exports("internal/file", ExportsCondition::empty(), &[]),
+      Err(PackageJsonError::PackagePathBlocked)
+    ));
+
+    // A pattern must capture at least one character.
+    let pkg = PackageJson {
+      path: "/foo/package.json".into(),
+      name: "foobar",
+      exports: ExportsField::Map(indexmap! {
+        "./pizza/*".into() => ExportsField::String("./pizza/*.mjs".into()),
+      })
+      .into(),
+      ..PackageJson::default()
+    };
+    assert!(matches!(
+      pkg.resolve_package_exports("pizza/", ExportsCondition::empty(), &[]),
       Err(PackageJsonError::PackagePathNotExported)
     ));
   }
@@ -1228,9 +2685,10 @@ mod tests {
       path: "/foo/package.json".into(),
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
-        "./features/*.js".into() => ExportsField::String("./src/features/*.js"),
+        "./features/*.js".into() => ExportsField::String("./src/features/*.js".into()),
         "./features/private-internal/*".into() => ExportsField::None,
-      }),
+      })
+      .into(),
       ..PackageJson::default()
     };
 
@@ -1252,7 +2710,7 @@ mod tests {
         ExportsCondition::empty(),
         &[]
       ),
-      Err(PackageJsonError::PackagePathNotExported)
+      Err(PackageJsonError::PackagePathBlocked)
     ),);
   }
 
@@ -1264,15 +2722,15 @@ mod tests {
       exports: ExportsField::Map(indexmap! {
         "./utils/*".into() => ExportsField::Map(indexmap! {
           "browser".into() => ExportsField::Map(indexmap! {
-            "worklet".into() => ExportsField::Array(vec![ExportsField::String("./*"), ExportsField::String("./node/*")]),
+            "worklet".into() => ExportsField::Array(vec![ExportsField::String("./*".into()), ExportsField::String("./node/*".into())]),
             "default".into() => ExportsField::Map(indexmap! {
-              "node".into() => ExportsField::String("./node/*")
+              "node".into() => ExportsField::String("./node/*".into())
             })
           })
         }),
-        "./test/*".into() => ExportsField::Array(vec![ExportsField::String("lodash/*"), ExportsField::String("./bar/*")]),
-        "./file".into() => ExportsField::Array(vec![ExportsField::String("http://a.com"), ExportsField::String("./file.js")])
-      }),
+        "./test/*".into() => ExportsField::Array(vec![ExportsField::String("lodash/*".into()), ExportsField::String("./bar/*".into())]),
+        "./file".into() => ExportsField::Array(vec![ExportsField::String("http://a.com".into()), ExportsField::String("./file.js".into())])
+      }).into(),
       ..PackageJson::default()
     };
 
@@ -1322,10 +2780,11 @@ mod tests {
       name: "foobar",
       exports: ExportsField::Array(vec![
         ExportsField::Map(indexmap! {
-          "node".into() => ExportsField::String("./a.js")
+          "node".into() => ExportsField::String("./a.js".into())
         }),
-        ExportsField::String("./b.js"),
-      ]),
+        ExportsField::String("./b.js".into()),
+      ])
+      .into(),
       ..PackageJson::default()
     };
 
@@ -1343,21 +2802,111 @@ mod tests {
     );
   }
 
+  #[test]
+  fn exports_deeply_nested() {
+    // Matching uses an explicit stack, so deeply nested conditions can't overflow the call stack.
+    let mut exports = ExportsField::String("./deep.js".into());
+    for i in 0..2000 {
+      exports = if i % 2 == 0 {
+        ExportsField::Map(indexmap! { "default".into() => exports })
+      } else {
+        ExportsField::Array(vec![ExportsField::String("lodash".into()), exports])
+      };
+    }
+
+    let pkg = PackageJson {
+      path: "/foo/package.json".into(),
+      name: "foobar",
+      exports: exports.into(),
+      ..PackageJson::default()
+    };
+    assert_eq!(
+      pkg
+        .resolve_package_exports("", ExportsCondition::empty(), &[])
+        .unwrap(),
+      PathBuf::from("/foo/deep.js")
+    );
+    assert_eq!(pkg.exports_key_count(), 3000);
+    // The count is cached.
+    assert_eq!(pkg.exports_keys.get(), Some(&3000));
+  }
+
+  #[test]
+  fn exports_array_exists() {
+    let pkg = PackageJson {
+      path: "/foo/package.json".into(),
+      name: "foobar",
+      exports: ExportsField::Map(indexmap! {
+        ".".into() => ExportsField::Array(vec![ExportsField::String("./missing.js".into()), ExportsField::String("./main.js".into())]),
+        "./nested".into() => ExportsField::Map(indexmap! {
+          "import".into() => ExportsField::Array(vec![
+            ExportsField::Array(vec![ExportsField::String("./missing.mjs".into())]),
+            ExportsField::String("./nested.mjs".into())
+          ]),
+          "default".into() => ExportsField::String("./nested.cjs".into())
+        }),
+        "./none".into() => ExportsField::Array(vec![ExportsField::String("lodash".into()), ExportsField::String("./a.js".into()), ExportsField::String("./b.js".into())])
+      }).into(),
+      ..PackageJson::default()
+    };
+
+    let exists =
+      |path: &Path| path == Path::new("/foo/main.js") || path == Path::new("/foo/nested.mjs");
+    let resolve = |subpath, conditions| {
+      pkg.resolve_package_exports_compat(
+        subpath,
+        conditions,
+        &[],
+        ExportsOptions {
+          exists: Some(&exists),
+          ..Default::default()
+        },
+      )
+    };
+
+    assert_eq!(
+      resolve("", ExportsCondition::empty()).unwrap(),
+      PathBuf::from("/foo/main.js")
+    );
+    assert_eq!(
+      resolve("nested", ExportsCondition::IMPORT).unwrap(),
+      PathBuf::from("/foo/nested.mjs")
+    );
+    assert_eq!(
+      resolve("nested", ExportsCondition::REQUIRE).unwrap(),
+      PathBuf::from("/foo/nested.cjs")
+    );
+    // If no entries exist, the first valid target is returned.
+    assert_eq!(
+      resolve("none", ExportsCondition::empty()).unwrap(),
+      PathBuf::from("/foo/a.js")
+    );
+
+    // Without an existence check, only invalid targets are skipped.
+    assert_eq!(
+      pkg
+        .resolve_package_exports("", ExportsCondition::empty(), &[])
+        .unwrap(),
+      PathBuf::from("/foo/missing.js")
+    );
+  }
+
   #[test]
   fn exports_invalid() {
     let pkg = PackageJson {
       path: "/foo/package.json".into(),
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
-        "./invalid".into() => ExportsField::String("../invalid"),
-        "./absolute".into() => ExportsField::String("/absolute"),
-        "./package".into() => ExportsField::String("package"),
-        "./utils/index".into() => ExportsField::String("./src/../index.js"),
-        "./dist/*".into() => ExportsField::String("./src/../../*"),
-        "./modules/*".into() => ExportsField::String("./node_modules/*"),
-        "./modules2/*".into() => ExportsField::String("./NODE_MODULES/*"),
-        "./*/*".into() => ExportsField::String("./file.js")
-      }),
+        "./invalid".into() => ExportsField::String("../invalid".into()),
+        "./absolute".into() => ExportsField::String("/absolute".into()),
+        "./package".into() => ExportsField::String("package".into()),
+        "./utils/index".into() => ExportsField::String("./src/../index.js".into()),
+        "./dist/*".into() => ExportsField::String("./src/../../*".into()),
+        "./modules/*".into() => ExportsField::String("./node_modules/*".into()),
+        "./modules2/*".into() => ExportsField::String("./NODE_MODULES/*".into()),
+        "./*/*".into() => ExportsField::String("./file.js".into())
+      })
+      .into(),
       ..PackageJson::default()
     };
 
@@ -1394,13 +2943,57 @@ mod tests {
       Err(PackageJsonError::PackagePathNotExported)
     ));
 
+    // Wildcards may not capture ".", "..", or "node_modules" segments, even percent encoded.
+    let pkg = PackageJson {
+      path: "/foo/package.json".into(),
+      name: "foobar",
+      exports: ExportsField::Map(indexmap! {
+        "./lib/*".into() => ExportsField::String("./lib/*".into()),
+      })
+      .into(),
+      imports: indexmap! {
+        "#dep/*".into() => ExportsField::String("dep/*".into()),
+      }
+      .into(),
+      ..PackageJson::default()
+    };
+
+    for subpath in [
+      "lib/../../other-pkg/file.js",
+      "lib/a/../b.js",
+      "lib/./a.js",
+      "lib/a\\..\\b.js",
+      "lib/%2e%2e/other-pkg/file.js",
+      "lib/%2E/a.js",
+      "lib/NODE_MODULES/a.js",
+      "lib/%6eode_modules/a.js",
+    ] {
+      assert_eq!(
+        pkg.resolve_package_exports(subpath, ExportsCondition::empty(), &[]),
+        Err(PackageJsonError::InvalidSpecifier),
+        "{}",
+        subpath
+      );
+    }
+    assert_eq!(
+      pkg
+        .resolve_package_exports("lib/a..b/..c.js", ExportsCondition::empty(), &[])
+        .unwrap(),
+      PathBuf::from("/foo/lib/a..b/..c.js")
+    );
+    assert_eq!(
+      pkg.resolve_package_imports("dep/../other-pkg", ExportsCondition::empty(), &[]),
+      Err(PackageJsonError::InvalidSpecifier)
+    );
+
     let pkg = PackageJson {
       path: "/foo/package.json".into(),
       name: "foobar",
       exports: ExportsField::Map(indexmap! {
-        ".".into() => ExportsField::String("./foo.js"),
-        "node".into() => ExportsField::String("./bar.js"),
-      }),
+        ".".into() => ExportsField::String("./foo.js".into()),
+        "node".into() => ExportsField::String("./bar.js".into()),
+      })
+      .into(),
       ..PackageJson::default()
     };
 
@@ -1420,10 +3013,11 @@ mod tests {
       path: "/foo/package.json".into(),
       name: "foobar",
       imports: indexmap! {
-        "#foo".into() => ExportsField::String("./foo.mjs"),
-        "#internal/*".into() => ExportsField::String("./src/internal/*.mjs"),
-        "#bar".into() => ExportsField::String("bar"),
-      },
+        "#foo".into() => ExportsField::String("./foo.mjs".into()),
+        "#internal/*".into() => ExportsField::String("./src/internal/*.mjs".into()),
+        "#bar".into() => ExportsField::String("bar".into()),
+      }
+      .into(),
       ..PackageJson::default()
     };
 
@@ -1447,6 +3041,75 @@ mod tests {
     );
   }
 
+  #[test]
+  fn imports_pattern_conditions() {
+    let pkg = PackageJson {
+      path: "/foo/package.json".into(),
+      name: "foobar",
+      imports: indexmap! {
+        "#internal/*".into() => ExportsField::Map(indexmap! {
+          "node-addons".into() => ExportsField::String("./native/*.node".into()),
+          "node".into() => ExportsField::Map(indexmap! {
+            "import".into() => ExportsField::String("./node/*.mjs".into()),
+            "require".into() => ExportsField::String("./node/*.cjs".into()),
+          }),
+          "custom".into() => ExportsField::String("./custom/*.js".into()),
+          "default".into() => ExportsField::String("./default/*.js".into()),
+        }),
+      }
+      .into(),
+      ..PackageJson::default()
+    };
+
+    let resolve = |specifier, conditions, custom_conditions: &[String]| {
+      pkg
+        .resolve_package_imports(specifier, conditions, custom_conditions)
+        .unwrap()
+    };
+    let path = |path: &str| ExportsResolution::Path(PathBuf::from(path));
+    let node = ExportsCondition::NODE;
+    let custom = ["custom".to_owned()];
+
+    // Conditions are matched in object order, not by the order they are enabled in.
+    assert_eq!(
+      resolve(
+        "internal/foo",
+        node | ExportsCondition::NODE_ADDONS | ExportsCondition::IMPORT,
+        &custom
+      ),
+      path("/foo/native/foo.node")
+    );
+    assert_eq!(
+      resolve("internal/foo", node | ExportsCondition::IMPORT, &custom),
+      path("/foo/node/foo.mjs")
+    );
+    assert_eq!(
+      resolve("internal/a/b", node | ExportsCondition::REQUIRE, &[]),
+      path("/foo/node/a/b.cjs")
+    );
+    // A matching condition whose nested conditions don't match falls through to the next one.
+    assert_eq!(
+      resolve("internal/foo", node, &custom),
+      path("/foo/custom/foo.js")
+    );
+    assert_eq!(
+      resolve("internal/foo", node, &[]),
+      path("/foo/default/foo.js")
+    );
+    assert_eq!(
+      resolve("internal/foo", ExportsCondition::IMPORT, &custom),
+      path("/foo/custom/foo.js")
+    );
+    assert_eq!(
+      resolve("internal/foo", ExportsCondition::empty(), &[]),
+      path("/foo/default/foo.js")
+    );
+    assert_eq!(
+      pkg.resolve_package_imports("other", node, &[]),
+      Err(PackageJsonError::ImportNotDefined)
+    );
+  }
+
   #[test]
   fn import_conditions() {
     let pkg = PackageJson {
@@ -1454,10 +3117,11 @@ mod tests {
       name: "foobar",
       imports: indexmap! {
         "#entry/*".into() => ExportsField::Map(indexmap! {
-          "node".into() => ExportsField::String("./node/*.js"),
-          "browser".into() => ExportsField::String("./browser/*.js")
+          "node".into() => ExportsField::String("./node/*.js".into()),
+          "browser".into() => ExportsField::String("./browser/*.js".into())
         })
-      },
+      }
+      .into(),
       ..PackageJson::default()
     };
     assert_eq!(
@@ -1507,65 +3171,89 @@ mod tests {
     };
 
     assert_eq!(
-      pkg.resolve_aliases(&"./foo.js".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"./foo.js".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("./foo-alias.js".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"bar".into(), Fields::ALIAS),
+      pkg.resolve_aliases(&"bar".into(), Fields::ALIAS).unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("./bar-alias.js".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"lodash".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"lodash".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("my-lodash".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"lodash/foo".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"lodash/foo".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("my-lodash/foo".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"lodash/clone".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"lodash/clone".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("./clone.js".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"test".into(), Fields::ALIAS),
+      pkg.resolve_aliases(&"test".into(), Fields::ALIAS).unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("./test".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"test/foo".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"test/foo".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("./test/foo".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"foo/hi".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"foo/hi".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("bar/hi".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"./foo/src/a/b".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"./foo/src/a/b".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("./foo/lib/a/b".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"/foo/src/a/b".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"/foo/src/a/b".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("/foo/lib/a/b".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"~/foo/src/a/b".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"~/foo/src/a/b".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("~/foo/lib/a/b".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"url".into(), Fields::ALIAS),
+      pkg.resolve_aliases(&"url".into(), Fields::ALIAS).unwrap(),
       Some(Cow::Owned(AliasValue::Bool(false)))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"@internal/foo".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"@internal/foo".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("./internal/foo".into())))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"@internal/foo/bar".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"@internal/foo/bar".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier(
         "./internal/foo/bar".into()
       )))
     );
     assert_eq!(
-      pkg.resolve_aliases(&"@foo/a/bar/b".into(), Fields::ALIAS),
+      pkg
+        .resolve_aliases(&"@foo/a/bar/b".into(), Fields::ALIAS)
+        .unwrap(),
       Some(Cow::Owned(AliasValue::Specifier("./test/a/b".into())))
     );
   }
@@ -1690,4 +3378,340 @@ mod tests {
     assert!(!pkg.has_side_effects(Path::new("/foo/bar/baz.js")));
     assert!(pkg.has_side_effects(Path::new("/index.js")));
   }
+
+  #[test]
+  fn validate_exports() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::MemoryFileSystem;
+
+    let fs = MemoryFileSystem::from_files([
+      ("/pkg/dist/index.js", ""),
+      ("/pkg/dist/utils/a.js", ""),
+      ("/pkg/dist/utils/b.js", ""),
+      ("/pkg/node_modules/dep/index.js", ""),
+    ]);
+    let path = PathBuf::from("/pkg/package.json");
+
+    let data = r#"{
+      "exports": {
+        ".": {
+          "import": "./dist/index.mjs",
+          "default": "./dist/index.js",
+          "require": "./dist/index.cjs"
+        },
+        "./utils/*": "./dist/utils/*.js",
+        "./components/*": "./dist/components/*.js",
+        "./dep": "./node_modules/dep/index.js",
+        "./outside": "../outside.js",
+        "./node": {
+          "node": "./dist/index.js"
+        },
+        "./nested": {
+          "./foo": "./dist/index.js",
+          "default": "./dist/index.js"
+        }
+      }
+    }"#;
+    let pkg = PackageJson::parse(path.clone(), data)?;
+    assert_eq!(
+      pkg.validate_exports(&fs),
+      vec![
+        ExportsLintIssue::MissingTarget {
+          key: ".".into(),
+          target: "./dist/index.mjs".into()
+        },
+        ExportsLintIssue::ShadowedKey {
+          key: ".".into(),
+          condition: "require".into()
+        },
+        ExportsLintIssue::MissingTarget {
+          key: "./components/*".into(),
+          target: "./dist/components/*.js".into()
+        },
+        ExportsLintIssue::InvalidTarget {
+          key: "./dep".into(),
+          target: "./node_modules/dep/index.js".into()
+        },
+        ExportsLintIssue::InvalidTarget {
+          key: "./outside".into(),
+          target: "../outside.js".into()
+        },
+        ExportsLintIssue::MissingDefault {
+          key: "./node".into()
+        },
+        ExportsLintIssue::InvalidKey {
+          key: "./foo".into()
+        },
+      ]
+    );
+
+    let pkg = PackageJson::parse(path.clone(), r#"{"exports": "./dist/index.js"}"#)?;
+    assert_eq!(pkg.validate_exports(&fs), vec![]);
+
+    let pkg = PackageJson::parse(
+      path.clone(),
+      r#"{"exports": {"import": "./dist/index.js", ".": "./dist/index.js"}}"#,
+    )?;
+    assert_eq!(
+      pkg.validate_exports(&fs),
+      vec![ExportsLintIssue::InvalidKey {
+        key: "import".into()
+      }]
+    );
+
+    let pkg = PackageJson::parse(
+      path,
+      r#"{
+        "exports": {
+          "./utils/*/*": "./dist/utils/*/*.js",
+          "./legacy/": "./dist/",
+          "./utils/*": "./dist/utils/*.js"
+        }
+      }"#,
+    )?;
+    assert_eq!(
+      pkg.validate_exports(&fs),
+      vec![
+        ExportsLintIssue::UnreachableKey {
+          key: "./utils/*/*".into()
+        },
+        ExportsLintIssue::UnreachableKey {
+          key: "./legacy/".into()
+        },
+      ]
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn validate_exports_coverage() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::MemoryFileSystem;
+
+    let fs = MemoryFileSystem::from_files([
+      ("/pkg/package.json", ""),
+      ("/pkg/README.md", ""),
+      ("/pkg/LICENSE", ""),
+      ("/pkg/dist/index.js", ""),
+      ("/pkg/dist/index.d.ts", ""),
+      ("/pkg/dist/index.js.map", ""),
+      ("/pkg/dist/internal.js", ""),
+      ("/pkg/dist/utils/a.js", ""),
+      ("/pkg/dist/utils/b.js", ""),
+      ("/pkg/dist/assets/logo.svg", ""),
+      ("/pkg/dist/test/index.test.js", ""),
+      ("/pkg/src/index.ts", ""),
+      ("/pkg/node_modules/dep/index.js", ""),
+    ]);
+    let path = PathBuf::from("/pkg/package.json");
+
+    let pkg = PackageJson::parse(
+      path.clone(),
+      r#"{
+        "files": ["dist", "!dist/test"],
+        "exports": {
+          ".": {"types": "./dist/index.d.ts", "default": "./dist/index.js"},
+          "./utils/*": "./dist/utils/*.js",
+          "./assets/*": "./dist/assets/*"
+        }
+      }"#,
+    )?;
+    assert_eq!(
+      pkg
+        .validate_exports(&fs)
+        .into_iter()
+        .filter(|issue| matches!(issue, ExportsLintIssue::UncoveredFile { .. }))
+        .collect::<Vec<_>>(),
+      vec![ExportsLintIssue::UncoveredFile {
+        path: "./dist/internal.js".into()
+      }]
+    );
+
+    // Without a "files" field, it isn't known which files are public.
+    let pkg = PackageJson::parse(path, r#"{"exports": "./dist/index.js"}"#)?;
+    assert_eq!(pkg.validate_exports(&fs), vec![]);
+
+    Ok(())
+  }
+
+  #[test]
+  fn module_type() {
+    let parse = |json| PackageJson::parse(PathBuf::from("/foo/package.json"), json).unwrap();
+    assert_eq!(
+      parse(r#"{"type": "module"}"#).module_type(),
+      Some(ModuleType::Module)
+    );
+    assert_eq!(
+      parse(r#"{"type": "commonjs"}"#).module_type(),
+      Some(ModuleType::CommonJs)
+    );
+    assert_eq!(parse(r#"{}"#).module_type(), None);
+    assert_eq!(parse(r#"{"type": "other"}"#).module_type(), None);
+
+    let order = |module_type: ModuleType| {
+      let mut extensions = vec!["ts", "mjs", "js", "cjs", "json"];
+      module_type.order_extensions(&mut extensions);
+      extensions
+    };
+    assert_eq!(
+      order(ModuleType::Module),
+      vec!["ts", "mjs", "js", "cjs", "json"]
+    );
+    assert_eq!(
+      order(ModuleType::CommonJs),
+      vec!["ts", "js", "cjs", "mjs", "json"]
+    );
+  }
+
+  #[test]
+  fn custom_entries() {
+    let pkg = PackageJson::parse(
+      PathBuf::from("/foo/package.json"),
+      r#"{
+        "name": "foobar",
+        "main": "index.js",
+        "dependencies": {"svelte": "^4.0.0"},
+        "svelte": "src/index.js",
+        "sass": "scss/_index.scss",
+        "style": {"./a.css": "./b.css"},
+        "unpkg": 1,
+        "react-native": {"foobar": "./native.js", "./lib/fs.js": "./lib/fs-native.js", "fs": false}
+      }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+      pkg.custom_entry("svelte"),
+      Some(PathBuf::from("/foo/src/index.js"))
+    );
+    assert_eq!(
+      pkg.custom_entry("sass"),
+      Some(PathBuf::from("/foo/scss/_index.scss"))
+    );
+    assert_eq!(
+      pkg.custom_entry("react-native"),
+      Some(PathBuf::from("/foo/native.js"))
+    );
+    // Only "react-native" and "svelte" may be objects, and other types are ignored.
+    assert_eq!(pkg.custom_entry("style"), None);
+    assert_eq!(pkg.custom_entry("unpkg"), None);
+    assert_eq!(pkg.custom_entry("missing"), None);
+    // Nested keys aren't top-level fields.
+    assert_eq!(pkg.custom_entry("dependencies"), None);
+
+    assert_eq!(
+      pkg.resolve_custom_aliases(&"./lib/fs.js".into(), "react-native"),
+      Some(Cow::Owned(AliasValue::Specifier(
+        "./lib/fs-native.js".into()
+      )))
+    );
+    assert_eq!(
+      pkg.resolve_custom_aliases(&"fs".into(), "react-native"),
+      Some(Cow::Owned(AliasValue::Bool(false)))
+    );
+    assert_eq!(pkg.resolve_custom_aliases(&"fs".into(), "svelte"), None);
+    assert_eq!(pkg.resolve_custom_aliases(&"./a.css".into(), "style"), None);
+  }
+
+  #[test]
+  fn exported_subpath() {
+    let pkg = PackageJson::parse(
+      "/foo/package.json".into(),
+      r#"{
+        "name": "foo",
+        "exports": {
+          ".": "./src/index.js",
+          "./button": "./src/components/button.js",
+          "./components/*": "./src/components/*.js",
+          "./components/internal/*": null,
+          "./styles/*": {"import": "./dist/*.mjs", "default": "./dist/*.css"}
+        }
+      }"#,
+    )
+    .unwrap();
+    let subpath = |path: &str, conditions| pkg.exported_subpath(Path::new(path), conditions, &[]);
+
+    assert_eq!(
+      subpath("/foo/src/index.js", ExportsCondition::empty()),
+      Some(".".into())
+    );
+    // Exact subpaths are preferred over patterns that map to the same file.
+    assert_eq!(
+      subpath("/foo/src/components/button.js", ExportsCondition::empty()),
+      Some("./button".into())
+    );
+    assert_eq!(
+      subpath(
+        "/foo/src/components/menu/item.js",
+        ExportsCondition::empty()
+      ),
+      Some("./components/menu/item".into())
+    );
+    // Blocked by a more specific key.
+    assert_eq!(
+      subpath(
+        "/foo/src/components/internal/a.js",
+        ExportsCondition::empty()
+      ),
+      None
+    );
+    assert_eq!(
+      subpath("/foo/dist/theme.mjs", ExportsCondition::IMPORT),
+      Some("./styles/theme".into())
+    );
+    assert_eq!(
+      subpath("/foo/dist/theme.mjs", ExportsCondition::empty()),
+      None
+    );
+    assert_eq!(
+      subpath("/foo/src/other.ts", ExportsCondition::empty()),
+      None
+    );
+  }
+
+  #[test]
+  fn lazy_fields() {
+    let data = concat!(
+      "{\n",
+      "  \"name\": \"foobar\",\n",
+      "  \"keywords\": [\"a\", \"b\"],\n",
+      "  \"browser\": 1,\n",
+      "  \"exports\": 1,\n",
+      "  \"imports\": {\"#foo\": \"./foo.js\"}\n",
+      "}"
+    );
+    let pkg = PackageJson::parse(PathBuf::from("/foo/package.json"), data).unwrap();
+
+    assert_eq!(
+      pkg
+        .resolve_package_imports("foo", ExportsCondition::empty(), &[])
+        .unwrap(),
+      ExportsResolution::Path(PathBuf::from("/foo/foo.js"))
+    );
+    // Invalid "browser" and "exports" fields are reported when used, at their position in the
+    // file.
+    assert_eq!(
+      pkg.resolve_aliases(&"./foo.js".into(), Fields::BROWSER),
+      Err(PackageJsonError::InvalidField(JsonError {
+        path: "/foo/package.json".into(),
+        line: 4,
+        column: 14,
+        message: "data did not match any variant of untagged enum BrowserField at line 4 column 14"
+          .into(),
+      }))
+    );
+    assert!(pkg.entries(Fields::MAIN | Fields::BROWSER).is_err());
+    assert!(pkg.entries(Fields::MAIN).is_ok());
+    assert!(pkg.has_exports());
+    assert_eq!(
+      pkg.resolve_package_exports("", ExportsCondition::empty(), &[]),
+      Err(PackageJsonError::InvalidField(JsonError {
+        path: "/foo/package.json".into(),
+        line: 5,
+        column: 14,
+        message:
+          "invalid type: integer `1`, expected a string, array, object, or null at line 5 column 14"
+            .into(),
+      }))
+    );
+  }
 }
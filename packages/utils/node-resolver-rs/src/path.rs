@@ -1,4 +1,5 @@
 use dashmap::DashMap;
+use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::path::{Component, Path, PathBuf};
 
@@ -57,6 +58,60 @@ pub fn resolve_path<A: AsRef<Path>, B: AsRef<Path>>(base: A, subpath: B) -> Path
   ret
 }
 
+/// Computes the relative path from the directory `from` to `to`, using ".." segments
+/// where needed. Both paths are normalized first. Returns `None` if they have different
+/// roots (e.g. Windows drive prefixes), in which case no relative path exists.
+pub fn relative_path(from: &Path, to: &Path) -> Option<PathBuf> {
+  let from = normalize_path(from);
+  let to = normalize_path(to);
+  let mut from_components = from.components().peekable();
+  let mut to_components = to.components().peekable();
+  if matches!(
+    from_components.peek(),
+    Some(Component::Prefix(..) | Component::RootDir)
+  ) != matches!(
+    to_components.peek(),
+    Some(Component::Prefix(..) | Component::RootDir)
+  ) {
+    return None;
+  }
+
+  while let (Some(a), Some(b)) = (from_components.peek(), to_components.peek()) {
+    if a != b {
+      break;
+    }
+    from_components.next();
+    to_components.next();
+  }
+
+  let mut ret = PathBuf::new();
+  for component in from_components {
+    if matches!(component, Component::Prefix(..) | Component::RootDir) {
+      return None;
+    }
+    ret.push("..");
+  }
+  for component in to_components {
+    if matches!(component, Component::Prefix(..) | Component::RootDir) {
+      return None;
+    }
+    ret.push(component);
+  }
+
+  Some(ret)
+}
+
+/// Returns whether the path ends with a "/", meaning it can only refer to a directory.
+pub fn has_trailing_slash(path: &Path) -> bool {
+  path.as_os_str().to_str().is_some_and(|s| s.ends_with('/'))
+}
+
+/// Returns whether a relative path can only refer to a directory, i.e. `.`, `..`,
+/// or a path ending with a "/". An empty path (e.g. from `./`) is the current directory.
+pub fn is_directory_path(path: &Path) -> bool {
+  matches!(path.as_os_str().to_str(), Some("" | "." | "..")) || has_trailing_slash(path)
+}
+
 // A reimplementation of std::fs::canonicalize with intermediary caching.
 pub fn canonicalize(
   path: &Path,
@@ -66,7 +121,9 @@ pub fn canonicalize(
   let mut seen_links = 0;
   let mut queue = VecDeque::new();
 
-  queue.push_back(path);
+  // Links are cloned out of the cache, since entries may be removed by `Cache::invalidate_path`
+  // while a path is being canonicalized.
+  queue.push_back(Cow::Borrowed(path));
 
   while let Some(cur_path) = queue.pop_front() {
     let mut components = cur_path.components();
@@ -84,11 +141,9 @@ pub fn canonicalize(
           ret.push(c);
 
           // First, check the cache for the path up to this point.
-          let link: &Path = if let Some(cached) = cache.get(&ret) {
+          let link = if let Some(cached) = cache.get(&ret) {
             if let Some(link) = &*cached {
-              // SAFETY: Keys are never removed from the cache or mutated
-              // and PathBuf has a stable address for path data even when moved.
-              unsafe { &*(link.as_path() as *const _) }
+              link.clone()
             } else {
               continue;
             }
@@ -100,9 +155,8 @@ pub fn canonicalize(
             }
 
             let link = std::fs::read_link(&ret)?;
-            let ptr = unsafe { &*(link.as_path() as *const _) };
-            cache.insert(ret.clone(), Some(link));
-            ptr
+            cache.insert(ret.clone(), Some(link.clone()));
+            link
           };
 
           seen_links += 1;
@@ -124,9 +178,9 @@ pub fn canonicalize(
 
           let remaining = components.as_path();
           if !remaining.as_os_str().is_empty() {
-            queue.push_front(remaining);
+            queue.push_front(Cow::Owned(remaining.to_owned()));
           }
-          queue.push_front(link);
+          queue.push_front(Cow::Owned(link));
           break;
         }
       }
@@ -0,0 +1,135 @@
+use std::borrow::Cow;
+
+use url::form_urlencoded;
+
+/// The query string of a specifier, e.g. `?foo=bar`. The raw string, including the leading "?",
+/// is kept as is for callers that need to pass it through exactly. Key/value pairs are parsed
+/// as `application/x-www-form-urlencoded` on demand.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Query<'a> {
+  raw: Cow<'a, str>,
+}
+
+impl<'a> Query<'a> {
+  pub fn new<S: Into<Cow<'a, str>>>(raw: S) -> Self {
+    Query { raw: raw.into() }
+  }
+
+  /// Returns the query string exactly as written, including the leading "?".
+  pub fn raw(&self) -> &str {
+    &self.raw
+  }
+
+  pub fn into_owned(self) -> Query<'static> {
+    Query {
+      raw: Cow::Owned(self.raw.into_owned()),
+    }
+  }
+
+  /// Iterates over the decoded key/value pairs, in the order they were written.
+  pub fn iter(&self) -> form_urlencoded::Parse<'_> {
+    let query = self.raw.strip_prefix('?').unwrap_or(&self.raw);
+    form_urlencoded::parse(query.as_bytes())
+  }
+
+  /// Returns the decoded value of the first pair with the given key.
+  pub fn get(&self, key: &str) -> Option<Cow<'_, str>> {
+    self.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+  }
+
+  /// Returns a canonical form of the query with pairs sorted by key, e.g. for use in cache keys.
+  /// The order of pairs with the same key is preserved, since it may be significant.
+  pub fn normalized(&self) -> String {
+    let mut pairs: Vec<_> = self.iter().collect();
+    pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut serializer = form_urlencoded::Serializer::for_suffix(String::from("?"), 1);
+    serializer.extend_pairs(pairs);
+    serializer.finish()
+  }
+
+  /// Removes the pairs with any of the given keys, e.g. cache-busting parameters like `v` or `t`.
+  /// The remaining pairs are kept exactly as written. Returns `None` if no pairs remain.
+  pub fn without_keys<S: AsRef<str>>(self, keys: &[S]) -> Option<Query<'a>> {
+    let is_ignored = |pair: &str| {
+      form_urlencoded::parse(pair.as_bytes())
+        .next()
+        .is_some_and(|(key, _)| keys.iter().any(|k| k.as_ref() == key))
+    };
+
+    let query = self.raw.strip_prefix('?').unwrap_or(&self.raw);
+    if !query.split('&').any(is_ignored) {
+      return Some(self);
+    }
+
+    let kept: Vec<&str> = query
+      .split('&')
+      .filter(|pair| !pair.is_empty() && !is_ignored(pair))
+      .collect();
+    if kept.is_empty() {
+      return None;
+    }
+
+    Some(Query::new(format!("?{}", kept.join("&"))))
+  }
+
+  /// Returns whether two queries contain the same pairs, ignoring the order of keys.
+  pub fn eq_ignore_order(&self, other: &Query) -> bool {
+    self.normalized() == other.normalized()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_query() {
+    let query = Query::new("?foo=bar&a+b=c%20d&empty&foo=baz");
+    assert_eq!(query.raw(), "?foo=bar&a+b=c%20d&empty&foo=baz");
+    assert_eq!(query.get("foo").as_deref(), Some("bar"));
+    assert_eq!(query.get("a b").as_deref(), Some("c d"));
+    assert_eq!(query.get("empty").as_deref(), Some(""));
+    assert_eq!(query.get("missing"), None);
+    assert_eq!(
+      query
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>(),
+      vec!["foo=bar", "a b=c d", "empty=", "foo=baz"]
+    );
+  }
+
+  #[test]
+  fn test_normalized() {
+    let a = Query::new("?a=1&b=2");
+    let b = Query::new("?b=2&a=1");
+    assert_ne!(a, b);
+    assert!(a.eq_ignore_order(&b));
+    assert_eq!(b.normalized(), "?a=1&b=2");
+
+    // Repeated keys keep their relative order.
+    assert_eq!(Query::new("?b=1&a=2&b=0").normalized(), "?a=2&b=1&b=0");
+    assert!(!Query::new("?a=1&a=2").eq_ignore_order(&Query::new("?a=2&a=1")));
+  }
+
+  #[test]
+  fn test_without_keys() {
+    let without = |query, keys: &[&str]| {
+      Query::new(query)
+        .without_keys(keys)
+        .map(|q| q.raw().to_owned())
+    };
+    assert_eq!(without("?v=abc&real=1", &["v"]), Some("?real=1".into()));
+    assert_eq!(
+      without("?t=123&a=1&v=abc&b=%20", &["v", "t"]),
+      Some("?a=1&b=%20".into())
+    );
+    assert_eq!(without("?v=abc", &["v"]), None);
+    assert_eq!(without("?v&t=1", &["v", "t"]), None);
+    // Keys are compared after decoding.
+    assert_eq!(without("?%76=1&x=2", &["v"]), Some("?x=2".into()));
+    // Queries without ignored keys are returned as written.
+    assert_eq!(without("?a=1&&b", &["v"]), Some("?a=1&&b".into()));
+    assert_eq!(without("?a=1", &[]), Some("?a=1".into()));
+  }
+}
@@ -1,12 +1,22 @@
-use crate::{builtins::BUILTINS, Flags};
-use percent_encoding::percent_decode_str;
+use crate::{
+  builtins::BUILTINS,
+  package_json::{best_pattern_match, deserialize_str},
+  path::{has_trailing_slash, is_directory_path, normalize_path, relative_path},
+  Flags,
+};
+use memchr::memchr;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
 use std::{
   borrow::Cow,
-  path::{is_separator, Path, PathBuf},
+  collections::HashMap,
+  fmt,
+  hash::{Hash, Hasher},
+  path::{is_separator, Component, Path, PathBuf},
 };
 use url::Url;
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SpecifierType {
   Esm,
   Cjs,
@@ -21,6 +31,37 @@ pub enum SpecifierError {
   #[serde(serialize_with = "serialize_url_error")]
   UrlError(url::ParseError),
   InvalidFileUrl,
+  UnknownScheme(String),
+  /// The path contains a NUL byte, or a percent-escape that doesn't decode to valid UTF-8
+  /// (e.g. an encoded UTF-16 surrogate). Holds the byte offset of the offending character.
+  InvalidPath(usize),
+  /// A hand-built `Specifier::Builtin` that isn't one of `BUILTINS`, see `Specifier::validate`.
+  UnknownBuiltin(String),
+  /// The path contains a percent-encoded separator (`%2F` or `%5C`), which Node ESM rejects.
+  /// Only reported with `Flags::REJECT_ENCODED_SEPARATORS`. Holds the byte offset of the escape.
+  EncodedSeparator(usize),
+  /// A package specifier was parsed with `Flags::ONLY_BUILTINS`. Holds the package name.
+  PackagesNotAllowed(String),
+}
+
+impl fmt::Display for SpecifierError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SpecifierError::EmptySpecifier => write!(f, "the specifier is empty"),
+      SpecifierError::InvalidPackageSpecifier => write!(f, "invalid package name"),
+      SpecifierError::UrlError(error) => write!(f, "{}", error),
+      SpecifierError::InvalidFileUrl => write!(f, "invalid file URL"),
+      SpecifierError::UnknownScheme(scheme) => write!(f, "unknown scheme '{}:'", scheme),
+      SpecifierError::InvalidPath(pos) => write!(f, "invalid character at offset {}", pos),
+      SpecifierError::UnknownBuiltin(name) => write!(f, "'{}' is not a builtin module", name),
+      SpecifierError::EncodedSeparator(pos) => {
+        write!(f, "encoded path separator at offset {}", pos)
+      }
+      SpecifierError::PackagesNotAllowed(name) => {
+        write!(f, "'{}' is a package, but only builtins are allowed", name)
+      }
+    }
+  }
 }
 
 impl From<url::ParseError> for SpecifierError {
@@ -37,55 +78,292 @@ where
   value.to_string().serialize(serializer)
 }
 
+/// Embedder-provided configuration for parsing specifiers.
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+  /// Prefixes of virtual modules provided by frameworks, e.g. `$app/` or `astro:`.
+  /// These are matched literally, so include a trailing `/` or `:` to match whole segments only.
+  pub virtual_prefixes: Vec<String>,
+  /// Additional URL schemes that are allowed in ESM and CommonJS specifiers, e.g. `https`.
+  /// These and `data` URLs resolve to `Resolution::External`. Other than `npm`, `node`, and
+  /// `file`, unknown schemes are an error in ESM.
+  pub external_schemes: Vec<String>,
+  /// Custom schemes mapped to directories, e.g. `mylib:components/x` resolves to `components/x`
+  /// within the mapped directory. Schemes are matched exactly, and may be any prefix before ":".
+  pub scheme_aliases: HashMap<String, PathBuf>,
+  /// Typed import prefixes mapped to the import type they indicate, e.g. `text:./readme.md`
+  /// is parsed as `./readme.md` with the type `text`. Only used by `Specifier::parse_typed`.
+  pub typed_schemes: HashMap<String, String>,
+  /// Whether to strip a leading byte order mark (`U+FEFF`) and zero-width characters before
+  /// classifying a specifier, e.g. when they were copied along with it. Off by default, since
+  /// they are otherwise significant.
+  pub strip_invisible_prefix: bool,
+  /// Query keys to drop, e.g. cache-busting parameters like `v` or `t` appended by dev tooling.
+  /// `Specifier::parse_with_options` returns the query as written, but these keys are removed
+  /// from the `Query` returned by the resolver and `SpecifierCache`, and from resolution cache keys.
+  pub ignored_query_keys: Vec<String>,
+}
+
+/// Extensions made of several parts, returned whole by `Specifier::extension` with `compound`.
+const COMPOUND_EXTENSIONS: &[&str] = &["d.ts", "d.mts", "d.cts", "tar.gz", "tar.bz2", "tar.xz"];
+
+/// Characters that are invisible in most editors, and may be copied along with a specifier.
+const INVISIBLE_CHARS: &[char] = &['\u{FEFF}', '\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}'];
+
+/// Strips a leading byte order mark and zero-width characters from a specifier.
+pub fn strip_invisible_prefix(specifier: &str) -> &str {
+  specifier.trim_start_matches(INVISIBLE_CHARS)
+}
+
+/// A specifier type and flags, configured once and reused to parse many specifiers.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct ParseContext {
+  pub specifier_type: SpecifierType,
+  pub flags: Flags,
+}
+
+impl ParseContext {
+  pub fn new(specifier_type: SpecifierType, flags: Flags) -> Self {
+    ParseContext {
+      specifier_type,
+      flags,
+    }
+  }
+
+  /// Parses a specifier, equivalent to `Specifier::parse` with this context's type and flags.
+  pub fn parse<'a>(
+    &self,
+    specifier: &'a str,
+  ) -> Result<(Specifier<'a>, Option<&'a str>), SpecifierError> {
+    Specifier::parse(specifier, self.specifier_type, self.flags)
+  }
+}
+
 #[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub enum Specifier<'a> {
   Relative(Cow<'a, Path>),
   Absolute(Cow<'a, Path>),
+  /// A path relative to the project root, e.g. `/src/index.js` with `Flags::ROOT_ABSOLUTE`.
+  /// The leading "/" is not included.
+  RootRelative(Cow<'a, Path>),
   Tilde(Cow<'a, Path>),
   Hash(Cow<'a, str>),
   Package(Cow<'a, str>, Cow<'a, str>),
   Builtin(Cow<'a, str>),
-  Url(&'a str),
+  Url(Cow<'a, str>),
+  Virtual(Cow<'a, str>),
+  /// A specifier using a scheme from `ParseOptions::scheme_aliases`, with the path after the ":".
+  SchemeAlias {
+    scheme: Cow<'a, str>,
+    rest: Cow<'a, Path>,
+  },
 }
 
+/// The result of parsing a specifier and its query, see `Specifier::parse_both`.
+type Parsed<'a> = Result<(Specifier<'a>, Option<&'a str>), SpecifierError>;
+
 impl<'a> Specifier<'a> {
   pub fn parse(
     specifier: &'a str,
     specifier_type: SpecifierType,
     flags: Flags,
+  ) -> Result<(Specifier<'a>, Option<&'a str>), SpecifierError> {
+    Specifier::parse_with_options(specifier, specifier_type, flags, &ParseOptions::default())
+  }
+
+  /// Parses a specifier as both ESM and CommonJS, returning the ESM result first, e.g. for
+  /// diagnostics that flag specifiers whose meaning changes when migrating between them. Bare
+  /// words differ when they contain a query or percent-escape, which only ESM splits off or
+  /// decodes, and Windows absolute paths are only supported in CommonJS.
+  pub fn parse_both(specifier: &'a str, flags: Flags) -> (Parsed<'a>, Parsed<'a>) {
+    (
+      Specifier::parse(specifier, SpecifierType::Esm, flags),
+      Specifier::parse(specifier, SpecifierType::Cjs, flags),
+    )
+  }
+
+  /// Like `parse_with_options`, but also returns details about how the specifier was parsed,
+  /// e.g. for lint rules. This does extra work, so prefer `parse_with_options` when they
+  /// aren't needed.
+  pub fn parse_with_meta(
+    specifier: &'a str,
+    specifier_type: SpecifierType,
+    flags: Flags,
+    options: &ParseOptions,
+  ) -> Result<(Specifier<'a>, Option<&'a str>, SpecifierMeta), SpecifierError> {
+    let (parsed, query) = Specifier::parse_with_options(specifier, specifier_type, flags, options)?;
+    // CommonJS specifiers are never decoded, and only paths and packages are decoded otherwise.
+    let decoded = specifier_type != SpecifierType::Cjs
+      && matches!(
+        parsed,
+        Specifier::Relative(_)
+          | Specifier::Absolute(_)
+          | Specifier::RootRelative(_)
+          | Specifier::Tilde(_)
+          | Specifier::Package(..)
+          | Specifier::SchemeAlias { .. }
+      )
+      && has_percent_escape(parse_path(specifier).0);
+
+    let ambiguous = parsed.is_ambiguous(specifier_type, flags);
+    Ok((parsed, query, SpecifierMeta { decoded, ambiguous }))
+  }
+
+  /// Returns whether a bare specifier may refer to a local file, i.e. an unscoped ESM package
+  /// specifier with `Flags::IMPLICIT_RELATIVE`. Builtins are never ambiguous.
+  pub fn is_ambiguous(&self, specifier_type: SpecifierType, flags: Flags) -> bool {
+    specifier_type == SpecifierType::Esm
+      && flags.contains(Flags::IMPLICIT_RELATIVE)
+      && matches!(self, Specifier::Package(module, _) if !module.starts_with('@'))
+  }
+
+  /// Like `parse_with_options`, but first strips a typed import prefix configured in
+  /// `ParseOptions::typed_schemes`, and returns the import type it maps to.
+  pub fn parse_typed<'o>(
+    specifier: &'a str,
+    specifier_type: SpecifierType,
+    flags: Flags,
+    options: &'o ParseOptions,
+  ) -> Result<(Specifier<'a>, Option<&'a str>, Option<&'o str>), SpecifierError> {
+    let specifier = if options.strip_invisible_prefix {
+      strip_invisible_prefix(specifier)
+    } else {
+      specifier
+    };
+
+    if let Some((scheme, rest)) = specifier.split_once(':') {
+      if let Some(import_type) = options.typed_schemes.get(scheme) {
+        let (specifier, query) =
+          Specifier::parse_with_options(rest, specifier_type, flags, options)?;
+        return Ok((specifier, query, Some(import_type.as_str())));
+      }
+    }
+
+    let (specifier, query) =
+      Specifier::parse_with_options(specifier, specifier_type, flags, options)?;
+    Ok((specifier, query, None))
+  }
+
+  pub fn parse_with_options(
+    specifier: &'a str,
+    specifier_type: SpecifierType,
+    flags: Flags,
+    options: &ParseOptions,
+  ) -> Result<(Specifier<'a>, Option<&'a str>), SpecifierError> {
+    let specifier = if options.strip_invisible_prefix {
+      strip_invisible_prefix(specifier)
+    } else {
+      specifier
+    };
+
+    let (parsed, query) = Specifier::parse_unvalidated(specifier, specifier_type, flags, options)?;
+
+    if let Specifier::Package(module, _) = &parsed {
+      if flags.contains(Flags::ONLY_BUILTINS) {
+        return Err(SpecifierError::PackagesNotAllowed(module.to_string()));
+      }
+    }
+
+    // Paths must not reach the file system with characters it can't represent.
+    if matches!(
+      parsed,
+      Specifier::Relative(_)
+        | Specifier::Absolute(_)
+        | Specifier::RootRelative(_)
+        | Specifier::Tilde(_)
+        | Specifier::Hash(_)
+        | Specifier::Package(..)
+        | Specifier::SchemeAlias { .. }
+    ) {
+      let path = match specifier_type {
+        SpecifierType::Cjs => specifier,
+        _ => parse_path(specifier).0,
+      };
+      validate_path(path, specifier_type)?;
+
+      if flags.contains(Flags::REJECT_ENCODED_SEPARATORS) && specifier_type != SpecifierType::Cjs {
+        if let Some(offset) = find_encoded_separator(path) {
+          return Err(SpecifierError::EncodedSeparator(offset));
+        }
+      }
+    }
+
+    Ok((parsed, query))
+  }
+
+  fn parse_unvalidated(
+    specifier: &'a str,
+    specifier_type: SpecifierType,
+    flags: Flags,
+    options: &ParseOptions,
   ) -> Result<(Specifier<'a>, Option<&'a str>), SpecifierError> {
     if specifier.is_empty() {
       return Err(SpecifierError::EmptySpecifier);
     }
 
+    // Framework virtual modules (e.g. `$app/stores`) are passed through as is.
+    // This must happen first, since they may otherwise look like packages or URLs.
+    if options
+      .virtual_prefixes
+      .iter()
+      .any(|prefix| specifier.starts_with(prefix.as_str()))
+    {
+      return Ok((Specifier::Virtual(Cow::Borrowed(specifier)), None));
+    }
+
+    if let Some((scheme, rest)) = specifier.split_once(':') {
+      if options.scheme_aliases.contains_key(scheme) {
+        let (path, query) =
+          decode_path_with_flags(rest.trim_start_matches('/'), specifier_type, flags);
+        return Ok((
+          Specifier::SchemeAlias {
+            scheme: Cow::Borrowed(scheme),
+            rest: path,
+          },
+          query,
+        ));
+      }
+    }
+
     Ok(match specifier.as_bytes()[0] {
       b'.' => {
-        let specifier = if specifier.starts_with("./") {
-          &specifier[2..]
-        } else {
-          specifier
-        };
-        let (path, query) = decode_path(specifier, specifier_type);
-        (Specifier::Relative(path), query)
+        let specifier = strip_current_dir(specifier, flags);
+        let (path, query) = decode_path_with_flags(specifier, specifier_type, flags);
+        (Specifier::Relative(current_dir_if_empty(path)), query)
       }
       b'~' => {
         let mut specifier = &specifier[1..];
-        if !specifier.is_empty() && is_separator(specifier.as_bytes()[0] as char) {
+        if !specifier.is_empty() && is_separator_with(specifier.as_bytes()[0] as char, flags) {
           specifier = &specifier[1..];
         }
-        let (path, query) = decode_path(specifier, specifier_type);
+        let (path, query) = decode_path_with_flags(specifier, specifier_type, flags);
         (Specifier::Tilde(path), query)
       }
       b'/' => {
         if specifier.starts_with("//") && specifier_type == SpecifierType::Url {
           // A protocol-relative URL, e.g `url('//example.com/foo.png')`.
-          (Specifier::Url(specifier), None)
+          (Specifier::Url(Cow::Borrowed(specifier)), None)
+        } else if flags.contains(Flags::ROOT_ABSOLUTE) && !specifier.starts_with("//") {
+          let (path, query) = decode_path_with_flags(&specifier[1..], specifier_type, flags);
+          (Specifier::RootRelative(path), query)
         } else {
-          let (path, query) = decode_path(specifier, specifier_type);
+          let (path, query) = decode_path_with_flags(specifier, specifier_type, flags);
           (Specifier::Absolute(path), query)
         }
       }
-      b'#' => (Specifier::Hash(Cow::Borrowed(&specifier[1..])), None),
+      b'#' => match specifier_type {
+        SpecifierType::Esm => {
+          // Internal imports may carry a query like other specifiers, e.g. `#db?pool=primary`.
+          let (hash, rest) = parse_path(&specifier[1..]);
+          let (query, _) = parse_query(rest);
+          (Specifier::Hash(Cow::Borrowed(hash)), query)
+        }
+        // In urls, the whole specifier is a fragment reference, e.g. `url(#gradient)`.
+        // CommonJS specifiers never have a query.
+        SpecifierType::Url | SpecifierType::Cjs => {
+          (Specifier::Hash(Cow::Borrowed(&specifier[1..])), None)
+        }
+      },
       _ => {
         // Bare specifier.
         match specifier_type {
@@ -101,7 +379,7 @@ impl<'a> Specifier<'a> {
                   }
 
                   (
-                    parse_package(percent_decode_str(path).decode_utf8_lossy())?,
+                    parse_package(decode_path_segments(path), specifier_type, flags)?,
                     query,
                   )
                 }
@@ -122,7 +400,13 @@ impl<'a> Specifier<'a> {
                     query,
                   )
                 }
-                _ => (Specifier::Url(specifier), None),
+                "npm" | "data" => (Specifier::Url(Cow::Borrowed(specifier)), None),
+                _ if specifier_type == SpecifierType::Url
+                  || options.external_schemes.iter().any(|s| *s == scheme) =>
+                {
+                  (Specifier::Url(Cow::Borrowed(specifier)), None)
+                }
+                _ => return Err(SpecifierError::UnknownScheme(scheme.into_owned())),
               }
             } else {
               // If not, then parse as an npm package if this is an ESM specifier,
@@ -135,11 +419,11 @@ impl<'a> Specifier<'a> {
 
                 let (query, _) = parse_query(rest);
                 (
-                  parse_package(percent_decode_str(path).decode_utf8_lossy())?,
+                  parse_package(decode_path_segments(path), specifier_type, flags)?,
                   query,
                 )
               } else {
-                let (path, query) = decode_path(specifier, specifier_type);
+                let (path, query) = decode_path_with_flags(specifier, specifier_type, flags);
                 (Specifier::Relative(path), query)
               }
             }
@@ -147,6 +431,11 @@ impl<'a> Specifier<'a> {
           SpecifierType::Cjs => {
             if BUILTINS.contains(&specifier.as_ref()) {
               (Specifier::Builtin(Cow::Borrowed(specifier)), None)
+            } else if parse_scheme(specifier).is_ok_and(|(scheme, _)| {
+              scheme == "data" || options.external_schemes.iter().any(|s| *s == scheme)
+            }) {
+              // Package names can't contain ":", so these can only be URLs.
+              (Specifier::Url(Cow::Borrowed(specifier)), None)
             } else {
               #[cfg(windows)]
               if !flags.contains(Flags::ABSOLUTE_SPECIFIERS) {
@@ -156,7 +445,10 @@ impl<'a> Specifier<'a> {
                 }
               }
 
-              (parse_package(Cow::Borrowed(specifier))?, None)
+              (
+                parse_package(Cow::Borrowed(specifier), specifier_type, flags)?,
+                None,
+              )
             }
           }
         }
@@ -164,22 +456,343 @@ impl<'a> Specifier<'a> {
     })
   }
 
-  pub fn to_string(&'a self) -> Cow<'a, str> {
-    match self {
-      Specifier::Relative(path) | Specifier::Absolute(path) | Specifier::Tilde(path) => {
-        path.as_os_str().to_string_lossy()
+  /// Parses a specifier that is known to be a file path, e.g. from a list of files,
+  /// skipping scheme and package parsing. Bare words are treated as relative paths,
+  /// so this only ever returns `Relative`, `Absolute`, or `Tilde` (with `Flags::TILDE_SPECIFIERS`).
+  pub fn parse_path_only(
+    specifier: &'a str,
+    specifier_type: SpecifierType,
+    flags: Flags,
+  ) -> Result<(Specifier<'a>, Option<&'a str>), SpecifierError> {
+    if specifier.is_empty() {
+      return Err(SpecifierError::EmptySpecifier);
+    }
+
+    Ok(match specifier.as_bytes()[0] {
+      b'~' if flags.contains(Flags::TILDE_SPECIFIERS) => {
+        let mut specifier = &specifier[1..];
+        if !specifier.is_empty() && is_separator_with(specifier.as_bytes()[0] as char, flags) {
+          specifier = &specifier[1..];
+        }
+        let (path, query) = decode_path_with_flags(specifier, specifier_type, flags);
+        (Specifier::Tilde(path), query)
+      }
+      _ if Path::new(specifier).is_absolute() || specifier.starts_with('/') => {
+        let (path, query) = decode_path_with_flags(specifier, specifier_type, flags);
+        (Specifier::Absolute(path), query)
+      }
+      _ => {
+        let specifier = strip_current_dir(specifier, flags);
+        let (path, query) = decode_path_with_flags(specifier, specifier_type, flags);
+        (Specifier::Relative(current_dir_if_empty(path)), query)
       }
+    })
+  }
+
+  /// Returns a stable label for the kind of specifier, e.g. `relative` or `package`,
+  /// for use in logs and metrics without formatting the specifier itself.
+  pub const fn kind_str(&self) -> &'static str {
+    match self {
+      Specifier::Relative(_) => "relative",
+      Specifier::Absolute(_) => "absolute",
+      Specifier::RootRelative(_) => "root_relative",
+      Specifier::Tilde(_) => "tilde",
+      Specifier::Hash(_) => "hash",
+      Specifier::Package(..) => "package",
+      Specifier::Builtin(_) => "builtin",
+      Specifier::Url(_) => "url",
+      Specifier::Virtual(_) => "virtual",
+      Specifier::SchemeAlias { .. } => "scheme_alias",
+    }
+  }
+
+  pub fn to_string(&self) -> Cow<'_, str> {
+    match self {
+      Specifier::Relative(path)
+      | Specifier::Absolute(path)
+      | Specifier::RootRelative(path)
+      | Specifier::Tilde(path) => path.as_os_str().to_string_lossy(),
       Specifier::Hash(path) => path.clone(),
       Specifier::Package(module, subpath) => {
         if subpath.is_empty() {
           Cow::Borrowed(module)
+        } else if subpath == "." {
+          Cow::Owned(format!("{}/", module))
         } else {
           Cow::Owned(format!("{}/{}", module, subpath))
         }
       }
       Specifier::Builtin(builtin) => Cow::Borrowed(&builtin),
       Specifier::Url(url) => Cow::Borrowed(url),
+      Specifier::Virtual(specifier) => Cow::Borrowed(specifier),
+      Specifier::SchemeAlias { scheme, rest } => {
+        Cow::Owned(format!("{}:{}", scheme, rest.to_string_lossy()))
+      }
+    }
+  }
+
+  /// Like `to_string`, but percent encodes the path of path and package specifiers with the URL
+  /// path percent-encode set, keeping `/` separators, e.g. `pkg/my%20file.js` for the subpath
+  /// `my file.js`. This restores the escapes decoded while parsing, for use in a URL.
+  pub fn to_url_string(&self) -> Cow<'_, str> {
+    match self {
+      Specifier::Relative(_)
+      | Specifier::Absolute(_)
+      | Specifier::RootRelative(_)
+      | Specifier::Tilde(_)
+      | Specifier::Package(..) => encode_path_segments(self.to_string()),
+      _ => self.to_string(),
+    }
+  }
+
+  /// Returns whether this is a relative specifier that can only refer to a directory,
+  /// e.g. `.`, `..`, or `./utils/`. These resolve to the directory's package.json entry
+  /// or index file, never to a file with the same name.
+  pub fn is_directory_import(&self) -> bool {
+    match self {
+      Specifier::Relative(path) => is_directory_path(path),
+      _ => false,
+    }
+  }
+
+  /// Returns the extension of the file named by the specifier, without the leading dot, e.g.
+  /// `js` for `./x.js` or `lodash/get.js`. Builtins, URLs, hashes, virtual modules, bare package
+  /// names, and directory imports have no extension. With `compound`, known multi-part
+  /// extensions are returned whole, e.g. `d.ts` rather than `ts` for `./x.d.ts`, but `js`
+  /// for `./x.min.js`.
+  pub fn extension(&self, compound: bool) -> Option<&str> {
+    let file_name = match self {
+      Specifier::Relative(path)
+      | Specifier::Absolute(path)
+      | Specifier::RootRelative(path)
+      | Specifier::Tilde(path)
+      | Specifier::SchemeAlias { rest: path, .. } => {
+        if has_trailing_slash(path) {
+          return None;
+        }
+        path.file_name()?.to_str()?
+      }
+      Specifier::Package(_, subpath) => subpath.rsplit('/').next()?,
+      Specifier::Hash(_) | Specifier::Builtin(_) | Specifier::Url(_) | Specifier::Virtual(_) => {
+        return None
+      }
+    };
+
+    // A leading dot is part of the name of a hidden file, e.g. `.babelrc`, not an extension.
+    let name = file_name.trim_start_matches('.');
+    if compound {
+      let compound = COMPOUND_EXTENSIONS.iter().find_map(|ext| {
+        let stem = name.strip_suffix(ext)?.strip_suffix('.')?;
+        (!stem.is_empty()).then(|| &name[stem.len() + 1..])
+      });
+      if compound.is_some() {
+        return compound;
+      }
+    }
+
+    match &name[name.rfind('.')? + 1..] {
+      "" => None,
+      ext => Some(ext),
+    }
+  }
+
+  /// Returns whether resolving this specifier reads the file system, e.g. to find a file or
+  /// a package. Builtins, URLs (including `data:`), and virtual modules don't, so a scheduler
+  /// can resolve them synchronously and batch the rest. Builtins may still be remapped by
+  /// package.json aliases, which are read from the (usually cached) package of the importer.
+  ///
+  /// This is not the same as resolving to `Resolution::External`: a package requires the file
+  /// system but is bundled, while a URL is external without reading anything.
+  pub fn requires_fs(&self) -> bool {
+    match self {
+      Specifier::Relative(_)
+      | Specifier::Absolute(_)
+      | Specifier::RootRelative(_)
+      | Specifier::Tilde(_)
+      | Specifier::Hash(_)
+      | Specifier::Package(_, _)
+      | Specifier::SchemeAlias { .. } => true,
+      Specifier::Builtin(_) | Specifier::Url(_) | Specifier::Virtual(_) => false,
+    }
+  }
+
+  /// Returns whether two specifiers name the same target regardless of their queries and
+  /// fragments, e.g. to group `./img.png?width=100` with `./img.png?width=200`.
+  ///
+  /// `parse` returns the query separately, so it is never part of a path or package specifier
+  /// and those compare as with `==`. URLs keep the specifier as written, so their query and
+  /// fragment are stripped first, except for `data:` URLs where they are part of the content.
+  /// Comparisons are otherwise exact; compare `NormalizedSpecifier`s' `normalized()` forms to
+  /// also treat e.g. `./a/../b` as `./b`.
+  pub fn eq_ignoring_query(&self, other: &Specifier) -> bool {
+    match (self, other) {
+      (Specifier::Url(a), Specifier::Url(b)) => url_without_query(a) == url_without_query(b),
+      (a, b) => a == b,
+    }
+  }
+
+  /// Builds a stable id for the module this specifier resolved to, e.g. `/abs/path/foo.js?inline`,
+  /// for keying modules in a bundler's graph. Paths always use `/` separators, e.g.
+  /// `C:/src/foo.js` on Windows, so ids cached on one platform match those computed on another.
+  /// `query` is the query returned by `parse`, with or without its leading "?".
+  ///
+  /// Builtins, URLs, and virtual modules have no path, so `resolved` is ignored and the id is
+  /// the specifier itself, e.g. `node:fs`.
+  pub fn module_id(&self, resolved: &Path, query: Option<&str>) -> String {
+    let mut id = match self {
+      Specifier::Builtin(name) => format!("node:{}", name),
+      Specifier::Url(url) => url.to_string(),
+      Specifier::Virtual(id) => id.to_string(),
+      _ => path_id(&resolved.to_string_lossy(), cfg!(windows)),
+    };
+
+    if let Some(query) = query {
+      let query = query.strip_prefix('?').unwrap_or(query);
+      if !query.is_empty() {
+        id.push('?');
+        id.push_str(query);
+      }
+    }
+
+    id
+  }
+
+  /// Matches a package specifier against an alias key, which is a module name optionally followed
+  /// by subpath segments, e.g. `@scope/pkg/feature`. Returns the rest of the subpath if the key is
+  /// a prefix of whole segments, e.g. `button` for `@scope/pkg/feature/button`, or an empty string
+  /// for an exact match. Other specifiers never match.
+  pub fn strip_package_prefix(&self, prefix: &str) -> Option<&str> {
+    let (module, subpath) = match self {
+      Specifier::Package(module, subpath) => (module.as_ref(), subpath.as_ref()),
+      _ => return None,
+    };
+
+    let prefix_subpath = match prefix.strip_prefix(module)? {
+      "" => return Some(subpath),
+      rest => rest.strip_prefix('/')?.trim_end_matches('/'),
+    };
+    if prefix_subpath.is_empty() {
+      return Some(subpath);
+    }
+
+    match subpath.strip_prefix(prefix_subpath)? {
+      "" => Some(""),
+      rest => rest.strip_prefix('/'),
+    }
+  }
+
+  /// Checks the invariants that `parse` upholds, for specifiers that were constructed by hand,
+  /// e.g. from alias configuration. A package name must be non-empty, and either a single
+  /// segment or a scope and name (`@scope/pkg`). A builtin must be one of `BUILTINS`, and a
+  /// hash (`#internal`) must be non-empty. Other specifiers are always valid.
+  pub fn validate(&self) -> Result<(), SpecifierError> {
+    match self {
+      Specifier::Package(module, _) => {
+        let valid = match module.strip_prefix('@') {
+          Some(scoped) => matches!(
+            scoped.split_once('/'),
+            Some((scope, name)) if !scope.is_empty() && !name.is_empty() && !name.contains('/')
+          ),
+          None => !module.is_empty() && !module.contains('/'),
+        };
+        if !valid {
+          return Err(SpecifierError::InvalidPackageSpecifier);
+        }
+      }
+      Specifier::Builtin(name) if !BUILTINS.contains(&name.as_ref()) => {
+        return Err(SpecifierError::UnknownBuiltin(name.to_string()));
+      }
+      Specifier::Hash(hash) if hash.is_empty() => return Err(SpecifierError::EmptySpecifier),
+      _ => {}
+    }
+
+    Ok(())
+  }
+
+  /// Recomputes a relative specifier written in the directory `old_base` so that it still refers
+  /// to the same target when written in `new_base`, e.g. after moving the importing module.
+  /// Directory imports such as `./utils/` remain directory imports. Other specifiers don't depend
+  /// on the importer's location and are returned unchanged.
+  pub fn rebase(&self, old_base: &Path, new_base: &Path) -> Specifier<'static> {
+    let path = match self {
+      Specifier::Relative(path) => path,
+      _ => return self.clone().into_owned(),
+    };
+
+    let target = old_base.join(path);
+    let rebased = match relative_path(new_base, &target) {
+      Some(rebased) => rebased,
+      None => return Specifier::Absolute(Cow::Owned(normalize_path(&target))),
+    };
+
+    let mut rebased = rebased.into_os_string();
+    if is_directory_path(path) {
+      if !is_directory_path(Path::new(&rebased)) {
+        rebased.push("/");
+      }
+    } else if rebased.is_empty() {
+      rebased.push(".");
+    }
+
+    Specifier::Relative(Cow::Owned(PathBuf::from(rebased)))
+  }
+
+  /// Converts the specifier into an owned string, e.g. to pass across an FFI boundary.
+  pub fn into_owned_string(self) -> String {
+    self.to_string().into_owned()
+  }
+
+  /// Converts the specifier into one that owns all of its data, so it is no longer tied to
+  /// the lifetime of the input string.
+  pub fn into_owned(self) -> Specifier<'static> {
+    match self {
+      Specifier::Relative(path) => Specifier::Relative(Cow::Owned(path.into_owned())),
+      Specifier::Absolute(path) => Specifier::Absolute(Cow::Owned(path.into_owned())),
+      Specifier::RootRelative(path) => Specifier::RootRelative(Cow::Owned(path.into_owned())),
+      Specifier::Tilde(path) => Specifier::Tilde(Cow::Owned(path.into_owned())),
+      Specifier::Hash(hash) => Specifier::Hash(Cow::Owned(hash.into_owned())),
+      Specifier::Package(module, subpath) => Specifier::Package(
+        Cow::Owned(module.into_owned()),
+        Cow::Owned(subpath.into_owned()),
+      ),
+      Specifier::Builtin(builtin) => Specifier::Builtin(Cow::Owned(builtin.into_owned())),
+      Specifier::Url(url) => Specifier::Url(Cow::Owned(url.into_owned())),
+      Specifier::Virtual(specifier) => Specifier::Virtual(Cow::Owned(specifier.into_owned())),
+      Specifier::SchemeAlias { scheme, rest } => Specifier::SchemeAlias {
+        scheme: Cow::Owned(scheme.into_owned()),
+        rest: Cow::Owned(rest.into_owned()),
+      },
+    }
+  }
+
+  /// Finds the most specific key in an "exports" or "imports" map matching this specifier.
+  /// Package specifiers are matched against "./" keys, and hash specifiers against "#" keys.
+  /// Exact keys take precedence over patterns, and patterns are ordered using the same rules
+  /// as Node (longest prefix before the "*" wins). Returns the key along with the substring
+  /// captured by the wildcard, if any.
+  pub fn best_export_match<'k>(
+    &self,
+    mut keys: impl Iterator<Item = &'k str>,
+  ) -> Option<(&'k str, Option<&str>)> {
+    let (prefix, match_key) = match self {
+      Specifier::Package(_, subpath) => {
+        if subpath.is_empty() {
+          return keys.find(|key| *key == ".").map(|key| (key, None));
+        }
+        ("./", subpath.as_ref())
+      }
+      Specifier::Hash(hash) => ("#", hash.as_ref()),
+      _ => return None,
+    };
+
+    let patterns: Vec<_> = keys
+      .filter_map(|key| Some((key, key.strip_prefix(prefix)?)))
+      .collect();
+    if let Some((key, _)) = patterns.iter().find(|(_, pattern)| *pattern == match_key) {
+      return Some((key, None));
     }
+
+    best_pattern_match(match_key, patterns.into_iter()).map(|(key, capture)| (key, Some(capture)))
   }
 }
 
@@ -198,6 +811,12 @@ pub fn parse_scheme<'a>(input: &'a str) -> Result<(Cow<'a, str>, &'a str), ()> {
       }
       'a'..='z' | '0'..='9' | '+' | '-' | '.' => {}
       ':' => {
+        // On Windows, a single letter followed by ":" is a drive letter, e.g. `C:\foo`.
+        #[cfg(windows)]
+        if i == 1 {
+          return Err(());
+        }
+
         let scheme = &input[0..i];
         let rest = &input[i + 1..];
         return Ok(if is_lowercase {
@@ -217,11 +836,95 @@ pub fn parse_scheme<'a>(input: &'a str) -> Result<(Cow<'a, str>, &'a str), ()> {
   Err(())
 }
 
+/// The raw components of a specifier, as returned by [split_specifier].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpecifierParts<'a> {
+  /// The scheme, without the trailing `:`, e.g. `npm` in `npm:react`. The case is preserved.
+  pub scheme: Option<&'a str>,
+  /// Everything after the scheme, up to the query or fragment. It is not decoded.
+  pub path: &'a str,
+  /// The query, without the leading `?`.
+  pub query: Option<&'a str>,
+  /// The fragment, without the leading `#`.
+  pub fragment: Option<&'a str>,
+}
+
+/// Splits a specifier into its scheme, path, query, and fragment. This is a purely lexical
+/// split: unlike [Specifier::parse], it does not decode the path or classify the specifier,
+/// e.g. as a package or builtin, and a `#` always starts the fragment.
+pub fn split_specifier(input: &str) -> SpecifierParts<'_> {
+  let (scheme, rest) = match parse_scheme(input) {
+    Ok((scheme, rest)) => (Some(&input[0..scheme.len()]), rest),
+    Err(()) => (None, input),
+  };
+
+  let (path, rest) = parse_path(rest);
+  let (query, rest) = parse_query(rest);
+  SpecifierParts {
+    scheme,
+    path,
+    query: query.map(|query| &query[1..]),
+    fragment: rest.strip_prefix('#'),
+  }
+}
+
+/// Returns whether `c` is a path separator, including backslashes on Windows
+/// or with `Flags::WINDOWS_SEPARATORS`.
+fn is_separator_with(c: char, flags: Flags) -> bool {
+  is_separator(c) || (c == '\\' && flags.contains(Flags::WINDOWS_SEPARATORS))
+}
+
+/// Strips a leading "./", or ".\" on Windows or with `Flags::WINDOWS_SEPARATORS`.
+fn strip_current_dir(specifier: &str, flags: Flags) -> &str {
+  match specifier.strip_prefix("./") {
+    Some(rest) => rest,
+    None if cfg!(windows) || flags.contains(Flags::WINDOWS_SEPARATORS) => {
+      specifier.strip_prefix(".\\").unwrap_or(specifier)
+    }
+    None => specifier,
+  }
+}
+
+/// Returns "." for an empty relative path, e.g. after stripping "./" from `./` or `./?query`,
+/// so that it refers to the current directory like `.` rather than to the importing file.
+fn current_dir_if_empty(path: Cow<'_, Path>) -> Cow<'_, Path> {
+  if path.as_os_str().is_empty() {
+    Cow::Borrowed(Path::new("."))
+  } else {
+    path
+  }
+}
+
+/// Like `decode_path`, but with `Flags::WINDOWS_SEPARATORS`, backslashes are replaced with
+/// forward slashes so that the path has the same components as on Windows, on any host.
+/// On Windows, backslashes in ESM and URL specifiers are always replaced, since code generated
+/// on Windows may emit them even though "/" is the only separator in URLs.
+fn decode_path_with_flags<'a>(
+  specifier: &'a str,
+  specifier_type: SpecifierType,
+  flags: Flags,
+) -> (Cow<'a, Path>, Option<&'a str>) {
+  let (path, query) = decode_path_unchecked(specifier, specifier_type);
+  let replace = if cfg!(windows) {
+    specifier_type != SpecifierType::Cjs
+  } else {
+    flags.contains(Flags::WINDOWS_SEPARATORS)
+  };
+  if !replace {
+    return (path, query);
+  }
+
+  match path.to_str() {
+    Some(s) if s.contains('\\') => (Cow::Owned(PathBuf::from(s.replace('\\', "/"))), query),
+    _ => (path, query),
+  }
+}
+
 // https://url.spec.whatwg.org/#path-state
 fn parse_path<'a>(input: &'a str) -> (&'a str, &'a str) {
   // We don't really want to normalize the path (e.g. replacing ".." and "." segments).
   // That is done later. For now, we just need to find the end of the path.
-  if let Some(pos) = input.chars().position(|c| c == '?' || c == '#') {
+  if let Some(pos) = input.find(['?', '#']) {
     (&input[0..pos], &input[pos..])
   } else {
     (input, "")
@@ -231,7 +934,7 @@ fn parse_path<'a>(input: &'a str) -> (&'a str, &'a str) {
 // https://url.spec.whatwg.org/#query-state
 fn parse_query<'a>(input: &'a str) -> (Option<&'a str>, &'a str) {
   if !input.is_empty() && input.as_bytes()[0] == b'?' {
-    if let Some(pos) = input.chars().position(|c| c == '#') {
+    if let Some(pos) = input.find('#') {
       (Some(&input[0..pos]), &input[pos..])
     } else {
       (Some(input), "")
@@ -247,53 +950,177 @@ fn ascii_alpha(ch: char) -> bool {
   matches!(ch, 'a'..='z' | 'A'..='Z')
 }
 
-fn parse_package<'a>(specifier: Cow<'a, str>) -> Result<Specifier, SpecifierError> {
-  match specifier {
+fn parse_package<'a>(
+  specifier: Cow<'a, str>,
+  specifier_type: SpecifierType,
+  flags: Flags,
+) -> Result<Specifier<'a>, SpecifierError> {
+  let (module, subpath) = match specifier {
     Cow::Borrowed(specifier) => {
       let (module, subpath) = parse_package_specifier(specifier)?;
-      Ok(Specifier::Package(
-        Cow::Borrowed(module),
-        Cow::Borrowed(subpath),
-      ))
+      (Cow::Borrowed(module), Cow::Borrowed(subpath))
     }
     Cow::Owned(specifier) => {
       let (module, subpath) = parse_package_specifier(&specifier)?;
-      Ok(Specifier::Package(
+      (
         Cow::Owned(module.to_owned()),
         Cow::Owned(subpath.to_owned()),
-      ))
+      )
     }
+  };
+
+  // ".." segments must never be resolved against the package directory, since that could
+  // escape into a sibling package, e.g. `pkg/../other/file.js`. Node rejects them in ESM, unless
+  // `Flags::NORMALIZE_PACKAGE_SUBPATH` is set. CommonJS always resolves them lexically, within
+  // the package root.
+  let subpath = collapse_empty_segments(subpath);
+  let has_parent_dir = subpath.split('/').any(|s| s == "..");
+  let subpath = if flags.contains(Flags::NORMALIZE_PACKAGE_SUBPATH)
+    || (has_parent_dir && specifier_type == SpecifierType::Cjs)
+  {
+    normalize_package_subpath(subpath)?
+  } else if has_parent_dir {
+    return Err(SpecifierError::InvalidPackageSpecifier);
+  } else {
+    subpath
+  };
+
+  Ok(Specifier::Package(module, subpath))
+}
+
+/// Removes empty segments from a package subpath, e.g. `sub//file` becomes `sub/file`.
+/// These would otherwise never match an "exports" key. A trailing slash is kept, and a
+/// subpath made up only of slashes refers to the package directory itself (".").
+fn collapse_empty_segments(subpath: Cow<'_, str>) -> Cow<'_, str> {
+  if !subpath.starts_with('/') && !subpath.contains("//") {
+    return subpath;
   }
+
+  let mut collapsed = subpath
+    .split('/')
+    .filter(|s| !s.is_empty())
+    .collect::<Vec<_>>()
+    .join("/");
+  if collapsed.is_empty() {
+    return Cow::Borrowed(".");
+  }
+  if subpath.ends_with('/') {
+    collapsed.push('/');
+  }
+  Cow::Owned(collapsed)
 }
 
-pub fn parse_package_specifier(specifier: &str) -> Result<(&str, &str), SpecifierError> {
-  let idx = specifier.chars().position(|p| p == '/');
-  if specifier.starts_with('@') {
-    let idx = idx.ok_or(SpecifierError::InvalidPackageSpecifier)?;
-    if let Some(next) = &specifier[idx + 1..].chars().position(|p| p == '/') {
-      Ok((
-        &specifier[0..idx + 1 + *next],
-        &specifier[idx + *next + 2..],
-      ))
-    } else {
-      Ok((&specifier[..], ""))
+/// Lexically resolves "." and ".." segments in a package subpath.
+/// A ".." segment that would escape the package root is an error.
+/// The package directory itself (".") is kept as is.
+fn normalize_package_subpath<'a>(subpath: Cow<'a, str>) -> Result<Cow<'a, str>, SpecifierError> {
+  if subpath == "." || !subpath.split('/').any(|s| s == "." || s == "..") {
+    return Ok(subpath);
+  }
+
+  let mut segments = Vec::new();
+  for segment in subpath.split('/') {
+    match segment {
+      "." => {}
+      ".." => {
+        if segments.pop().is_none() {
+          return Err(SpecifierError::InvalidPackageSpecifier);
+        }
+      }
+      segment => segments.push(segment),
     }
-  } else if let Some(idx) = idx {
-    Ok((&specifier[0..idx], &specifier[idx + 1..]))
-  } else {
-    Ok((&specifier[..], ""))
   }
+
+  Ok(Cow::Owned(segments.join("/")))
+}
+
+/// Formats a resolved path for `module_id`. Windows paths use `/` separators, and lose the
+/// verbatim prefix that canonicalizing adds (`\\?\C:\...`), so the id doesn't depend on how the
+/// path was obtained.
+fn path_id(path: &str, windows: bool) -> String {
+  if !windows {
+    return path.to_owned();
+  }
+
+  let path = match path.strip_prefix(r"\\?\") {
+    Some(rest) => match rest.strip_prefix(r"UNC\") {
+      Some(unc) => Cow::Owned(format!(r"\\{}", unc)),
+      None => Cow::Borrowed(rest),
+    },
+    None => Cow::Borrowed(path),
+  };
+  path.replace('\\', "/")
+}
+
+fn url_without_query(url: &str) -> &str {
+  if url.starts_with("data:") {
+    return url;
+  }
+
+  url.find(['?', '#']).map_or(url, |end| &url[..end])
+}
+
+/// Splits a bare specifier into the package name and the subpath within it. A trailing slash
+/// directly after the name (`pkg/`) refers to the package directory rather than its main
+/// entry, and is returned as the subpath ".".
+pub fn parse_package_specifier(specifier: &str) -> Result<(&str, &str), SpecifierError> {
+  let (module, subpath) = split_package_specifier(specifier)?;
+  if subpath.is_empty() && module.len() < specifier.len() {
+    return Ok((module, "."));
+  }
+
+  Ok((module, subpath))
+}
+
+fn split_package_specifier(specifier: &str) -> Result<(&str, &str), SpecifierError> {
+  // All offsets are in bytes and only ever point at an ASCII "/" or "@", so slicing is safe
+  // even when the scope or name contains multi-byte characters.
+  let scoped = match specifier.strip_prefix('@') {
+    Some(scoped) => scoped,
+    None => return Ok(specifier.split_once('/').unwrap_or((specifier, ""))),
+  };
+
+  // A scoped package needs a name, so `@scope` and `@scope/` are invalid. The scope may be empty,
+  // since `@/` is a common alias prefix, e.g. in tsconfig.json "paths".
+  let (scope, rest) = scoped
+    .split_once('/')
+    .ok_or(SpecifierError::InvalidPackageSpecifier)?;
+  let name = rest.split('/').next().unwrap_or(rest);
+  if name.is_empty() {
+    return Err(SpecifierError::InvalidPackageSpecifier);
+  }
+
+  let end = 1 + scope.len() + 1 + name.len();
+  Ok((&specifier[..end], specifier.get(end + 1..).unwrap_or("")))
 }
 
+/// Decodes the path of a specifier, returning it along with the query.
+/// Fails if the path can't be represented on the file system, see `validate_path`.
 pub fn decode_path<'a>(
   specifier: &'a str,
   specifier_type: SpecifierType,
+) -> Result<(Cow<'a, Path>, Option<&'a str>), SpecifierError> {
+  let path = match specifier_type {
+    SpecifierType::Cjs => specifier,
+    _ => parse_path(specifier).0,
+  };
+  validate_path(path, specifier_type)?;
+  Ok(decode_path_unchecked(specifier, specifier_type))
+}
+
+fn decode_path_unchecked<'a>(
+  specifier: &'a str,
+  specifier_type: SpecifierType,
 ) -> (Cow<'a, Path>, Option<&'a str>) {
   match specifier_type {
     SpecifierType::Url | SpecifierType::Esm => {
       let (path, rest) = parse_path(specifier);
       let (query, _) = parse_query(rest);
-      let path = match percent_decode_str(path).decode_utf8_lossy() {
+      let path = match collapse_duplicate_slashes(path) {
+        Cow::Borrowed(path) => decode_path_segments(path),
+        Cow::Owned(path) => Cow::Owned(decode_path_segments(&path).into_owned()),
+      };
+      let path = match path {
         Cow::Borrowed(v) => Cow::Borrowed(Path::new(v)),
         Cow::Owned(v) => Cow::Owned(PathBuf::from(v)),
       };
@@ -303,25 +1130,2015 @@ pub fn decode_path<'a>(
   }
 }
 
-impl<'a> From<&'a str> for Specifier<'a> {
-  fn from(specifier: &'a str) -> Self {
-    Specifier::parse(specifier, SpecifierType::Cjs, Flags::empty())
-      .unwrap()
-      .0
-  }
+/// Returns whether a URL path contains an empty segment, e.g. `a//b.js`. Leading slashes
+/// are not counted, since they may start an absolute or UNC path.
+pub fn has_duplicate_slashes(path: &str) -> bool {
+  path.trim_start_matches('/').contains("//")
 }
 
-impl<'a, 'de: 'a> serde::Deserialize<'de> for Specifier<'a> {
-  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+/// Collapses runs of "/" in a URL path into a single separator, e.g. `./a//b.js` becomes
+/// `./a/b.js`, since URLs treat them as empty segments, which don't exist on the file system.
+/// Leading slashes are kept as is. This happens before decoding, so an encoded separator
+/// (`%2F`) never merges with a literal one.
+fn collapse_duplicate_slashes(path: &str) -> Cow<'_, str> {
+  if !has_duplicate_slashes(path) {
+    return Cow::Borrowed(path);
+  }
+
+  let rest = path.trim_start_matches('/');
+  let mut collapsed = String::with_capacity(path.len());
+  collapsed.push_str(&path[..path.len() - rest.len()]);
+  for (i, segment) in rest.split('/').filter(|s| !s.is_empty()).enumerate() {
+    if i > 0 {
+      collapsed.push('/');
+    }
+    collapsed.push_str(segment);
+  }
+  if rest.ends_with('/') {
+    collapsed.push('/');
+  }
+  Cow::Owned(collapsed)
+}
+
+/// Checks that a path is safe to pass to the file system once decoded. It must not contain NUL
+/// bytes, literally or percent-encoded, and escapes must decode to valid UTF-8 rather than e.g.
+/// an encoded UTF-16 surrogate (`%ED%A0%80`). URLs are decoded lossily instead, as in browsers,
+/// and CommonJS specifiers are never decoded. Errors hold the byte offset in `path`.
+fn validate_path(path: &str, specifier_type: SpecifierType) -> Result<(), SpecifierError> {
+  if let Some(offset) = path.find('\0') {
+    return Err(SpecifierError::InvalidPath(offset));
+  }
+
+  if specifier_type == SpecifierType::Cjs || memchr(b'%', path.as_bytes()).is_none() {
+    return Ok(());
+  }
+
+  // Decode byte by byte, remembering where each decoded byte came from.
+  let bytes = path.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut offsets = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let (byte, len) = match bytes.get(i + 1..i + 3) {
+      Some([a, b]) if bytes[i] == b'%' && a.is_ascii_hexdigit() && b.is_ascii_hexdigit() => {
+        (u8::from_str_radix(&path[i + 1..i + 3], 16).unwrap(), 3)
+      }
+      _ => (bytes[i], 1),
+    };
+    if byte == 0 {
+      return Err(SpecifierError::InvalidPath(i));
+    }
+
+    decoded.push(byte);
+    offsets.push(i);
+    i += len;
+  }
+
+  if specifier_type != SpecifierType::Url {
+    if let Err(err) = std::str::from_utf8(&decoded) {
+      return Err(SpecifierError::InvalidPath(offsets[err.valid_up_to()]));
+    }
+  }
+
+  Ok(())
+}
+
+/// Details about how a specifier was parsed, returned by `Specifier::parse_with_meta`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpecifierMeta {
+  /// Whether the path contained percent-encoded characters that were decoded, e.g. `%20` in
+  /// `./a%20b.js`. Encoded separators (`%2F` and `%5C`) are kept as is, so they don't count.
+  pub decoded: bool,
+  /// Whether the specifier may refer to a local file rather than a package, see
+  /// `Specifier::is_ambiguous`.
+  pub ambiguous: bool,
+}
+
+/// Records that a specifier was remapped to another, e.g. by the "browser" field,
+/// so that the change can be shown in diagnostics.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SpecifierRewrite<'a> {
+  pub before: Specifier<'a>,
+  pub after: Specifier<'a>,
+  pub reason: &'static str,
+}
+
+impl<'a> SpecifierRewrite<'a> {
+  pub fn into_owned(self) -> SpecifierRewrite<'static> {
+    SpecifierRewrite {
+      before: self.before.into_owned(),
+      after: self.after.into_owned(),
+      reason: self.reason,
+    }
+  }
+}
+
+impl<'a> fmt::Display for SpecifierRewrite<'a> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write_specifier(f, &self.before)?;
+    f.write_str(" → ")?;
+    write_specifier(f, &self.after)?;
+    write!(f, " ({})", self.reason)
+  }
+}
+
+/// Writes a specifier as the user would have written it, restoring prefixes that are
+/// stripped while parsing (e.g. `./`), unlike `Specifier::to_string`.
+fn write_specifier(f: &mut fmt::Formatter<'_>, specifier: &Specifier) -> fmt::Result {
+  let s = specifier.to_string();
+  match specifier {
+    Specifier::Relative(path) if !path.starts_with("..") && path.as_ref() != Path::new(".") => {
+      write!(f, "./{}", s)
+    }
+    Specifier::Tilde(_) => write!(f, "~/{}", s),
+    Specifier::RootRelative(_) => write!(f, "/{}", s),
+    Specifier::Hash(_) => write!(f, "#{}", s),
+    _ => f.write_str(&s),
+  }
+}
+
+/// Percent decodes a path, except for encoded separators (`%2F` and `%5C`), which are kept
+/// as is. Decoding these would split a single path segment into several.
+fn decode_path_segments(path: &str) -> Cow<'_, str> {
+  if memchr(b'%', path.as_bytes()).is_none() {
+    return Cow::Borrowed(path);
+  }
+
+  let is_encoded_separator =
+    |s: &str| s.eq_ignore_ascii_case("%2f") || s.eq_ignore_ascii_case("%5c");
+  let mut result = String::new();
+  let mut start = 0;
+  for (index, _) in path.match_indices('%') {
+    if !path.get(index..index + 3).is_some_and(is_encoded_separator) {
+      continue;
+    }
+
+    result.push_str(&percent_decode_path(&path[start..index]));
+    result.push_str(&path[index..index + 3]);
+    start = index + 3;
+  }
+
+  if start == 0 {
+    return percent_decode_path(path);
+  }
+
+  result.push_str(&percent_decode_path(&path[start..]));
+  Cow::Owned(result)
+}
+
+/// The URL path percent-encode set, plus `%` so that an encoded path decodes to the original.
+const PATH_ENCODE_SET: &AsciiSet = &CONTROLS
+  .add(b' ')
+  .add(b'"')
+  .add(b'#')
+  .add(b'%')
+  .add(b'<')
+  .add(b'>')
+  .add(b'?')
+  .add(b'`')
+  .add(b'{')
+  .add(b'}');
+
+/// Percent encodes a decoded path for use in a URL, the inverse of `decode_path_segments`.
+/// Encoded separators, which are kept while decoding, are kept as is.
+fn encode_path_segments(path: Cow<'_, str>) -> Cow<'_, str> {
+  let mut result = String::new();
+  let mut start = 0;
+  while let Some(index) = find_encoded_separator(&path[start..]).map(|i| start + i) {
+    result.extend(utf8_percent_encode(&path[start..index], PATH_ENCODE_SET));
+    result.push_str(&path[index..index + 3]);
+    start = index + 3;
+  }
+  result.extend(utf8_percent_encode(&path[start..], PATH_ENCODE_SET));
+
+  if result == *path {
+    path
+  } else {
+    Cow::Owned(result)
+  }
+}
+
+/// Returns the offset of the first percent-encoded separator (`%2F` or `%5C`) in a path.
+fn find_encoded_separator(path: &str) -> Option<usize> {
+  path.match_indices('%').map(|(i, _)| i).find(|&i| {
+    path
+      .get(i + 1..i + 3)
+      .is_some_and(|hex| hex.eq_ignore_ascii_case("2f") || hex.eq_ignore_ascii_case("5c"))
+  })
+}
+
+/// Percent decodes a path. Without a `%`, the path is returned as is, skipping both the
+/// decoder and the UTF-8 validation of its output, since a `&str` is already valid UTF-8.
+fn percent_decode_path(path: &str) -> Cow<'_, str> {
+  if memchr(b'%', path.as_bytes()).is_none() {
+    return Cow::Borrowed(path);
+  }
+
+  percent_decode_str(path).decode_utf8_lossy()
+}
+
+/// Returns whether a path contains a percent-encoded character that `decode_path_segments` decodes.
+fn has_percent_escape(path: &str) -> bool {
+  path.match_indices('%').any(
+    |(index, _)| match path.as_bytes().get(index + 1..index + 3) {
+      Some([a, b]) if a.is_ascii_hexdigit() && b.is_ascii_hexdigit() => {
+        !matches!((a, b.to_ascii_lowercase()), (b'2', b'f') | (b'5', b'c'))
+      }
+      _ => false,
+    },
+  )
+}
+
+/// A specifier that compares and hashes by its normalized form, so that specifiers which
+/// name the same module share a key: `./a/../b` equals `./b`, and `node:fs` equals `fs`.
+/// `Specifier` itself compares exactly; wrap it where normalized equality is wanted,
+/// e.g. as a map key when deduplicating imports.
+#[derive(Clone, Debug)]
+pub struct NormalizedSpecifier<'a>(pub Specifier<'a>);
+
+impl<'a> NormalizedSpecifier<'a> {
+  pub fn new(specifier: Specifier<'a>) -> Self {
+    NormalizedSpecifier(specifier)
+  }
+
+  pub fn into_inner(self) -> Specifier<'a> {
+    self.0
+  }
+
+  /// Returns the normalized form used for comparisons. "." and ".." segments are collapsed
+  /// lexically (leading ".." segments of relative paths are kept), and the `node:` scheme
+  /// is removed from builtins.
+  pub fn normalized(&self) -> Specifier<'_> {
+    match &self.0 {
+      Specifier::Relative(path) => Specifier::Relative(normalize_lexically(path)),
+      Specifier::Absolute(path) => Specifier::Absolute(normalize_lexically(path)),
+      Specifier::RootRelative(path) => Specifier::RootRelative(normalize_lexically(path)),
+      Specifier::Tilde(path) => Specifier::Tilde(normalize_lexically(path)),
+      Specifier::SchemeAlias { scheme, rest } => Specifier::SchemeAlias {
+        scheme: Cow::Borrowed(scheme),
+        rest: normalize_lexically(rest),
+      },
+      Specifier::Builtin(builtin) => Specifier::Builtin(Cow::Borrowed(
+        builtin.strip_prefix("node:").unwrap_or(builtin),
+      )),
+      // CommonJS specifiers such as `node:fs` are parsed as packages.
+      Specifier::Package(module, subpath)
+        if subpath.is_empty()
+          && module
+            .strip_prefix("node:")
+            .is_some_and(|name| BUILTINS.contains(&name)) =>
+      {
+        Specifier::Builtin(Cow::Borrowed(&module["node:".len()..]))
+      }
+      Specifier::Package(module, subpath) => Specifier::Package(
+        Cow::Borrowed(module),
+        normalize_package_subpath(Cow::Borrowed(subpath)).unwrap_or(Cow::Borrowed(subpath)),
+      ),
+      Specifier::Hash(hash) => Specifier::Hash(Cow::Borrowed(hash)),
+      Specifier::Url(url) => Specifier::Url(Cow::Borrowed(url)),
+      Specifier::Virtual(specifier) => Specifier::Virtual(Cow::Borrowed(specifier)),
+    }
+  }
+}
+
+impl<'a> From<Specifier<'a>> for NormalizedSpecifier<'a> {
+  fn from(specifier: Specifier<'a>) -> Self {
+    NormalizedSpecifier(specifier)
+  }
+}
+
+impl<'a> PartialEq for NormalizedSpecifier<'a> {
+  fn eq(&self, other: &Self) -> bool {
+    self.normalized() == other.normalized()
+  }
+}
+
+impl<'a> Eq for NormalizedSpecifier<'a> {}
+
+impl<'a> Hash for NormalizedSpecifier<'a> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.normalized().hash(state)
+  }
+}
+
+/// Collapses "." and ".." segments without touching the file system. Unlike `normalize_path`,
+/// leading ".." segments of a relative path are kept.
+fn normalize_lexically(path: &Path) -> Cow<'_, Path> {
+  if !path
+    .components()
+    .any(|c| matches!(c, Component::CurDir | Component::ParentDir))
+  {
+    return Cow::Borrowed(path);
+  }
+
+  let mut normalized = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir => match normalized.components().next_back() {
+        Some(Component::Normal(_)) => {
+          normalized.pop();
+        }
+        Some(Component::RootDir | Component::Prefix(_)) => {}
+        _ => normalized.push(".."),
+      },
+      component => normalized.push(component.as_os_str()),
+    }
+  }
+
+  Cow::Owned(normalized)
+}
+
+impl<'a> From<&'a str> for Specifier<'a> {
+  fn from(specifier: &'a str) -> Self {
+    Specifier::parse(specifier, SpecifierType::Cjs, Flags::empty())
+      .unwrap()
+      .0
+  }
+}
+
+impl<'a, 'de: 'a> serde::Deserialize<'de> for Specifier<'a> {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
   where
     D: serde::Deserializer<'de>,
   {
-    use serde::Deserialize;
-    let s: &'de str = Deserialize::deserialize(deserializer)?;
     // Specifiers are only deserialized as part of the "alias" and "browser" fields,
     // so we assume CJS specifiers in Parcel mode.
-    Specifier::parse(s, SpecifierType::Cjs, Flags::empty())
-      .map(|s| s.0)
-      .map_err(|_| serde::de::Error::custom("Invalid specifier"))
+    let parsed = match deserialize_str(deserializer)? {
+      Cow::Borrowed(s) => Specifier::parse(s, SpecifierType::Cjs, Flags::empty()).map(|s| s.0),
+      Cow::Owned(s) => {
+        Specifier::parse(&s, SpecifierType::Cjs, Flags::empty()).map(|s| s.0.into_owned())
+      }
+    };
+    parsed.map_err(|_| serde::de::Error::custom("Invalid specifier"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn package(specifier: &str) -> Specifier {
+    Specifier::parse(specifier, SpecifierType::Esm, Flags::empty())
+      .unwrap()
+      .0
+  }
+
+  #[test]
+  fn best_export_match() {
+    let keys = [
+      "./*",
+      "./feature/*",
+      "./feature/*.js",
+      "./feature/exact",
+      ".",
+    ];
+
+    assert_eq!(
+      package("foo").best_export_match(keys.iter().copied()),
+      Some((".", None))
+    );
+    assert_eq!(
+      package("foo/bar").best_export_match(keys.iter().copied()),
+      Some(("./*", Some("bar")))
+    );
+    assert_eq!(
+      package("foo/feature/bar").best_export_match(keys.iter().copied()),
+      Some(("./feature/*", Some("bar")))
+    );
+    assert_eq!(
+      package("foo/feature/bar.js").best_export_match(keys.iter().copied()),
+      Some(("./feature/*.js", Some("bar")))
+    );
+    assert_eq!(
+      package("foo/feature/exact").best_export_match(keys.iter().copied()),
+      Some(("./feature/exact", None))
+    );
+    assert_eq!(
+      package("@scope/foo/feature/a/b").best_export_match(keys.iter().copied()),
+      Some(("./feature/*", Some("a/b")))
+    );
+    assert_eq!(
+      package("foo/bar").best_export_match(["./feature/*", "import"].into_iter()),
+      None
+    );
+    assert_eq!(
+      package("#internal/foo").best_export_match(["#internal/*", "#*", "./*"].into_iter()),
+      Some(("#internal/*", Some("foo")))
+    );
+    assert_eq!(
+      package("./foo").best_export_match(keys.iter().copied()),
+      None
+    );
+    // Node requires a non-empty capture, so "feature/" only matches the shorter pattern.
+    assert_eq!(
+      package("foo/feature/").best_export_match(keys.iter().copied()),
+      Some(("./*", Some("feature/")))
+    );
+    assert_eq!(
+      package("foo/feature/").best_export_match(["./feature/*"].into_iter()),
+      None
+    );
+  }
+
+  #[test]
+  fn into_owned() {
+    let specifiers = [
+      ("./foo/bar.js", SpecifierType::Esm),
+      ("./with%20space.js", SpecifierType::Esm),
+      ("~/foo", SpecifierType::Esm),
+      ("/foo/bar", SpecifierType::Esm),
+      ("#internal", SpecifierType::Esm),
+      ("@scope/pkg/sub/path", SpecifierType::Esm),
+      ("foo%20bar/baz", SpecifierType::Esm),
+      ("lodash", SpecifierType::Cjs),
+      ("fs", SpecifierType::Esm),
+      ("node:fs", SpecifierType::Esm),
+      ("https://example.com/foo.js", SpecifierType::Url),
+    ];
+
+    for (input, specifier_type) in specifiers {
+      let (specifier, _) = Specifier::parse(input, specifier_type, Flags::empty()).unwrap();
+      let string = specifier.to_string().into_owned();
+      let owned: Specifier<'static> = specifier.clone().into_owned();
+      assert_eq!(owned, specifier);
+      assert_eq!(specifier.into_owned_string(), string);
+    }
+  }
+
+  #[test]
+  fn parse_path_only() {
+    let parse = |specifier, specifier_type, flags| {
+      Specifier::parse_path_only(specifier, specifier_type, flags).unwrap()
+    };
+
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs, SpecifierType::Url] {
+      // Bare words are relative paths, not packages, builtins, or URLs.
+      assert_eq!(
+        parse("foo.js", specifier_type, Flags::empty()),
+        (Specifier::Relative(Path::new("foo.js").into()), None)
+      );
+      assert_eq!(
+        parse("react", specifier_type, Flags::empty()),
+        (Specifier::Relative(Path::new("react").into()), None)
+      );
+      assert_eq!(
+        parse("fs", specifier_type, Flags::empty()),
+        (Specifier::Relative(Path::new("fs").into()), None)
+      );
+      assert_eq!(
+        parse("src/index.js", specifier_type, Flags::empty()),
+        (Specifier::Relative(Path::new("src/index.js").into()), None)
+      );
+      assert_eq!(
+        parse("./foo.js", specifier_type, Flags::empty()),
+        (Specifier::Relative(Path::new("foo.js").into()), None)
+      );
+      assert_eq!(
+        parse("../foo.js", specifier_type, Flags::empty()),
+        (Specifier::Relative(Path::new("../foo.js").into()), None)
+      );
+      assert_eq!(
+        parse("/foo.js", specifier_type, Flags::empty()),
+        (Specifier::Absolute(Path::new("/foo.js").into()), None)
+      );
+      assert_eq!(
+        parse("~/foo.js", specifier_type, Flags::TILDE_SPECIFIERS),
+        (Specifier::Tilde(Path::new("foo.js").into()), None)
+      );
+      assert_eq!(
+        parse("~foo.js", specifier_type, Flags::empty()),
+        (Specifier::Relative(Path::new("~foo.js").into()), None)
+      );
+    }
+
+    // Things that look like URLs or package imports are still paths.
+    assert_eq!(
+      parse("#foo", SpecifierType::Cjs, Flags::empty()),
+      (Specifier::Relative(Path::new("#foo").into()), None)
+    );
+    assert_eq!(
+      parse("node:fs", SpecifierType::Esm, Flags::empty()),
+      (Specifier::Relative(Path::new("node:fs").into()), None)
+    );
+    assert_eq!(
+      parse("foo%20bar.js?query", SpecifierType::Esm, Flags::empty()),
+      (
+        Specifier::Relative(Path::new("foo bar.js").into()),
+        Some("?query")
+      )
+    );
+    assert_eq!(
+      Specifier::parse_path_only("", SpecifierType::Esm, Flags::empty()),
+      Err(SpecifierError::EmptySpecifier)
+    );
+  }
+
+  #[test]
+  fn directory_imports() {
+    for specifier_type in [SpecifierType::Cjs, SpecifierType::Esm, SpecifierType::Url] {
+      let parse = |specifier| {
+        Specifier::parse(specifier, specifier_type, Flags::empty())
+          .unwrap()
+          .0
+      };
+
+      assert_eq!(
+        parse("."),
+        Specifier::Relative(Cow::Borrowed(Path::new(".")))
+      );
+      assert_eq!(
+        parse(".."),
+        Specifier::Relative(Cow::Borrowed(Path::new("..")))
+      );
+      assert_eq!(
+        parse("./"),
+        Specifier::Relative(Cow::Borrowed(Path::new(".")))
+      );
+      assert_eq!(
+        parse("../"),
+        Specifier::Relative(Cow::Borrowed(Path::new("..")))
+      );
+      for specifier in [".", "..", "./", "../", "./utils/", "../utils/"] {
+        assert!(parse(specifier).is_directory_import(), "{}", specifier);
+      }
+
+      for specifier in ["./utils", "../utils", "./.hidden", "..foo"] {
+        assert!(!parse(specifier).is_directory_import(), "{}", specifier);
+      }
+    }
+
+    assert!(!Specifier::from("utils/").is_directory_import());
+    assert!(!Specifier::from("/utils/").is_directory_import());
+  }
+
+  #[test]
+  fn extension() {
+    let extension = |specifier, compound| {
+      let (specifier, _) =
+        Specifier::parse(specifier, SpecifierType::Esm, Flags::TILDE_SPECIFIERS).unwrap();
+      specifier.extension(compound).map(|ext| ext.to_owned())
+    };
+
+    assert_eq!(extension("./x.js", false).as_deref(), Some("js"));
+    assert_eq!(extension("./x.js", true).as_deref(), Some("js"));
+    assert_eq!(extension("./x.d.ts", false).as_deref(), Some("ts"));
+    assert_eq!(extension("./x.d.ts", true).as_deref(), Some("d.ts"));
+    assert_eq!(extension("./x.d.mts", true).as_deref(), Some("d.mts"));
+    assert_eq!(extension("./x.tar.gz", true).as_deref(), Some("tar.gz"));
+    assert_eq!(extension("./x.min.js", true).as_deref(), Some("js"));
+    assert_eq!(
+      extension("./jquery-3.7.1.min.js", true).as_deref(),
+      Some("js")
+    );
+    assert_eq!(extension("./d.ts", true).as_deref(), Some("ts"));
+    assert_eq!(
+      extension("/foo/bar.module.css?inline", false).as_deref(),
+      Some("css")
+    );
+    assert_eq!(extension("~/foo.json", false).as_deref(), Some("json"));
+    assert_eq!(extension("lodash/get.js", false).as_deref(), Some("js"));
+    assert_eq!(extension("./.eslintrc.json", true).as_deref(), Some("json"));
+    for specifier in [
+      "lodash/fp",
+      "lodash.merge",
+      "./.babelrc",
+      "./utils.v2/",
+      "./x.",
+      "..",
+      "fs",
+      "#foo.js",
+      "data:text/javascript,export default 2",
+    ] {
+      assert_eq!(extension(specifier, false), None, "{}", specifier);
+      assert_eq!(extension(specifier, true), None, "{}", specifier);
+    }
+  }
+
+  #[test]
+  fn requires_fs() {
+    let options = ParseOptions {
+      virtual_prefixes: vec!["$app/".into()],
+      ..ParseOptions::default()
+    };
+    let requires_fs = |specifier, specifier_type| {
+      Specifier::parse_with_options(specifier, specifier_type, Flags::TILDE_SPECIFIERS, &options)
+        .unwrap()
+        .0
+        .requires_fs()
+    };
+
+    for specifier in [
+      "./foo.js",
+      "../foo",
+      "/foo/bar.js",
+      "~/foo",
+      "#foo",
+      "react",
+      "react/jsx-runtime",
+    ] {
+      assert!(requires_fs(specifier, SpecifierType::Esm), "{}", specifier);
+    }
+    for specifier in [
+      "node:fs",
+      "fs",
+      "data:text/javascript,export default 2",
+      "$app/stores",
+    ] {
+      assert!(!requires_fs(specifier, SpecifierType::Esm), "{}", specifier);
+    }
+    assert!(!requires_fs(
+      "https://example.com/foo.png",
+      SpecifierType::Url
+    ));
+  }
+
+  #[test]
+  fn rebase() {
+    let rebase = |specifier: &str, old_base: &str, new_base: &str| {
+      Specifier::parse(specifier, SpecifierType::Esm, Flags::empty())
+        .unwrap()
+        .0
+        .rebase(Path::new(old_base), Path::new(new_base))
+    };
+    let relative = |path: &str| Specifier::Relative(Cow::Owned(PathBuf::from(path)));
+
+    assert_eq!(
+      rebase("./foo.js", "/app/src", "/app/src"),
+      relative("foo.js")
+    );
+    // Moving into nested directories.
+    assert_eq!(
+      rebase("./foo.js", "/app/src", "/app/src/nested/deep"),
+      relative("../../foo.js")
+    );
+    assert_eq!(
+      rebase(
+        "../lib/util.js",
+        "/app/src/components",
+        "/app/src/components/forms/inputs"
+      ),
+      relative("../../../lib/util.js")
+    );
+    // Moving out of nested directories.
+    assert_eq!(
+      rebase("../../lib/util.js", "/app/src/a/b", "/app/src"),
+      relative("lib/util.js")
+    );
+    assert_eq!(
+      rebase("./x.js", "/app/a/b/c", "/app/d/e"),
+      relative("../../a/b/c/x.js")
+    );
+    assert_eq!(
+      rebase("./../shared/./x.js", "/app/a/b", "/app/a"),
+      relative("shared/x.js")
+    );
+    // Directory imports remain directory imports.
+    assert_eq!(
+      rebase("./utils/", "/app/src", "/app"),
+      relative("src/utils/")
+    );
+    assert_eq!(rebase(".", "/app/src/a", "/app/src"), relative("a/"));
+    assert_eq!(rebase("..", "/app/src/a", "/app/src/b"), relative(".."));
+    assert_eq!(rebase("./", "/app/src", "/app/src"), relative(""));
+    assert_eq!(rebase("../a", "/app/src/b", "/app/src/a"), relative("."));
+
+    // Other specifiers are unchanged.
+    for specifier in [
+      "react",
+      "@scope/pkg/sub",
+      "/abs/file.js",
+      "#internal",
+      "node:fs",
+    ] {
+      assert_eq!(
+        rebase(specifier, "/app/src", "/app/other"),
+        Specifier::parse(specifier, SpecifierType::Esm, Flags::empty())
+          .unwrap()
+          .0
+      );
+    }
+  }
+
+  #[test]
+  fn hash_query() {
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, Flags::empty()).unwrap()
+    };
+
+    assert_eq!(
+      parse("#db", SpecifierType::Esm),
+      (Specifier::Hash("db".into()), None)
+    );
+    assert_eq!(
+      parse("#db?x=1", SpecifierType::Esm),
+      (Specifier::Hash("db".into()), Some("?x=1"))
+    );
+    assert_eq!(
+      parse("#db/sub?x=1", SpecifierType::Esm),
+      (Specifier::Hash("db/sub".into()), Some("?x=1"))
+    );
+    assert_eq!(
+      parse("#db?pool=primary", SpecifierType::Cjs),
+      (Specifier::Hash("db?pool=primary".into()), None)
+    );
+  }
+
+  #[test]
+  fn parse_context() {
+    let specifiers = [
+      "./foo.js?bar",
+      "~/foo",
+      "/foo",
+      "#foo",
+      "foo/bar",
+      "node:fs",
+      "npm:foo",
+      "https://example.com/foo.js",
+      "",
+    ];
+
+    for specifier_type in [SpecifierType::Cjs, SpecifierType::Esm, SpecifierType::Url] {
+      for flags in [Flags::empty(), Flags::PARCEL, Flags::NODE_ESM] {
+        let context = ParseContext::new(specifier_type, flags);
+        let copy = context;
+        for specifier in specifiers {
+          assert_eq!(
+            context.parse(specifier),
+            Specifier::parse(specifier, specifier_type, flags)
+          );
+          assert_eq!(copy.parse(specifier), context.parse(specifier));
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn parse_both() {
+    // Relative paths and plain package names mean the same in both.
+    let (esm, cjs) = Specifier::parse_both("./foo.js", Flags::empty());
+    assert_eq!(esm, cjs);
+    let (esm, cjs) = Specifier::parse_both("lodash/get", Flags::empty());
+    assert_eq!(esm, cjs);
+
+    // A bare word with a query or escape names a different package.
+    let (esm, cjs) = Specifier::parse_both("sibling?raw", Flags::empty());
+    assert_eq!(
+      esm,
+      Ok((
+        Specifier::Package("sibling".into(), "".into()),
+        Some("?raw")
+      ))
+    );
+    assert_eq!(
+      cjs,
+      Ok((Specifier::Package("sibling?raw".into(), "".into()), None))
+    );
+    let (esm, cjs) = Specifier::parse_both("my%20pkg", Flags::empty());
+    assert_eq!(
+      esm,
+      Ok((Specifier::Package("my pkg".into(), "".into()), None))
+    );
+    assert_eq!(
+      cjs,
+      Ok((Specifier::Package("my%20pkg".into(), "".into()), None))
+    );
+
+    // Only CommonJS supports Windows absolute paths. Elsewhere, ESM reads the drive as a scheme.
+    let (esm, cjs) = Specifier::parse_both(r"C:\foo.js", Flags::empty());
+    assert_ne!(esm, cjs);
+    #[cfg(not(windows))]
+    assert_eq!(esm, Err(SpecifierError::UnknownScheme("c".into())));
+  }
+
+  #[test]
+  fn only_builtins() {
+    let flags = Flags::ONLY_BUILTINS | Flags::NPM_SCHEME;
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, flags).map(|(specifier, _)| specifier)
+    };
+
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs] {
+      assert_eq!(
+        parse("fs", specifier_type),
+        Ok(Specifier::Builtin("fs".into()))
+      );
+      assert_eq!(
+        parse("lodash/get", specifier_type),
+        Err(SpecifierError::PackagesNotAllowed("lodash".into()))
+      );
+      assert_eq!(
+        parse("./lodash", specifier_type),
+        Ok(Specifier::Relative(Path::new("lodash").into()))
+      );
+    }
+    assert_eq!(
+      parse("node:fs", SpecifierType::Esm),
+      Ok(Specifier::Builtin("fs".into()))
+    );
+    assert_eq!(
+      parse("npm:@scope/pkg", SpecifierType::Esm),
+      Err(SpecifierError::PackagesNotAllowed("@scope/pkg".into()))
+    );
+  }
+
+  #[test]
+  fn to_url_string() {
+    let parse = |specifier| {
+      Specifier::parse(specifier, SpecifierType::Esm, Flags::empty())
+        .unwrap()
+        .0
+    };
+
+    let specifier = parse("pkg/my%20dir/caf%C3%A9.js");
+    assert_eq!(specifier.to_string(), "pkg/my dir/café.js");
+    assert_eq!(specifier.to_url_string(), "pkg/my%20dir/caf%C3%A9.js");
+    let specifier = parse("@scope/pkg/日本語 file.js");
+    assert_eq!(
+      specifier.to_url_string(),
+      "@scope/pkg/%E6%97%A5%E6%9C%AC%E8%AA%9E%20file.js"
+    );
+
+    // Decoded percent signs are re-encoded, but encoded separators are kept as written.
+    assert_eq!(parse("pkg/100%25.js").to_url_string(), "pkg/100%25.js");
+    assert_eq!(parse("pkg/a%2Fb.js").to_url_string(), "pkg/a%2Fb.js");
+
+    assert_eq!(parse("./a%20b/c.js").to_url_string(), "a%20b/c.js");
+    assert!(matches!(parse("pkg").to_url_string(), Cow::Borrowed("pkg")));
+  }
+
+  #[test]
+  fn strip_invisible_prefix() {
+    let parse = |specifier, options: &ParseOptions| {
+      Specifier::parse_with_options(specifier, SpecifierType::Esm, Flags::empty(), options)
+        .map(|s| s.0)
+    };
+
+    let options = ParseOptions::default();
+    assert_eq!(
+      parse("\u{FEFF}./foo.js", &options),
+      Ok(Specifier::Package(
+        Cow::Borrowed("\u{FEFF}."),
+        Cow::Borrowed("foo.js")
+      ))
+    );
+
+    let options = ParseOptions {
+      strip_invisible_prefix: true,
+      ..ParseOptions::default()
+    };
+    assert_eq!(
+      parse("\u{FEFF}./foo.js", &options),
+      Ok(Specifier::Relative(Cow::Borrowed(Path::new("foo.js"))))
+    );
+    assert_eq!(
+      parse("\u{200B}\u{200D}../foo.js", &options),
+      Ok(Specifier::Relative(Cow::Borrowed(Path::new("../foo.js"))))
+    );
+    assert_eq!(
+      parse("\u{FEFF}", &options),
+      Err(SpecifierError::EmptySpecifier)
+    );
+    assert_eq!(super::strip_invisible_prefix("foo\u{FEFF}"), "foo\u{FEFF}");
+  }
+
+  #[test]
+  fn typed_schemes() {
+    let mut options = ParseOptions::default();
+    options.typed_schemes.insert("text".into(), "text".into());
+    options.typed_schemes.insert("sqlite".into(), "sql".into());
+    let parse = |specifier, specifier_type| {
+      Specifier::parse_typed(specifier, specifier_type, Flags::empty(), &options).unwrap()
+    };
+
+    for specifier_type in [SpecifierType::Cjs, SpecifierType::Esm] {
+      assert_eq!(
+        parse("text:./readme.md", specifier_type),
+        (
+          Specifier::Relative(Cow::Borrowed(Path::new("readme.md"))),
+          None,
+          Some("text")
+        )
+      );
+      assert_eq!(
+        parse("sqlite:./db/schema.sql", specifier_type),
+        (
+          Specifier::Relative(Cow::Borrowed(Path::new("db/schema.sql"))),
+          None,
+          Some("sql")
+        )
+      );
+      assert_eq!(
+        parse("text:pkg/LICENSE", specifier_type),
+        (
+          Specifier::Package(Cow::Borrowed("pkg"), Cow::Borrowed("LICENSE")),
+          None,
+          Some("text")
+        )
+      );
+      assert_eq!(
+        parse("./readme.md", specifier_type),
+        (
+          Specifier::Relative(Cow::Borrowed(Path::new("readme.md"))),
+          None,
+          None
+        )
+      );
+    }
+
+    assert_eq!(
+      parse("text:./readme.md?raw", SpecifierType::Esm),
+      (
+        Specifier::Relative(Cow::Borrowed(Path::new("readme.md"))),
+        Some("?raw"),
+        Some("text")
+      )
+    );
+
+    // Unconfigured schemes are unaffected.
+    assert_eq!(
+      Specifier::parse_typed(
+        "bytes:./a.bin",
+        SpecifierType::Esm,
+        Flags::empty(),
+        &options
+      ),
+      Err(SpecifierError::UnknownScheme("bytes".into()))
+    );
+    assert_eq!(
+      Specifier::parse_with_options(
+        "text:./readme.md",
+        SpecifierType::Esm,
+        Flags::empty(),
+        &options
+      ),
+      Err(SpecifierError::UnknownScheme("text".into()))
+    );
+  }
+
+  #[test]
+  fn scheme_aliases() {
+    let mut options = ParseOptions::default();
+    options
+      .scheme_aliases
+      .insert("mylib".into(), PathBuf::from("/lib"));
+    options
+      .scheme_aliases
+      .insert("@company".into(), PathBuf::from("/company"));
+    let parse = |specifier, specifier_type, options| {
+      Specifier::parse_with_options(specifier, specifier_type, Flags::empty(), options)
+    };
+
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs, SpecifierType::Url] {
+      assert_eq!(
+        parse("mylib:components/x", specifier_type, &options),
+        Ok((
+          Specifier::SchemeAlias {
+            scheme: "mylib".into(),
+            rest: Path::new("components/x").into()
+          },
+          None
+        ))
+      );
+      assert_eq!(
+        parse("@company:/button.js", specifier_type, &options),
+        Ok((
+          Specifier::SchemeAlias {
+            scheme: "@company".into(),
+            rest: Path::new("button.js").into()
+          },
+          None
+        ))
+      );
+    }
+
+    assert_eq!(
+      parse("mylib:a%20b.js?foo", SpecifierType::Esm, &options),
+      Ok((
+        Specifier::SchemeAlias {
+          scheme: "mylib".into(),
+          rest: Path::new("a b.js").into()
+        },
+        Some("?foo")
+      ))
+    );
+    assert_eq!(
+      parse("mylib:components/x", SpecifierType::Esm, &options)
+        .unwrap()
+        .0
+        .to_string(),
+      "mylib:components/x"
+    );
+
+    // Unconfigured schemes are unaffected.
+    assert_eq!(
+      parse("otherlib:components/x", SpecifierType::Url, &options),
+      Ok((Specifier::Url("otherlib:components/x".into()), None))
+    );
+    assert_eq!(
+      parse(
+        "mylib:components/x",
+        SpecifierType::Url,
+        &ParseOptions::default()
+      ),
+      Ok((Specifier::Url("mylib:components/x".into()), None))
+    );
+  }
+
+  #[test]
+  fn known_schemes() {
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, Flags::NPM_SCHEME).map(|s| s.0)
+    };
+
+    // Unknown schemes are an error in ESM, but allowed in URLs.
+    assert_eq!(
+      parse("v8:inspector", SpecifierType::Esm),
+      Err(SpecifierError::UnknownScheme("v8".into()))
+    );
+    assert_eq!(
+      parse("HTTP://example.com", SpecifierType::Esm),
+      Err(SpecifierError::UnknownScheme("http".into()))
+    );
+    assert_eq!(
+      parse("v8:inspector", SpecifierType::Url),
+      Ok(Specifier::Url("v8:inspector".into()))
+    );
+    assert_eq!(
+      parse("https://example.com/foo.png", SpecifierType::Url),
+      Ok(Specifier::Url("https://example.com/foo.png".into()))
+    );
+
+    // CommonJS specifiers never have a scheme.
+    assert_eq!(
+      parse("v8:inspector", SpecifierType::Cjs),
+      Ok(Specifier::Package("v8:inspector".into(), "".into()))
+    );
+
+    // Known schemes.
+    assert_eq!(
+      parse("npm:react", SpecifierType::Esm),
+      Ok(Specifier::Package("react".into(), "".into()))
+    );
+    assert_eq!(
+      Specifier::parse("npm:react", SpecifierType::Esm, Flags::empty()).map(|s| s.0),
+      Ok(Specifier::Url("npm:react".into()))
+    );
+    assert_eq!(
+      parse("node:fs", SpecifierType::Esm),
+      Ok(Specifier::Builtin("fs".into()))
+    );
+    assert_eq!(
+      parse("data:text/javascript,export default 2", SpecifierType::Esm),
+      Ok(Specifier::Url(
+        "data:text/javascript,export default 2".into()
+      ))
+    );
+    #[cfg(not(windows))]
+    assert_eq!(
+      parse("file:///foo/bar.js", SpecifierType::Esm),
+      Ok(Specifier::Absolute(Path::new("/foo/bar.js").into()))
+    );
+
+    // Configured external schemes.
+    let options = ParseOptions {
+      external_schemes: vec!["https".into()],
+      ..ParseOptions::default()
+    };
+    assert_eq!(
+      Specifier::parse_with_options(
+        "https://example.com/foo.js",
+        SpecifierType::Esm,
+        Flags::empty(),
+        &options
+      )
+      .map(|s| s.0),
+      Ok(Specifier::Url("https://example.com/foo.js".into()))
+    );
+
+    // Package names that look a bit like URLs.
+    assert_eq!(
+      parse("http2", SpecifierType::Esm),
+      Ok(Specifier::Builtin("http2".into()))
+    );
+    assert_eq!(
+      parse("c++-lib", SpecifierType::Esm),
+      Ok(Specifier::Package("c++-lib".into(), "".into()))
+    );
+    assert_eq!(
+      parse("c++-lib/a:b", SpecifierType::Esm),
+      Ok(Specifier::Package("c++-lib".into(), "a:b".into()))
+    );
+
+    // Single letter schemes are drive letters on Windows.
+    #[cfg(not(windows))]
+    assert_eq!(
+      parse("c:foo", SpecifierType::Esm),
+      Err(SpecifierError::UnknownScheme("c".into()))
+    );
+    #[cfg(windows)]
+    assert_eq!(parse_scheme("c:foo"), Err(()));
+  }
+
+  #[cfg(windows)]
+  #[test]
+  fn windows_relative_prefixes() {
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, Flags::empty())
+        .unwrap()
+        .0
+    };
+
+    for specifier_type in [SpecifierType::Cjs, SpecifierType::Esm, SpecifierType::Url] {
+      assert_eq!(
+        parse(".\\foo.js", specifier_type),
+        Specifier::Relative(Path::new("foo.js").into())
+      );
+      assert_eq!(
+        parse("..\\bar.js", specifier_type),
+        Specifier::Relative(Path::new("../bar.js").into())
+      );
+    }
+
+    // ESM specifiers use forward slashes, like on other platforms.
+    match parse(".\\dir\\foo.js", SpecifierType::Esm) {
+      Specifier::Relative(path) => assert_eq!(path.to_str(), Some("dir/foo.js")),
+      specifier => panic!("unexpected {:?}", specifier),
+    }
+  }
+
+  #[test]
+  fn windows_separators() {
+    let flags = Flags::TILDE_SPECIFIERS | Flags::WINDOWS_SEPARATORS;
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, flags)
+        .unwrap()
+        .0
+    };
+
+    for specifier_type in [SpecifierType::Cjs, SpecifierType::Esm] {
+      assert_eq!(
+        parse("~\\foo\\bar.js", specifier_type),
+        Specifier::Tilde(Path::new("foo/bar.js").into())
+      );
+      assert_eq!(
+        parse(".\\foo\\bar.js", specifier_type),
+        Specifier::Relative(Path::new("foo/bar.js").into())
+      );
+      assert_eq!(
+        parse("..\\foo.js", specifier_type),
+        Specifier::Relative(Path::new("../foo.js").into())
+      );
+    }
+    assert_eq!(
+      Specifier::parse_path_only("src\\index.js", SpecifierType::Cjs, flags)
+        .unwrap()
+        .0,
+      Specifier::Relative(Path::new("src/index.js").into())
+    );
+
+    // Without the flag, backslashes are only separators on Windows.
+    #[cfg(not(windows))]
+    assert_eq!(
+      Specifier::parse(
+        "~\\foo\\bar.js",
+        SpecifierType::Cjs,
+        Flags::TILDE_SPECIFIERS
+      )
+      .unwrap()
+      .0,
+      Specifier::Tilde(Path::new("\\foo\\bar.js").into())
+    );
+  }
+
+  #[test]
+  fn encoded_separators() {
+    let parse = |specifier| {
+      Specifier::parse(specifier, SpecifierType::Esm, Flags::empty())
+        .unwrap()
+        .0
+    };
+
+    let specifier = parse("./a%2Fb.js");
+    assert_eq!(specifier, Specifier::Relative(Path::new("a%2Fb.js").into()));
+    match specifier {
+      Specifier::Relative(path) => assert_eq!(path.components().count(), 1),
+      _ => unreachable!(),
+    }
+
+    // The original case is preserved, and other characters are still decoded.
+    assert_eq!(
+      parse("./a%2fb%20c%5Cd.js"),
+      Specifier::Relative(Path::new("a%2fb c%5Cd.js").into())
+    );
+    assert_eq!(
+      parse("/foo%2F%2F%25.js"),
+      Specifier::Absolute(Path::new("/foo%2F%2F%.js").into())
+    );
+    assert_eq!(
+      parse("./a%20b.js"),
+      Specifier::Relative(Path::new("a b.js").into())
+    );
+
+    // CommonJS specifiers are not decoded at all.
+    assert_eq!(
+      Specifier::parse("./a%2Fb.js", SpecifierType::Cjs, Flags::empty())
+        .unwrap()
+        .0,
+      Specifier::Relative(Path::new("a%2Fb.js").into())
+    );
+  }
+
+  #[test]
+  fn specifier_rewrite() {
+    let rewrite = |before, after, reason| SpecifierRewrite {
+      before: Specifier::from(before),
+      after: Specifier::from(after),
+      reason,
+    };
+
+    assert_eq!(
+      rewrite("react", "preact/compat", "browser field").to_string(),
+      "react → preact/compat (browser field)"
+    );
+    assert_eq!(
+      rewrite("./foo.js", "./foo-browser.js", "browser field").to_string(),
+      "./foo.js → ./foo-browser.js (browser field)"
+    );
+    assert_eq!(
+      rewrite("lodash", "../vendor/lodash.js", "alias field").to_string(),
+      "lodash → ../vendor/lodash.js (alias field)"
+    );
+    assert_eq!(
+      rewrite("~/foo", "/bar", "alias field").to_string(),
+      "~/foo → /bar (alias field)"
+    );
+  }
+
+  #[test]
+  fn root_absolute() {
+    let parse = |specifier, specifier_type, flags| {
+      Specifier::parse(specifier, specifier_type, flags).unwrap()
+    };
+
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs, SpecifierType::Url] {
+      assert_eq!(
+        parse("/src/index.js", specifier_type, Flags::ROOT_ABSOLUTE),
+        (
+          Specifier::RootRelative(Path::new("src/index.js").into()),
+          None
+        )
+      );
+      assert_eq!(
+        parse("/src/index.js", specifier_type, Flags::empty()),
+        (Specifier::Absolute(Path::new("/src/index.js").into()), None)
+      );
+    }
+
+    assert_eq!(
+      parse(
+        "/src/index.js?foo",
+        SpecifierType::Esm,
+        Flags::ROOT_ABSOLUTE
+      ),
+      (
+        Specifier::RootRelative(Path::new("src/index.js").into()),
+        Some("?foo")
+      )
+    );
+
+    // Protocol-relative URLs are unaffected.
+    assert_eq!(
+      parse(
+        "//example.com/foo.png",
+        SpecifierType::Url,
+        Flags::ROOT_ABSOLUTE
+      ),
+      (Specifier::Url("//example.com/foo.png".into()), None)
+    );
+    assert_eq!(
+      parse("//foo/bar.js", SpecifierType::Esm, Flags::ROOT_ABSOLUTE),
+      (Specifier::Absolute(Path::new("//foo/bar.js").into()), None)
+    );
+  }
+
+  #[test]
+  fn virtual_prefixes() {
+    let options = ParseOptions {
+      virtual_prefixes: vec!["$app/".into(), "$env/".into(), "astro:".into()],
+      ..ParseOptions::default()
+    };
+    let parse = |specifier, specifier_type, options| {
+      Specifier::parse_with_options(specifier, specifier_type, Flags::empty(), options)
+        .unwrap()
+        .0
+    };
+
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs, SpecifierType::Url] {
+      assert_eq!(
+        parse("$app/stores", specifier_type, &options),
+        Specifier::Virtual("$app/stores".into())
+      );
+      assert_eq!(
+        parse("$env/static/public", specifier_type, &options),
+        Specifier::Virtual("$env/static/public".into())
+      );
+      assert_eq!(
+        parse("astro:content", specifier_type, &options),
+        Specifier::Virtual("astro:content".into())
+      );
+    }
+
+    // Prefixes are matched literally.
+    assert_eq!(
+      parse("$apple/foo", SpecifierType::Esm, &options),
+      Specifier::Package("$apple".into(), "foo".into())
+    );
+
+    // Without the allowlist, these are parsed as before.
+    let default = ParseOptions::default();
+    assert_eq!(
+      parse("$app/stores", SpecifierType::Esm, &default),
+      Specifier::Package("$app".into(), "stores".into())
+    );
+    assert_eq!(
+      parse("astro:content", SpecifierType::Url, &default),
+      Specifier::Url("astro:content".into())
+    );
+    assert_eq!(
+      Specifier::parse_with_options(
+        "astro:content",
+        SpecifierType::Esm,
+        Flags::empty(),
+        &default
+      ),
+      Err(SpecifierError::UnknownScheme("astro".into()))
+    );
+  }
+
+  #[test]
+  fn normalize_package_subpath() {
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, Flags::NORMALIZE_PACKAGE_SUBPATH).map(|s| s.0)
+    };
+
+    assert_eq!(
+      parse("lodash/./fp/../merge", SpecifierType::Esm),
+      Ok(Specifier::Package("lodash".into(), "merge".into()))
+    );
+    assert_eq!(
+      parse("lodash/./fp/../merge", SpecifierType::Cjs),
+      Ok(Specifier::Package("lodash".into(), "merge".into()))
+    );
+    assert_eq!(
+      parse("@scope/pkg/a/./b/", SpecifierType::Esm),
+      Ok(Specifier::Package("@scope/pkg".into(), "a/b/".into()))
+    );
+    assert_eq!(
+      parse("lodash/fp/..", SpecifierType::Esm),
+      Ok(Specifier::Package("lodash".into(), "".into()))
+    );
+    assert_eq!(
+      parse("lodash/../react", SpecifierType::Esm),
+      Err(SpecifierError::InvalidPackageSpecifier)
+    );
+    assert_eq!(
+      parse("lodash/fp/../../react", SpecifierType::Cjs),
+      Err(SpecifierError::InvalidPackageSpecifier)
+    );
+
+    // Without the flag, the subpath is kept verbatim.
+    assert_eq!(
+      Specifier::parse("lodash/./fp/merge", SpecifierType::Esm, Flags::empty())
+        .unwrap()
+        .0,
+      Specifier::Package("lodash".into(), "./fp/merge".into())
+    );
+  }
+
+  #[test]
+  fn package_subpath_parent_dir() {
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, Flags::empty()).map(|s| s.0)
+    };
+
+    // ".." segments are invalid in ESM, including percent encoded forms.
+    for specifier in [
+      "pkg/../other-pkg/file.js",
+      "pkg/lib/../index.js",
+      "pkg/%2e%2e/other-pkg/file.js",
+      "pkg/%2E./other-pkg/file.js",
+      "@scope/pkg/..",
+    ] {
+      assert_eq!(
+        parse(specifier, SpecifierType::Esm),
+        Err(SpecifierError::InvalidPackageSpecifier),
+        "{}",
+        specifier
+      );
+    }
+    assert_eq!(
+      parse("pkg/..foo/a..b.js", SpecifierType::Esm),
+      Ok(Specifier::Package("pkg".into(), "..foo/a..b.js".into()))
+    );
+
+    // CommonJS resolves them within the package root, but never escapes it.
+    assert_eq!(
+      parse("pkg/lib/../index.js", SpecifierType::Cjs),
+      Ok(Specifier::Package("pkg".into(), "index.js".into()))
+    );
+    assert_eq!(
+      parse("pkg/../other-pkg/file.js", SpecifierType::Cjs),
+      Err(SpecifierError::InvalidPackageSpecifier)
+    );
+    assert_eq!(
+      parse("@scope/pkg/a/../../b", SpecifierType::Cjs),
+      Err(SpecifierError::InvalidPackageSpecifier)
+    );
+    // CommonJS specifiers are not percent decoded, so this is a directory name.
+    assert_eq!(
+      parse("pkg/%2e%2e/file.js", SpecifierType::Cjs),
+      Ok(Specifier::Package("pkg".into(), "%2e%2e/file.js".into()))
+    );
+  }
+
+  #[test]
+  fn duplicate_slashes() {
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, Flags::empty()).map(|s| s.0)
+    };
+
+    assert!(has_duplicate_slashes("./a//b.js"));
+    assert!(has_duplicate_slashes("a///b"));
+    assert!(!has_duplicate_slashes("//server/share/a.js"));
+    assert!(!has_duplicate_slashes("./a/b.js"));
+
+    // URL and ESM paths treat "//" as an empty segment, which is removed.
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Url] {
+      assert_eq!(
+        parse("./a//b.js", specifier_type),
+        Ok(Specifier::Relative(Path::new("a/b.js").into()))
+      );
+      assert_eq!(
+        parse("./a///b/?q", specifier_type),
+        Ok(Specifier::Relative(Path::new("a/b/").into()))
+      );
+      assert_eq!(
+        parse("/a//b.js", specifier_type),
+        Ok(Specifier::Absolute(Path::new("/a/b.js").into()))
+      );
+    }
+    assert_eq!(
+      parse("a///b", SpecifierType::Url),
+      Ok(Specifier::Relative(Path::new("a/b").into()))
+    );
+    assert_eq!(
+      parse("a///b", SpecifierType::Esm),
+      Ok(Specifier::Package("a".into(), "b".into()))
+    );
+    // Encoded separators are kept, and never merge with a literal one.
+    assert_eq!(
+      parse("./a/%2F/b.js", SpecifierType::Esm),
+      Ok(Specifier::Relative(Path::new("a/%2F/b.js").into()))
+    );
+
+    // CommonJS specifiers are OS paths, and are left alone.
+    assert_eq!(
+      parse("./a//b.js", SpecifierType::Cjs),
+      Ok(Specifier::Relative(Path::new("a//b.js").into()))
+    );
+  }
+
+  #[test]
+  fn split_specifier_parts() {
+    let parts = |scheme, path, query, fragment| SpecifierParts {
+      scheme,
+      path,
+      query,
+      fragment,
+    };
+
+    assert_eq!(
+      split_specifier("./foo.js?query#hash"),
+      parts(None, "./foo.js", Some("query"), Some("hash"))
+    );
+    assert_eq!(
+      split_specifier("https://example.com/a.js#frag?not-query"),
+      parts(
+        Some("https"),
+        "//example.com/a.js",
+        None,
+        Some("frag?not-query")
+      )
+    );
+    assert_eq!(
+      split_specifier("NPM:react@18/jsx-runtime"),
+      parts(Some("NPM"), "react@18/jsx-runtime", None, None)
+    );
+    assert_eq!(
+      split_specifier("#internal"),
+      parts(None, "", None, Some("internal"))
+    );
+    assert_eq!(
+      split_specifier("lodash?#"),
+      parts(None, "lodash", Some(""), Some(""))
+    );
+    // Byte offsets are used, so multibyte characters before a delimiter are fine.
+    assert_eq!(
+      split_specifier("./ünïcödé.js?ä#ö"),
+      parts(None, "./ünïcödé.js", Some("ä"), Some("ö"))
+    );
+    assert_eq!(split_specifier(""), parts(None, "", None, None));
+  }
+
+  #[test]
+  fn normalized_specifier() {
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash<T: Hash>(value: &T) -> u64 {
+      let mut hasher = DefaultHasher::new();
+      value.hash(&mut hasher);
+      hasher.finish()
+    }
+
+    let a = Specifier::from("./a/../b");
+    let b = Specifier::from("./b");
+    assert_ne!(a, b);
+    assert_ne!(hash(&a), hash(&b));
+
+    let a = NormalizedSpecifier(a);
+    let b = NormalizedSpecifier(b);
+    assert_eq!(a, b);
+    assert_eq!(hash(&a), hash(&b));
+    assert_eq!(a.into_inner(), Specifier::from("./a/../b"));
+
+    let normalized = |s| NormalizedSpecifier(Specifier::from(s));
+    assert_eq!(normalized("../x/./y/.."), normalized("../x"));
+    assert_ne!(normalized("../b"), normalized("./b"));
+    assert_eq!(normalized("/a/../../b"), normalized("/b"));
+    assert_eq!(normalized("node:fs"), normalized("fs"));
+    assert_eq!(normalized("pkg/a/../b"), normalized("pkg/b"));
+    assert_ne!(normalized("pkg/b"), normalized("other/b"));
+
+    let esm = |s| {
+      NormalizedSpecifier(
+        Specifier::parse(s, SpecifierType::Esm, Flags::empty())
+          .unwrap()
+          .0,
+      )
+    };
+    assert_eq!(esm("node:path"), esm("path"));
+    assert_eq!(esm("node:path"), normalized("path"));
+  }
+
+  #[test]
+  fn parse_with_meta() {
+    let decoded = |specifier, specifier_type| {
+      Specifier::parse_with_meta(
+        specifier,
+        specifier_type,
+        Flags::empty(),
+        &ParseOptions::default(),
+      )
+      .unwrap()
+      .2
+      .decoded
+    };
+
+    assert!(decoded("./a%20b.js", SpecifierType::Esm));
+    assert!(!decoded("./ab.js", SpecifierType::Esm));
+    assert!(decoded("./a%20b.js", SpecifierType::Url));
+    assert!(decoded("@scope/pkg/a%20b", SpecifierType::Esm));
+    // The query is not part of the path.
+    assert!(!decoded("./ab.js?q=%20", SpecifierType::Esm));
+    // Encoded separators and invalid escapes are kept as is.
+    assert!(!decoded("./a%2Fb.js", SpecifierType::Esm));
+    assert!(!decoded("./100%.js", SpecifierType::Esm));
+    // CommonJS specifiers are never decoded.
+    assert!(!decoded("./a%20b.js", SpecifierType::Cjs));
+
+    let ambiguous = |specifier, specifier_type, flags| {
+      Specifier::parse_with_meta(specifier, specifier_type, flags, &ParseOptions::default())
+        .unwrap()
+        .2
+        .ambiguous
+    };
+    let flags = Flags::IMPLICIT_RELATIVE;
+    assert!(ambiguous("sibling", SpecifierType::Esm, flags));
+    assert!(ambiguous("lib/helper", SpecifierType::Esm, flags));
+    assert!(!ambiguous("sibling", SpecifierType::Esm, Flags::empty()));
+    // Builtins, scoped packages, and explicit paths are never ambiguous.
+    assert!(!ambiguous("fs", SpecifierType::Esm, flags));
+    assert!(!ambiguous("node:fs", SpecifierType::Esm, flags));
+    assert!(!ambiguous("@scope/pkg", SpecifierType::Esm, flags));
+    assert!(!ambiguous("./sibling", SpecifierType::Esm, flags));
+    assert!(!ambiguous("sibling", SpecifierType::Cjs, flags));
+
+    let (specifier, query, _) = Specifier::parse_with_meta(
+      "./a%20b.js?q",
+      SpecifierType::Esm,
+      Flags::empty(),
+      &ParseOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(specifier, Specifier::Relative(Path::new("a b.js").into()));
+    assert_eq!(query, Some("?q"));
+  }
+
+  #[test]
+  fn kind_str() {
+    let path = || Cow::Borrowed(Path::new("foo.js"));
+    let cases = [
+      (Specifier::Relative(path()), "relative"),
+      (Specifier::Absolute(path()), "absolute"),
+      (Specifier::RootRelative(path()), "root_relative"),
+      (Specifier::Tilde(path()), "tilde"),
+      (Specifier::Hash("foo".into()), "hash"),
+      (Specifier::Package("foo".into(), "".into()), "package"),
+      (Specifier::Builtin("fs".into()), "builtin"),
+      (Specifier::Url("https://example.com".into()), "url"),
+      (Specifier::Virtual("$app/stores".into()), "virtual"),
+      (
+        Specifier::SchemeAlias {
+          scheme: "lib".into(),
+          rest: path(),
+        },
+        "scheme_alias",
+      ),
+    ];
+    for (specifier, kind) in cases {
+      assert_eq!(specifier.kind_str(), kind);
+    }
+  }
+
+  #[test]
+  fn package_trailing_slashes() {
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, Flags::empty()).map(|s| s.0)
+    };
+
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs] {
+      assert_eq!(
+        parse("pkg/", specifier_type),
+        Ok(Specifier::Package("pkg".into(), ".".into()))
+      );
+      assert_eq!(
+        parse("@scope/pkg/", specifier_type),
+        Ok(Specifier::Package("@scope/pkg".into(), ".".into()))
+      );
+      assert_eq!(
+        parse("pkg//", specifier_type),
+        Ok(Specifier::Package("pkg".into(), ".".into()))
+      );
+      assert_eq!(
+        parse("pkg/sub//double", specifier_type),
+        Ok(Specifier::Package("pkg".into(), "sub/double".into()))
+      );
+      assert_eq!(
+        parse("pkg/sub//", specifier_type),
+        Ok(Specifier::Package("pkg".into(), "sub/".into()))
+      );
+    }
+
+    assert_eq!(parse_package_specifier("pkg/"), Ok(("pkg", ".")));
+    assert_eq!(parse_package_specifier("pkg"), Ok(("pkg", "")));
+    assert_eq!(Specifier::from("@scope/pkg/").to_string(), "@scope/pkg/");
+    assert_eq!(
+      Specifier::parse("pkg/", SpecifierType::Esm, Flags::NORMALIZE_PACKAGE_SUBPATH)
+        .map(|(specifier, _)| specifier),
+      Ok(Specifier::Package("pkg".into(), ".".into()))
+    );
+  }
+
+  #[test]
+  fn invalid_paths() {
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, Flags::empty()).map(|s| s.0)
+    };
+
+    // Interior NUL bytes are rejected for every specifier type, literal or encoded.
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs, SpecifierType::Url] {
+      assert_eq!(
+        parse("./a\0b.js", specifier_type),
+        Err(SpecifierError::InvalidPath(3))
+      );
+      assert_eq!(
+        parse("pkg/a\0b", specifier_type),
+        Err(SpecifierError::InvalidPath(5))
+      );
+    }
+    assert_eq!(
+      parse("./a%00b.js", SpecifierType::Esm),
+      Err(SpecifierError::InvalidPath(3))
+    );
+    assert_eq!(
+      parse("./a%00b.js", SpecifierType::Url),
+      Err(SpecifierError::InvalidPath(3))
+    );
+
+    // An encoded lone surrogate isn't valid UTF-8. URLs are decoded lossily instead, and
+    // CommonJS specifiers are not decoded at all.
+    assert_eq!(
+      parse("./a%ED%A0%80b", SpecifierType::Esm),
+      Err(SpecifierError::InvalidPath(3))
+    );
+    assert_eq!(
+      parse("@scope/pkg/%C3%A9%ED%A0%80", SpecifierType::Esm),
+      Err(SpecifierError::InvalidPath(17))
+    );
+    assert_eq!(
+      parse("./a%ED%A0%80b", SpecifierType::Url),
+      Ok(Specifier::Relative(
+        Path::new("a\u{FFFD}\u{FFFD}\u{FFFD}b").into()
+      ))
+    );
+    assert_eq!(
+      parse("./a%ED%A0%80b", SpecifierType::Cjs),
+      Ok(Specifier::Relative(Path::new("a%ED%A0%80b").into()))
+    );
+    // Valid escapes and invalid escape syntax are unaffected, as is the query.
+    assert!(parse("./%C3%A9.js", SpecifierType::Esm).is_ok());
+    assert!(parse("./100%.js", SpecifierType::Esm).is_ok());
+    assert!(parse("./a.js?%00", SpecifierType::Esm).is_ok());
+
+    assert_eq!(
+      decode_path("./a%00b", SpecifierType::Esm),
+      Err(SpecifierError::InvalidPath(3))
+    );
+  }
+
+  #[test]
+  fn percent_decode_fast_path() {
+    // Without a `%`, the input is borrowed as is.
+    for path in ["foo/bar.js", "", "é/ü.js", "a b"] {
+      assert!(matches!(percent_decode_path(path), Cow::Borrowed(p) if p == path));
+      assert!(matches!(decode_path_segments(path), Cow::Borrowed(p) if p == path));
+    }
+
+    assert_eq!(percent_decode_path("a%20b"), "a b");
+    assert_eq!(percent_decode_path("%C3%A9"), "é");
+    assert_eq!(percent_decode_path("100%"), "100%");
+    assert_eq!(percent_decode_path("%zz"), "%zz");
+    assert_eq!(decode_path_segments("a%2Fb%20c"), "a%2Fb c");
+    assert_eq!(decode_path_segments("%2f%C3%A9"), "%2fé");
+
+    let parse = |specifier| {
+      Specifier::parse(specifier, SpecifierType::Esm, Flags::empty())
+        .unwrap()
+        .0
+    };
+    assert_eq!(
+      parse("@scope/pkg/a%20b"),
+      Specifier::Package("@scope/pkg".into(), "a b".into())
+    );
+    assert!(matches!(
+      parse("@scope/pkg/a-b"),
+      Specifier::Package(Cow::Borrowed("@scope/pkg"), Cow::Borrowed("a-b"))
+    ));
+  }
+
+  #[test]
+  fn validate() {
+    for specifier in [
+      "foo",
+      "foo/bar",
+      "@scope/pkg/sub",
+      "fs",
+      "#internal",
+      "./a.js",
+    ] {
+      let (parsed, _) = Specifier::parse(specifier, SpecifierType::Esm, Flags::empty()).unwrap();
+      assert_eq!(parsed.validate(), Ok(()), "{}", specifier);
+    }
+
+    for module in [
+      "",
+      "foo/bar",
+      "@scope",
+      "@scope/",
+      "@/pkg",
+      "@scope/pkg/sub",
+    ] {
+      assert_eq!(
+        Specifier::Package(module.into(), "".into()).validate(),
+        Err(SpecifierError::InvalidPackageSpecifier),
+        "{:?}",
+        module
+      );
+    }
+
+    assert_eq!(
+      Specifier::Builtin("not-a-builtin".into()).validate(),
+      Err(SpecifierError::UnknownBuiltin("not-a-builtin".into()))
+    );
+    assert_eq!(Specifier::Builtin("path".into()).validate(), Ok(()));
+    assert_eq!(
+      Specifier::Hash("".into()).validate(),
+      Err(SpecifierError::EmptySpecifier)
+    );
+  }
+
+  #[test]
+  fn scoped_package_names() {
+    assert_eq!(
+      parse_package_specifier("@ünicode/pkg/ßub"),
+      Ok(("@ünicode/pkg", "ßub"))
+    );
+    assert_eq!(
+      parse_package_specifier("@scope/ünï/ßub/é"),
+      Ok(("@scope/ünï", "ßub/é"))
+    );
+    assert_eq!(parse_package_specifier("@ü/ß"), Ok(("@ü/ß", "")));
+    assert_eq!(parse_package_specifier("@ü/ß/"), Ok(("@ü/ß", ".")));
+    assert_eq!(
+      parse_package_specifier("ünicode/ßub"),
+      Ok(("ünicode", "ßub"))
+    );
+    assert_eq!(parse_package_specifier("@/pkg"), Ok(("@/pkg", "")));
+    assert_eq!(
+      parse_package_specifier("@/components/*"),
+      Ok(("@/components", "*"))
+    );
+
+    for specifier in [
+      "@scope",
+      "@scope/",
+      "@scope//pkg",
+      "@/",
+      "@",
+      "@ü",
+      "@ü/",
+      "@ü//ß",
+    ] {
+      assert_eq!(
+        parse_package_specifier(specifier),
+        Err(SpecifierError::InvalidPackageSpecifier),
+        "{:?}",
+        specifier
+      );
+    }
+
+    for specifier_type in [SpecifierType::Esm, SpecifierType::Cjs] {
+      assert_eq!(
+        Specifier::parse("@ünicode/pkg/ßub", specifier_type, Flags::empty())
+          .map(|(specifier, _)| specifier),
+        Ok(Specifier::Package("@ünicode/pkg".into(), "ßub".into()))
+      );
+      assert_eq!(
+        Specifier::parse("@scope/", specifier_type, Flags::empty()),
+        Err(SpecifierError::InvalidPackageSpecifier)
+      );
+    }
+  }
+
+  #[test]
+  fn eq_ignoring_query() {
+    let parse = |specifier, specifier_type| {
+      Specifier::parse(specifier, specifier_type, Flags::empty())
+        .unwrap()
+        .0
+    };
+
+    for (a, b, specifier_type) in [
+      (
+        "./img.png?width=100",
+        "./img.png?width=200",
+        SpecifierType::Esm,
+      ),
+      ("./img.png?width=100", "./img.png", SpecifierType::Url),
+      ("pkg/img.png?a=1", "pkg/img.png#frag", SpecifierType::Esm),
+      (
+        "https://example.com/a.png?x=1",
+        "https://example.com/a.png#top",
+        SpecifierType::Url,
+      ),
+    ] {
+      assert!(
+        parse(a, specifier_type).eq_ignoring_query(&parse(b, specifier_type)),
+        "{} {}",
+        a,
+        b
+      );
+    }
+
+    for (a, b, specifier_type) in [
+      (
+        "./img.png?width=100",
+        "./other.png?width=100",
+        SpecifierType::Esm,
+      ),
+      (
+        "https://example.com/a.png?x=1",
+        "https://example.com/b.png?x=1",
+        SpecifierType::Url,
+      ),
+      (
+        "data:text/plain,a?b",
+        "data:text/plain,a?c",
+        SpecifierType::Url,
+      ),
+      // Comparisons are exact apart from the query.
+      ("./a/../img.png", "./img.png", SpecifierType::Esm),
+    ] {
+      assert!(
+        !parse(a, specifier_type).eq_ignoring_query(&parse(b, specifier_type)),
+        "{} {}",
+        a,
+        b
+      );
+    }
+  }
+
+  #[test]
+  fn strip_package_prefix() {
+    let strip = |specifier, prefix| {
+      let (specifier, _) = Specifier::parse(specifier, SpecifierType::Esm, Flags::empty()).unwrap();
+      specifier.strip_package_prefix(prefix).map(str::to_owned)
+    };
+
+    // Exact module prefixes.
+    assert_eq!(strip("react", "react"), Some("".into()));
+    assert_eq!(
+      strip("react/jsx-runtime", "react"),
+      Some("jsx-runtime".into())
+    );
+    assert_eq!(
+      strip("react/jsx-runtime", "react/"),
+      Some("jsx-runtime".into())
+    );
+    assert_eq!(strip("@scope/pkg/a/b", "@scope/pkg"), Some("a/b".into()));
+    assert_eq!(strip("react-dom", "react"), None);
+    assert_eq!(strip("@scope/pkg", "@scope"), None);
+
+    // Prefixes with leading subpath segments.
+    assert_eq!(
+      strip("@scope/pkg/feature/button", "@scope/pkg/feature"),
+      Some("button".into())
+    );
+    assert_eq!(
+      strip("@scope/pkg/feature", "@scope/pkg/feature"),
+      Some("".into())
+    );
+    assert_eq!(strip("lodash/fp/get", "lodash/fp/"), Some("get".into()));
+    assert_eq!(strip("@scope/pkg/features", "@scope/pkg/feature"), None);
+    assert_eq!(strip("@scope/pkg", "@scope/pkg/feature"), None);
+
+    // Only package specifiers match.
+    assert_eq!(strip("./react", "react"), None);
+    assert_eq!(strip("#react", "react"), None);
+  }
+
+  #[test]
+  fn module_id() {
+    let parse =
+      |specifier| Specifier::parse(specifier, SpecifierType::Esm, Flags::empty()).unwrap();
+
+    let (specifier, query) = parse("./foo.js?inline");
+    assert_eq!(query, Some("?inline"));
+    assert_eq!(
+      specifier.module_id(Path::new("/abs/path/foo.js"), query),
+      "/abs/path/foo.js?inline"
+    );
+    assert_eq!(
+      specifier.module_id(Path::new("/abs/path/foo.js"), Some("inline")),
+      "/abs/path/foo.js?inline"
+    );
+    assert_eq!(
+      specifier.module_id(Path::new("/abs/path/foo.js"), None),
+      "/abs/path/foo.js"
+    );
+    assert_eq!(
+      specifier.module_id(Path::new("/abs/path/foo.js"), Some("?")),
+      "/abs/path/foo.js"
+    );
+
+    // Ids don't depend on the resolved path for specifiers without one.
+    let (specifier, _) = parse("node:fs");
+    assert_eq!(specifier.module_id(Path::new("/ignored"), None), "node:fs");
+    let (specifier, _) = Specifier::parse(
+      "https://example.com/a.js?v=1",
+      SpecifierType::Url,
+      Flags::empty(),
+    )
+    .unwrap();
+    assert_eq!(
+      specifier.module_id(Path::new("/ignored"), None),
+      "https://example.com/a.js?v=1"
+    );
+
+    // Windows paths, simulated so this runs everywhere.
+    assert_eq!(path_id(r"C:\src\foo.js", true), "C:/src/foo.js");
+    assert_eq!(path_id(r"\\?\C:\src\foo.js", true), "C:/src/foo.js");
+    assert_eq!(
+      path_id(r"\\?\UNC\server\share\foo.js", true),
+      "//server/share/foo.js"
+    );
+    assert_eq!(
+      path_id(r"\\server\share\foo.js", true),
+      "//server/share/foo.js"
+    );
+    // Elsewhere a backslash is part of the file name.
+    assert_eq!(path_id("/src/foo.js", false), "/src/foo.js");
+    assert_eq!(path_id(r"/src/a\b.js", false), r"/src/a\b.js");
   }
 }
@@ -0,0 +1,236 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use crate::{Flags, ParseOptions, Query, Specifier, SpecifierError, SpecifierType};
+
+type ParseResult = Result<(Specifier<'static>, Option<Query<'static>>), SpecifierError>;
+
+/// Memoizes parsed specifiers, e.g. for a long-running dev server that parses the same
+/// specifiers on every rebuild. Results (including errors) are keyed by the specifier, its type,
+/// and the flags. Once `capacity` entries are cached, the least recently used one is evicted.
+///
+/// This is an optional helper: `Specifier::parse` never consults it.
+pub struct SpecifierCache {
+  capacity: usize,
+  options: ParseOptions,
+  inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+  // Keyed by type and flags first, so that lookups by `&str` don't allocate.
+  entries: HashMap<(SpecifierType, Flags), HashMap<String, Entry>>,
+  len: usize,
+  tick: u64,
+}
+
+struct Entry {
+  result: ParseResult,
+  last_used: u64,
+}
+
+impl SpecifierCache {
+  pub fn new(capacity: usize) -> Self {
+    Self::with_options(capacity, ParseOptions::default())
+  }
+
+  /// Creates a cache that parses with the given options, like `Specifier::parse_with_options`.
+  pub fn with_options(capacity: usize, options: ParseOptions) -> Self {
+    SpecifierCache {
+      capacity,
+      options,
+      inner: Mutex::new(Inner::default()),
+    }
+  }
+
+  /// Parses a specifier, or returns the cached result of parsing it with the same type and flags.
+  pub fn parse(&self, specifier: &str, specifier_type: SpecifierType, flags: Flags) -> ParseResult {
+    let mut inner = self.inner.lock().unwrap();
+    inner.tick += 1;
+    let tick = inner.tick;
+    if let Some(entry) = inner
+      .entries
+      .get_mut(&(specifier_type, flags))
+      .and_then(|entries| entries.get_mut(specifier))
+    {
+      entry.last_used = tick;
+      return entry.result.clone();
+    }
+
+    let result = Specifier::parse_with_options(specifier, specifier_type, flags, &self.options)
+      .map(|(specifier, query)| {
+        (
+          specifier.into_owned(),
+          query
+            .and_then(|q| Query::new(q).without_keys(&self.options.ignored_query_keys))
+            .map(Query::into_owned),
+        )
+      });
+    if self.capacity == 0 {
+      return result;
+    }
+
+    if inner.len >= self.capacity {
+      inner.evict();
+    }
+
+    inner.len += 1;
+    inner
+      .entries
+      .entry((specifier_type, flags))
+      .or_default()
+      .insert(
+        specifier.to_owned(),
+        Entry {
+          result: result.clone(),
+          last_used: tick,
+        },
+      );
+    result
+  }
+
+  /// Returns whether the result for the given specifier, type, and flags is cached.
+  pub fn contains(&self, specifier: &str, specifier_type: SpecifierType, flags: Flags) -> bool {
+    let inner = self.inner.lock().unwrap();
+    inner
+      .entries
+      .get(&(specifier_type, flags))
+      .is_some_and(|entries| entries.contains_key(specifier))
+  }
+
+  pub fn len(&self) -> usize {
+    self.inner.lock().unwrap().len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  pub fn clear(&self) {
+    *self.inner.lock().unwrap() = Inner::default();
+  }
+}
+
+impl Inner {
+  /// Removes the least recently used entry. This scans every entry, but only runs when
+  /// inserting into a full cache, so hits stay cheap.
+  fn evict(&mut self) {
+    let oldest = self
+      .entries
+      .iter()
+      .flat_map(|(key, entries)| {
+        entries
+          .iter()
+          .map(move |(specifier, entry)| (entry.last_used, *key, specifier))
+      })
+      .min_by_key(|(last_used, _, _)| *last_used)
+      .map(|(_, key, specifier)| (key, specifier.clone()));
+
+    if let Some((key, specifier)) = oldest {
+      let entries = self.entries.get_mut(&key).unwrap();
+      entries.remove(&specifier);
+      if entries.is_empty() {
+        self.entries.remove(&key);
+      }
+      self.len -= 1;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn cache_hits() {
+    let cache = SpecifierCache::new(4);
+    assert!(cache.is_empty());
+
+    let first = cache.parse("./foo.js?bar=1", SpecifierType::Esm, Flags::empty());
+    let (specifier, query) = Specifier::parse("./foo.js?bar=1", SpecifierType::Esm, Flags::empty())
+      .map(|(specifier, query)| (specifier.into_owned(), query))
+      .unwrap();
+    assert_eq!(first, Ok((specifier, query.map(Query::new))));
+    assert!(cache.contains("./foo.js?bar=1", SpecifierType::Esm, Flags::empty()));
+    assert_eq!(
+      cache.parse("./foo.js?bar=1", SpecifierType::Esm, Flags::empty()),
+      first
+    );
+    assert_eq!(cache.len(), 1);
+
+    // Errors are cached too.
+    let err = cache.parse("", SpecifierType::Esm, Flags::empty());
+    assert_eq!(err, Err(SpecifierError::EmptySpecifier));
+    assert!(cache.contains("", SpecifierType::Esm, Flags::empty()));
+    assert_eq!(cache.len(), 2);
+
+    cache.clear();
+    assert!(cache.is_empty());
+  }
+
+  #[test]
+  fn eviction() {
+    let cache = SpecifierCache::new(2);
+    let parse = |specifier| cache.parse(specifier, SpecifierType::Esm, Flags::empty());
+    parse("a").unwrap();
+    parse("b").unwrap();
+    // Using "a" again makes "b" the least recently used.
+    parse("a").unwrap();
+    parse("c").unwrap();
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.contains("a", SpecifierType::Esm, Flags::empty()));
+    assert!(!cache.contains("b", SpecifierType::Esm, Flags::empty()));
+    assert!(cache.contains("c", SpecifierType::Esm, Flags::empty()));
+
+    // A zero capacity disables caching.
+    let cache = SpecifierCache::new(0);
+    assert!(cache.parse("a", SpecifierType::Esm, Flags::empty()).is_ok());
+    assert!(cache.is_empty());
+  }
+
+  #[test]
+  fn flag_sensitivity() {
+    let cache = SpecifierCache::new(8);
+    let parse = |specifier_type, flags| cache.parse("/foo", specifier_type, flags).unwrap().0;
+    let without_flag = parse(SpecifierType::Esm, Flags::empty());
+    let with_flag = parse(SpecifierType::Esm, Flags::ROOT_ABSOLUTE);
+    assert_eq!(
+      without_flag,
+      Specifier::Absolute(std::path::Path::new("/foo").into())
+    );
+    assert_eq!(
+      with_flag,
+      Specifier::RootRelative(std::path::Path::new("foo").into())
+    );
+
+    // Each combination of type and flags is cached separately.
+    assert_eq!(parse(SpecifierType::Esm, Flags::empty()), without_flag);
+    assert_eq!(parse(SpecifierType::Esm, Flags::ROOT_ABSOLUTE), with_flag);
+    parse(SpecifierType::Cjs, Flags::ROOT_ABSOLUTE);
+    assert_eq!(cache.len(), 3);
+  }
+
+  #[test]
+  fn ignored_query_keys() {
+    let cache = SpecifierCache::with_options(
+      4,
+      ParseOptions {
+        ignored_query_keys: vec!["v".into()],
+        ..Default::default()
+      },
+    );
+    let query = |specifier| {
+      cache
+        .parse(specifier, SpecifierType::Esm, Flags::empty())
+        .unwrap()
+        .1
+    };
+    assert_eq!(query("./foo.js?v=abc&real=1"), Some(Query::new("?real=1")));
+    assert_eq!(query("./foo.js?v=abc"), None);
+    assert_eq!(query("./foo.js?real=1"), Some(Query::new("?real=1")));
+  }
+}
@@ -20,7 +20,17 @@ pub struct TsConfig<'a> {
   #[serde(skip)]
   paths_base: PathBuf,
   pub module_suffixes: Option<Vec<&'a str>>,
+  root_dir: Option<Cow<'a, Path>>,
+  out_dir: Option<Cow<'a, Path>>,
   // rootDirs??
+  /// This tsconfig and those it extends, in extends order.
+  #[serde(skip)]
+  chain: Vec<PathBuf>,
+  /// The tsconfigs that set `paths` and `baseUrl`, which may have been inherited.
+  #[serde(skip)]
+  paths_origin: PathBuf,
+  #[serde(skip)]
+  base_url_origin: PathBuf,
 }
 
 fn deserialize_extends<'a, 'de: 'a, D>(deserializer: D) -> Result<Vec<Specifier<'a>>, D::Error>
@@ -62,8 +72,19 @@ impl<'a> TsConfig<'a> {
   }
 
   fn validate(&mut self) {
+    self.chain = vec![self.path.clone()];
+
     if let Some(base_url) = &mut self.base_url {
       *base_url = Cow::Owned(resolve_path(&self.path, &base_url));
+      self.base_url_origin = self.path.clone();
+    }
+
+    if let Some(root_dir) = &mut self.root_dir {
+      *root_dir = Cow::Owned(resolve_path(&self.path, &root_dir));
+    }
+
+    if let Some(out_dir) = &mut self.out_dir {
+      *out_dir = Cow::Owned(resolve_path(&self.path, &out_dir));
     }
 
     if self.paths.is_some() {
@@ -72,25 +93,72 @@ impl<'a> TsConfig<'a> {
       } else {
         self.path.parent().unwrap().to_owned()
       };
+      self.paths_origin = self.path.clone();
     }
   }
 
   pub fn extend(&mut self, extended: &TsConfig<'a>) {
     if self.base_url.is_none() {
       self.base_url = extended.base_url.clone();
+      self.base_url_origin = extended.base_url_origin.clone();
     }
 
     if self.paths.is_none() {
       self.paths_base = extended.paths_base.clone();
       self.paths = extended.paths.clone();
+      self.paths_origin = extended.paths_origin.clone();
+    }
+
+    for path in &extended.chain {
+      if !self.chain.contains(path) {
+        self.chain.push(path.clone());
+      }
     }
 
     if self.module_suffixes.is_none() {
       self.module_suffixes = extended.module_suffixes.clone();
     }
+
+    if self.root_dir.is_none() {
+      self.root_dir = extended.root_dir.clone();
+    }
+
+    if self.out_dir.is_none() {
+      self.out_dir = extended.out_dir.clone();
+    }
+  }
+
+  pub fn out_dir(&self) -> Option<&Path> {
+    self.out_dir.as_deref()
+  }
+
+  /// Maps a path inside `outDir` to the corresponding path inside `rootDir`, e.g. `dist/index.js`
+  /// to `src/index.js`. The extension is unchanged. `rootDir` defaults to the tsconfig's directory.
+  pub fn source_path(&self, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(self.out_dir.as_ref()?).ok()?;
+    let root_dir = match &self.root_dir {
+      Some(root_dir) => root_dir.as_ref(),
+      None => self.path.parent().unwrap(),
+    };
+
+    Some(root_dir.join(relative))
+  }
+
+  /// This tsconfig's path, followed by those of the tsconfigs it extends, in extends order.
+  pub fn chain(&self) -> &[PathBuf] {
+    &self.chain
   }
 
   pub fn paths(&'a self, specifier: &'a Specifier) -> impl Iterator<Item = PathBuf> + 'a {
+    self.paths_with_origin(specifier).map(|(path, _)| path)
+  }
+
+  /// Like `paths`, but also returns the tsconfig whose `paths` or `baseUrl` produced each path,
+  /// which may be one that this tsconfig extends.
+  pub fn paths_with_origin(
+    &'a self,
+    specifier: &'a Specifier,
+  ) -> impl Iterator<Item = (PathBuf, &'a Path)> + 'a {
     if !matches!(specifier, Specifier::Package(..) | Specifier::Builtin(..)) {
       return Either::Right(Either::Right(std::iter::empty()));
     }
@@ -98,7 +166,7 @@ impl<'a> TsConfig<'a> {
     // If there is a base url setting, resolve it relative to the tsconfig.json file.
     // Otherwise, the base for paths is implicitly the directory containing the tsconfig.
     let base_url_iter = if let Some(base_url) = &self.base_url {
-      Either::Left(base_url_iter(base_url, specifier))
+      Either::Left(base_url_iter(base_url, specifier, &self.base_url_origin))
     } else {
       Either::Right(std::iter::empty())
     };
@@ -106,7 +174,9 @@ impl<'a> TsConfig<'a> {
     if let Some(paths) = &self.paths {
       // Check exact match first.
       if let Some(paths) = paths.get(specifier) {
-        return Either::Left(join_paths(&self.paths_base, paths, None).chain(base_url_iter));
+        return Either::Left(
+          join_paths(&self.paths_base, paths, None, &self.paths_origin).chain(base_url_iter),
+        );
       }
 
       // Check patterns
@@ -136,6 +206,7 @@ impl<'a> TsConfig<'a> {
             &self.paths_base,
             paths,
             Some((full_specifier, longest_prefix_length, longest_suffix_length)),
+            &self.paths_origin,
           )
           .chain(base_url_iter),
         );
@@ -156,31 +227,34 @@ fn join_paths<'a>(
   base_url: &'a Path,
   paths: &'a Vec<&'a str>,
   replacement: Option<(Cow<'a, str>, usize, usize)>,
-) -> impl Iterator<Item = PathBuf> + 'a {
+  origin: &'a Path,
+) -> impl Iterator<Item = (PathBuf, &'a Path)> + 'a {
   paths
     .iter()
     .filter(|p| !p.ends_with(".d.ts"))
     .map(move |path| {
-      if let Some((replacement, start, end)) = &replacement {
+      let path = if let Some((replacement, start, end)) = &replacement {
         let path = path.replace('*', &replacement[*start..replacement.len() - *end]);
         base_url.join(&path)
       } else {
         base_url.join(&path)
-      }
+      };
+      (path, origin)
     })
 }
 
 fn base_url_iter<'a>(
   base_url: &'a Path,
   specifier: &'a Specifier,
-) -> impl Iterator<Item = PathBuf> + 'a {
+  origin: &'a Path,
+) -> impl Iterator<Item = (PathBuf, &'a Path)> + 'a {
   std::iter::once_with(move || {
     let mut path = base_url.to_owned();
     if let Specifier::Package(module, subpath) = specifier {
       path.push(module.as_ref());
       path.push(subpath.as_ref());
     }
-    path
+    (path, origin)
   })
 }
 
@@ -229,6 +303,33 @@ mod tests {
     assert_eq!(test("url"), vec![PathBuf::from("/foo/node_modules/my-url")]);
   }
 
+  #[test]
+  fn test_source_path() {
+    let mut tsconfig = TsConfig {
+      path: "/foo/tsconfig.json".into(),
+      root_dir: Some(Path::new("src").into()),
+      out_dir: Some(Path::new("./dist").into()),
+      ..Default::default()
+    };
+    tsconfig.validate();
+
+    assert_eq!(
+      tsconfig.source_path(Path::new("/foo/dist/index.js")),
+      Some(PathBuf::from("/foo/src/index.js"))
+    );
+    assert_eq!(
+      tsconfig.source_path(Path::new("/foo/dist/utils/a.mjs")),
+      Some(PathBuf::from("/foo/src/utils/a.mjs"))
+    );
+    assert_eq!(tsconfig.source_path(Path::new("/foo/lib/index.js")), None);
+
+    tsconfig.root_dir = None;
+    assert_eq!(
+      tsconfig.source_path(Path::new("/foo/dist/index.js")),
+      Some(PathBuf::from("/foo/index.js"))
+    );
+  }
+
   #[test]
   fn test_base_url() {
     let mut tsconfig = TsConfig {
@@ -0,0 +1,198 @@
+use indexmap::IndexMap;
+
+/// A TypeScript version, used to select a mapping from a package's "typesVersions" field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TsVersion {
+  pub major: u32,
+  pub minor: u32,
+  pub patch: u32,
+}
+
+impl TsVersion {
+  pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+    TsVersion {
+      major,
+      minor,
+      patch,
+    }
+  }
+
+  /// Parses a version such as `4.9.5`. Missing minor and patch components default to 0,
+  /// and pre-release tags (e.g. `5.0.0-beta`) are ignored.
+  pub fn parse(version: &str) -> Option<TsVersion> {
+    parse_partial(version).map(|(version, _)| version)
+  }
+
+  /// Returns whether the version satisfies a "typesVersions" range, e.g. `>=4.2`,
+  /// `>=3.1 <4`, or `<3.8 || >=4.5`. The `*` range matches every version.
+  pub fn satisfies(&self, range: &str) -> bool {
+    range.split("||").any(|comparators| {
+      comparators
+        .split_whitespace()
+        .all(|comparator| self.satisfies_comparator(comparator))
+    })
+  }
+
+  fn satisfies_comparator(&self, comparator: &str) -> bool {
+    let (op, version) = ["<=", ">=", "<", ">", "="]
+      .iter()
+      .find_map(|op| comparator.strip_prefix(op).map(|version| (*op, version)))
+      .unwrap_or(("", comparator));
+
+    if matches!(version, "*" | "x" | "X") {
+      return true;
+    }
+
+    let (version, components) = match parse_partial(version) {
+      Some(res) => res,
+      None => return false,
+    };
+
+    match op {
+      "<=" => *self <= version,
+      ">=" => *self >= version,
+      "<" => *self < version,
+      ">" => *self > version,
+      // A partial version matches all versions it is a prefix of, e.g. `4.1` matches `4.1.5`.
+      _ => {
+        self.major == version.major
+          && (components < 2 || self.minor == version.minor)
+          && (components < 3 || self.patch == version.patch)
+      }
+    }
+  }
+}
+
+/// Parses a possibly partial version, returning the number of components that were present.
+fn parse_partial(version: &str) -> Option<(TsVersion, usize)> {
+  let version = version.trim().trim_start_matches('v');
+  let version = version
+    .split_once('-')
+    .map_or(version, |(version, _)| version);
+  let mut components = [0; 3];
+  let mut count = 0;
+  for part in version.split('.') {
+    if count == 3 {
+      return None;
+    }
+    components[count] = part.parse().ok()?;
+    count += 1;
+  }
+
+  Some((
+    TsVersion::new(components[0], components[1], components[2]),
+    count,
+  ))
+}
+
+/// Returns the mapping of the first range in a "typesVersions" field that the version satisfies.
+pub(crate) fn select<'m, 'a>(
+  types_versions: &'m IndexMap<&'a str, IndexMap<&'a str, Vec<&'a str>>>,
+  version: TsVersion,
+) -> Option<&'m IndexMap<&'a str, Vec<&'a str>>> {
+  types_versions
+    .iter()
+    .find(|(range, _)| version.satisfies(range))
+    .map(|(_, mapping)| mapping)
+}
+
+/// Maps a path within a package through a "typesVersions" mapping, e.g. `{"*": ["ts3.1/*"]}`.
+/// An exact key is preferred, then the `*` pattern with the longest prefix. Returns the
+/// targets in the order they should be tried, or `None` if no key matches.
+pub(crate) fn map_path(mapping: &IndexMap<&str, Vec<&str>>, path: &str) -> Option<Vec<String>> {
+  let path = path.strip_prefix("./").unwrap_or(path);
+  let normalize = |key: &str| key.strip_prefix("./").unwrap_or(key).to_owned();
+
+  if let Some((_, targets)) = mapping.iter().find(|(key, _)| normalize(key) == path) {
+    return Some(targets.iter().map(|target| normalize(target)).collect());
+  }
+
+  let mut best: Option<(&str, &str, &Vec<&str>)> = None;
+  for (key, targets) in mapping {
+    let key = key.strip_prefix("./").unwrap_or(*key);
+    if let Some((prefix, suffix)) = key.split_once('*') {
+      if best.is_none_or(|(best_prefix, _, _)| prefix.len() > best_prefix.len())
+        && path.len() >= prefix.len() + suffix.len()
+        && path.starts_with(prefix)
+        && path.ends_with(suffix)
+      {
+        best = Some((prefix, suffix, targets));
+      }
+    }
+  }
+
+  let (prefix, suffix, targets) = best?;
+  let captured = &path[prefix.len()..path.len() - suffix.len()];
+  Some(
+    targets
+      .iter()
+      .map(|target| normalize(&target.replacen('*', captured, 1)))
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_satisfies() {
+    let v4_9 = TsVersion::parse("4.9.5").unwrap();
+    assert!(v4_9.satisfies("*"));
+    assert!(v4_9.satisfies(">=4.2"));
+    assert!(v4_9.satisfies(">=3.1 <5"));
+    assert!(v4_9.satisfies("<3.8 || >=4.5"));
+    assert!(v4_9.satisfies("4.9"));
+    assert!(v4_9.satisfies("=4"));
+    assert!(!v4_9.satisfies("<4.9"));
+    assert!(!v4_9.satisfies(">=5.0"));
+    assert!(!v4_9.satisfies("4.8"));
+    assert!(!v4_9.satisfies("not a range"));
+
+    let v3_1 = TsVersion::parse("3.1").unwrap();
+    assert_eq!(v3_1, TsVersion::new(3, 1, 0));
+    assert!(v3_1.satisfies("<=3.1"));
+    assert!(!v3_1.satisfies(">3.1"));
+    assert_eq!(
+      TsVersion::parse("5.0.0-beta"),
+      Some(TsVersion::new(5, 0, 0))
+    );
+    assert_eq!(TsVersion::parse("1.2.3.4"), None);
+  }
+
+  #[test]
+  fn test_map_path() {
+    let mut mapping = IndexMap::new();
+    mapping.insert("*", vec!["ts3.1/*"]);
+    mapping.insert("sub/*", vec!["ts3.1/sub/*.d.ts", "fallback/*"]);
+    mapping.insert("exact", vec!["./exact.d.ts"]);
+
+    assert_eq!(
+      map_path(&mapping, "index.d.ts"),
+      Some(vec!["ts3.1/index.d.ts".into()])
+    );
+    assert_eq!(
+      map_path(&mapping, "sub/foo"),
+      Some(vec!["ts3.1/sub/foo.d.ts".into(), "fallback/foo".into()])
+    );
+    assert_eq!(
+      map_path(&mapping, "./exact"),
+      Some(vec!["exact.d.ts".into()])
+    );
+
+    let mut mapping = IndexMap::new();
+    mapping.insert("lib/*", vec!["types/*"]);
+    assert_eq!(map_path(&mapping, "index.d.ts"), None);
+  }
+
+  #[test]
+  fn test_select() {
+    let mut old = IndexMap::new();
+    old.insert("*", vec!["ts3.1/*"]);
+    let mut types_versions = IndexMap::new();
+    types_versions.insert("<3.8", old);
+
+    assert!(select(&types_versions, TsVersion::new(3, 1, 0)).is_some());
+    assert!(select(&types_versions, TsVersion::new(4, 0, 0)).is_none());
+  }
+}